@@ -0,0 +1,134 @@
+//! 后台自动保存：合并频繁的内容更新，按固定周期写盘；每个窗口分别启用，只写入自己绑定的文件
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager};
+
+use crate::checksum;
+use crate::editor_windows::EditorWindows;
+use crate::error::AppError;
+use crate::watcher::FileWatcher;
+use crate::{now_millis, write_file_atomic};
+
+#[derive(Default)]
+struct AutosaveState {
+    path: Option<PathBuf>,
+    pending: Option<String>,
+    /// 每次启用/停用都会递增，旧的后台任务据此退出
+    generation: u64,
+}
+
+/// 自动保存管理器，作为 `tauri::State` 托管；每个窗口的自动保存相互独立，以窗口标签区分
+#[derive(Default)]
+pub struct Autosave {
+    windows: Mutex<HashMap<String, AutosaveState>>,
+}
+
+#[derive(Serialize, Clone)]
+struct AutosaveSaved {
+    path: String,
+    timestamp: u64,
+}
+
+#[derive(Serialize, Clone)]
+struct AutosaveError {
+    path: String,
+    message: String,
+}
+
+impl Autosave {
+    /// 启用窗口的自动保存；若之前已启用，先把尚未保存的内容写入旧路径
+    pub fn enable(&self, app: AppHandle, label: String, path: PathBuf, interval: Duration) {
+        let generation = {
+            let mut windows = self.windows.lock().unwrap();
+            let state = windows.entry(label.clone()).or_default();
+            if let (Some(old_path), Some(content)) = (state.path.take(), state.pending.take()) {
+                flush(&app, &label, &old_path, &content);
+            }
+            state.generation += 1;
+            state.path = Some(path);
+            state.generation
+        };
+
+        tauri::async_runtime::spawn(async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let autosave = app.state::<Autosave>();
+                let job = {
+                    let mut windows = autosave.windows.lock().unwrap();
+                    match windows.get_mut(&label) {
+                        Some(state) if state.generation == generation => {
+                            state.pending.take().zip(state.path.clone())
+                        }
+                        _ => break,
+                    }
+                };
+                if let Some((content, path)) = job {
+                    flush(&app, &label, &path, &content);
+                }
+            }
+        });
+    }
+
+    /// 停用窗口的自动保存，停用前写入尚未保存的内容
+    pub fn disable(&self, app: &AppHandle, label: &str) {
+        let mut windows = self.windows.lock().unwrap();
+        if let Some(mut state) = windows.remove(label) {
+            if let (Some(path), Some(content)) = (state.path.take(), state.pending.take()) {
+                flush(app, label, &path, &content);
+            }
+        }
+    }
+
+    /// 记录窗口的最新内容，覆盖之前尚未写入的内容
+    pub fn queue(&self, label: &str, content: String) -> Result<(), AppError> {
+        let mut windows = self.windows.lock().unwrap();
+        match windows.get_mut(label) {
+            Some(state) if state.path.is_some() => {
+                state.pending = Some(content);
+                Ok(())
+            }
+            _ => Err(AppError::AutosaveDisabled),
+        }
+    }
+}
+
+/// 写入文件并通知窗口结果；文件已被其他窗口绑定时不写入
+fn flush(app: &AppHandle, label: &str, path: &Path, content: &str) {
+    let display = path.to_string_lossy().to_string();
+    let written = app
+        .state::<EditorWindows>()
+        .check_write(label, path)
+        .and_then(|()| {
+            app.state::<FileWatcher>().note_self_write(path);
+            write_file_atomic(path, content.as_bytes())?;
+            // 手动保存时写过校验和文件，自动保存也要更新，否则下次加载会误报文件损坏
+            checksum::record(path);
+            Ok(())
+        });
+    let result = match written {
+        Ok(()) => app.emit_to(
+            label,
+            "autosave:saved",
+            AutosaveSaved {
+                path: display,
+                timestamp: now_millis(),
+            },
+        ),
+        Err(e) => app.emit_to(
+            label,
+            "autosave:error",
+            AutosaveError {
+                path: display,
+                message: e.to_string(),
+            },
+        ),
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to emit autosave event: {}", e);
+    }
+}
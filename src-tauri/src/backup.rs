@@ -0,0 +1,139 @@
+//! 覆盖公式文件前的轮转备份：`name.json.bak.1` 为最新，数字越大越旧
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::Serialize;
+
+use crate::checksum;
+use crate::error::AppError;
+use crate::gzip;
+use crate::write_file_atomic;
+
+/// 默认保留的备份数量
+pub const DEFAULT_BACKUP_COUNT: usize = 5;
+
+/// 列出备份时检查的最大序号
+const MAX_BACKUP_INDEX: usize = 50;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupInfo {
+    pub index: usize,
+    pub path: String,
+    pub size: u64,
+    /// 备份文件的修改时间（Unix 毫秒时间戳）
+    pub modified: Option<u64>,
+}
+
+/// 保存结果；`warning` 记录备份失败等不影响保存本身的问题
+#[derive(Serialize, Default)]
+pub struct SaveReport {
+    pub warning: Option<String>,
+}
+
+/// 第 `index` 个备份文件的路径
+pub fn backup_path(path: &Path, index: usize) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".bak.{}", index));
+    path.with_file_name(name)
+}
+
+/// 将现有文件轮转为 `.bak.1`，更旧的备份依次后移，超出 `keep` 的丢弃
+fn rotate(path: &Path, keep: usize) -> std::io::Result<()> {
+    let oldest = backup_path(path, keep);
+    if oldest.exists() {
+        fs::remove_file(&oldest)?;
+    }
+    for index in (1..keep).rev() {
+        let from = backup_path(path, index);
+        if from.exists() {
+            fs::rename(&from, backup_path(path, index + 1))?;
+        }
+    }
+    fs::copy(path, backup_path(path, 1))?;
+    Ok(())
+}
+
+/// 写入文件并记录校验和；若内容有变化则先备份原文件。备份失败不会阻止保存，只在结果中给出警告
+pub fn save_with_backup(path: &Path, content: &[u8], keep: usize) -> Result<SaveReport, AppError> {
+    let mut report = SaveReport::default();
+    if keep > 0 {
+        if let Ok(existing) = gzip::read(path, u64::MAX) {
+            if existing != content {
+                if let Err(e) = rotate(path, keep) {
+                    report.warning = Some(format!("Failed to create backup: {}", e));
+                }
+            }
+        }
+    }
+    write_file_atomic(path, content)?;
+    checksum::record(path);
+    Ok(report)
+}
+
+/// 列出文件的全部备份，按序号从新到旧排列
+pub fn list(path: &Path) -> Vec<BackupInfo> {
+    (1..=MAX_BACKUP_INDEX)
+        .filter_map(|index| {
+            let backup = backup_path(path, index);
+            let meta = fs::metadata(&backup).ok()?;
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_millis() as u64);
+            Some(BackupInfo {
+                index,
+                path: backup.to_string_lossy().to_string(),
+                size: meta.len(),
+                modified,
+            })
+        })
+        .collect()
+}
+
+/// 用指定备份覆盖原文件，覆盖前会照常备份当前内容；返回恢复后的内容
+///
+/// 恢复最旧的一个备份（`index == keep`）时，按 `keep` 轮转会在写入前删掉它；此时多轮转一格，
+/// 写入成功后再删除移到 `keep + 1` 的这份备份，写入失败时它仍然保留。
+pub fn restore(path: &Path, index: usize, keep: usize) -> Result<String, AppError> {
+    let backup = backup_path(path, index);
+    let bytes = gzip::read(&backup, u64::MAX)?;
+    let content = String::from_utf8(bytes).map_err(|_| AppError::UnsupportedEncoding {
+        attempted: vec!["UTF-8".to_string()],
+    })?;
+    let oldest = keep > 0 && index == keep;
+    let rotation = if oldest { keep + 1 } else { keep };
+    save_with_backup(path, content.as_bytes(), rotation)?;
+    let moved = backup_path(path, keep + 1);
+    if oldest && gzip::read(&moved, u64::MAX).is_ok_and(|b| b == content.as_bytes()) {
+        let _ = fs::remove_file(moved);
+    }
+    Ok(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn restoring_the_oldest_backup_keeps_the_backup_count() {
+        let dir = std::env::temp_dir().join(format!("mathlive-backup-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("a.json");
+        for version in ["v1", "v2", "v3"] {
+            save_with_backup(&path, version.as_bytes(), 2).unwrap();
+        }
+        // 当前为 v3，.bak.1 为 v2，.bak.2 为 v1
+        assert_eq!(restore(&path, 2, 2).unwrap(), "v1");
+        let read = |p: PathBuf| fs::read_to_string(p).unwrap();
+        assert_eq!(read(path.clone()), "v1");
+        assert_eq!(read(backup_path(&path, 1)), "v3");
+        assert_eq!(read(backup_path(&path, 2)), "v2");
+        assert!(!backup_path(&path, 3).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,114 @@
+//! 公式文件的内容校验和，用于发现同步服务返回的截断或冲突文件
+//!
+//! 每次成功保存后在文件旁写入 `name.json.sha256`，格式与 `sha256sum` 的输出相同，可以用
+//! `sha256sum -c` 手动校验。加载时若文件与记录的校验和不一致，向窗口发送
+//! `file:checksum-mismatch` 事件，前端据此提示从备份恢复。校验和文件只是辅助信息，写入失败
+//! 只记录日志，不影响保存。
+
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::backup;
+
+/// 流式计算时每次读取的字节数
+const CHUNK_SIZE: usize = 64 * 1024;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FileChecksum {
+    /// 小写十六进制
+    pub sha256: String,
+    pub size: u64,
+    /// 修改时间（Unix 毫秒时间戳）
+    pub mtime: Option<u64>,
+}
+
+/// 文件内容与上次保存时记录的校验和不一致
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ChecksumMismatch {
+    /// 固定为 `checksum_mismatch`，与错误码的写法一致
+    pub code: &'static str,
+    pub path: String,
+    pub expected: String,
+    pub actual: String,
+    /// 可用于恢复的备份数量
+    pub backups: usize,
+}
+
+/// 校验和文件的路径：`name.json` → `name.json.sha256`
+pub fn sidecar_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".sha256");
+    path.with_file_name(name)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// 分块读取文件计算 SHA-256，不会把整个文件读入内存
+pub fn compute(path: &Path) -> io::Result<FileChecksum> {
+    let mut file = File::open(path)?;
+    let meta = file.metadata()?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; CHUNK_SIZE];
+    let mut size = 0;
+    loop {
+        let read = match file.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        hasher.update(&buffer[..read]);
+        size += read as u64;
+    }
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64);
+    Ok(FileChecksum {
+        sha256: to_hex(&hasher.finalize()),
+        size,
+        mtime,
+    })
+}
+
+/// 记录刚保存的文件的校验和；按磁盘上的字节计算，压缩文件记录的是压缩后的内容。失败时只记录日志
+pub fn record(path: &Path) {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let sidecar = sidecar_path(path);
+    let written = compute(path)
+        .and_then(|checksum| fs::write(&sidecar, format!("{}  {}\n", checksum.sha256, name)));
+    if let Err(e) = written {
+        log::warn!("Failed to write checksum {}: {}", sidecar.display(), e);
+    }
+}
+
+/// 读取记录的校验和；没有校验和文件或内容无法识别时返回 `None`
+fn recorded(path: &Path) -> Option<String> {
+    let content = fs::read_to_string(sidecar_path(path)).ok()?;
+    let hash = content.split_whitespace().next()?.to_ascii_lowercase();
+    (hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_hexdigit())).then_some(hash)
+}
+
+/// 比较读到的文件字节与记录的校验和；一致或没有记录时返回 `None`。`bytes` 是读取时从
+/// 磁盘得到的原始字节，不重新读取文件，避免两次读取之间的写入造成误报
+pub fn verify(path: &Path, bytes: &[u8]) -> Option<ChecksumMismatch> {
+    let expected = recorded(path)?;
+    let actual = to_hex(&Sha256::digest(bytes));
+    (actual != expected).then(|| ChecksumMismatch {
+        code: "checksum_mismatch",
+        path: path.to_string_lossy().into_owned(),
+        expected,
+        actual,
+        backups: backup::list(path).len(),
+    })
+}
@@ -0,0 +1,260 @@
+//! 命令行导出：`--export tex|md|html --input formulas.json --output out.tex [--options options.json]`
+//!
+//! 在创建 Tauri 应用之前处理，不打开窗口。选项文件的字段与对应导出命令的参数相同，
+//! 例如 LaTeX 导出可写 `{ "includeTags": true, "documentClass": "article" }`。
+//! 错误以 `code: message` 的形式写到标准错误，便于脚本用 grep 判断。
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::Value;
+use tauri::PackageInfo;
+
+use crate::error::AppError;
+use crate::{
+    format_latex, format_markdown, formula_items, html_document, normalize_formula_value,
+    read_text_file, write_file_atomic, HtmlOptions, LatexOptions, MarkdownFlavor, MarkdownOptions,
+    NotePosition, DEFAULT_MAX_READ_BYTES,
+};
+
+const EXPORT_FLAG: &str = "--export";
+const INPUT_FLAG: &str = "--input";
+const OUTPUT_FLAG: &str = "--output";
+const OPTIONS_FLAG: &str = "--options";
+const FLAGS: &[&str] = &[EXPORT_FLAG, INPUT_FLAG, OUTPUT_FLAG, OPTIONS_FLAG];
+
+/// 参数错误的退出码，其他错误为 1
+const USAGE_EXIT_CODE: i32 = 2;
+
+#[derive(Clone, Copy)]
+enum Format {
+    Tex,
+    Markdown,
+    Html,
+}
+
+impl Format {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "tex" | "latex" => Some(Format::Tex),
+            "md" | "markdown" => Some(Format::Markdown),
+            "html" => Some(Format::Html),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            Format::Tex => "tex",
+            Format::Markdown => "md",
+            Format::Html => "html",
+        }
+    }
+}
+
+struct Request {
+    format: Format,
+    input: PathBuf,
+    output: PathBuf,
+    options: Option<PathBuf>,
+}
+
+enum Failure {
+    /// 命令行参数有误
+    Usage(String),
+    App(AppError),
+}
+
+impl Failure {
+    fn code(&self) -> &'static str {
+        match self {
+            Failure::Usage(_) => "invalid_arguments",
+            Failure::App(e) => e.code(),
+        }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Failure::Usage(message) => write!(f, "{}", message),
+            Failure::App(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<AppError> for Failure {
+    fn from(err: AppError) -> Self {
+        Failure::App(err)
+    }
+}
+
+/// 选项文件中不属于各格式选项结构的参数
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct ExportFlags {
+    include_tags: Option<bool>,
+    note_position: Option<NotePosition>,
+    flavor: Option<MarkdownFlavor>,
+}
+
+/// 参数中没有导出相关的选项时返回 `None`，照常启动界面；否则执行导出并返回退出码
+pub fn run(args: &[String], package_info: &PackageInfo) -> Option<i32> {
+    let recognized = args.iter().any(|arg| {
+        FLAGS
+            .iter()
+            .any(|flag| arg == flag || arg.starts_with(&format!("{}=", flag)))
+    });
+    if !recognized {
+        return None;
+    }
+    attach_console();
+    let result = parse(args).and_then(|request| export(&request, package_info));
+    Some(match result {
+        Ok(summary) => {
+            println!("{}", summary);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}: {}", e.code(), e);
+            match e {
+                Failure::Usage(_) => USAGE_EXIT_CODE,
+                Failure::App(_) => 1,
+            }
+        }
+    })
+}
+
+fn parse(args: &[String]) -> Result<Request, Failure> {
+    let mut format = None;
+    let mut input = None;
+    let mut output = None;
+    let mut options = None;
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        let (flag, inline) = match arg.split_once('=') {
+            Some((flag, value)) if flag.starts_with("--") => (flag, Some(value.to_string())),
+            _ => (arg.as_str(), None),
+        };
+        if !FLAGS.contains(&flag) {
+            return Err(Failure::Usage(format!("unknown argument {}", arg)));
+        }
+        let value = match inline.or_else(|| rest.next().cloned()) {
+            Some(value) if !value.is_empty() => value,
+            _ => return Err(Failure::Usage(format!("{} requires a value", flag))),
+        };
+        match flag {
+            EXPORT_FLAG => {
+                format = Some(Format::parse(&value).ok_or_else(|| {
+                    Failure::Usage(format!(
+                        "unsupported format {}, expected tex|md|html",
+                        value
+                    ))
+                })?)
+            }
+            INPUT_FLAG => input = Some(PathBuf::from(value)),
+            OUTPUT_FLAG => output = Some(PathBuf::from(value)),
+            _ => options = Some(PathBuf::from(value)),
+        }
+    }
+    let missing = |flag: &str| Failure::Usage(format!("missing {}", flag));
+    Ok(Request {
+        format: format.ok_or_else(|| missing(EXPORT_FLAG))?,
+        input: input.ok_or_else(|| missing(INPUT_FLAG))?,
+        output: output.ok_or_else(|| missing(OUTPUT_FLAG))?,
+        options,
+    })
+}
+
+/// 读取 JSON 选项文件；没有指定时为空对象
+fn read_options(path: Option<&Path>) -> Result<Value, AppError> {
+    let Some(path) = path else {
+        return Ok(Value::Object(Default::default()));
+    };
+    let content = read_text_file(path, DEFAULT_MAX_READ_BYTES)?;
+    let value: Value = serde_json::from_str(&content)?;
+    if !value.is_object() {
+        return Err(AppError::InvalidOption {
+            name: OPTIONS_FLAG.to_string(),
+            value: path.display().to_string(),
+        });
+    }
+    Ok(value)
+}
+
+fn options_as<T: DeserializeOwned>(options: &Value) -> Result<T, AppError> {
+    Ok(serde_json::from_value(options.clone())?)
+}
+
+fn export(request: &Request, package_info: &PackageInfo) -> Result<String, Failure> {
+    let content = read_text_file(&request.input, DEFAULT_MAX_READ_BYTES)?;
+    let value: Value = serde_json::from_str(&content).map_err(AppError::from)?;
+    let normalized = normalize_formula_value(&value)?;
+    let options = read_options(request.options.as_deref())?;
+    let flags: ExportFlags = options_as(&options)?;
+    let items = formula_items(&normalized.entries);
+    let count = items.len();
+
+    let mut warnings = Vec::new();
+    if !normalized.warnings.is_empty() {
+        warnings.push(format!(
+            "skipped {} invalid entries",
+            normalized.warnings.len()
+        ));
+    }
+    let output = match request.format {
+        Format::Tex => {
+            let document = tauri::async_runtime::block_on(format_latex(
+                items,
+                flags.include_tags,
+                Some(options_as::<LatexOptions>(&options)?),
+            ))?;
+            warnings.extend(document.warnings);
+            document.content
+        }
+        Format::Markdown => tauri::async_runtime::block_on(format_markdown(
+            items,
+            flags.note_position,
+            flags.include_tags,
+            Some(options_as::<MarkdownOptions>(&options)?),
+            flags.flavor,
+        ))?,
+        Format::Html => {
+            let katex_dir = tauri::api::path::resource_dir(package_info, &tauri::Env::default())
+                .map(|dir| dir.join("resources/katex"));
+            let html_options: HtmlOptions = options_as(&options)?;
+            if items.is_empty() {
+                String::new()
+            } else {
+                html_document(katex_dir.as_deref(), &items, &html_options, "", "")?
+            }
+        }
+    };
+    write_file_atomic(&request.output, output.as_bytes())?;
+    for warning in &warnings {
+        eprintln!("warning: {}", warning);
+    }
+    let size = fs::metadata(&request.output).map_or(0, |m| m.len());
+    Ok(format!(
+        "exported {} formulas to {} ({}, {} bytes)",
+        count,
+        request.output.display(),
+        request.format.name(),
+        size
+    ))
+}
+
+/// Windows 发布版没有控制台窗口，连接到启动本程序的终端以便输出结果
+#[cfg(windows)]
+fn attach_console() {
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+#[cfg(not(windows))]
+fn attach_console() {}
@@ -0,0 +1,95 @@
+//! 系统剪贴板
+//!
+//! Linux 上剪贴板内容由持有 `arboard::Clipboard` 的进程提供，实例销毁后内容随之消失，
+//! 因此作为 `tauri::State` 长期持有；出错后丢弃实例，下次使用时重新连接。arboard 的调用会同步
+//! 等待系统剪贴板，全部放到阻塞线程池中执行。
+//!
+//! 发布构建使用 `panic = "abort"`，arboard 内部 panic 会直接终止进程；只有开发构建中 panic
+//! 会通过 `spawn_blocking` 的 `JoinError` 返回为 `task_failed` 错误。
+
+use std::borrow::Cow;
+use std::sync::{Arc, Mutex, PoisonError};
+
+use arboard::{Clipboard, ImageData};
+
+use crate::error::AppError;
+
+#[derive(Default)]
+pub struct SystemClipboard {
+    inner: Arc<Mutex<Option<Clipboard>>>,
+}
+
+impl SystemClipboard {
+    async fn with<T, F>(&self, f: F) -> Result<T, AppError>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Clipboard) -> Result<T, arboard::Error> + Send + 'static,
+    {
+        let inner = self.inner.clone();
+        tauri::async_runtime::spawn_blocking(move || {
+            // 锁只会在开发构建的 panic 后被污染，此时沿用其中的实例，出错时再重建
+            let mut inner = inner.lock().unwrap_or_else(PoisonError::into_inner);
+            let clipboard = match inner.as_mut() {
+                Some(clipboard) => clipboard,
+                None => inner.insert(Clipboard::new().map_err(failed)?),
+            };
+            f(clipboard).map_err(|e| {
+                *inner = None;
+                failed(e)
+            })
+        })
+        .await?
+    }
+
+    pub async fn write_text(&self, text: String) -> Result<(), AppError> {
+        self.with(move |clipboard| clipboard.set_text(text)).await
+    }
+
+    /// 读取文字；剪贴板为空或只有图片等其他格式时返回 `ClipboardEmpty`
+    pub async fn read_text(&self) -> Result<String, AppError> {
+        self.with(|clipboard| clipboard.get_text()).await
+    }
+
+    /// 写入非预乘的 RGBA 图像，由 arboard 转换为各平台的原生格式
+    pub async fn write_image(
+        &self,
+        width: u32,
+        height: u32,
+        rgba: Vec<u8>,
+    ) -> Result<(), AppError> {
+        self.with(move |clipboard| {
+            clipboard.set_image(ImageData {
+                width: width as usize,
+                height: height as usize,
+                bytes: Cow::Owned(rgba),
+            })
+        })
+        .await
+    }
+}
+
+/// 平台不支持剪贴板时返回 `ClipboardUnsupported`，前端可改为保存临时文件；
+/// 没有可读取的内容时返回 `ClipboardEmpty`；数据转换失败等其他错误带上原始信息
+fn failed(err: arboard::Error) -> AppError {
+    match err {
+        arboard::Error::ContentNotAvailable => AppError::ClipboardEmpty,
+        arboard::Error::ClipboardNotSupported => AppError::ClipboardUnsupported,
+        err => AppError::ClipboardFailed(err.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conversion_failure_keeps_the_original_message() {
+        let message = arboard::Error::ConversionFailure.to_string();
+        match failed(arboard::Error::ConversionFailure) {
+            AppError::ClipboardFailed(failed) => assert_eq!(failed, message),
+            other => panic!("unexpected {}", other.code()),
+        }
+        let unsupported = failed(arboard::Error::ClipboardNotSupported);
+        assert_eq!(unsupported.code(), "clipboard_unsupported");
+    }
+}
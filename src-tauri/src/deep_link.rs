@@ -0,0 +1,234 @@
+//! 深度链接：`mathlive-formula://open?path=...` 打开公式集，`mathlive-formula://formula?id=...` 定位公式
+//!
+//! Windows 和 Linux 在第一次运行时把协议登记到当前用户，之后只在用户要求时重新登记，
+//! 以免覆盖用户改用的其他处理程序；链接作为启动参数传入。macOS 的协议由
+//! Info.plist 声明，但链接通过 Apple Event 传递，Tauri 1.8 的 `RunEvent` 没有对应的事件
+//! （Tauri 2 才有 `RunEvent::Opened`），因此只能处理启动参数中的链接。
+//! 前端调用 `frontend_ready` 之前收到的链接先缓存，就绪后再发送 `deeplink:open` 事件。
+
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::{AppHandle, Url};
+
+use crate::error::{AppError, Location};
+use crate::settings::SettingsStore;
+use crate::window_state::show_main;
+
+pub const SCHEME: &str = "mathlive-formula";
+
+/// 是否已经自动登记过协议在设置中的键名
+pub const SCHEME_REGISTERED_KEY: &str = "urlSchemeRegistered";
+
+/// 链接解析出的操作，作为 `deeplink:open` 事件的内容
+#[derive(Serialize, Clone, Debug)]
+#[serde(tag = "action", rename_all = "camelCase")]
+pub enum DeepLink {
+    /// 打开公式集文件
+    Open { path: String },
+    /// 定位到指定 id 的公式
+    Formula { id: String },
+}
+
+/// 解析链接；查询参数会被百分号解码，`open` 要求路径存在且是公式集文件
+pub fn parse(link: &str) -> Result<DeepLink, String> {
+    let url = Url::parse(link).map_err(|e| e.to_string())?;
+    if url.scheme() != SCHEME {
+        return Err(format!("unexpected scheme {}", url.scheme()));
+    }
+    let param = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.trim().to_string())
+            .filter(|value| !value.is_empty())
+    };
+    // `mathlive-formula://open` 的操作在主机名中，`mathlive-formula:open` 的在路径中
+    let action = url
+        .host_str()
+        .filter(|host| !host.is_empty())
+        .unwrap_or_else(|| url.path().trim_matches('/'));
+    match action {
+        "open" => {
+            let path = param("path").ok_or("missing path")?;
+            let file = Path::new(&path);
+            if !crate::is_collection_path(file) {
+                return Err(format!("not a formula collection: {}", path));
+            }
+            if !file.is_file() {
+                return Err(format!("file not found: {}", path));
+            }
+            Ok(DeepLink::Open { path })
+        }
+        "formula" => Ok(DeepLink::Formula {
+            id: param("id").ok_or("missing id")?,
+        }),
+        other => Err(format!("unknown action {:?}", other)),
+    }
+}
+
+/// 启动参数中的第一个深度链接
+pub fn find_in_args(args: impl IntoIterator<Item = String>) -> Option<String> {
+    let prefix = format!("{}:", SCHEME);
+    args.into_iter().find(|arg| {
+        arg.get(..prefix.len())
+            .is_some_and(|head| head.eq_ignore_ascii_case(&prefix))
+    })
+}
+
+#[derive(Default)]
+struct Inner {
+    ready: bool,
+    pending: Vec<DeepLink>,
+}
+
+/// 前端就绪前收到的链接，作为 `tauri::State` 托管
+#[derive(Default)]
+pub struct DeepLinks {
+    inner: Mutex<Inner>,
+}
+
+impl DeepLinks {
+    /// 处理收到的链接：无效链接只记录警告；前端未就绪时先缓存
+    pub fn handle(&self, app: &AppHandle, link: &str) {
+        let parsed = match parse(link) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                log::warn!("Ignoring deep link {}: {}", link, e);
+                return;
+            }
+        };
+        let mut inner = self.inner.lock().unwrap();
+        if inner.ready {
+            drop(inner);
+            emit(app, &parsed);
+        } else {
+            inner.pending.push(parsed);
+        }
+    }
+
+    /// 前端已就绪，发送缓存的链接
+    pub fn mark_ready(&self, app: &AppHandle) {
+        let pending = {
+            let mut inner = self.inner.lock().unwrap();
+            inner.ready = true;
+            std::mem::take(&mut inner.pending)
+        };
+        for link in &pending {
+            emit(app, link);
+        }
+    }
+}
+
+fn emit(app: &AppHandle, link: &DeepLink) {
+    if let Some(window) = show_main(app) {
+        if let Err(e) = window.emit("deeplink:open", link) {
+            log::warn!("Failed to emit deep link event: {}", e);
+        }
+    }
+}
+
+/// 把协议登记到当前用户，指向正在运行的程序
+pub fn register_scheme() -> Result<(), AppError> {
+    let exe = std::env::current_exe().map_err(|e| {
+        log::error!("Failed to locate executable: {}", e);
+        AppError::LocationUnavailable(Location::Executable)
+    })?;
+    register_native(&exe)
+}
+
+/// 只在第一次运行时登记协议，成功后记录在设置中；开发构建不登记，避免覆盖已安装的版本
+pub fn register_on_first_run(store: &SettingsStore) {
+    if cfg!(debug_assertions) || store.get(SCHEME_REGISTERED_KEY).is_some() {
+        return;
+    }
+    let result =
+        register_scheme().and_then(|_| store.set(SCHEME_REGISTERED_KEY, Value::Bool(true)));
+    if let Err(e) = result {
+        log::error!("Failed to register {} scheme: {}", SCHEME, e);
+    }
+}
+
+#[cfg(windows)]
+fn register_native(exe: &Path) -> Result<(), AppError> {
+    use crate::registry::set_value;
+
+    let key = format!("Software\\Classes\\{}", SCHEME);
+    let command_key = format!("{}\\shell\\open\\command", key);
+    let command = format!("\"{}\" \"%1\"", exe.display());
+    let values = [
+        (&key, None, "URL:MathLive Formula"),
+        (&key, Some("URL Protocol"), ""),
+        (&command_key, None, command.as_str()),
+    ];
+    for (key, name, value) in values {
+        set_value(key, name, value).map_err(|e| {
+            AppError::io(
+                Path::new(&format!("HKEY_CURRENT_USER\\{}", key)),
+                "Failed to update registry",
+                e,
+            )
+        })?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn register_native(exe: &Path) -> Result<(), AppError> {
+    use std::process::Command;
+
+    const DESKTOP_FILE: &str = "mathlive-formula-handler.desktop";
+
+    let dir = tauri::api::path::data_dir()
+        .ok_or(AppError::LocationUnavailable(Location::DataDir))?
+        .join("applications");
+    std::fs::create_dir_all(&dir)
+        .map_err(|e| AppError::io(&dir, "Failed to create directory", e))?;
+    let entry = format!(
+        "[Desktop Entry]\nType=Application\nName=MathLive Formula Editor\nExec={} %u\nNoDisplay=true\nMimeType=x-scheme-handler/{};\n",
+        desktop_quote(&exe.to_string_lossy()),
+        SCHEME
+    );
+    let desktop = dir.join(DESKTOP_FILE);
+    std::fs::write(&desktop, entry)
+        .map_err(|e| AppError::io(&desktop, "Failed to write desktop entry", e))?;
+    let status = Command::new("xdg-mime")
+        .args(["default", DESKTOP_FILE])
+        .arg(format!("x-scheme-handler/{}", SCHEME))
+        .status();
+    match status {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(AppError::SpawnFailed {
+            program: "xdg-mime".to_string(),
+            message: status.to_string(),
+        }),
+        Err(e) => Err(AppError::SpawnFailed {
+            program: "xdg-mime".to_string(),
+            message: e.to_string(),
+        }),
+    }
+}
+
+/// macOS 的协议在 Info.plist 中声明，无需运行时登记
+#[cfg(target_os = "macos")]
+fn register_native(_exe: &Path) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// 按 Desktop Entry 规范给 `Exec` 中的参数加引号
+#[cfg(target_os = "linux")]
+pub fn desktop_quote(arg: &str) -> String {
+    let mut quoted = String::from("\"");
+    for ch in arg.chars() {
+        match ch {
+            '"' | '`' | '$' | '\\' => quoted.push('\\'),
+            // `%` 在 Exec 中是字段代码的前缀
+            '%' => quoted.push('%'),
+            _ => {}
+        }
+        quoted.push(ch);
+    }
+    quoted.push('"');
+    quoted
+}
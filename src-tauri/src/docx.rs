@@ -0,0 +1,887 @@
+//! Word 文档与公式的互相转换：导出时公式转换为 Office 原生公式（OMML），Word 中可直接编辑；
+//! 导入时从文档中读出 OMML 公式并转换回 LaTeX
+//!
+//! 两个方向都经过 `math` 模块的语法树；无法解析的公式导出时以等宽字体保留原始 LaTeX。
+
+use std::io::{BufReader, Cursor, Read, Seek, Write};
+
+use quick_xml::events::Event;
+use quick_xml::{Reader, Writer};
+use zip::write::FileOptions;
+use zip::{ZipArchive, ZipWriter};
+
+use crate::error::AppError;
+use crate::locale::{self, localized};
+use crate::math::{self, escape_xml, Accent, Node, Variant};
+
+const CONTENT_TYPES: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types">
+<Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/>
+<Default Extension="xml" ContentType="application/xml"/>
+<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/>
+</Types>"#;
+
+const ROOT_RELS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+<Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+</Relationships>"#;
+
+const DOCUMENT_START: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:m="http://schemas.openxmlformats.org/officeDocument/2006/math"><w:body>"#;
+
+const DOCUMENT_END: &str = "<w:sectPr/></w:body></w:document>";
+
+/// 一条待写入的公式
+pub struct Entry<'a> {
+    pub latex: &'a str,
+    pub note: Option<&'a str>,
+}
+
+/// 转换失败的公式：位置与原因
+pub struct Failure {
+    pub position: usize,
+    pub message: String,
+}
+
+/// 生成 `.docx` 文件内容，同时返回转换失败的公式
+pub fn build(entries: &[Entry]) -> Result<(Vec<u8>, Vec<Failure>), AppError> {
+    let mut body = String::new();
+    let mut failures = Vec::new();
+    for (position, entry) in entries.iter().enumerate() {
+        if let Some(note) = entry.note.map(str::trim).filter(|n| !n.is_empty()) {
+            body.push_str(&format!(
+                "<w:p><w:r><w:rPr><w:b/></w:rPr><w:t xml:space=\"preserve\">{}</w:t></w:r></w:p>",
+                escape_xml(note)
+            ));
+        }
+        match math::parse(entry.latex) {
+            Ok(node) => {
+                let mut omml = String::new();
+                write_omml(&node, None, &mut omml);
+                body.push_str(&format!(
+                    "<w:p><m:oMathPara><m:oMath>{}</m:oMath></m:oMathPara></w:p>",
+                    omml
+                ));
+            }
+            Err(error) => {
+                body.push_str(&format!(
+                    "<w:p><w:r><w:rPr><w:rFonts w:ascii=\"Consolas\" w:hAnsi=\"Consolas\"/><w:shd w:val=\"clear\" w:fill=\"F2F2F2\"/></w:rPr><w:t xml:space=\"preserve\">{}</w:t></w:r></w:p>",
+                    escape_xml(entry.latex)
+                ));
+                failures.push(Failure {
+                    position,
+                    message: error.to_string(),
+                });
+            }
+        }
+    }
+
+    let document = format!("{}{}{}", DOCUMENT_START, body, DOCUMENT_END);
+    let bytes = package(&[
+        ("[Content_Types].xml", CONTENT_TYPES),
+        ("_rels/.rels", ROOT_RELS),
+        ("word/document.xml", &document),
+    ])
+    .map_err(|e| AppError::EncodeFailed {
+        format: "Word".to_string(),
+        message: e.to_string(),
+    })?;
+    Ok((bytes, failures))
+}
+
+fn package(parts: &[(&str, &str)]) -> zip::result::ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+    for (name, content) in parts {
+        zip.start_file(*name, options)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    Ok(zip.finish()?.into_inner())
+}
+
+fn run(text: &str, variant: Option<Variant>, out: &mut String) {
+    let props = match variant {
+        Some(Variant::Normal) => "<m:rPr><m:sty m:val=\"p\"/></m:rPr>",
+        Some(Variant::Bold) => "<m:rPr><m:sty m:val=\"b\"/></m:rPr>",
+        Some(Variant::Italic) => "<m:rPr><m:sty m:val=\"i\"/></m:rPr>",
+        Some(Variant::DoubleStruck) => "<m:rPr><m:scr m:val=\"double-struck\"/></m:rPr>",
+        Some(Variant::Script) => "<m:rPr><m:scr m:val=\"script\"/></m:rPr>",
+        Some(Variant::Fraktur) => "<m:rPr><m:scr m:val=\"fraktur\"/></m:rPr>",
+        Some(Variant::SansSerif) => "<m:rPr><m:scr m:val=\"sans-serif\"/></m:rPr>",
+        Some(Variant::Monospace) => "<m:rPr><m:scr m:val=\"monospace\"/></m:rPr>",
+        None => "",
+    };
+    out.push_str(&format!(
+        "<m:r>{}<m:t xml:space=\"preserve\">{}</m:t></m:r>",
+        props,
+        escape_xml(text)
+    ));
+}
+
+/// 写出 `<m:xxx>...</m:xxx>` 包裹的子表达式
+fn part(tag: &str, node: Option<&Node>, variant: Option<Variant>, out: &mut String) {
+    out.push_str(&format!("<m:{}>", tag));
+    if let Some(node) = node {
+        write_omml(node, variant, out);
+    }
+    out.push_str(&format!("</m:{}>", tag));
+}
+
+fn delimiters(open: &str, close: &str) -> String {
+    format!(
+        "<m:dPr><m:begChr m:val=\"{}\"/><m:endChr m:val=\"{}\"/></m:dPr>",
+        escape_xml(open),
+        escape_xml(close)
+    )
+}
+
+fn write_omml(node: &Node, variant: Option<Variant>, out: &mut String) {
+    match node {
+        Node::Ident(name) if name.chars().count() > 1 => {
+            run(name, Some(variant.unwrap_or(Variant::Normal)), out)
+        }
+        Node::Ident(name) | Node::Number(name) => run(name, variant, out),
+        Node::Operator(op) | Node::LargeOp(op) => run(op, None, out),
+        Node::Text(text) => out.push_str(&format!(
+            "<m:r><m:rPr><m:nor/></m:rPr><m:t xml:space=\"preserve\">{}</m:t></m:r>",
+            escape_xml(text)
+        )),
+        Node::Space(_) => run("\u{2009}", None, out),
+        Node::Row(nodes) => write_row(nodes, variant, out),
+        Node::Frac { num, den, bar } => {
+            out.push_str("<m:f>");
+            if !bar {
+                out.push_str("<m:fPr><m:type m:val=\"noBar\"/></m:fPr>");
+            }
+            part("num", Some(num), variant, out);
+            part("den", Some(den), variant, out);
+            out.push_str("</m:f>");
+        }
+        Node::Sqrt { body, index } => {
+            out.push_str("<m:rad>");
+            if index.is_none() {
+                out.push_str("<m:radPr><m:degHide m:val=\"1\"/></m:radPr>");
+            }
+            part("deg", index.as_deref(), variant, out);
+            part("e", Some(body), variant, out);
+            out.push_str("</m:rad>");
+        }
+        Node::Scripts { base, sub, sup } => {
+            if let Node::LargeOp(op) = base.as_ref() {
+                write_nary(op, sub.as_deref(), sup.as_deref(), None, variant, out);
+                return;
+            }
+            let tag = match (sub, sup) {
+                (Some(_), Some(_)) => "sSubSup",
+                (Some(_), None) => "sSub",
+                _ => "sSup",
+            };
+            out.push_str(&format!("<m:{}>", tag));
+            part("e", Some(base), variant, out);
+            if let Some(sub) = sub {
+                part("sub", Some(sub), variant, out);
+            }
+            if let Some(sup) = sup {
+                part("sup", Some(sup), variant, out);
+            }
+            out.push_str(&format!("</m:{}>", tag));
+        }
+        Node::Accent { body, accent } => match accent {
+            Accent::Overline | Accent::Underline => {
+                let pos = if *accent == Accent::Overline {
+                    "top"
+                } else {
+                    "bot"
+                };
+                out.push_str(&format!(
+                    "<m:bar><m:barPr><m:pos m:val=\"{}\"/></m:barPr>",
+                    pos
+                ));
+                part("e", Some(body), variant, out);
+                out.push_str("</m:bar>");
+            }
+            _ => {
+                let chr = match accent {
+                    Accent::Hat => "\u{0302}",
+                    Accent::Bar => "\u{0305}",
+                    Accent::Vec => "\u{20D7}",
+                    Accent::Dot => "\u{0307}",
+                    Accent::Ddot => "\u{0308}",
+                    _ => "\u{0303}",
+                };
+                out.push_str(&format!(
+                    "<m:acc><m:accPr><m:chr m:val=\"{}\"/></m:accPr>",
+                    chr
+                ));
+                part("e", Some(body), variant, out);
+                out.push_str("</m:acc>");
+            }
+        },
+        Node::Fenced { open, close, body } => {
+            out.push_str("<m:d>");
+            out.push_str(&delimiters(open, close));
+            part("e", Some(body), variant, out);
+            out.push_str("</m:d>");
+        }
+        Node::Matrix {
+            rows,
+            open,
+            close,
+            aligned,
+        } => {
+            let fenced = !open.is_empty() || !close.is_empty();
+            if fenced {
+                out.push_str("<m:d>");
+                out.push_str(&delimiters(open, close));
+                out.push_str("<m:e>");
+            }
+            if *aligned {
+                // 公式数组中每行是一个整体，`&` 对齐点合并到同一行
+                out.push_str("<m:eqArr>");
+                for row in rows {
+                    out.push_str("<m:e>");
+                    for cell in row {
+                        write_omml(cell, variant, out);
+                    }
+                    out.push_str("</m:e>");
+                }
+                out.push_str("</m:eqArr>");
+            } else {
+                out.push_str("<m:m>");
+                for row in rows {
+                    out.push_str("<m:mr>");
+                    for cell in row {
+                        part("e", Some(cell), variant, out);
+                    }
+                    out.push_str("</m:mr>");
+                }
+                out.push_str("</m:m>");
+            }
+            if fenced {
+                out.push_str("</m:e></m:d>");
+            }
+        }
+        Node::Styled { variant, body } => write_omml(body, Some(*variant), out),
+    }
+}
+
+/// 大型运算符在 OMML 中需要把后面紧跟的表达式作为运算对象
+fn write_row(nodes: &[Node], variant: Option<Variant>, out: &mut String) {
+    let mut idx = 0;
+    while idx < nodes.len() {
+        let (op, sub, sup) = match &nodes[idx] {
+            Node::LargeOp(op) => (op, None, None),
+            Node::Scripts { base, sub, sup } => match base.as_ref() {
+                Node::LargeOp(op) => (op, sub.as_deref(), sup.as_deref()),
+                _ => {
+                    write_omml(&nodes[idx], variant, out);
+                    idx += 1;
+                    continue;
+                }
+            },
+            other => {
+                write_omml(other, variant, out);
+                idx += 1;
+                continue;
+            }
+        };
+        let body = nodes.get(idx + 1);
+        write_nary(op, sub, sup, body, variant, out);
+        idx += if body.is_some() { 2 } else { 1 };
+    }
+}
+
+fn write_nary(
+    op: &str,
+    sub: Option<&Node>,
+    sup: Option<&Node>,
+    body: Option<&Node>,
+    variant: Option<Variant>,
+    out: &mut String,
+) {
+    out.push_str(&format!(
+        "<m:nary><m:naryPr><m:chr m:val=\"{}\"/>",
+        escape_xml(op)
+    ));
+    if sub.is_none() {
+        out.push_str("<m:subHide m:val=\"1\"/>");
+    }
+    if sup.is_none() {
+        out.push_str("<m:supHide m:val=\"1\"/>");
+    }
+    out.push_str("</m:naryPr>");
+    part("sub", sub, variant, out);
+    part("sup", sup, variant, out);
+    part("e", body, variant, out);
+    out.push_str("</m:nary>");
+}
+
+/// 导入的备注最多保留的字符数
+const NOTE_LIMIT: usize = 80;
+
+/// 从文档中读出的公式
+pub struct Imported {
+    pub latex: String,
+    pub note: Option<String>,
+}
+
+/// 无法转换的公式，保留原始 OMML
+pub struct ImportFailure {
+    /// 在文档所有公式中的序号，从 1 开始
+    pub position: usize,
+    pub omml: String,
+    pub message: String,
+    pub note: Option<String>,
+}
+
+/// 公式的 OMML 元素树；只保留 `m:` 命名空间的元素
+struct Element {
+    name: String,
+    /// `m:val` 属性
+    val: Option<String>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    /// 读取 `xxxPr` 属性元素中的某一项
+    fn prop(&self, pr: &str, key: &str) -> Option<&Element> {
+        self.child(pr)?.child(key)
+    }
+
+    /// 开关型属性：存在且值不是 `0`/`off`/`false`
+    fn flag(&self, pr: &str, key: &str) -> bool {
+        self.prop(pr, key)
+            .is_some_and(|e| !matches!(e.val.as_deref(), Some("0" | "off" | "false")))
+    }
+
+    fn prop_val(&self, pr: &str, key: &str) -> Option<&str> {
+        self.prop(pr, key).and_then(|e| e.val.as_deref())
+    }
+}
+
+/// 段落中已读到的文字和公式
+#[derive(Default)]
+struct Paragraph {
+    text: String,
+    /// 本段中的公式：`(是否转换成功, 在对应列表中的下标)`
+    equations: Vec<(bool, usize)>,
+}
+
+fn truncate_note(text: &str) -> Option<String> {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.is_empty() {
+        return None;
+    }
+    if text.chars().count() <= NOTE_LIMIT {
+        return Some(text);
+    }
+    let mut short: String = text.chars().take(NOTE_LIMIT).collect();
+    short.push('…');
+    Some(short)
+}
+
+/// 读取 `.docx` 中的所有公式，备注取公式所在段落的文字；公式独占一段时取前一段的文字
+///
+/// `word/document.xml` 以流式方式读取，只有单个公式的元素树会保留在内存中。
+pub fn read_equations<R: Read + Seek>(
+    reader: R,
+) -> Result<(Vec<Imported>, Vec<ImportFailure>), AppError> {
+    let invalid = |message: String| AppError::InvalidDocument {
+        format: "Word".to_string(),
+        message,
+    };
+    let mut archive = ZipArchive::new(reader).map_err(|e| invalid(e.to_string()))?;
+    let document = archive
+        .by_name("word/document.xml")
+        .map_err(|e| invalid(format!("word/document.xml: {}", e)))?;
+    let mut xml = Reader::from_reader(BufReader::new(document));
+    let invalid_xml = |e: quick_xml::Error| invalid(format!("word/document.xml: {}", e));
+
+    let mut imported = Vec::new();
+    let mut failures = Vec::new();
+    let mut paragraphs: Vec<Paragraph> = Vec::new();
+    let mut previous_text = String::new();
+    let mut in_text = false;
+    // 正在读取的公式：元素栈与原始 OMML
+    let mut stack: Vec<Element> = Vec::new();
+    let mut raw: Option<Writer<Vec<u8>>> = None;
+    let mut buf = Vec::new();
+
+    loop {
+        // 循环体中有多处 `continue`，在读取前清空缓冲区才不会遗漏
+        buf.clear();
+        let event = xml.read_event_into(&mut buf).map_err(invalid_xml)?;
+        if let Event::Eof = event {
+            break;
+        }
+        if let Some(writer) = raw.as_mut() {
+            writer
+                .write_event(event.borrow())
+                .map_err(|e| invalid_xml(e.into()))?;
+        }
+        match &event {
+            Event::Start(e) | Event::Empty(e) => {
+                let name = e.name();
+                let local = String::from_utf8_lossy(name.local_name().as_ref()).to_string();
+                let is_math = name.prefix().is_some_and(|p| p.as_ref() == b"m");
+                let empty = matches!(event, Event::Empty(_));
+                if is_math && local == "oMath" && raw.is_none() {
+                    if empty {
+                        continue;
+                    }
+                    let mut writer = Writer::new(Vec::new());
+                    writer
+                        .write_event(event.borrow())
+                        .map_err(|e| invalid_xml(e.into()))?;
+                    raw = Some(writer);
+                }
+                if raw.is_some() {
+                    if !is_math {
+                        // 非公式元素只占位，内容不参与转换
+                        if !empty {
+                            stack.push(Element {
+                                name: String::new(),
+                                val: None,
+                                children: Vec::new(),
+                                text: String::new(),
+                            });
+                        }
+                        continue;
+                    }
+                    let val = e
+                        .try_get_attribute("m:val")
+                        .ok()
+                        .flatten()
+                        .and_then(|a| a.unescape_value().ok())
+                        .map(|v| v.to_string());
+                    let element = Element {
+                        name: local,
+                        val,
+                        children: Vec::new(),
+                        text: String::new(),
+                    };
+                    if empty {
+                        if let Some(parent) = stack.last_mut() {
+                            parent.children.push(element);
+                        }
+                    } else {
+                        stack.push(element);
+                    }
+                    continue;
+                }
+                match (name.as_ref(), empty) {
+                    (b"w:p", false) => paragraphs.push(Paragraph::default()),
+                    (b"w:t", false) => in_text = true,
+                    (b"w:tab" | b"w:br", _) => {
+                        if let Some(p) = paragraphs.last_mut() {
+                            p.text.push(' ');
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            Event::Text(t) => {
+                let text = t.unescape().map_err(invalid_xml)?;
+                if let Some(element) = stack.last_mut() {
+                    if element.name == "t" {
+                        element.text.push_str(&text);
+                    }
+                } else if in_text {
+                    if let Some(p) = paragraphs.last_mut() {
+                        p.text.push_str(&text);
+                    }
+                }
+            }
+            Event::End(e) => {
+                if raw.is_some() {
+                    let Some(element) = stack.pop() else {
+                        continue;
+                    };
+                    if let Some(parent) = stack.last_mut() {
+                        if !element.name.is_empty() {
+                            parent.children.push(element);
+                        }
+                        continue;
+                    }
+                    // oMath 结束
+                    let omml =
+                        String::from_utf8_lossy(&raw.take().unwrap().into_inner()).to_string();
+                    let position = imported.len() + failures.len() + 1;
+                    let entry = convert(&element)
+                        .map(|node| math::to_latex(&node))
+                        .and_then(|latex| {
+                            if latex.is_empty() {
+                                Err(localized!(locale::current(),
+                                    zh: "公式为空",
+                                    en: "empty formula"))
+                            } else {
+                                Ok(latex)
+                            }
+                        });
+                    let slot = match entry {
+                        Ok(latex) => {
+                            imported.push(Imported { latex, note: None });
+                            (true, imported.len() - 1)
+                        }
+                        Err(message) => {
+                            failures.push(ImportFailure {
+                                position,
+                                omml,
+                                message,
+                                note: None,
+                            });
+                            (false, failures.len() - 1)
+                        }
+                    };
+                    if let Some(p) = paragraphs.last_mut() {
+                        p.equations.push(slot);
+                    }
+                    continue;
+                }
+                match e.name().as_ref() {
+                    b"w:t" => in_text = false,
+                    b"w:p" => {
+                        let Some(paragraph) = paragraphs.pop() else {
+                            continue;
+                        };
+                        let own = truncate_note(&paragraph.text);
+                        if paragraph.equations.is_empty() {
+                            if own.is_some() {
+                                previous_text = paragraph.text;
+                            }
+                            continue;
+                        }
+                        let note = own.or_else(|| truncate_note(&previous_text));
+                        previous_text.clear();
+                        for (ok, idx) in paragraph.equations {
+                            if ok {
+                                imported[idx].note = note.clone();
+                            } else {
+                                failures[idx].note = note.clone();
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok((imported, failures))
+}
+
+/// 转换所有子元素（跳过 `xxxPr` 属性元素）并合并为一行
+fn convert_children(element: &Element) -> Result<Node, String> {
+    let mut nodes = Vec::new();
+    for child in &element.children {
+        if child.name.ends_with("Pr") {
+            continue;
+        }
+        match convert(child)? {
+            Node::Row(mut inner) => nodes.append(&mut inner),
+            node => nodes.push(node),
+        }
+    }
+    Ok(Node::Row(nodes))
+}
+
+fn convert_part(element: &Element, name: &str) -> Result<Option<Node>, String> {
+    element.child(name).map(convert_children).transpose()
+}
+
+fn convert_required(element: &Element, name: &str) -> Result<Node, String> {
+    Ok(convert_part(element, name)?.unwrap_or(Node::Row(Vec::new())))
+}
+
+fn is_empty_node(node: &Node) -> bool {
+    matches!(node, Node::Row(nodes) if nodes.is_empty())
+}
+
+fn non_empty(node: Option<Node>) -> Option<Box<Node>> {
+    node.filter(|n| !is_empty_node(n)).map(Box::new)
+}
+
+fn convert(element: &Element) -> Result<Node, String> {
+    match element.name.as_str() {
+        "oMath" | "oMathPara" | "e" | "num" | "den" | "sub" | "sup" | "deg" | "lim" | "fName"
+        | "box" | "borderBox" | "phant" | "groupChr" | "mr" => convert_children(element),
+        "r" => convert_run(element),
+        "f" => Ok(Node::Frac {
+            num: Box::new(convert_required(element, "num")?),
+            den: Box::new(convert_required(element, "den")?),
+            bar: element.prop_val("fPr", "type") != Some("noBar"),
+        }),
+        "rad" => {
+            let index = if element.flag("radPr", "degHide") {
+                None
+            } else {
+                non_empty(convert_part(element, "deg")?)
+            };
+            Ok(Node::Sqrt {
+                body: Box::new(convert_required(element, "e")?),
+                index,
+            })
+        }
+        "sSub" | "sSup" | "sSubSup" | "limLow" | "limUpp" => {
+            let (sub, sup) = match element.name.as_str() {
+                "limLow" => (convert_part(element, "lim")?, None),
+                "limUpp" => (None, convert_part(element, "lim")?),
+                _ => (convert_part(element, "sub")?, convert_part(element, "sup")?),
+            };
+            Ok(Node::Scripts {
+                base: Box::new(convert_required(element, "e")?),
+                sub: non_empty(sub),
+                sup: non_empty(sup),
+            })
+        }
+        "sPre" => Ok(Node::Row(vec![
+            Node::Scripts {
+                base: Box::new(Node::Row(Vec::new())),
+                sub: non_empty(convert_part(element, "sub")?),
+                sup: non_empty(convert_part(element, "sup")?),
+            },
+            convert_required(element, "e")?,
+        ])),
+        "nary" => {
+            let op = element.prop_val("naryPr", "chr").unwrap_or("∫").to_string();
+            let sub = if element.flag("naryPr", "subHide") {
+                None
+            } else {
+                non_empty(convert_part(element, "sub")?)
+            };
+            let sup = if element.flag("naryPr", "supHide") {
+                None
+            } else {
+                non_empty(convert_part(element, "sup")?)
+            };
+            let op = if math::is_large_operator(&op) {
+                Node::LargeOp(op)
+            } else {
+                Node::Operator(op)
+            };
+            let op = if sub.is_some() || sup.is_some() {
+                Node::Scripts {
+                    base: Box::new(op),
+                    sub,
+                    sup,
+                }
+            } else {
+                op
+            };
+            Ok(Node::Row(vec![op, convert_required(element, "e")?]))
+        }
+        "d" => {
+            let open = element.prop_val("dPr", "begChr").unwrap_or("(").to_string();
+            let close = element.prop_val("dPr", "endChr").unwrap_or(")").to_string();
+            let sep = element.prop_val("dPr", "sepChr").unwrap_or("|").to_string();
+            let mut parts = Vec::new();
+            for (idx, e) in element
+                .children
+                .iter()
+                .filter(|c| c.name == "e")
+                .enumerate()
+            {
+                if idx > 0 {
+                    parts.push(Node::Operator(sep.clone()));
+                }
+                parts.push(convert_children(e)?);
+            }
+            let body = match math_single(parts) {
+                Node::Row(mut nodes) if nodes.len() == 1 => nodes.pop().unwrap(),
+                body => body,
+            };
+            if let Node::Matrix {
+                rows,
+                open: inner_open,
+                close: inner_close,
+                aligned: false,
+            } = &body
+            {
+                if inner_open.is_empty() && inner_close.is_empty() {
+                    return Ok(Node::Matrix {
+                        rows: rows.clone(),
+                        open,
+                        close,
+                        aligned: false,
+                    });
+                }
+            }
+            Ok(Node::Fenced {
+                open,
+                close,
+                body: Box::new(body),
+            })
+        }
+        "acc" => {
+            let accent = match element.prop_val("accPr", "chr").unwrap_or("\u{0302}") {
+                "\u{0305}" | "\u{00AF}" | "\u{0304}" => Accent::Bar,
+                "\u{20D7}" | "\u{2192}" => Accent::Vec,
+                "\u{0307}" | "\u{02D9}" => Accent::Dot,
+                "\u{0308}" | "\u{00A8}" => Accent::Ddot,
+                "\u{0303}" | "~" => Accent::Tilde,
+                _ => Accent::Hat,
+            };
+            Ok(Node::Accent {
+                body: Box::new(convert_required(element, "e")?),
+                accent,
+            })
+        }
+        "bar" => Ok(Node::Accent {
+            body: Box::new(convert_required(element, "e")?),
+            accent: if element.prop_val("barPr", "pos") == Some("top") {
+                Accent::Overline
+            } else {
+                Accent::Underline
+            },
+        }),
+        "m" => {
+            let rows = element
+                .children
+                .iter()
+                .filter(|c| c.name == "mr")
+                .map(|row| {
+                    row.children
+                        .iter()
+                        .filter(|c| c.name == "e")
+                        .map(convert_children)
+                        .collect::<Result<Vec<_>, _>>()
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(Node::Matrix {
+                rows,
+                open: String::new(),
+                close: String::new(),
+                aligned: false,
+            })
+        }
+        "eqArr" => {
+            // 每行中的 `&` 是对齐点，拆分为单元格
+            let rows = element
+                .children
+                .iter()
+                .filter(|c| c.name == "e")
+                .map(|row| {
+                    let Node::Row(nodes) = convert_children(row)? else {
+                        return Err(localized!(locale::current(),
+                            zh: "m:eqArr 的行结构无效",
+                            en: "invalid m:eqArr row structure"));
+                    };
+                    Ok(nodes
+                        .split(|n| matches!(n, Node::Operator(op) if op == "&"))
+                        .map(|cell| Node::Row(cell.to_vec()))
+                        .collect())
+                })
+                .collect::<Result<Vec<_>, String>>()?;
+            Ok(Node::Matrix {
+                rows,
+                open: String::new(),
+                close: String::new(),
+                aligned: true,
+            })
+        }
+        "func" => Ok(Node::Row(vec![
+            convert_required(element, "fName")?,
+            convert_required(element, "e")?,
+        ])),
+        // 非 `m:` 命名空间的占位元素
+        "" => Ok(Node::Row(Vec::new())),
+        name if name.ends_with("Pr") => Ok(Node::Row(Vec::new())),
+        name => Err(localized!(locale::current(),
+            zh: "不支持的公式结构 m:{}",
+            en: "unsupported formula structure m:{}",
+            name)),
+    }
+}
+
+fn math_single(mut nodes: Vec<Node>) -> Node {
+    if nodes.len() == 1 {
+        nodes.pop().unwrap()
+    } else {
+        Node::Row(nodes)
+    }
+}
+
+/// 把文本块拆分为数字、字母和运算符；`m:nor` 的文本块是普通文字
+fn convert_run(element: &Element) -> Result<Node, String> {
+    let text: String = element
+        .children
+        .iter()
+        .filter(|c| c.name == "t")
+        .map(|c| c.text.as_str())
+        .collect();
+    if element.prop("rPr", "nor").is_some() {
+        return Ok(Node::Text(text));
+    }
+    let plain = element.prop_val("rPr", "sty") == Some("p");
+    let variant = match (
+        element.prop_val("rPr", "sty"),
+        element.prop_val("rPr", "scr"),
+    ) {
+        (_, Some("double-struck")) => Some(Variant::DoubleStruck),
+        (_, Some("script")) => Some(Variant::Script),
+        (_, Some("fraktur")) => Some(Variant::Fraktur),
+        (_, Some("sans-serif")) => Some(Variant::SansSerif),
+        (_, Some("monospace")) => Some(Variant::Monospace),
+        (Some("b" | "bi"), _) => Some(Variant::Bold),
+        _ => None,
+    };
+
+    let mut nodes = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() || c == '\u{2009}' {
+            continue;
+        }
+        if c.is_ascii_digit() {
+            let mut number = c.to_string();
+            while let Some(&d) = chars.peek() {
+                if d.is_ascii_digit() || (d == '.' && !number.contains('.')) {
+                    number.push(d);
+                    chars.next();
+                } else {
+                    break;
+                }
+            }
+            nodes.push(Node::Number(number));
+        } else if c.is_ascii_alphabetic() {
+            let mut word = c.to_string();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_alphabetic()) {
+                word.push(d);
+                chars.next();
+            }
+            if math::is_function(&word) || (plain && word.len() > 1) {
+                nodes.push(Node::Ident(word));
+            } else {
+                nodes.extend(word.chars().map(|l| {
+                    let ident = Node::Ident(l.to_string());
+                    if plain {
+                        Node::Styled {
+                            variant: Variant::Normal,
+                            body: Box::new(ident),
+                        }
+                    } else {
+                        ident
+                    }
+                }));
+            }
+        } else if c.is_alphabetic() {
+            nodes.push(Node::Ident(c.to_string()));
+        } else if math::is_large_operator(&c.to_string()) {
+            nodes.push(Node::LargeOp(c.to_string()));
+        } else {
+            nodes.push(Node::Operator(c.to_string()));
+        }
+    }
+    Ok(match variant {
+        Some(variant) if !nodes.is_empty() => Node::Styled {
+            variant,
+            body: Box::new(math_single(nodes)),
+        },
+        _ => Node::Row(nodes),
+    })
+}
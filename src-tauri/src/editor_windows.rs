@@ -0,0 +1,84 @@
+//! 多个编辑器窗口：为新窗口分配标签，并记录每个窗口绑定的文件
+//!
+//! 同一个文件只能由一个窗口绑定，另一个窗口写入时返回 `OpenInOtherWindow`，
+//! 避免两个窗口的自动保存互相覆盖。
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use crate::error::AppError;
+
+/// 额外窗口的标签前缀，主窗口的标签为 "main"
+const EDITOR_LABEL_PREFIX: &str = "editor-";
+
+/// 编辑器窗口登记表，作为 `tauri::State` 托管
+#[derive(Default)]
+pub struct EditorWindows {
+    next_id: AtomicU64,
+    /// 窗口标签到其绑定文件（规范化路径）的映射
+    files: Mutex<HashMap<String, PathBuf>>,
+}
+
+impl EditorWindows {
+    /// 分配一个未使用过的窗口标签
+    pub fn next_label(&self) -> String {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        format!("{}{}", EDITOR_LABEL_PREFIX, id)
+    }
+
+    /// 绑定了该文件的窗口标签
+    pub fn owner(&self, path: &Path) -> Option<String> {
+        let path = canonical_path(path);
+        self.files
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|(_, file)| **file == path)
+            .map(|(label, _)| label.clone())
+    }
+
+    /// 登记窗口绑定的文件，`None` 表示解除绑定；文件已被其他窗口绑定时报错
+    pub fn claim(&self, label: &str, path: Option<&Path>) -> Result<(), AppError> {
+        let mut files = self.files.lock().unwrap();
+        let Some(path) = path else {
+            files.remove(label);
+            return Ok(());
+        };
+        let path = canonical_path(path);
+        if let Some((other, _)) = files
+            .iter()
+            .find(|(l, f)| l.as_str() != label && **f == path)
+        {
+            return Err(AppError::OpenInOtherWindow {
+                path: path.to_string_lossy().to_string(),
+                window: other.clone(),
+            });
+        }
+        files.insert(label.to_string(), path);
+        Ok(())
+    }
+
+    /// 写入前检查文件是否被其他窗口绑定
+    pub fn check_write(&self, label: &str, path: &Path) -> Result<(), AppError> {
+        match self.owner(path) {
+            Some(owner) if owner != label => Err(AppError::OpenInOtherWindow {
+                path: path.to_string_lossy().to_string(),
+                window: owner,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// 窗口销毁时释放其绑定的文件
+    pub fn release(&self, label: &str) {
+        self.files.lock().unwrap().remove(label);
+    }
+}
+
+/// 尽量规范化路径，使同一文件的不同写法能匹配；文件尚不存在时按原样比较
+fn canonical_path(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
@@ -0,0 +1,251 @@
+//! 文本文件编码：读取时识别 BOM、UTF-16 以及 Windows 工具常见的 GBK 编码，
+//! 导出时按选项转换编码和换行符
+
+use encoding_rs::{EncoderResult, Encoding, GB18030, GBK, UTF_16BE, UTF_16LE};
+use serde::Deserialize;
+
+use crate::locale::{self, Locale};
+
+/// 按顺序尝试的编码名称，解码失败时用于错误提示
+pub const ATTEMPTED_ENCODINGS: &[&str] = &["UTF-8", "UTF-16LE", "UTF-16BE", "GBK"];
+
+/// 将文件字节解码为字符串；无法识别时返回 `None`
+pub fn decode_text(bytes: &[u8]) -> Option<String> {
+    if let Some((encoding, bom_len)) = Encoding::for_bom(bytes) {
+        let (text, had_errors) = encoding.decode_without_bom_handling(&bytes[bom_len..]);
+        return (!had_errors).then(|| text.into_owned());
+    }
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Some(text.to_string());
+    }
+    if let Some(encoding) = guess_utf16(bytes) {
+        let (text, had_errors) = encoding.decode_without_bom_handling(bytes);
+        if !had_errors {
+            return Some(text.into_owned());
+        }
+    }
+    GBK.decode_without_bom_handling_and_without_replacement(bytes)
+        .map(|text| text.into_owned())
+}
+
+/// 解码文件开头截取的一段字节。截断处可能落在多字节字符中间：除末尾不完整的字符外都是有效
+/// UTF-8 时按 UTF-8 解码，不能让截断导致回退到 GBK；其他编码去掉末尾至多 3 个字节后重试，
+/// 仍无法识别时按 UTF-8 宽松解码
+pub fn decode_head(bytes: &[u8]) -> String {
+    let utf8 = match std::str::from_utf8(bytes) {
+        Ok(_) => Some(bytes),
+        Err(e) if e.error_len().is_none() => Some(&bytes[..e.valid_up_to()]),
+        Err(_) => None,
+    };
+    if let Some(text) = utf8.and_then(decode_text) {
+        return text;
+    }
+    (0..=bytes.len().min(3))
+        .find_map(|trim| decode_text(&bytes[..bytes.len() - trim]))
+        .unwrap_or_else(|| String::from_utf8_lossy(bytes).into_owned())
+}
+
+/// 无 BOM 的 UTF-16：JSON 以 ASCII 为主，高字节几乎全为 0
+fn guess_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    if bytes.len() < 2 || bytes.len() % 2 != 0 {
+        return None;
+    }
+    let pairs = bytes.len() / 2;
+    let zeros_at = |offset: usize| bytes.iter().skip(offset).step_by(2).filter(|b| **b == 0).count();
+    let (even, odd) = (zeros_at(0), zeros_at(1));
+    if odd * 2 > pairs && even * 10 < pairs {
+        Some(UTF_16LE)
+    } else if even * 2 > pairs && odd * 10 < pairs {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// 导出文本文件的编码
+#[derive(Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+pub enum TextEncoding {
+    #[default]
+    #[serde(rename = "utf8")]
+    Utf8,
+    /// 带 BOM 的 UTF-8，部分 Windows 编辑器依赖它识别编码
+    #[serde(rename = "utf8-bom")]
+    Utf8Bom,
+    #[serde(rename = "gb18030")]
+    Gb18030,
+}
+
+/// 导出文本文件的换行符
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LineEndings {
+    Lf,
+    Crlf,
+    /// Windows 上为 CRLF，其他系统为 LF
+    #[default]
+    Platform,
+}
+
+/// 警告中最多列出的无法编码的字符数
+const MAX_UNMAPPABLE_SHOWN: usize = 5;
+
+/// 按编码和换行符选项把文本转换为要写入文件的字节；目标编码无法表示的字符替换为 `?`，
+/// 并在返回的警告中列出前几个，警告使用当前的后端语言
+pub fn encode_text(
+    text: &str,
+    encoding: TextEncoding,
+    line_endings: LineEndings,
+) -> (Vec<u8>, Vec<String>) {
+    let crlf = match line_endings {
+        LineEndings::Lf => false,
+        LineEndings::Crlf => true,
+        LineEndings::Platform => cfg!(windows),
+    };
+    let text = text.replace("\r\n", "\n");
+    let text = if crlf {
+        text.replace('\n', "\r\n")
+    } else {
+        text
+    };
+    match encoding {
+        TextEncoding::Utf8 => (text.into_bytes(), Vec::new()),
+        TextEncoding::Utf8Bom => {
+            let mut bytes = b"\xEF\xBB\xBF".to_vec();
+            bytes.extend_from_slice(text.as_bytes());
+            (bytes, Vec::new())
+        }
+        TextEncoding::Gb18030 => encode_legacy(&text, GB18030, locale::current()),
+    }
+}
+
+fn encode_legacy(
+    text: &str,
+    encoding: &'static Encoding,
+    locale: Locale,
+) -> (Vec<u8>, Vec<String>) {
+    let mut encoder = encoding.new_encoder();
+    let capacity = encoder
+        .max_buffer_length_from_utf8_without_replacement(text.len())
+        .unwrap_or(text.len() * 4);
+    let mut bytes = Vec::with_capacity(capacity);
+    let mut unmappable: Vec<char> = Vec::new();
+    let mut count = 0;
+    let mut rest = text;
+    loop {
+        let (result, read) =
+            encoder.encode_from_utf8_to_vec_without_replacement(rest, &mut bytes, true);
+        rest = &rest[read..];
+        match result {
+            EncoderResult::InputEmpty => break,
+            EncoderResult::OutputFull => bytes.reserve(rest.len() * 4 + 16),
+            EncoderResult::Unmappable(ch) => {
+                count += 1;
+                if unmappable.len() < MAX_UNMAPPABLE_SHOWN && !unmappable.contains(&ch) {
+                    unmappable.push(ch);
+                }
+                bytes.push(b'?');
+            }
+        }
+    }
+    let mut warnings = Vec::new();
+    if count > 0 {
+        let shown: Vec<String> = unmappable
+            .iter()
+            .map(|ch| format!("{} (U+{:04X})", ch, *ch as u32))
+            .collect();
+        warnings.push(match locale {
+            Locale::ZhCn => format!(
+                "{} 个字符无法用 {} 编码表示，已替换为 ?：{}",
+                count,
+                encoding.name(),
+                shown.join("、")
+            ),
+            Locale::En => format!(
+                "{} character(s) cannot be represented in {} and were replaced with ?: {}",
+                count,
+                encoding.name(),
+                shown.join(", ")
+            ),
+        });
+    }
+    (bytes, warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPECTED: &str =
+        "[\n  { \"id\": \"f1\", \"latex\": \"a^2 + b^2 = c^2\", \"note\": \"勾股定理\" }\n]\n";
+
+    #[test]
+    fn decodes_utf8_with_bom() {
+        let bytes = include_bytes!("../tests/fixtures/utf8-bom.json");
+        assert_eq!(decode_text(bytes).as_deref(), Some(EXPECTED));
+    }
+
+    #[test]
+    fn decodes_utf16le_with_and_without_bom() {
+        let with_bom = include_bytes!("../tests/fixtures/utf16le.json");
+        assert_eq!(decode_text(with_bom).as_deref(), Some(EXPECTED));
+        let without_bom = include_bytes!("../tests/fixtures/utf16le-nobom.json");
+        assert_eq!(decode_text(without_bom).as_deref(), Some(EXPECTED));
+    }
+
+    #[test]
+    fn decodes_gbk() {
+        let bytes = include_bytes!("../tests/fixtures/gbk.json");
+        assert_eq!(decode_text(bytes).as_deref(), Some(EXPECTED));
+    }
+
+    #[test]
+    fn rejects_undecodable_bytes() {
+        assert_eq!(decode_text(&[b'[', 0x81, 0x20, b']']), None);
+    }
+
+    #[test]
+    fn truncated_utf8_head_drops_partial_character() {
+        let bytes = EXPECTED.as_bytes();
+        let note = EXPECTED.find("勾股").unwrap();
+        // 截断在“股”的第二个字节之后
+        let head = &bytes[..note + "勾".len() + 2];
+        assert_eq!(decode_head(head), &EXPECTED[..note + "勾".len()]);
+    }
+
+    #[test]
+    fn truncated_utf16_head_drops_odd_byte() {
+        let bytes = include_bytes!("../tests/fixtures/utf16le.json");
+        let head = decode_head(&bytes[..bytes.len() - 1]);
+        assert_eq!(head, EXPECTED.trim_end_matches('\n'));
+    }
+
+    #[test]
+    fn truncated_gbk_head_drops_partial_character() {
+        let bytes = include_bytes!("../tests/fixtures/gbk.json");
+        let note = bytes.iter().position(|b| *b >= 0x80).unwrap();
+        // 截断在“股”的首字节之后
+        let head = decode_head(&bytes[..note + 3]);
+        assert!(head.ends_with("\"note\": \"勾"), "{:?}", head);
+    }
+
+    #[test]
+    fn unmappable_warning_follows_locale() {
+        let (_, warnings) = encode_legacy("x ≠ 😀", GB18030, Locale::ZhCn);
+        assert!(warnings.is_empty());
+
+        let (bytes, zh) = encode_legacy("a😀b", GBK, Locale::ZhCn);
+        assert_eq!(bytes, b"a?b");
+        assert_eq!(zh, ["1 个字符无法用 GBK 编码表示，已替换为 ?：😀 (U+1F600)"]);
+        let (_, en) = encode_legacy("a😀b", GBK, Locale::En);
+        assert_eq!(
+            en,
+            ["1 character(s) cannot be represented in GBK and were replaced with ?: 😀 (U+1F600)"]
+        );
+    }
+
+    #[test]
+    fn complete_head_is_decoded_normally() {
+        let bytes = include_bytes!("../tests/fixtures/gbk.json");
+        assert_eq!(decode_head(bytes), EXPECTED);
+    }
+}
@@ -0,0 +1,713 @@
+//! 命令返回给前端的结构化错误
+//!
+//! 序列化为 `{ code, message, details }`：`code` 是稳定的字符串，前端据此分支处理；
+//! `message` 面向用户展示，使用 `set_backend_locale` 选择的语言；`details` 携带路径、行列号等附加信息。
+
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
+use serde_json::{json, Value};
+
+use crate::locale::{self, localized, Locale};
+
+#[derive(Debug)]
+pub enum AppError {
+    /// 文件读写失败；`message` 是失败的阶段和系统给出的原因，只放在 details 中，不直接展示
+    Io {
+        path: String,
+        kind: io::ErrorKind,
+        message: String,
+    },
+    /// 没有访问文件的权限
+    PermissionDenied { path: String },
+    /// 目标文件已存在且未确认覆盖；`existing_mtime` 是 Unix 毫秒时间戳
+    Conflict {
+        path: String,
+        existing_size: u64,
+        existing_mtime: u64,
+    },
+    /// 文件已被另一个编辑器窗口绑定，`window` 是该窗口的标签
+    OpenInOtherWindow { path: String, window: String },
+    /// 文件超过允许读取的大小
+    TooLarge { size: u64, limit: u64 },
+    /// 行数超过导入上限
+    TooManyRows { limit: usize },
+    /// 内容不是合法的 JSON
+    InvalidJson { line: usize, column: usize },
+    /// JSON 合法但结构不符合要求，内容是 serde 给出的说明
+    InvalidFormat(String),
+    /// 公式集不是列表
+    NotFormulaList,
+    /// YAML 语法错误
+    InvalidYaml { message: String },
+    /// CSV 语法错误
+    InvalidCsv { message: String },
+    /// CSV 表头中没有指定名称的列
+    CsvColumnNotFound { name: String },
+    /// 未指定列时 CSV 第一行不是含 latex 列的表头
+    CsvHeaderMissing,
+    /// Word 文档、SVG 等无法解析，`format` 是格式名称
+    InvalidDocument { format: String, message: String },
+    /// 文件中没有可导入的公式
+    NothingToImport { format: String },
+    /// 剪贴板中的内容无法识别为公式
+    UnrecognizedPaste,
+    /// 模板库中没有该 id 的分类
+    CategoryNotFound { id: String },
+    /// 命令参数或选项的值无效，`name` 是参数名
+    InvalidOption { name: String, value: String },
+    /// 无法识别文件的文本编码
+    UnsupportedEncoding { attempted: Vec<String> },
+    /// gzip 压缩的文件已损坏或被截断，无法解压
+    CorruptGzip { path: String, message: String },
+    /// 搜索用的正则表达式无法编译
+    InvalidRegex { pattern: String, message: String },
+    /// 公式中有无法解析的 LaTeX，`position` 是 UTF-16 偏移
+    InvalidLatex { message: String, position: usize },
+    /// 文件类型不对，例如把模板库当作公式集导入
+    WrongFileType { expected: String, found: String },
+    /// 文件格式版本高于当前应用支持的版本
+    UnsupportedVersion { found: u64, supported: u64 },
+    /// LaTeX 编译失败，`log` 是日志中与错误相关的行
+    CompileFailed { engine: String, log: Vec<String> },
+    /// 本机没有可用的 LaTeX 引擎
+    EngineNotFound { tried: Vec<String> },
+    /// 无法启动外部程序，例如系统的文件管理器
+    SpawnFailed { program: String, message: String },
+    /// 系统没有关联可以打开该文件的程序
+    NoAssociation { path: String },
+    /// 拒绝打开可执行文件
+    ExecutableBlocked { path: String, extension: String },
+    /// 全局快捷键无效或已被其他程序占用
+    ShortcutUnavailable { accelerator: String, message: String },
+    /// 无法读写系统剪贴板
+    ClipboardFailed(String),
+    /// 系统剪贴板不接受该类型的数据（如图像）
+    ClipboardUnsupported,
+    /// 剪贴板中没有文字，或内容是二进制数据
+    ClipboardEmpty,
+    /// 恢复快照不存在，或 id 不合法
+    SnapshotNotFound { id: String },
+    /// 窗口尚未启用自动保存
+    AutosaveDisabled,
+    /// 无法监视文件的变化
+    WatchFailed { path: String, message: String },
+    /// 窗口、菜单或托盘操作失败
+    WindowFailed { message: String },
+    /// 无法生成要写入的文件内容，`format` 是格式名称
+    EncodeFailed { format: String, message: String },
+    /// 无法确定系统目录或文件的位置
+    LocationUnavailable(Location),
+    /// 后台任务意外终止
+    TaskFailed,
+    /// 用户取消了操作
+    Cancelled,
+}
+
+/// `LocationUnavailable` 中无法确定的位置
+#[derive(Debug, Clone, Copy)]
+pub enum Location {
+    ConfigDir,
+    DataDir,
+    Executable,
+    LogFile,
+    ResourceDir,
+}
+
+impl Location {
+    fn key(self) -> &'static str {
+        match self {
+            Location::ConfigDir => "configDir",
+            Location::DataDir => "dataDir",
+            Location::Executable => "executable",
+            Location::LogFile => "logFile",
+            Location::ResourceDir => "resourceDir",
+        }
+    }
+
+    fn name(self, locale: Locale) -> &'static str {
+        match (self, locale) {
+            (Location::ConfigDir, Locale::ZhCn) => "配置目录",
+            (Location::ConfigDir, Locale::En) => "the configuration directory",
+            (Location::DataDir, Locale::ZhCn) => "用户数据目录",
+            (Location::DataDir, Locale::En) => "the user data directory",
+            (Location::Executable, Locale::ZhCn) => "程序文件",
+            (Location::Executable, Locale::En) => "the executable",
+            (Location::LogFile, Locale::ZhCn) => "日志文件",
+            (Location::LogFile, Locale::En) => "the log file",
+            (Location::ResourceDir, Locale::ZhCn) => "应用资源目录",
+            (Location::ResourceDir, Locale::En) => "the application resource directory",
+        }
+    }
+}
+
+impl AppError {
+    /// 由 IO 错误构造，权限错误会单独归类
+    pub fn io(path: &Path, context: &str, err: io::Error) -> Self {
+        let path = path.to_string_lossy().to_string();
+        if err.kind() == io::ErrorKind::PermissionDenied {
+            return AppError::PermissionDenied { path };
+        }
+        AppError::Io {
+            path,
+            kind: err.kind(),
+            message: format!("{}: {}", context, err),
+        }
+    }
+
+    /// 由已存在的目标文件构造冲突错误，附带其大小和修改时间
+    pub fn conflict(path: &Path) -> Self {
+        let metadata = std::fs::metadata(path).ok();
+        AppError::Conflict {
+            path: path.to_string_lossy().to_string(),
+            existing_size: metadata.as_ref().map_or(0, |m| m.len()),
+            existing_mtime: metadata
+                .and_then(|m| m.modified().ok())
+                .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                .map_or(0, |d| d.as_millis() as u64),
+        }
+    }
+
+    /// 无法把数据序列化为 JSON
+    pub fn encode_json(err: serde_json::Error) -> Self {
+        AppError::EncodeFailed {
+            format: "JSON".to_string(),
+            message: err.to_string(),
+        }
+    }
+
+    /// 稳定的错误代码
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::Io { .. } => "io_error",
+            AppError::PermissionDenied { .. } => "permission_denied",
+            AppError::Conflict { .. } => "conflict",
+            AppError::OpenInOtherWindow { .. } => "open_in_other_window",
+            AppError::TooLarge { .. } => "too_large",
+            AppError::TooManyRows { .. } => "too_many_rows",
+            AppError::InvalidJson { .. } => "invalid_json",
+            AppError::InvalidFormat(_) => "invalid_format",
+            AppError::NotFormulaList => "not_formula_list",
+            AppError::InvalidYaml { .. } => "invalid_yaml",
+            AppError::InvalidCsv { .. } => "invalid_csv",
+            AppError::CsvColumnNotFound { .. } => "csv_column_not_found",
+            AppError::CsvHeaderMissing => "csv_header_missing",
+            AppError::InvalidDocument { .. } => "invalid_document",
+            AppError::NothingToImport { .. } => "nothing_to_import",
+            AppError::UnrecognizedPaste => "unrecognized_paste",
+            AppError::CategoryNotFound { .. } => "category_not_found",
+            AppError::InvalidOption { .. } => "invalid_option",
+            AppError::UnsupportedEncoding { .. } => "unsupported_encoding",
+            AppError::CorruptGzip { .. } => "corrupt_gzip",
+            AppError::InvalidRegex { .. } => "invalid_regex",
+            AppError::InvalidLatex { .. } => "invalid_latex",
+            AppError::WrongFileType { .. } => "wrong_file_type",
+            AppError::UnsupportedVersion { .. } => "unsupported_version",
+            AppError::ShortcutUnavailable { .. } => "shortcut_unavailable",
+            AppError::CompileFailed { .. } => "compile_failed",
+            AppError::EngineNotFound { .. } => "engine_not_found",
+            AppError::SpawnFailed { .. } => "spawn_failed",
+            AppError::NoAssociation { .. } => "no_association",
+            AppError::ExecutableBlocked { .. } => "executable_blocked",
+            AppError::ClipboardFailed(_) => "clipboard_failed",
+            AppError::ClipboardUnsupported => "clipboard_unsupported",
+            AppError::ClipboardEmpty => "clipboard_empty",
+            AppError::SnapshotNotFound { .. } => "snapshot_not_found",
+            AppError::AutosaveDisabled => "autosave_disabled",
+            AppError::WatchFailed { .. } => "watch_failed",
+            AppError::WindowFailed { .. } => "window_failed",
+            AppError::EncodeFailed { .. } => "encode_failed",
+            AppError::LocationUnavailable(_) => "location_unavailable",
+            AppError::TaskFailed => "task_failed",
+            AppError::Cancelled => "cancelled",
+        }
+    }
+
+    fn details(&self) -> Value {
+        match self {
+            AppError::Io {
+                path,
+                kind,
+                message,
+            } => json!({ "path": path, "kind": format!("{:?}", kind), "cause": message }),
+            AppError::PermissionDenied { path } => json!({ "path": path }),
+            AppError::Conflict {
+                path,
+                existing_size,
+                existing_mtime,
+            } => json!({
+                "path": path,
+                "existingSize": existing_size,
+                "existingMtime": existing_mtime,
+            }),
+            AppError::OpenInOtherWindow { path, window } => {
+                json!({ "path": path, "window": window })
+            }
+            AppError::TooLarge { size, limit } => json!({ "size": size, "limit": limit }),
+            AppError::TooManyRows { limit } => json!({ "limit": limit }),
+            AppError::InvalidJson { line, column } => json!({ "line": line, "column": column }),
+            AppError::UnsupportedEncoding { attempted } => json!({ "attempted": attempted }),
+            AppError::CorruptGzip { path, .. } => json!({ "path": path }),
+            AppError::InvalidRegex { pattern, .. } => json!({ "pattern": pattern }),
+            AppError::InvalidLatex { position, .. } => json!({ "position": position }),
+            AppError::WrongFileType { expected, found } => {
+                json!({ "expected": expected, "found": found })
+            }
+            AppError::UnsupportedVersion { found, supported } => {
+                json!({ "found": found, "supported": supported })
+            }
+            AppError::ShortcutUnavailable { accelerator, .. } => {
+                json!({ "accelerator": accelerator })
+            }
+            AppError::CompileFailed { engine, log } => json!({ "engine": engine, "log": log }),
+            AppError::EngineNotFound { tried } => json!({ "tried": tried }),
+            AppError::SpawnFailed { program, .. } => json!({ "program": program }),
+            AppError::NoAssociation { path } => json!({ "path": path }),
+            AppError::ExecutableBlocked { path, extension } => {
+                json!({ "path": path, "extension": extension })
+            }
+            AppError::CsvColumnNotFound { name } => json!({ "name": name }),
+            AppError::InvalidDocument { format, .. } | AppError::NothingToImport { format } => {
+                json!({ "format": format })
+            }
+            AppError::CategoryNotFound { id } | AppError::SnapshotNotFound { id } => {
+                json!({ "id": id })
+            }
+            AppError::InvalidOption { name, value } => json!({ "name": name, "value": value }),
+            AppError::WatchFailed { path, .. } => json!({ "path": path }),
+            AppError::EncodeFailed { format, .. } => json!({ "format": format }),
+            AppError::LocationUnavailable(location) => json!({ "location": location.key() }),
+            AppError::InvalidFormat(_)
+            | AppError::NotFormulaList
+            | AppError::InvalidYaml { .. }
+            | AppError::InvalidCsv { .. }
+            | AppError::CsvHeaderMissing
+            | AppError::UnrecognizedPaste
+            | AppError::ClipboardFailed(_)
+            | AppError::ClipboardUnsupported
+            | AppError::ClipboardEmpty
+            | AppError::AutosaveDisabled
+            | AppError::WindowFailed { .. }
+            | AppError::TaskFailed
+            | AppError::Cancelled => Value::Null,
+        }
+    }
+}
+
+/// IO 错误类别的描述
+fn io_kind(kind: io::ErrorKind, locale: Locale) -> &'static str {
+    match (kind, locale) {
+        (io::ErrorKind::NotFound, Locale::ZhCn) => "文件或文件夹不存在",
+        (io::ErrorKind::NotFound, Locale::En) => "file or folder not found",
+        (io::ErrorKind::AlreadyExists, Locale::ZhCn) => "文件已存在",
+        (io::ErrorKind::AlreadyExists, Locale::En) => "file already exists",
+        (io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData, Locale::ZhCn) => "数据无效",
+        (io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData, Locale::En) => "invalid data",
+        (io::ErrorKind::TimedOut, Locale::ZhCn) => "操作超时",
+        (io::ErrorKind::TimedOut, Locale::En) => "operation timed out",
+        (io::ErrorKind::Interrupted, Locale::ZhCn) => "操作被中断",
+        (io::ErrorKind::Interrupted, Locale::En) => "operation interrupted",
+        (io::ErrorKind::UnexpectedEof, Locale::ZhCn) => "文件意外结束",
+        (io::ErrorKind::UnexpectedEof, Locale::En) => "unexpected end of file",
+        (io::ErrorKind::WriteZero, Locale::ZhCn) => "无法写入数据，磁盘可能已满",
+        (io::ErrorKind::WriteZero, Locale::En) => "unable to write data; the disk may be full",
+        (io::ErrorKind::OutOfMemory, Locale::ZhCn) => "内存不足",
+        (io::ErrorKind::OutOfMemory, Locale::En) => "out of memory",
+        (io::ErrorKind::Unsupported, Locale::ZhCn) => "系统不支持此操作",
+        (io::ErrorKind::Unsupported, Locale::En) => "operation not supported",
+        (_, Locale::ZhCn) => "系统错误",
+        (_, Locale::En) => "system error",
+    }
+}
+
+impl AppError {
+    /// 面向用户的消息
+    ///
+    /// 这里就是错误信息的目录：按错误类型穷举，每一项都必须同时提供中文和英文模板，
+    /// 新增的错误类型缺少翻译时无法通过编译。变体中携带的说明文字只能是路径、名称或第三方库给出的
+    /// 技术细节，不能是面向用户的句子。
+    pub fn message(&self, locale: Locale) -> String {
+        match self {
+            AppError::Io { path, kind, .. } => localized!(locale,
+                zh: "无法读写文件：{}（{}）",
+                en: "Unable to read or write the file: {} ({})",
+                path, io_kind(*kind, locale)),
+            AppError::PermissionDenied { path } => localized!(locale,
+                zh: "没有访问文件的权限：{}",
+                en: "Permission denied: {}",
+                path),
+            AppError::Conflict { path, .. } => localized!(locale,
+                zh: "文件已存在：{}",
+                en: "File already exists: {}",
+                path),
+            AppError::OpenInOtherWindow { path, .. } => localized!(locale,
+                zh: "文件已在另一个窗口中打开：{}",
+                en: "The file is already open in another window: {}",
+                path),
+            AppError::TooLarge { size, limit } => localized!(locale,
+                zh: "文件过大（{:.1} MB），超过 {:.1} MB 的限制",
+                en: "The file is too large ({:.1} MB); the limit is {:.1} MB",
+                *size as f64 / 1_048_576.0,
+                *limit as f64 / 1_048_576.0),
+            AppError::TooManyRows { limit } => localized!(locale,
+                zh: "超过 {} 行的导入上限，已停止导入",
+                en: "Import stopped after reaching the limit of {} rows",
+                limit),
+            AppError::InvalidJson { line, column } => localized!(locale,
+                zh: "文件内容不是有效的 JSON 格式（第 {} 行第 {} 列）",
+                en: "The file is not valid JSON (line {}, column {})",
+                line, column),
+            AppError::InvalidFormat(message) => localized!(locale,
+                zh: "内容结构不符合要求：{}",
+                en: "Unexpected content structure: {}",
+                message),
+            AppError::NotFormulaList => localized!(locale,
+                zh: "文件格式错误：公式集必须是列表",
+                en: "Invalid file: a formula collection must be a list"),
+            AppError::InvalidYaml { message } => localized!(locale,
+                zh: "YAML 格式错误：{}",
+                en: "Invalid YAML: {}",
+                message),
+            AppError::InvalidCsv { message } => localized!(locale,
+                zh: "CSV 格式错误：{}",
+                en: "Invalid CSV: {}",
+                message),
+            AppError::CsvColumnNotFound { name } => localized!(locale,
+                zh: "CSV 中没有名为 {} 的列",
+                en: "The CSV has no column named {}",
+                name),
+            AppError::CsvHeaderMissing => localized!(locale,
+                zh: "CSV 第一行不是含 latex 列的表头，请指定公式所在的列",
+                en: "The first CSV row is not a header with a latex column; please choose the formula column"),
+            AppError::InvalidDocument { format, message } => localized!(locale,
+                zh: "{} 文件无效：{}",
+                en: "Invalid {} file: {}",
+                format, message),
+            AppError::NothingToImport { format } => localized!(locale,
+                zh: "文件中没有找到可导入的 {} 公式",
+                en: "No {} formulas were found in the file",
+                format),
+            AppError::UnrecognizedPaste => localized!(locale,
+                zh: "无法识别剪贴板中的公式格式",
+                en: "Unable to recognize the formula format on the clipboard"),
+            AppError::CategoryNotFound { id } => localized!(locale,
+                zh: "模板库中没有 id 为 {} 的分类",
+                en: "The template library has no category with id {}",
+                id),
+            AppError::InvalidOption { name, value } => localized!(locale,
+                zh: "{} 的值无效：{}",
+                en: "Invalid value for {}: {}",
+                name, value),
+            AppError::UnsupportedEncoding { attempted } => localized!(locale,
+                zh: "无法识别文件编码，已尝试：{}",
+                en: "Unable to detect the file encoding; tried {}",
+                attempted.join(match locale {
+                    Locale::ZhCn => "、",
+                    Locale::En => ", ",
+                })),
+            AppError::CorruptGzip { path, message } => localized!(locale,
+                zh: "压缩文件已损坏，无法解压：{}（{}）",
+                en: "The compressed file is corrupted and cannot be decompressed: {} ({})",
+                path, message),
+            AppError::InvalidRegex { message, .. } => localized!(locale,
+                zh: "正则表达式无效：{}",
+                en: "Invalid regular expression: {}",
+                message),
+            AppError::InvalidLatex { message, position } => localized!(locale,
+                zh: "公式无法解析：{}（位置 {}）",
+                en: "Unable to parse the formula: {} (at position {})",
+                message, position),
+            AppError::WrongFileType { found, .. } if found == "templates" => localized!(locale,
+                zh: "这是模板库文件，请使用“绑定模板”功能导入",
+                en: "This is a template library; import it with \"Bind templates\""),
+            AppError::WrongFileType { expected, found } => localized!(locale,
+                zh: "文件类型错误：需要{}，实际为{}",
+                en: "Wrong file type: expected {}, found {}",
+                expected, found),
+            AppError::UnsupportedVersion { found, supported } => localized!(locale,
+                zh: "文件格式版本 {} 高于当前应用支持的版本 {}，请升级应用后再打开",
+                en: "File format version {} is newer than the supported version {}; please update the app",
+                found, supported),
+            AppError::ShortcutUnavailable { accelerator, message } => localized!(locale,
+                zh: "无法注册快捷键 {}（{}），可能已被其他程序占用，请换一个组合",
+                en: "Unable to register shortcut {} ({}); it may be in use by another program",
+                accelerator, message),
+            AppError::CompileFailed { engine, .. } => localized!(locale,
+                zh: "{} 编译失败，请查看日志",
+                en: "{} failed to compile the document; see the log for details",
+                engine),
+            AppError::EngineNotFound { tried } => localized!(locale,
+                zh: "未找到 LaTeX 引擎，请安装 {} 之一",
+                en: "No LaTeX engine found; please install {}",
+                tried.join(match locale {
+                    Locale::ZhCn => " 或 ",
+                    Locale::En => " or ",
+                })),
+            AppError::SpawnFailed { program, message } => localized!(locale,
+                zh: "无法启动 {}：{}",
+                en: "Unable to start {}: {}",
+                program, message),
+            AppError::NoAssociation { path } => localized!(locale,
+                zh: "没有可以打开此文件的程序：{}",
+                en: "No application is associated with this file: {}",
+                path),
+            AppError::ExecutableBlocked { extension, .. } => localized!(locale,
+                zh: "出于安全考虑，不能直接打开 .{} 文件",
+                en: "For security reasons, .{} files cannot be opened directly",
+                extension),
+            AppError::ClipboardFailed(message) => localized!(locale,
+                zh: "无法访问剪贴板：{}",
+                en: "Unable to access the clipboard: {}",
+                message),
+            AppError::ClipboardUnsupported => localized!(locale,
+                zh: "当前系统的剪贴板不支持图片",
+                en: "The system clipboard does not support images"),
+            AppError::ClipboardEmpty => localized!(locale,
+                zh: "剪贴板中没有可导入的文字",
+                en: "The clipboard contains no text to import"),
+            AppError::SnapshotNotFound { id } => localized!(locale,
+                zh: "恢复快照不存在：{}",
+                en: "Recovery snapshot not found: {}",
+                id),
+            AppError::AutosaveDisabled => localized!(locale,
+                zh: "自动保存尚未启用",
+                en: "Autosave is not enabled"),
+            AppError::WatchFailed { path, message } => localized!(locale,
+                zh: "无法监视文件的变化：{}（{}）",
+                en: "Unable to watch the file for changes: {} ({})",
+                path, message),
+            AppError::WindowFailed { message } => localized!(locale,
+                zh: "窗口操作失败：{}",
+                en: "Window operation failed: {}",
+                message),
+            AppError::EncodeFailed { format, message } => localized!(locale,
+                zh: "无法生成 {} 文件：{}",
+                en: "Unable to generate the {} file: {}",
+                format, message),
+            AppError::LocationUnavailable(location) => localized!(locale,
+                zh: "无法确定{}的位置",
+                en: "Unable to locate {}",
+                location.name(locale)),
+            AppError::TaskFailed => localized!(locale,
+                zh: "后台任务意外终止",
+                en: "A background task terminated unexpectedly"),
+            AppError::Cancelled => localized!(locale,
+                zh: "操作已取消",
+                en: "Operation cancelled"),
+        }
+    }
+}
+
+/// 使用当前语言的消息
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message(locale::current()))
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 3)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", &self.to_string())?;
+        state.serialize_field("details", &self.details())?;
+        state.end()
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(err: serde_json::Error) -> Self {
+        if err.is_syntax() || err.is_eof() {
+            AppError::InvalidJson {
+                line: err.line(),
+                column: err.column(),
+            }
+        } else {
+            AppError::InvalidFormat(err.to_string())
+        }
+    }
+}
+
+/// 窗口、菜单、托盘等 Tauri 操作的错误
+impl From<tauri::Error> for AppError {
+    fn from(err: tauri::Error) -> Self {
+        AppError::WindowFailed {
+            message: err.to_string(),
+        }
+    }
+}
+
+/// `spawn_blocking` 的任务 panic 或被取消；原因只记录到日志
+impl From<tokio::task::JoinError> for AppError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        log::error!("Background task failed: {}", err);
+        AppError::TaskFailed
+    }
+}
+
+/// 只在内部记录日志、不返回给前端的地方使用
+impl From<AppError> for String {
+    fn from(err: AppError) -> Self {
+        err.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_error_serializes_code_message_and_details() {
+        let err = AppError::io(
+            Path::new("/tmp/formulas.json"),
+            "Failed to read file",
+            io::Error::from(io::ErrorKind::NotFound),
+        );
+        let value = serde_json::to_value(&err).unwrap();
+        assert_eq!(value["code"], "io_error");
+        assert_eq!(value["message"], err.to_string());
+        assert_eq!(value["details"]["path"], "/tmp/formulas.json");
+        assert_eq!(value["details"]["kind"], "NotFound");
+        assert!(value["details"]["cause"]
+            .as_str()
+            .is_some_and(|cause| cause.starts_with("Failed to read file: ")));
+    }
+
+    #[test]
+    fn io_message_describes_the_error_kind_in_each_locale() {
+        let err = AppError::io(
+            Path::new("/tmp/formulas.json"),
+            "Failed to read file",
+            io::Error::from(io::ErrorKind::NotFound),
+        );
+        assert_eq!(
+            err.message(Locale::ZhCn),
+            "无法读写文件：/tmp/formulas.json（文件或文件夹不存在）"
+        );
+        assert_eq!(
+            err.message(Locale::En),
+            "Unable to read or write the file: /tmp/formulas.json (file or folder not found)"
+        );
+    }
+
+    fn is_cjk(c: char) -> bool {
+        matches!(c, '\u{3000}'..='\u{303F}' | '\u{4E00}'..='\u{9FFF}' | '\u{FF00}'..='\u{FFEF}')
+    }
+
+    #[test]
+    fn english_messages_contain_no_chinese_text() {
+        let errors = [
+            AppError::io(
+                Path::new("a.json"),
+                "Failed to write file",
+                io::Error::from(io::ErrorKind::WriteZero),
+            ),
+            AppError::InvalidFormat("invalid type: string, expected u32".to_string()),
+            AppError::NotFormulaList,
+            AppError::InvalidYaml {
+                message: "did not find expected key".to_string(),
+            },
+            AppError::InvalidCsv {
+                message: "unequal lengths".to_string(),
+            },
+            AppError::CsvColumnNotFound {
+                name: "formula".to_string(),
+            },
+            AppError::CsvHeaderMissing,
+            AppError::InvalidDocument {
+                format: "Word".to_string(),
+                message: "invalid Zip archive".to_string(),
+            },
+            AppError::NothingToImport {
+                format: "LaTeX".to_string(),
+            },
+            AppError::UnrecognizedPaste,
+            AppError::CategoryNotFound {
+                id: "c1".to_string(),
+            },
+            AppError::InvalidOption {
+                name: "locale".to_string(),
+                value: "fr".to_string(),
+            },
+            AppError::InvalidLatex {
+                message: "\\documentclass{a b}".to_string(),
+                position: 0,
+            },
+            AppError::SnapshotNotFound {
+                id: "untitled".to_string(),
+            },
+            AppError::AutosaveDisabled,
+            AppError::WatchFailed {
+                path: "a.json".to_string(),
+                message: "no space left".to_string(),
+            },
+            AppError::WindowFailed {
+                message: "window not found".to_string(),
+            },
+            AppError::EncodeFailed {
+                format: "JSON".to_string(),
+                message: "key must be a string".to_string(),
+            },
+            AppError::LocationUnavailable(Location::ConfigDir),
+            AppError::LocationUnavailable(Location::LogFile),
+            AppError::TaskFailed,
+            AppError::Cancelled,
+        ];
+        for err in &errors {
+            let en = err.message(Locale::En);
+            assert!(!en.chars().any(is_cjk), "{}: {}", err.code(), en);
+            assert!(err.message(Locale::ZhCn).chars().any(is_cjk), "{}", err.code());
+        }
+    }
+
+    #[test]
+    fn new_variants_have_stable_codes_and_details() {
+        let option = AppError::InvalidOption {
+            name: "theme".to_string(),
+            value: "neon".to_string(),
+        };
+        let value = serde_json::to_value(&option).unwrap();
+        assert_eq!(value["code"], "invalid_option");
+        assert_eq!(value["details"], json!({ "name": "theme", "value": "neon" }));
+
+        let location = serde_json::to_value(AppError::LocationUnavailable(Location::DataDir)).unwrap();
+        assert_eq!(location["code"], "location_unavailable");
+        assert_eq!(location["details"], json!({ "location": "dataDir" }));
+
+        let encode = AppError::encode_json(serde_json::from_str::<Value>("[").unwrap_err());
+        assert_eq!(encode.code(), "encode_failed");
+        assert_eq!(serde_json::to_value(&encode).unwrap()["details"], json!({ "format": "JSON" }));
+    }
+
+    #[test]
+    fn permission_denied_has_its_own_code() {
+        let err = AppError::io(
+            Path::new("/root/formulas.json"),
+            "Failed to write file",
+            io::Error::from(io::ErrorKind::PermissionDenied),
+        );
+        let value = serde_json::to_value(&err).unwrap();
+        assert_eq!(value["code"], "permission_denied");
+        assert_eq!(value["details"], json!({ "path": "/root/formulas.json" }));
+    }
+
+    #[test]
+    fn json_parse_error_reports_line_and_column() {
+        let parse_error = serde_json::from_str::<Value>("[\n  1,\n  }").unwrap_err();
+        let value = serde_json::to_value(AppError::from(parse_error)).unwrap();
+        assert_eq!(value["code"], "invalid_json");
+        assert_eq!(value["details"], json!({ "line": 3, "column": 3 }));
+        assert!(value["message"].as_str().is_some_and(|m| !m.is_empty()));
+    }
+
+    #[test]
+    fn structural_json_error_is_invalid_format() {
+        let err = serde_json::from_str::<Vec<u32>>("[\"a\"]").unwrap_err();
+        assert_eq!(AppError::from(err).code(), "invalid_format");
+    }
+}
@@ -0,0 +1,204 @@
+//! 把 `.mlformulas` 文件关联到本程序，只修改当前用户的设置
+//!
+//! Windows 写入 `HKCU\Software\Classes` 下的 ProgId；Linux 在用户数据目录中安装 MIME 类型和
+//! `.desktop` 文件；macOS 的文档类型在构建时由 Info.plist 声明，运行时无需登记。
+
+use std::path::Path;
+
+use crate::error::{AppError, Location};
+
+/// 本程序专用的公式集扩展名
+pub const EXTENSION: &str = "mlformulas";
+
+/// 登记文件关联，指向正在运行的程序
+pub fn register() -> Result<(), AppError> {
+    let exe = std::env::current_exe()
+        .map_err(|e| {
+            log::error!("Failed to locate executable: {}", e);
+            AppError::LocationUnavailable(Location::Executable)
+        })?;
+    register_native(&exe)
+}
+
+/// 撤销 `register` 所做的修改；没有登记过时不做任何事
+pub fn unregister() -> Result<(), AppError> {
+    unregister_native()
+}
+
+#[cfg(windows)]
+const PROG_ID: &str = "MathLiveFormulaEditor.Collection";
+
+#[cfg(windows)]
+fn registry_error(key: &str, err: std::io::Error) -> AppError {
+    AppError::io(
+        Path::new(&format!("HKEY_CURRENT_USER\\{}", key)),
+        "Failed to update registry",
+        err,
+    )
+}
+
+#[cfg(windows)]
+fn register_native(exe: &Path) -> Result<(), AppError> {
+    use crate::registry::set_value;
+
+    let ext_key = format!("Software\\Classes\\.{}", EXTENSION);
+    let prog_key = format!("Software\\Classes\\{}", PROG_ID);
+    let icon_key = format!("{}\\DefaultIcon", prog_key);
+    let command_key = format!("{}\\shell\\open\\command", prog_key);
+    let exe = exe.display();
+    let values = [
+        (&ext_key, PROG_ID.to_string()),
+        (&prog_key, "MathLive 公式集".to_string()),
+        (&icon_key, format!("\"{}\",0", exe)),
+        (&command_key, format!("\"{}\" \"%1\"", exe)),
+    ];
+    for (key, value) in values {
+        set_value(key, None, &value).map_err(|e| registry_error(key, e))?;
+    }
+    notify_shell();
+    Ok(())
+}
+
+#[cfg(windows)]
+fn unregister_native() -> Result<(), AppError> {
+    use crate::registry::delete_key;
+
+    for key in [
+        format!("Software\\Classes\\.{}", EXTENSION),
+        format!("Software\\Classes\\{}", PROG_ID),
+    ] {
+        delete_key(&key).map_err(|e| registry_error(&key, e))?;
+    }
+    notify_shell();
+    Ok(())
+}
+
+/// 通知资源管理器文件关联已改变，否则图标和打开方式要到重新登录后才会更新
+#[cfg(windows)]
+fn notify_shell() {
+    use windows::Win32::UI::Shell::{SHChangeNotify, SHCNE_ASSOCCHANGED, SHCNF_IDLIST};
+
+    unsafe {
+        SHChangeNotify(
+            SHCNE_ASSOCCHANGED,
+            SHCNF_IDLIST,
+            std::ptr::null(),
+            std::ptr::null(),
+        );
+    }
+}
+
+#[cfg(target_os = "linux")]
+const MIME_TYPE: &str = "application/x-mlformulas";
+#[cfg(target_os = "linux")]
+const DESKTOP_FILE: &str = "mathlive-formula-editor.desktop";
+#[cfg(target_os = "linux")]
+const MIME_PACKAGE: &str = "mathlive-formula-editor.xml";
+
+#[cfg(target_os = "linux")]
+fn data_dir() -> Result<std::path::PathBuf, AppError> {
+    tauri::api::path::data_dir()
+        .ok_or(AppError::LocationUnavailable(Location::DataDir))
+}
+
+/// 运行桌面数据库的更新工具；工具不存在时跳过，桌面环境会在下次登录时自行刷新
+#[cfg(target_os = "linux")]
+fn refresh(program: &str, dir: &Path) {
+    match std::process::Command::new(program).arg(dir).status() {
+        Ok(status) if !status.success() => log::error!("{} exited with {}", program, status),
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            log::error!("Failed to run {}: {}", program, e)
+        }
+        _ => {}
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn register_native(exe: &Path) -> Result<(), AppError> {
+    use std::fs;
+
+    let data = data_dir()?;
+    let mime_dir = data.join("mime");
+    let packages = mime_dir.join("packages");
+    let applications = data.join("applications");
+    for dir in [&packages, &applications] {
+        fs::create_dir_all(dir).map_err(|e| AppError::io(dir, "Failed to create directory", e))?;
+    }
+
+    let package = packages.join(MIME_PACKAGE);
+    let xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<mime-info xmlns="http://www.freedesktop.org/standards/shared-mime-info">
+  <mime-type type="{}">
+    <comment>MathLive formula collection</comment>
+    <comment xml:lang="zh_CN">MathLive 公式集</comment>
+    <glob pattern="*.{}"/>
+  </mime-type>
+</mime-info>
+"#,
+        MIME_TYPE, EXTENSION
+    );
+    fs::write(&package, xml).map_err(|e| AppError::io(&package, "Failed to write MIME type", e))?;
+
+    let desktop = applications.join(DESKTOP_FILE);
+    let entry = format!(
+        "[Desktop Entry]\nType=Application\nName=MathLive Formula Editor\nExec={} %f\nTerminal=false\nCategories=Office;Education;\nMimeType={};\n",
+        crate::deep_link::desktop_quote(&exe.to_string_lossy()),
+        MIME_TYPE
+    );
+    fs::write(&desktop, entry)
+        .map_err(|e| AppError::io(&desktop, "Failed to write desktop entry", e))?;
+
+    refresh("update-mime-database", &mime_dir);
+    refresh("update-desktop-database", &applications);
+    match std::process::Command::new("xdg-mime")
+        .args(["default", DESKTOP_FILE, MIME_TYPE])
+        .status()
+    {
+        Ok(status) if status.success() => Ok(()),
+        Ok(status) => Err(AppError::SpawnFailed {
+            program: "xdg-mime".to_string(),
+            message: status.to_string(),
+        }),
+        Err(e) => Err(AppError::SpawnFailed {
+            program: "xdg-mime".to_string(),
+            message: e.to_string(),
+        }),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn unregister_native() -> Result<(), AppError> {
+    use std::fs;
+    use std::io::ErrorKind;
+
+    let data = data_dir()?;
+    let mime_dir = data.join("mime");
+    let applications = data.join("applications");
+    for file in [
+        mime_dir.join("packages").join(MIME_PACKAGE),
+        applications.join(DESKTOP_FILE),
+    ] {
+        match fs::remove_file(&file) {
+            Err(e) if e.kind() != ErrorKind::NotFound => {
+                return Err(AppError::io(&file, "Failed to remove file", e))
+            }
+            _ => {}
+        }
+    }
+    refresh("update-mime-database", &mime_dir);
+    refresh("update-desktop-database", &applications);
+    Ok(())
+}
+
+/// 文档类型已在 Info.plist 中声明
+#[cfg(target_os = "macos")]
+fn register_native(_exe: &Path) -> Result<(), AppError> {
+    Ok(())
+}
+
+/// Info.plist 中的声明随应用一起删除，无需撤销
+#[cfg(target_os = "macos")]
+fn unregister_native() -> Result<(), AppError> {
+    Ok(())
+}
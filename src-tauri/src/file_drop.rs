@@ -0,0 +1,113 @@
+//! 拖放文件到窗口：逐个读取并规范化，结果通过事件发给前端
+//!
+//! 每个文件对应一个事件，按拖入的顺序发送：公式集为 `drop:formulas-loaded`，模板库为
+//! `drop:templates-loaded`，无法识别或读取失败为 `drop:rejected`。含有 `categories`
+//! 字段的文件视为模板库，其余按公式集处理。
+
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+use serde_json::Value;
+use tauri::Window;
+
+use crate::error::AppError;
+use crate::{
+    is_collection_path, normalize_formula_value, normalize_template_content, read_text_file,
+    DedupeScope, NormalizeResult, NormalizedTemplates, COLLECTION_EXTENSIONS,
+};
+
+/// 单个拖入文件的大小上限
+const MAX_DROP_BYTES: u64 = 50 * 1024 * 1024;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Loaded<T> {
+    path: String,
+    #[serde(flatten)]
+    result: T,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Rejected {
+    path: String,
+    code: &'static str,
+    reason: String,
+}
+
+enum Dropped {
+    Formulas(NormalizeResult),
+    Templates(NormalizedTemplates),
+}
+
+/// 在后台线程中按顺序处理拖入的文件，避免大文件阻塞事件循环
+pub fn handle(window: &Window, paths: &[PathBuf]) {
+    let window = window.clone();
+    let paths = paths.to_vec();
+    std::thread::spawn(move || {
+        for path in &paths {
+            let path_text = path.to_string_lossy().into_owned();
+            let (event, payload) = match load(path) {
+                Ok(Dropped::Formulas(result)) => (
+                    "drop:formulas-loaded",
+                    serde_json::to_value(Loaded {
+                        path: path_text,
+                        result,
+                    }),
+                ),
+                Ok(Dropped::Templates(result)) => (
+                    "drop:templates-loaded",
+                    serde_json::to_value(Loaded {
+                        path: path_text,
+                        result,
+                    }),
+                ),
+                Err(e) => (
+                    "drop:rejected",
+                    serde_json::to_value(Rejected {
+                        path: path_text,
+                        code: e.code(),
+                        reason: e.to_string(),
+                    }),
+                ),
+            };
+            // 规范化结果可能很大，转成 `Value` 后发送，不必为整棵结构实现 `Clone`
+            let sent = payload
+                .map_err(tauri::Error::from)
+                .and_then(|payload| window.emit(event, payload));
+            if let Err(e) = sent {
+                log::warn!("Failed to emit drop event: {}", e);
+            }
+        }
+    });
+}
+
+fn load(path: &Path) -> Result<Dropped, AppError> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    if !is_collection_path(path) {
+        return Err(AppError::WrongFileType {
+            expected: COLLECTION_EXTENSIONS
+                .iter()
+                .map(|e| format!(".{}", e))
+                .collect::<Vec<_>>()
+                .join(" 或 "),
+            found: extension.map_or_else(|| "无扩展名的文件".to_string(), |e| format!(".{}", e)),
+        });
+    }
+    if path.is_dir() {
+        return Err(AppError::WrongFileType {
+            expected: "文件".to_string(),
+            found: "文件夹".to_string(),
+        });
+    }
+    let content = read_text_file(path, MAX_DROP_BYTES)?;
+    let value: Value = serde_json::from_str(&content)?;
+    if value.get("categories").is_some() {
+        normalize_template_content(&content, false, None, Some(DedupeScope::Category))
+            .map(Dropped::Templates)
+    } else {
+        normalize_formula_value(&value).map(Dropped::Formulas)
+    }
+}
@@ -0,0 +1,116 @@
+//! gzip 压缩的公式集（`.json.gz`）
+//!
+//! 读取时按文件开头的 gzip 标志字节判断是否需要解压，与扩展名无关；写入时目标路径以 `.gz`
+//! 结尾就边写边压缩。解压同样是流式的，读取上限按解压后的大小计算。
+
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::Path;
+
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use crate::error::AppError;
+
+/// 压缩文件的扩展名，与 `json` 组合为 `.json.gz`
+pub const EXTENSION: &str = "gz";
+
+/// gzip 数据的前两个字节
+const MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// 路径以 `.gz` 结尾时写入压缩内容
+pub fn is_compressed_path(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case(EXTENSION))
+}
+
+/// 打开文件；内容是 gzip 时返回解压后的数据流，第二项为 true
+fn open(path: &Path) -> io::Result<(Box<dyn Read>, bool)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let compressed = reader.fill_buf()?.starts_with(&MAGIC);
+    if compressed {
+        Ok((Box::new(MultiGzDecoder::new(reader)), true))
+    } else {
+        Ok((Box::new(reader), false))
+    }
+}
+
+/// 解压时的数据错误、截断的数据都视为压缩文件损坏
+fn read_error(path: &Path, compressed: bool, err: io::Error) -> AppError {
+    let corrupt = matches!(
+        err.kind(),
+        io::ErrorKind::InvalidInput | io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+    );
+    if compressed && corrupt {
+        AppError::CorruptGzip {
+            path: path.to_string_lossy().into_owned(),
+            message: err.to_string(),
+        }
+    } else {
+        AppError::io(path, "Failed to read file", err)
+    }
+}
+
+/// 读取文件的全部内容，gzip 文件返回解压后的内容；解压后超过 `limit` 字节时拒绝读取
+pub fn read(path: &Path, limit: u64) -> Result<Vec<u8>, AppError> {
+    let (reader, compressed) =
+        open(path).map_err(|e| AppError::io(path, "Failed to read file", e))?;
+    read_limited(path, reader, compressed, limit)
+}
+
+/// 解压已经读入内存的文件内容，不是 gzip 时原样返回；`path` 只用于错误信息
+pub fn decode(path: &Path, raw: &[u8], limit: u64) -> Result<Vec<u8>, AppError> {
+    if raw.starts_with(&MAGIC) {
+        read_limited(path, MultiGzDecoder::new(raw), true, limit)
+    } else {
+        read_limited(path, raw, false, limit)
+    }
+}
+
+fn read_limited(
+    path: &Path,
+    mut reader: impl Read,
+    compressed: bool,
+    limit: u64,
+) -> Result<Vec<u8>, AppError> {
+    let mut bytes = Vec::new();
+    reader
+        .by_ref()
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut bytes)
+        .map_err(|e| read_error(path, compressed, e))?;
+    if bytes.len() as u64 > limit {
+        // 继续解压剩余部分只为统计大小，不保留内容
+        let rest =
+            io::copy(&mut reader, &mut io::sink()).map_err(|e| read_error(path, compressed, e))?;
+        return Err(AppError::TooLarge {
+            size: bytes.len() as u64 + rest,
+            limit,
+        });
+    }
+    Ok(bytes)
+}
+
+/// 读取开头的至多 `max` 字节，gzip 文件按解压后的内容计算
+pub fn read_head(path: &Path, max: u64) -> Result<Vec<u8>, AppError> {
+    let (reader, compressed) =
+        open(path).map_err(|e| AppError::io(path, "Failed to read file", e))?;
+    let mut bytes = Vec::new();
+    reader
+        .take(max)
+        .read_to_end(&mut bytes)
+        .map_err(|e| read_error(path, compressed, e))?;
+    Ok(bytes)
+}
+
+/// 把 `content` 写入 `file`，`path` 以 `.gz` 结尾时压缩；返回写完的文件以便调用方同步到磁盘
+pub fn write(mut file: File, path: &Path, content: &[u8]) -> io::Result<File> {
+    if !is_compressed_path(path) {
+        file.write_all(content)?;
+        return Ok(file);
+    }
+    let mut encoder = GzEncoder::new(file, Compression::default());
+    encoder.write_all(content)?;
+    encoder.finish()
+}
@@ -0,0 +1,742 @@
+//! 从其他文档中提取公式
+//!
+//! 只做词法层面的扫描：跳过注释、`verbatim`、代码块等原样输出的内容，
+//! 找出行间公式块，并以最近的标题、注释或粗体行作为备注。
+
+/// 提取出的一个公式块
+pub struct Block {
+    pub latex: String,
+    pub note: Option<String>,
+    pub tags: Vec<String>,
+    /// 所属章节，目前只有 Markdown 导入会识别
+    pub section: Option<String>,
+}
+
+/// 被跳过的内容及所在行（从 1 开始）
+pub struct Skipped {
+    pub line: usize,
+    pub message: String,
+}
+
+/// 内容按原样输出、其中的 `$` 和环境都不是公式的环境
+const VERBATIM_ENVS: &[&str] = &[
+    "verbatim",
+    "verbatim*",
+    "lstlisting",
+    "minted",
+    "comment",
+    "Verbatim",
+];
+
+/// 含有绘图命令、无法作为单个公式导入的环境，跳过并给出提示
+const SKIPPED_ENVS: &[&str] = &[
+    "tikzpicture",
+    "pgfpicture",
+    "picture",
+    "circuitikz",
+    "forest",
+];
+
+/// 行间公式环境；`true` 表示可以按 `\\` 拆分为多行
+const MATH_ENVS: &[(&str, bool)] = &[
+    ("equation", false),
+    ("equation*", false),
+    ("displaymath", false),
+    ("align", true),
+    ("align*", true),
+    ("alignat", true),
+    ("alignat*", true),
+    ("flalign", true),
+    ("flalign*", true),
+    ("eqnarray", true),
+    ("eqnarray*", true),
+    ("gather", true),
+    ("gather*", true),
+    ("multline", true),
+    ("multline*", true),
+];
+
+const SECTION_COMMANDS: &[&str] = &[
+    "chapter",
+    "section",
+    "subsection",
+    "subsubsection",
+    "paragraph",
+];
+
+struct Scanner<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn line(&self, pos: usize) -> usize {
+        self.text[..pos].matches('\n').count() + 1
+    }
+
+    fn skip_line(&mut self) -> &'a str {
+        let rest = self.rest();
+        let end = rest.find('\n').map_or(rest.len(), |i| i + 1);
+        self.pos += end;
+        &rest[..end]
+    }
+
+    /// 读取 `\` 之后的命令名
+    fn command(&mut self) -> &'a str {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        let len = if len == 0 {
+            rest.chars().next().map_or(0, char::len_utf8)
+        } else {
+            len
+        };
+        self.pos += len;
+        &rest[..len]
+    }
+
+    fn skip_spaces(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start_matches([' ', '\t']).len();
+    }
+
+    /// 读取 `{...}` 分组的内容；当前位置不是 `{` 时返回 `None`
+    fn group(&mut self) -> Option<&'a str> {
+        let rest = self.rest();
+        if !rest.starts_with('{') {
+            return None;
+        }
+        let end = matching_brace(rest)?;
+        self.pos += end + 1;
+        Some(&rest[1..end])
+    }
+
+    /// 跳到 `needle` 之后，返回之间的内容；找不到时返回 `None` 且不移动
+    fn until(&mut self, needle: &str) -> Option<&'a str> {
+        let rest = self.rest();
+        let idx = rest.find(needle)?;
+        self.pos += idx + needle.len();
+        Some(&rest[..idx])
+    }
+}
+
+/// `text` 以 `{` 开头，返回与之匹配的 `}` 的字节位置
+fn matching_brace(text: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut escaped = false;
+    for (idx, ch) in text.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(idx);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// 去掉公式中的注释、`\label`、`\nonumber` 等只影响编号的命令
+fn clean_math(body: &str) -> String {
+    let mut out = String::with_capacity(body.len());
+    for line in body.lines() {
+        out.push_str(strip_comment(line));
+        out.push('\n');
+    }
+    let mut text = out;
+    for command in ["\\nonumber", "\\notag"] {
+        text = text.replace(command, "");
+    }
+    while let Some(start) = text.find("\\label{") {
+        let end = matching_brace(&text[start + 6..]).map_or(text.len(), |e| start + 6 + e + 1);
+        text.replace_range(start..end, "");
+    }
+    text.trim().to_string()
+}
+
+/// 去掉行内未转义的 `%` 及其后的内容
+fn strip_comment(line: &str) -> &str {
+    let mut escaped = false;
+    for (idx, ch) in line.char_indices() {
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '%' => return &line[..idx],
+            _ => {}
+        }
+    }
+    line
+}
+
+/// 在顶层（不在花括号或嵌套环境内）按 `\\` 拆分多行公式，并去掉对齐用的 `&`
+fn split_rows(body: &str) -> Vec<String> {
+    let mut rows = Vec::new();
+    let mut current = String::new();
+    let mut depth = 0usize;
+    let mut env_depth = 0usize;
+    let mut chars = body.char_indices().peekable();
+    while let Some((idx, ch)) = chars.next() {
+        match ch {
+            '{' => depth += 1,
+            '}' => depth = depth.saturating_sub(1),
+            '&' if depth == 0 && env_depth == 0 => continue,
+            '\\' => {
+                let rest = &body[idx + 1..];
+                if rest.starts_with("begin") {
+                    env_depth += 1;
+                } else if rest.starts_with("end") {
+                    env_depth = env_depth.saturating_sub(1);
+                } else if rest.starts_with('\\') && depth == 0 && env_depth == 0 {
+                    chars.next();
+                    // 紧跟在 `\\` 后的 `[` 才是行距参数 `\\[2pt]`，换行后以 `[` 开头的内容原样保留
+                    if body[idx + 2..].starts_with('[') {
+                        for (_, c) in chars.by_ref() {
+                            if c == ']' {
+                                break;
+                            }
+                        }
+                    }
+                    rows.push(std::mem::take(&mut current));
+                    continue;
+                }
+                current.push(ch);
+                // 转义字符（如 `\&`、`\{`）原样保留
+                if let Some(&(_, next)) = chars.peek() {
+                    if !next.is_ascii_alphabetic() {
+                        current.push(next);
+                        chars.next();
+                    }
+                }
+                continue;
+            }
+            _ => {}
+        }
+        current.push(ch);
+    }
+    rows.push(current);
+    rows.into_iter()
+        .map(|row| row.trim().to_string())
+        .filter(|row| !row.is_empty())
+        .collect()
+}
+
+/// 多行环境不拆分时，用 MathLive 支持的对应环境包裹
+fn wrap_rows(env: &str, body: &str) -> String {
+    let inner = if env.starts_with("gather") || env.starts_with("multline") {
+        "gathered"
+    } else {
+        "aligned"
+    };
+    // 末尾多余的 `\\` 会产生空行
+    let body = body.trim_end();
+    let body = body.strip_suffix("\\\\").unwrap_or(body).trim_end();
+    format!("\\begin{{{0}}}\n{1}\n\\end{{{0}}}", inner, body)
+}
+
+/// 取注释行的文字作为备注，分隔线之类的注释忽略
+fn comment_note(line: &str) -> Option<String> {
+    let text = line.trim_start().trim_start_matches('%').trim();
+    if text.chars().any(char::is_alphanumeric) {
+        Some(text.to_string())
+    } else {
+        None
+    }
+}
+
+/// 扫描 `.tex` 文档中的行间公式：`equation`、`align`、`gather` 等环境以及 `\[...\]`、`$$...$$`
+///
+/// 有 `\begin{document}` 时只扫描正文。`split` 为 true 时多行环境的每一行单独成为一个公式。
+pub fn scan_tex(content: &str, split: bool) -> (Vec<Block>, Vec<Skipped>) {
+    let start = content
+        .find("\\begin{document}")
+        .map_or(0, |i| i + "\\begin{document}".len());
+    let end = content.find("\\end{document}").unwrap_or(content.len());
+    let mut scanner = Scanner {
+        text: &content[..end.max(start)],
+        pos: start,
+    };
+    let mut blocks = Vec::new();
+    let mut skipped = Vec::new();
+    let mut section: Option<String> = None;
+    let mut comment: Option<String> = None;
+    let mut at_line_start = true;
+
+    let mut push = |latex: String, note: Option<String>| {
+        if !latex.is_empty() {
+            blocks.push(Block {
+                latex,
+                note,
+                tags: Vec::new(),
+                section: None,
+            });
+        }
+    };
+
+    while let Some(ch) = scanner.rest().chars().next() {
+        let here = scanner.pos;
+        match ch {
+            '%' => {
+                let line = scanner.skip_line();
+                if at_line_start {
+                    if let Some(note) = comment_note(line) {
+                        comment = Some(note);
+                    }
+                }
+                at_line_start = true;
+                continue;
+            }
+            '\n' => {
+                scanner.pos += 1;
+                at_line_start = true;
+                continue;
+            }
+            ' ' | '\t' | '\r' => {
+                scanner.pos += 1;
+                continue;
+            }
+            '$' if scanner.rest().starts_with("$$") => {
+                scanner.pos += 2;
+                match scanner.until("$$") {
+                    Some(body) => {
+                        push(clean_math(body), comment.take().or_else(|| section.clone()))
+                    }
+                    None => {
+                        skipped.push(Skipped {
+                            line: scanner.line(here),
+                            message: "$$ 没有结束".to_string(),
+                        });
+                        break;
+                    }
+                }
+            }
+            '$' => {
+                // 行内公式不导入，但要整体跳过，避免其中的内容被误认为结构
+                scanner.pos += 1;
+                let rest = scanner.rest();
+                let mut escaped = false;
+                let close = rest.char_indices().find(|&(_, c)| {
+                    let found = c == '$' && !escaped;
+                    escaped = c == '\\' && !escaped;
+                    found
+                });
+                scanner.pos += close.map_or(rest.len(), |(i, _)| i + 1);
+            }
+            '\\' => {
+                scanner.pos += 1;
+                let name = scanner.command();
+                match name {
+                    "[" => match scanner.until("\\]") {
+                        Some(body) => {
+                            push(clean_math(body), comment.take().or_else(|| section.clone()))
+                        }
+                        None => {
+                            skipped.push(Skipped {
+                                line: scanner.line(here),
+                                message: "\\[ 没有结束".to_string(),
+                            });
+                            break;
+                        }
+                    },
+                    "(" => {
+                        scanner.until("\\)");
+                    }
+                    "verb" => {
+                        if let Some(delim) = scanner.rest().chars().next() {
+                            scanner.pos += delim.len_utf8();
+                            let mut buf = [0; 4];
+                            scanner.until(delim.encode_utf8(&mut buf));
+                        }
+                    }
+                    "begin" => {
+                        let Some(env) = scanner.group() else {
+                            continue;
+                        };
+                        let end_tag = format!("\\end{{{}}}", env);
+                        if VERBATIM_ENVS.contains(&env) {
+                            scanner.until(&end_tag);
+                        } else if SKIPPED_ENVS.contains(&env) {
+                            scanner.until(&end_tag);
+                            skipped.push(Skipped {
+                                line: scanner.line(here),
+                                message: format!("跳过 {} 环境", env),
+                            });
+                        } else if let Some(&(_, multi)) =
+                            MATH_ENVS.iter().find(|(name, _)| *name == env)
+                        {
+                            if env.starts_with("alignat") {
+                                scanner.group();
+                            }
+                            let Some(body) = scanner.until(&end_tag) else {
+                                skipped.push(Skipped {
+                                    line: scanner.line(here),
+                                    message: format!("{} 环境没有结束", env),
+                                });
+                                break;
+                            };
+                            let body = clean_math(body);
+                            let note = comment.take().or_else(|| section.clone());
+                            if !multi {
+                                push(body, note);
+                            } else {
+                                let rows = split_rows(&body);
+                                if split {
+                                    for row in rows {
+                                        push(row, note.clone());
+                                    }
+                                } else if rows.len() > 1 {
+                                    push(wrap_rows(env, &body), note);
+                                } else {
+                                    push(rows.concat(), note);
+                                }
+                            }
+                        }
+                    }
+                    _ if SECTION_COMMANDS.contains(&name) => {
+                        if scanner.rest().starts_with('*') {
+                            scanner.pos += 1;
+                        }
+                        scanner.skip_spaces();
+                        if let Some(title) = scanner.group() {
+                            let title = title.trim();
+                            section = (!title.is_empty()).then(|| title.to_string());
+                            comment = None;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            _ => {
+                scanner.pos += ch.len_utf8();
+            }
+        }
+        at_line_start = false;
+    }
+    (blocks, skipped)
+}
+
+/// `format_markdown` 生成的默认标题（`公式 N`）不是用户写的备注
+fn is_generated_heading(title: &str) -> bool {
+    title
+        .strip_prefix("公式 ")
+        .is_some_and(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// 导出时在备注、标题中加反斜杠转义的字符
+pub const MARKDOWN_ESCAPED: &[char] = &['\\', '*', '_', '|', '#', '`', '[', ']', '<', '>', '~'];
+
+/// `format_markdown` 中转义的逆操作
+fn unescape_markdown_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(ch) = chars.next() {
+        if ch == '\\' {
+            if let Some(next) = chars.next_if(|c| MARKDOWN_ESCAPED.contains(c)) {
+                result.push(next);
+                continue;
+            }
+        }
+        result.push(ch);
+    }
+    result
+}
+
+/// ATX 标题（`## 标题`），返回级别和文字
+fn atx_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    let rest = &line[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' ')))
+        .then(|| (level, rest.trim()))
+}
+
+/// 代码块以外出现过的标题级别
+fn heading_levels(content: &str) -> Vec<usize> {
+    let mut levels = Vec::new();
+    let mut fence: Option<(char, usize)> = None;
+    for line in content.lines().map(str::trim) {
+        if let Some((ch, len)) = fence {
+            if closes_fence(line, ch, len) {
+                fence = None;
+            }
+        } else if let Some(marker) = fence_marker(line) {
+            fence = Some(marker);
+        } else if let Some((level, _)) = atx_heading(line) {
+            if !levels.contains(&level) {
+                levels.push(level);
+            }
+        }
+    }
+    levels
+}
+
+/// 整行被 `marker` 包裹时返回其中的文字，例如 `**备注**`
+fn wrapped_line<'a>(line: &'a str, marker: &str) -> Option<&'a str> {
+    let inner = line.strip_prefix(marker)?.strip_suffix(marker)?.trim();
+    (!inner.is_empty() && !inner.starts_with('*')).then_some(inner)
+}
+
+/// 代码块的围栏标记（```` ``` ```` 或 `~~~`），返回标记字符和长度
+fn fence_marker(line: &str) -> Option<(char, usize)> {
+    let ch = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let len = line.chars().take_while(|c| *c == ch).count();
+    (len >= 3).then_some((ch, len))
+}
+
+fn closes_fence(line: &str, ch: char, len: usize) -> bool {
+    fence_marker(line).is_some_and(|(c, l)| c == ch && l >= len)
+        && line.trim_start_matches(ch).trim().is_empty()
+}
+
+/// 块级公式的开始和结束标记，与 `format_markdown` 的定界符选项对应
+const DISPLAY_DELIMITERS: &[(&str, &str)] = &[("$$", "$$"), ("\\[", "\\]"), ("\\(", "\\)")];
+
+/// 从去掉开始标记的首行起，读取到以 `close` 结尾的行为止；返回公式内容和额外读取的行数，
+/// 没有结束标记时返回 `None`
+fn read_display<'a>(
+    first: &str,
+    rest: impl Iterator<Item = &'a str>,
+    close: &str,
+) -> Option<(String, usize)> {
+    if let Some(single) = first.trim_end().strip_suffix(close) {
+        return Some((single.to_string(), 0));
+    }
+    let mut body = first.to_string();
+    for (count, next) in rest.enumerate() {
+        body.push('\n');
+        match next.trim_end().strip_suffix(close) {
+            Some(last) => {
+                body.push_str(last);
+                return Some((body, count + 1));
+            }
+            None => body.push_str(next),
+        }
+    }
+    None
+}
+
+/// 知乎导出的公式图片 `![...](https://www.zhihu.com/equation?tex=...)`，返回解码后的公式
+fn zhihu_image(line: &str) -> Option<String> {
+    const PREFIX: &str = "](https://www.zhihu.com/equation?tex=";
+    if !line.starts_with("![") {
+        return None;
+    }
+    let encoded = line[line.find(PREFIX)? + PREFIX.len()..].strip_suffix(')')?;
+    percent_decode(encoded)
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(decoded).ok()
+}
+
+/// 提取一行中的行内公式 `$...$`，跳过行内代码和转义的 `\$`
+fn inline_math(line: &str) -> Vec<String> {
+    let mut found = Vec::new();
+    let mut current: Option<String> = None;
+    let mut in_code = false;
+    let mut chars = line.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '`' if current.is_none() => in_code = !in_code,
+            _ if in_code => {}
+            '\\' => {
+                let next = chars.next();
+                if let Some(math) = current.as_mut() {
+                    math.push(ch);
+                    math.extend(next);
+                }
+            }
+            '$' => match current.take() {
+                Some(math) if !math.trim().is_empty() => found.push(math.trim().to_string()),
+                Some(_) => {}
+                None => current = Some(String::new()),
+            },
+            _ => {
+                if let Some(math) = current.as_mut() {
+                    math.push(ch);
+                }
+            }
+        }
+    }
+    found
+}
+
+/// 扫描 Markdown 中的行间公式：`$$...$$`、`\\[...\\]`、独占一行的 `\\(...\\)`、```` ```math ````
+/// 代码块和知乎公式图片；`inline` 为 true 时也提取 `$...$` 行内公式
+///
+/// 与 `format_markdown` 的输出相互对应：备注取最近的标题或整行粗体，紧跟在公式块后的整行
+/// 斜体和 `标签：` 行分别作为该公式的备注和标签，转义的字符还原。文档中有多级标题时，
+/// 最深一级以外的标题作为章节。其他代码块中的内容不会被当作公式。
+pub fn scan_markdown(content: &str, inline: bool) -> (Vec<Block>, Vec<Skipped>) {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut skipped = Vec::new();
+    let mut note: Option<String> = None;
+    let mut section: Option<String> = None;
+    let mut fence: Option<(char, usize)> = None;
+    // 上一个公式块之后是否只出现了空行、说明行
+    let mut after_block = false;
+    let levels = heading_levels(content);
+    let note_level = levels.iter().copied().max().unwrap_or(0);
+    let is_section = |level: usize| levels.len() > 1 && level < note_level;
+    let mut lines = content.lines().enumerate();
+
+    while let Some((idx, raw)) = lines.next() {
+        let line = raw.trim();
+        if let Some((ch, len)) = fence {
+            if closes_fence(line, ch, len) {
+                fence = None;
+            }
+            continue;
+        }
+        let mut display = None;
+        if let Some((ch, len)) = fence_marker(line) {
+            if line.trim_start_matches(ch).trim() != "math" {
+                fence = Some((ch, len));
+                after_block = false;
+                continue;
+            }
+            let mut body = Vec::new();
+            let mut closed = false;
+            for (_, next) in lines.by_ref() {
+                if closes_fence(next.trim(), ch, len) {
+                    closed = true;
+                    break;
+                }
+                body.push(next);
+            }
+            if !closed {
+                skipped.push(Skipped {
+                    line: idx + 1,
+                    message: "```math 没有结束".to_string(),
+                });
+                break;
+            }
+            display = Some(body.join("\n"));
+        }
+        if display.is_none() && line.is_empty() {
+            continue;
+        }
+
+        if display.is_none() && after_block {
+            let last = blocks.last_mut().expect("after_block implies a block");
+            if let Some(text) = wrapped_line(line, "*").filter(|_| last.note.is_none()) {
+                last.note = Some(unescape_markdown_text(text));
+                continue;
+            }
+            if let Some(tags) = line.strip_prefix("标签：") {
+                last.tags = tags
+                    .split_whitespace()
+                    .map(|t| t.trim_matches('`').to_string())
+                    .filter(|t| !t.is_empty())
+                    .collect();
+                continue;
+            }
+        }
+        after_block = false;
+
+        if display.is_none() {
+            if let Some((level, title)) = atx_heading(line) {
+                let title = unescape_markdown_text(title);
+                if is_section(level) {
+                    section = (!title.is_empty()).then_some(title);
+                    note = None;
+                } else {
+                    note = (!title.is_empty() && !is_generated_heading(&title)).then_some(title);
+                }
+                continue;
+            }
+            if let Some(text) = wrapped_line(line, "**") {
+                note = Some(unescape_markdown_text(text));
+                continue;
+            }
+            display = zhihu_image(line);
+        }
+
+        if display.is_none() {
+            if let Some((open, close)) = DISPLAY_DELIMITERS
+                .iter()
+                .find(|(open, _)| line.starts_with(open))
+            {
+                let first = &line[open.len()..];
+                match read_display(first, lines.clone().map(|(_, l)| l), close) {
+                    Some((body, consumed)) => {
+                        for _ in 0..consumed {
+                            lines.next();
+                        }
+                        display = Some(body);
+                    }
+                    // 行首的 `\(` 也可能是正文中的行内公式
+                    None if *open == "\\(" => {}
+                    None => {
+                        skipped.push(Skipped {
+                            line: idx + 1,
+                            message: format!("{} 没有结束", open),
+                        });
+                        break;
+                    }
+                }
+            }
+        }
+
+        if let Some(body) = display {
+            let latex = body.trim();
+            if !latex.is_empty() {
+                blocks.push(Block {
+                    latex: latex.to_string(),
+                    note: note.take(),
+                    tags: Vec::new(),
+                    section: section.clone(),
+                });
+                after_block = true;
+            }
+            continue;
+        }
+
+        if inline {
+            for latex in inline_math(line) {
+                blocks.push(Block {
+                    latex,
+                    note: note.clone(),
+                    tags: Vec::new(),
+                    section: section.clone(),
+                });
+            }
+        }
+    }
+    (blocks, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_rows_drops_spacing_argument_only_right_after_break() {
+        assert_eq!(split_rows("a &= b \\\\[2pt] c &= d"), ["a = b", "c = d"]);
+        assert_eq!(
+            split_rows("a \\\\ [0,1] \\subset \\mathbb{R}"),
+            ["a", "[0,1] \\subset \\mathbb{R}"]
+        );
+    }
+}
@@ -0,0 +1,125 @@
+//! 耗时任务：命令启动任务后立即返回任务 id，任务在阻塞线程池中执行，通过事件报告进度和结果
+//!
+//! - `job:progress`：`{ jobId, done, total, stage }`
+//! - `job:done`：`{ jobId, result }`，`result` 为任务的返回值
+//! - `job:failed`：`{ jobId, error }`，取消的任务以 `cancelled` 错误结束
+//!
+//! `cancel_job` 只设置取消标记，任务在处理相邻两项之间检查。任务结束后立即从登记表中移除，
+//! 取消已结束的任务不会产生效果。发布构建使用 `panic = "abort"`，任务 panic 会终止进程；
+//! 开发构建中 panic 的任务以 `task_failed` 错误结束。
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use serde::Serialize;
+use serde_json::{json, Value};
+use tauri::Window;
+
+use crate::error::AppError;
+
+type Tokens = Arc<Mutex<HashMap<String, Arc<AtomicBool>>>>;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress<'a> {
+    job_id: &'a str,
+    done: usize,
+    total: usize,
+    stage: &'a str,
+}
+
+/// 传给任务函数，用于报告进度和检查是否已取消
+pub struct Job {
+    id: String,
+    window: Window,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Job {
+    pub fn progress(&self, done: usize, total: usize, stage: &str) {
+        let payload = Progress {
+            job_id: &self.id,
+            done,
+            total,
+            stage,
+        };
+        self.emit("job:progress", json!(payload));
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// 已取消时返回 `AppError::Cancelled`，便于在循环中用 `?` 提前结束
+    pub fn check_cancelled(&self) -> Result<(), AppError> {
+        if self.is_cancelled() {
+            Err(AppError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn emit(&self, event: &str, payload: Value) {
+        if let Err(e) = self.window.emit(event, payload) {
+            log::warn!("Failed to emit {} for job {}: {}", event, self.id, e);
+        }
+    }
+}
+
+/// 运行中任务的取消标记，作为 `tauri::State` 托管
+#[derive(Default)]
+pub struct JobRegistry {
+    next_id: AtomicU64,
+    tokens: Tokens,
+}
+
+impl JobRegistry {
+    /// 在阻塞线程池中运行 `work`，返回任务 id；事件发往 `window`
+    pub fn start<T, F>(&self, window: &Window, kind: &str, work: F) -> String
+    where
+        T: Serialize + Send + 'static,
+        F: FnOnce(&Job) -> Result<T, AppError> + Send + 'static,
+    {
+        let id = format!(
+            "{}-{}",
+            kind,
+            self.next_id.fetch_add(1, Ordering::Relaxed) + 1
+        );
+        let cancelled = Arc::new(AtomicBool::new(false));
+        self.tokens
+            .lock()
+            .unwrap()
+            .insert(id.clone(), cancelled.clone());
+        let tokens = self.tokens.clone();
+        let job = Arc::new(Job {
+            id: id.clone(),
+            window: window.clone(),
+            cancelled,
+        });
+        tauri::async_runtime::spawn(async move {
+            let worker = job.clone();
+            let result = tauri::async_runtime::spawn_blocking(move || work(&worker))
+                .await
+                .unwrap_or_else(|e| Err(e.into()))
+                .and_then(|value| Ok(serde_json::to_value(value)?));
+            tokens.lock().unwrap().remove(&job.id);
+            match result {
+                Ok(value) => job.emit("job:done", json!({ "jobId": job.id, "result": value })),
+                Err(e) => job.emit("job:failed", json!({ "jobId": job.id, "error": e })),
+            }
+        });
+        id
+    }
+
+    /// 请求取消任务；任务不存在或已结束时返回 false
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.tokens.lock().unwrap().get(id) {
+            Some(cancelled) => {
+                cancelled.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! 后端消息（错误信息和解析失败的说明）使用的语言，支持简体中文和英文
+//!
+//! 用户选择的语言保存在设置中；没有选择过时跟随系统语言，系统语言既不是中文也不是英文时使用英文。
+
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::settings::SettingsStore;
+
+/// 按语言选择消息模板，两种语言的模板都必须给出
+macro_rules! localized {
+    ($locale:expr, zh: $zh:literal, en: $en:literal $(, $arg:expr)* $(,)?) => {
+        match $locale {
+            $crate::locale::Locale::ZhCn => format!($zh $(, $arg)*),
+            $crate::locale::Locale::En => format!($en $(, $arg)*),
+        }
+    };
+}
+pub(crate) use localized;
+
+/// 语言在设置中的键名
+pub const LOCALE_KEY: &str = "backendLocale";
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Locale {
+    ZhCn,
+    En,
+}
+
+impl Locale {
+    /// 接受 `zh-CN`、`zh_TW`、`en-US` 等写法，只按语言部分区分；无法识别时返回 `None`
+    pub fn parse(tag: &str) -> Option<Self> {
+        let language = tag.trim().split(['-', '_']).next()?.to_ascii_lowercase();
+        match language.as_str() {
+            "zh" => Some(Locale::ZhCn),
+            "en" => Some(Locale::En),
+            _ => None,
+        }
+    }
+
+    pub fn tag(self) -> &'static str {
+        match self {
+            Locale::ZhCn => "zh-CN",
+            Locale::En => "en",
+        }
+    }
+}
+
+/// 当前语言，以 `Locale` 的序号保存；默认简体中文
+static ACTIVE: AtomicU8 = AtomicU8::new(0);
+
+pub fn current() -> Locale {
+    match ACTIVE.load(Ordering::Relaxed) {
+        1 => Locale::En,
+        _ => Locale::ZhCn,
+    }
+}
+
+pub fn set(locale: Locale) {
+    let value = match locale {
+        Locale::ZhCn => 0,
+        Locale::En => 1,
+    };
+    ACTIVE.store(value, Ordering::Relaxed);
+}
+
+/// 设置中保存的语言，没有保存时使用系统语言
+pub fn preferred(store: &SettingsStore) -> Locale {
+    let saved = store
+        .get(LOCALE_KEY)
+        .and_then(|value| value.as_str().and_then(Locale::parse));
+    saved.unwrap_or_else(|| match crate::system_info::locale() {
+        Some(tag) => Locale::parse(&tag).unwrap_or(Locale::En),
+        None => Locale::ZhCn,
+    })
+}
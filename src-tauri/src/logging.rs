@@ -0,0 +1,191 @@
+//! 写入应用数据目录下 `logs/app.log` 的日志，实现 `log` 门面
+//!
+//! 日志行先放入有界队列，由后台线程写入文件，记录日志的调用方从不等待磁盘。队列满或写入失败
+//! （如磁盘已满）时直接丢弃，只在标准错误中提示一次。文件超过 `MAX_FILE_BYTES` 时轮换为
+//! `app.log.1`、`app.log.2`，连同当前文件最多保留 `KEEP_FILES` 个。初始化之前的日志只输出到
+//! 标准错误。
+
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, SyncSender};
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+
+pub const FILE_NAME: &str = "app.log";
+/// 前端通过 `log_message` 记录的日志使用的目标名
+pub const FRONTEND_TARGET: &str = "frontend";
+/// 单个日志文件的大小上限
+const MAX_FILE_BYTES: u64 = 5 * 1024 * 1024;
+/// 包括当前文件在内保留的文件数
+const KEEP_FILES: usize = 3;
+/// 等待写入的日志行数上限，超出的日志被丢弃
+const QUEUE_CAPACITY: usize = 1024;
+/// 本程序以外的库（Tauri、wry 等）只记录这个级别及以上的日志
+const DEPENDENCY_LEVEL: Level = Level::Warn;
+
+struct FileLogger {
+    sender: Mutex<Option<SyncSender<String>>>,
+    path: OnceLock<PathBuf>,
+    /// 已提示过日志被丢弃，避免重复输出
+    dropped: AtomicBool,
+}
+
+static LOGGER: FileLogger = FileLogger {
+    sender: Mutex::new(None),
+    path: OnceLock::new(),
+    dropped: AtomicBool::new(false),
+};
+
+fn max_level() -> LevelFilter {
+    if cfg!(debug_assertions) {
+        LevelFilter::Debug
+    } else {
+        LevelFilter::Info
+    }
+}
+
+/// 注册全局日志；应尽早调用，重复调用时忽略
+pub fn install() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(max_level());
+    }
+}
+
+/// 开始把日志写入 `dir` 下的 `app.log`；目录无法创建时继续只输出到标准错误
+pub fn init(dir: &Path) {
+    install();
+    if LOGGER.path.get().is_some() {
+        return;
+    }
+    if let Err(e) = fs::create_dir_all(dir) {
+        eprintln!("Failed to create log directory {}: {}", dir.display(), e);
+        return;
+    }
+    let path = dir.join(FILE_NAME);
+    let _ = LOGGER.path.set(path.clone());
+    let (sender, receiver) = mpsc::sync_channel(QUEUE_CAPACITY);
+    *LOGGER.sender.lock().unwrap_or_else(|e| e.into_inner()) = Some(sender);
+    std::thread::spawn(move || write_loop(&path, receiver));
+}
+
+/// 当前日志文件的路径；尚未初始化时为 `None`
+pub fn file_path() -> Option<&'static Path> {
+    LOGGER.path.get().map(PathBuf::as_path)
+}
+
+impl Log for FileLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let own = metadata.target().starts_with(env!("CARGO_CRATE_NAME"))
+            || metadata.target() == FRONTEND_TARGET;
+        own || metadata.level() <= DEPENDENCY_LEVEL
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "{} {:<5} [{}] {}\n",
+            timestamp(SystemTime::now()),
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        if cfg!(debug_assertions) {
+            eprint!("{}", line);
+        }
+        let sender = self.sender.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(sender) = sender.as_ref() else {
+            if !cfg!(debug_assertions) {
+                eprint!("{}", line);
+            }
+            return;
+        };
+        if sender.try_send(line).is_err() {
+            self.warn_dropped("queue is full");
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+impl FileLogger {
+    fn warn_dropped(&self, reason: &str) {
+        if !self.dropped.swap(true, Ordering::Relaxed) {
+            eprintln!("Dropping log entries: {}", reason);
+        }
+    }
+}
+
+fn write_loop(path: &Path, receiver: Receiver<String>) {
+    let mut file: Option<File> = None;
+    let mut size = fs::metadata(path).map_or(0, |m| m.len());
+    for line in receiver {
+        if size + line.len() as u64 > MAX_FILE_BYTES && size > 0 {
+            file = None;
+            rotate(path);
+            size = 0;
+        }
+        if file.is_none() {
+            file = OpenOptions::new().create(true).append(true).open(path).ok();
+        }
+        let written = file
+            .as_mut()
+            .is_some_and(|f| f.write_all(line.as_bytes()).is_ok());
+        if written {
+            size += line.len() as u64;
+        } else {
+            // 下一条日志重新打开文件，磁盘空间恢复后即可继续写入
+            file = None;
+            LOGGER.warn_dropped(&format!("cannot write {}", path.display()));
+        }
+    }
+}
+
+/// `app.log` → `app.log.1` → `app.log.2`，最旧的文件被覆盖
+fn rotate(path: &Path) {
+    let numbered = |n: usize| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(format!(".{}", n));
+        PathBuf::from(name)
+    };
+    for n in (1..KEEP_FILES).rev() {
+        let from = if n == 1 {
+            path.to_path_buf()
+        } else {
+            numbered(n - 1)
+        };
+        let _ = fs::rename(from, numbered(n));
+    }
+}
+
+/// UTC 时间，格式为 `2024-05-01T08:30:00.123Z`
+fn timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, rest) = (secs / 86_400, secs % 86_400);
+    // 由 1970-01-01 起的天数换算公历日期（Howard Hinnant 的 civil_from_days 算法）
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rest / 3_600,
+        rest % 3_600 / 60,
+        rest % 60,
+        since_epoch.subsec_millis()
+    )
+}
@@ -1,392 +1,6642 @@
 // Prevents additional console window on Windows in release builds
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod autosave;
+mod backup;
+mod checksum;
+mod cli;
+mod clipboard;
+mod deep_link;
+mod docx;
+mod editor_windows;
+mod encoding;
+mod error;
+mod file_association;
+mod file_drop;
+mod gzip;
+mod import;
+mod jobs;
+mod locale;
+mod logging;
+mod math;
+mod mathml;
+mod menu;
+mod opener;
+mod pdf;
+mod png;
+mod print;
+mod recent;
+mod recovery;
+#[cfg(windows)]
+mod registry;
+mod settings;
+mod shortcut;
+mod single_instance;
+mod svg;
+mod system_info;
+mod theme;
+mod tray;
+mod typst;
+mod validate;
+mod watcher;
+mod window_state;
+mod zoom;
+
+use autosave::Autosave;
+use backup::{BackupInfo, SaveReport};
+use checksum::FileChecksum;
+use clipboard::SystemClipboard;
+use deep_link::DeepLinks;
+use editor_windows::EditorWindows;
+use encoding::{LineEndings, TextEncoding};
+use error::{AppError, Location};
+use jobs::JobRegistry;
+use menu::RecentMenu;
+use recent::{RecentFile, RecentFiles};
+use recovery::{Recovery, SnapshotInfo};
+use settings::SettingsStore;
+use shortcut::GlobalShortcut;
+use single_instance::{Acquired, Instance};
+use system_info::SystemInfo;
+use tray::Tray;
+use watcher::FileWatcher;
+use window_state::WindowState;
+
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsString;
 use std::fs;
-use std::path::PathBuf;
-use tauri::{command, Window};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tauri::{command, FileDropEvent, LogicalSize, Manager, State, Window, WindowBuilder, WindowEvent, WindowUrl};
 use tokio::sync::oneshot;
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
+use serde_json::{Map, Value};
+
+/// `read_json_file` 默认允许读取的最大字节数
+const DEFAULT_MAX_READ_BYTES: u64 = 20 * 1024 * 1024;
+
+/// `read_json_file_head` 单次最多返回的字节数
+const MAX_HEAD_BYTES: usize = 1024 * 1024;
+
+/// 读取JSON文件，自动识别 UTF-8（含 BOM）、UTF-16 和 GBK 编码，gzip 压缩的文件自动解压；
+/// 超过 `max_bytes` 时拒绝读取。
+/// 内容与上次保存时记录的校验和不一致时发送 `file:checksum-mismatch` 事件，照常返回内容
+#[command]
+async fn read_json_file(window: Window, path: String, max_bytes: Option<u64>) -> Result<String, AppError> {
+    let file_path = Path::new(&path);
+    let (content, raw) = read_text_file_raw(file_path, max_bytes.unwrap_or(DEFAULT_MAX_READ_BYTES))?;
+    if let Some(mismatch) = checksum::verify(file_path, &raw) {
+        log::warn!("Checksum mismatch for {}: expected {}, got {}", path, mismatch.expected, mismatch.actual);
+        if let Err(e) = window.emit("file:checksum-mismatch", mismatch) {
+            log::warn!("Failed to emit checksum mismatch: {}", e);
+        }
+    }
+    Ok(content)
+}
+
+/// gzip 压缩的文件先解压，`limit` 按解压后的大小计算
+fn read_text_file(file_path: &Path, limit: u64) -> Result<String, AppError> {
+    read_text_file_raw(file_path, limit).map(|(content, _)| content)
+}
+
+/// 与 `read_text_file` 相同，同时返回磁盘上的原始字节，用于校验和比较
+fn read_text_file_raw(file_path: &Path, limit: u64) -> Result<(String, Vec<u8>), AppError> {
+    let size = fs::metadata(file_path)
+        .map_err(|e| AppError::io(file_path, "Failed to read file", e))?
+        .len();
+    if size > limit {
+        return Err(AppError::TooLarge { size, limit });
+    }
+    let raw = fs::read(file_path).map_err(|e| AppError::io(file_path, "Failed to read file", e))?;
+    let bytes = gzip::decode(file_path, &raw, limit)?;
+    let content = encoding::decode_text(&bytes).ok_or_else(|| AppError::UnsupportedEncoding {
+        attempted: encoding::ATTEMPTED_ENCODINGS.iter().map(|e| e.to_string()).collect(),
+    })?;
+    Ok((content, raw))
+}
+
+/// 只读取文件开头的若干字节，便于前端在完整加载前判断文件类型
+#[command]
+async fn read_json_file_head(path: String, bytes: usize) -> Result<String, AppError> {
+    let head = gzip::read_head(Path::new(&path), bytes.min(MAX_HEAD_BYTES) as u64)?;
+    Ok(encoding::decode_head(&head))
+}
+
+/// 流式计算文件的 SHA-256、大小和修改时间
+#[command]
+async fn compute_file_checksum(path: String) -> Result<FileChecksum, AppError> {
+    let file_path = PathBuf::from(path);
+    tokio::task::spawn_blocking(move || {
+        checksum::compute(&file_path).map_err(|e| AppError::io(&file_path, "Failed to read file", e))
+    })
+    .await?
+}
+
+/// 写入JSON文件；`overwrite` 为 false 时不覆盖已有的其他内容，以新建文件的方式写入，不产生备份
+#[command]
+async fn write_json_file(
+    window: Window,
+    store: State<'_, SettingsStore>,
+    watcher: State<'_, FileWatcher>,
+    editors: State<'_, EditorWindows>,
+    path: String,
+    content: String,
+    overwrite: Option<bool>,
+) -> Result<SaveReport, AppError> {
+    let path = Path::new(&path);
+    editors.check_write(window.label(), path)?;
+    if overwrite == Some(false) {
+        // 只有确实会新建文件时才让监视器忽略，冲突时不能掩盖随后的外部修改
+        if !path.exists() {
+            watcher.note_self_write(path);
+        }
+        write_file_new(path, content.as_bytes())?;
+        checksum::record(path);
+        return Ok(SaveReport::default());
+    }
+    watcher.note_self_write(path);
+    backup::save_with_backup(path, content.as_bytes(), backup_count(&store))
+}
+
+/// 启用后台自动保存：内容经 `queue_autosave_content` 提交，每个周期最多写盘一次
+#[command]
+async fn enable_autosave(
+    app: tauri::AppHandle,
+    window: Window,
+    autosave: State<'_, Autosave>,
+    path: String,
+    interval_secs: u64,
+) -> Result<(), AppError> {
+    if interval_secs == 0 {
+        return Err(AppError::InvalidOption {
+            name: "intervalSecs".to_string(),
+            value: interval_secs.to_string(),
+        });
+    }
+    autosave.enable(app, window.label().to_string(), PathBuf::from(path), Duration::from_secs(interval_secs));
+    Ok(())
+}
+
+/// 停用自动保存，停用前会先写入尚未保存的内容
+#[command]
+async fn disable_autosave(app: tauri::AppHandle, window: Window, autosave: State<'_, Autosave>) -> Result<(), AppError> {
+    autosave.disable(&app, window.label());
+    Ok(())
+}
+
+/// 提交最新内容，等待下一次自动保存
+#[command]
+async fn queue_autosave_content(window: Window, autosave: State<'_, Autosave>, content: String) -> Result<(), AppError> {
+    autosave.queue(window.label(), content)
+}
+
+/// 监视文件的外部修改，变化时向窗口发送 `file:changed` 事件；会替换该窗口之前的监视
+#[command]
+async fn watch_file(
+    window: Window,
+    watcher: State<'_, FileWatcher>,
+    path: String,
+) -> Result<(), AppError> {
+    watcher.watch(window, PathBuf::from(path))
+}
+
+/// 停止监视该窗口的文件
+#[command]
+async fn unwatch_file(window: Window, watcher: State<'_, FileWatcher>) -> Result<(), AppError> {
+    watcher.unwatch(window.label());
+    Ok(())
+}
+
+/// 保存未保存编辑的恢复快照；同一源文件只保留最新一份，返回快照 id
+#[command]
+async fn write_recovery_snapshot(
+    recovery: State<'_, Recovery>,
+    content: String,
+    source_path: Option<String>,
+) -> Result<String, AppError> {
+    recovery.write(source_path, content)
+}
 
-/// 读取JSON文件
+/// 列出全部恢复快照
 #[command]
-async fn read_json_file(path: String) -> Result<String, String> {
-    fs::read_to_string(&path)
-        .map_err(|e| format!("Failed to read file: {}", e))
+async fn list_recovery_snapshots(recovery: State<'_, Recovery>) -> Result<Vec<SnapshotInfo>, AppError> {
+    Ok(recovery.list())
 }
 
-/// 写入JSON文件
+/// 读取恢复快照的内容
 #[command]
-async fn write_json_file(path: String, content: String) -> Result<(), String> {
-    fs::write(&path, content)
-        .map_err(|e| format!("Failed to write file: {}", e))
+async fn recover_snapshot(recovery: State<'_, Recovery>, id: String) -> Result<String, AppError> {
+    recovery.recover(&id)
+}
+
+/// 删除恢复快照
+#[command]
+async fn discard_snapshot(recovery: State<'_, Recovery>, id: String) -> Result<(), AppError> {
+    recovery.discard(&id)
+}
+
+/// 恢复快照保留天数在设置中的键名
+const RECOVERY_RETENTION_KEY: &str = "recoveryRetentionDays";
+
+/// 备份数量在设置中的键名
+const BACKUP_COUNT_KEY: &str = "backupCount";
+
+/// 读取配置的备份数量，未设置时使用默认值
+fn backup_count(store: &SettingsStore) -> usize {
+    store
+        .get(BACKUP_COUNT_KEY)
+        .and_then(|value| value.as_u64())
+        .map(|count| count as usize)
+        .unwrap_or(backup::DEFAULT_BACKUP_COUNT)
+}
+
+/// 列出公式文件的历史备份
+#[command]
+async fn list_backups(path: String) -> Result<Vec<BackupInfo>, AppError> {
+    Ok(backup::list(Path::new(&path)))
+}
+
+/// 从指定备份恢复公式文件，返回恢复后的内容
+#[command]
+async fn restore_backup(
+    store: State<'_, SettingsStore>,
+    path: String,
+    backup_index: usize,
+) -> Result<String, AppError> {
+    backup::restore(Path::new(&path), backup_index, backup_count(&store))
+}
+
+/// 文件对话框的类型过滤器，由前端提供
+#[derive(Deserialize)]
+struct DialogFilter {
+    name: String,
+    extensions: Vec<String>,
+}
+
+/// 添加过滤器；未提供时只显示 JSON 文件
+fn dialog_with_filters(filters: Option<Vec<DialogFilter>>) -> tauri::api::dialog::FileDialogBuilder {
+    let mut builder = tauri::api::dialog::FileDialogBuilder::new();
+    match filters {
+        Some(filters) => {
+            for filter in &filters {
+                let extensions: Vec<&str> = filter
+                    .extensions
+                    .iter()
+                    .map(|e| e.trim_start_matches('.'))
+                    .collect();
+                builder = builder.add_filter(&filter.name, &extensions);
+            }
+        }
+        None => builder = builder.add_filter("Formula Files", COLLECTION_EXTENSIONS),
+    }
+    builder
+}
+
+/// 文件对话框相关命令的结果，序列化为 `{ status, path }`；写入失败仍作为错误返回
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+enum FileOutcome {
+    /// 已选择路径，尚未读写
+    Selected { path: String },
+    /// 已写入文件；`warnings` 为空时不序列化
+    Saved {
+        path: String,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        warnings: Vec<String>,
+    },
+    /// 已选择路径并启动后台任务，结果通过 `job:done` 事件给出
+    Started {
+        #[serde(rename = "jobId")]
+        job_id: String,
+    },
+    /// 用户取消，或对话框未返回结果
+    Cancelled,
 }
 
 /// 选择打开文件对话框
 #[command]
-async fn open_file_dialog() -> Result<Option<String>, String> {
-    use tauri::api::dialog::FileDialogBuilder;
+async fn open_file_dialog(filters: Option<Vec<DialogFilter>>) -> Result<FileOutcome, AppError> {
+    let (tx, rx) = oneshot::channel();
+    dialog_with_filters(filters).pick_file(move |file_path| {
+        let _ = tx.send(file_path);
+    });
+
+    Ok(match rx.await {
+        Ok(Some(path)) => FileOutcome::Selected {
+            path: path.to_string_lossy().to_string(),
+        },
+        _ => FileOutcome::Cancelled,
+    })
+}
 
+/// 一次选择多个文件；用户取消时返回 `None`，与选择了零个文件的空列表区分
+#[command]
+async fn pick_files(filters: Option<Vec<DialogFilter>>) -> Result<Option<Vec<String>>, AppError> {
     let (tx, rx) = oneshot::channel();
-    FileDialogBuilder::new()
-        .add_filter("JSON Files", &["json"])
-        .pick_file(move |file_path| {
-            let _ = tx.send(file_path);
-        });
+    dialog_with_filters(filters).pick_files(move |file_paths| {
+        let _ = tx.send(file_paths);
+    });
 
-    let file_path = match rx.await {
-        Ok(path) => path,
+    let file_paths = match rx.await {
+        Ok(paths) => paths,
         Err(_) => return Ok(None),
     };
-    Ok(file_path.map(|p| p.to_string_lossy().to_string()))
+    Ok(file_paths.map(|paths| {
+        paths
+            .iter()
+            .map(|p| p.to_string_lossy().to_string())
+            .collect()
+    }))
 }
 
 /// 选择保存文件对话框
 #[command]
-async fn save_file_dialog() -> Result<Option<String>, String> {
+async fn save_file_dialog() -> Result<FileOutcome, AppError> {
     use tauri::api::dialog::FileDialogBuilder;
 
     let (tx, rx) = oneshot::channel();
     FileDialogBuilder::new()
-        .add_filter("JSON Files", &["json"])
+        .add_filter("Formula Files", COLLECTION_EXTENSIONS)
         .set_file_name("formulas.json")
         .save_file(move |file_path| {
             let _ = tx.send(file_path);
         });
 
+    Ok(match rx.await {
+        Ok(Some(path)) => FileOutcome::Selected {
+            path: ensure_extension(path, COLLECTION_EXTENSIONS)?.to_string_lossy().to_string(),
+        },
+        _ => FileOutcome::Cancelled,
+    })
+}
+
+/// 当前时间的 Unix 毫秒时间戳
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// 原子写入：先写入同目录下的临时文件并同步到磁盘，再重命名覆盖目标文件
+///
+/// 任一阶段失败都会清理临时文件，原文件保持不变；错误信息会注明失败的阶段。
+fn write_file_atomic(path: &Path, content: &[u8]) -> Result<(), AppError> {
+    let tmp_path = write_temp_file(path, content)?;
+    replace_file(&tmp_path, path).map_err(|e| {
+        let _ = fs::remove_file(&tmp_path);
+        AppError::io(path, "Failed to replace file", e)
+    })?;
+    sync_parent_dir(path);
+    Ok(())
+}
+
+/// 在目标文件同目录下写入并同步临时文件，返回临时文件路径；失败时清理临时文件
+fn write_temp_file(path: &Path, content: &[u8]) -> Result<PathBuf, AppError> {
+    let dir = path
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let file_name = path
+        .file_name()
+        .ok_or_else(|| AppError::InvalidOption {
+            name: "path".to_string(),
+            value: path.display().to_string(),
+        })?;
+    let tmp_path = dir.join(format!(
+        ".{}.{}.tmp",
+        file_name.to_string_lossy(),
+        std::process::id()
+    ));
+
+    let fail = |context: &str, err: std::io::Error| {
+        let _ = fs::remove_file(&tmp_path);
+        AppError::io(path, context, err)
+    };
+    let mut file = fs::File::create(&tmp_path)
+        .and_then(|file| gzip::write(file, path, content))
+        .map_err(|e| fail("Failed to write temporary file", e))?;
+    file.flush()
+        .and_then(|_| file.sync_all())
+        .map_err(|e| fail("Failed to sync temporary file", e))?;
+    Ok(tmp_path)
+}
+
+/// 同步目录项，确保重命名或新建链接本身也已落盘
+fn sync_parent_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(dir) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        if let Ok(dir) = fs::File::open(dir) {
+            let _ = dir.sync_all();
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+/// 目标已存在且内容不同时返回 `Conflict`；目标不存在或内容相同时可以写入
+fn check_overwrite(path: &Path, content: &[u8]) -> Result<(), AppError> {
+    if !path.exists() || gzip::read(path, u64::MAX).is_ok_and(|existing| existing == content) {
+        Ok(())
+    } else {
+        Err(AppError::conflict(path))
+    }
+}
+
+/// 不覆盖已有文件的写入：目标已存在且内容不同时返回 `Conflict`，内容相同时视为已写入
+///
+/// 先写临时文件，再用硬链接原子地创建目标，检查之后才出现的文件同样不会被覆盖；
+/// 文件系统不支持硬链接时退回到 `create_new` 直接写入。
+fn write_file_new(path: &Path, content: &[u8]) -> Result<(), AppError> {
+    let conflict = || check_overwrite(path, content);
+    if path.exists() {
+        return conflict();
+    }
+    let tmp_path = write_temp_file(path, content)?;
+    let linked = fs::hard_link(&tmp_path, path);
+    let _ = fs::remove_file(&tmp_path);
+    match linked {
+        Ok(()) => {
+            sync_parent_dir(path);
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => conflict(),
+        Err(_) => {
+            let file = match fs::OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => return conflict(),
+                Err(e) => return Err(AppError::io(path, "Failed to create file", e)),
+            };
+            gzip::write(file, path, content)
+                .and_then(|file| file.sync_all())
+                .map_err(|e| AppError::io(path, "Failed to write file", e))
+        }
+    }
+}
+
+/// 按 `overwrite` 选择覆盖写入或不覆盖写入
+fn write_file_checked(path: &Path, content: &[u8], overwrite: bool) -> Result<(), AppError> {
+    if overwrite {
+        write_file_atomic(path, content)
+    } else {
+        write_file_new(path, content)
+    }
+}
+
+/// 用临时文件替换目标文件
+///
+/// Windows 上 `fs::rename` 会覆盖已有文件，但目标被杀毒软件或索引服务短暂占用时会失败，
+/// 因此对拒绝访问类错误做几次短暂重试。
+fn replace_file(from: &Path, to: &Path) -> std::io::Result<()> {
+    #[cfg(windows)]
+    {
+        let mut attempts = 0;
+        loop {
+            match fs::rename(from, to) {
+                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied && attempts < 5 => {
+                    attempts += 1;
+                    std::thread::sleep(Duration::from_millis(50 * attempts));
+                }
+                result => return result,
+            }
+        }
+    }
+    #[cfg(not(windows))]
+    {
+        fs::rename(from, to)
+    }
+}
+
+/// 另存副本的默认文件名：去掉完整的扩展名后加 `-copy`，保留原来的扩展名，
+/// `foo.json.gz` 得到 `foo-copy.json.gz`
+fn copy_file_name(source: Option<&Path>) -> String {
+    let Some(source) = source else {
+        return "formulas-copy.json".to_string();
+    };
+    let name = source.file_name().unwrap_or_default().to_string_lossy();
+    match collection_extension(source) {
+        Some(ext) => format!("{}-copy.{}", strip_extension(&name, ext).unwrap_or(&name), ext),
+        None => {
+            let stem = source.file_stem().map_or(name.clone(), |s| s.to_string_lossy());
+            format!("{}-copy.json", stem)
+        }
+    }
+}
+
+/// 另存副本：写入新文件但不改变当前打开的文档
+#[command]
+async fn save_copy_dialog(
+    content: String,
+    source_path: Option<String>,
+) -> Result<Option<String>, AppError> {
+    use tauri::api::dialog::FileDialogBuilder;
+
+    let source = source_path.map(PathBuf::from);
+    let (tx, rx) = oneshot::channel();
+    let mut builder = FileDialogBuilder::new()
+        .add_filter("Formula Files", COLLECTION_EXTENSIONS)
+        .set_file_name(&copy_file_name(source.as_deref()));
+    if let Some(dir) = source.as_ref().and_then(|p| p.parent()) {
+        builder = builder.set_directory(dir);
+    }
+    builder.save_file(move |file_path| {
+        let _ = tx.send(file_path);
+    });
+
     let file_path = match rx.await {
-        Ok(path) => path,
-        Err(_) => return Ok(None),
+        Ok(Some(path)) => path,
+        _ => return Ok(None),
+    };
+    write_file_atomic(&file_path, content.as_bytes())?;
+    checksum::record(&file_path);
+    Ok(Some(file_path.to_string_lossy().to_string()))
+}
+
+/// 记录最近打开的文件
+#[command]
+async fn add_recent_file(
+    app: tauri::AppHandle,
+    recent: State<'_, RecentFiles>,
+    path: String,
+) -> Result<Vec<RecentFile>, AppError> {
+    let files = recent.add(&path)?;
+    if let Err(e) = menu::refresh_recent(&app, &files) {
+        log::error!("Failed to refresh recent menu: {}", e);
+    }
+    Ok(files)
+}
+
+/// 获取最近打开的文件列表（已删除的文件会被移除）
+#[command]
+async fn get_recent_files(recent: State<'_, RecentFiles>) -> Result<Vec<RecentFile>, AppError> {
+    recent.list()
+}
+
+/// 清空最近打开的文件列表
+#[command]
+async fn clear_recent_files(app: tauri::AppHandle, recent: State<'_, RecentFiles>) -> Result<(), AppError> {
+    recent.clear()?;
+    Ok(menu::refresh_recent(&app, &[])?)
+}
+
+/// 显示或移除系统托盘图标，设置会被保存；无需重启即可生效
+#[command]
+async fn set_tray_enabled(
+    app: tauri::AppHandle,
+    store: State<'_, SettingsStore>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    let result = if enabled {
+        tray::enable(&app)
+    } else {
+        tray::disable(&app)
+    };
+    result?;
+    store.set(tray::TRAY_ENABLED_KEY, Value::Bool(enabled))
+}
+
+/// 设置关闭主窗口时是否隐藏到托盘；仅在托盘图标显示时生效
+#[command]
+async fn set_minimize_to_tray(store: State<'_, SettingsStore>, enabled: bool) -> Result<(), AppError> {
+    store.set(tray::MINIMIZE_TO_TRAY_KEY, Value::Bool(enabled))
+}
+
+/// 注册唤出主窗口的全局快捷键并保存；快捷键被占用时返回 `ShortcutUnavailable`
+#[command]
+async fn register_global_shortcut(
+    app: tauri::AppHandle,
+    store: State<'_, SettingsStore>,
+    shortcut: State<'_, GlobalShortcut>,
+    accelerator: String,
+) -> Result<(), AppError> {
+    shortcut.register(&app, &accelerator)?;
+    store
+        .set(shortcut::GLOBAL_SHORTCUT_KEY, Value::String(accelerator.trim().to_string()))
+}
+
+/// 注销全局快捷键，下次启动也不再注册
+#[command]
+async fn unregister_global_shortcut(
+    app: tauri::AppHandle,
+    store: State<'_, SettingsStore>,
+    shortcut: State<'_, GlobalShortcut>,
+) -> Result<(), AppError> {
+    shortcut.unregister(&app)?;
+    store.set(shortcut::GLOBAL_SHORTCUT_KEY, Value::Null)
+}
+
+/// 当前注册的全局快捷键，未注册时返回 `None`
+#[command]
+async fn get_global_shortcut(shortcut: State<'_, GlobalShortcut>) -> Result<Option<String>, AppError> {
+    Ok(shortcut.current())
+}
+
+/// 设置并保存所有窗口的缩放比例；超出 0.5–3.0 的值会被调整到范围内，返回实际使用的比例
+#[command]
+async fn set_zoom_preference(
+    app: tauri::AppHandle,
+    store: State<'_, SettingsStore>,
+    factor: f64,
+) -> Result<f64, AppError> {
+    let factor = zoom::clamp(factor);
+    zoom::apply_all(&app, factor)?;
+    let value = serde_json::Number::from_f64(factor)
+        .map(Value::Number)
+        .ok_or_else(|| AppError::InvalidOption {
+            name: "factor".to_string(),
+            value: factor.to_string(),
+        })?;
+    store.set(zoom::ZOOM_KEY, value)?;
+    Ok(factor)
+}
+
+/// 读取保存的缩放比例，未设置时为 1.0
+#[command]
+async fn get_zoom_preference(store: State<'_, SettingsStore>) -> Result<f64, AppError> {
+    Ok(zoom::saved(&store))
+}
+
+/// 按最近文件列表重建菜单中的“最近打开”子菜单
+#[command]
+async fn refresh_recent_menu(app: tauri::AppHandle, recent: State<'_, RecentFiles>) -> Result<(), AppError> {
+    let files = recent.list()?;
+    Ok(menu::refresh_recent(&app, &files)?)
+}
+
+/// 获取应用配置目录
+#[command]
+async fn get_app_config_dir(app: tauri::AppHandle) -> Result<String, AppError> {
+    app.path_resolver()
+        .app_config_dir()
+        .map(|p| p.to_string_lossy().to_string())
+        .ok_or(AppError::LocationUnavailable(Location::ConfigDir))
+}
+
+/// 检查文件是否存在
+#[command]
+async fn file_exists(path: String) -> Result<bool, AppError> {
+    Ok(PathBuf::from(path).exists())
+}
+
+/// 设置窗口标题
+#[command]
+async fn set_window_title(window: Window, title: String) -> Result<(), AppError> {
+    Ok(window.set_title(&title)?)
+}
+
+/// 应用名称，用于窗口标题和菜单
+const APP_NAME: &str = "MathLive Formula Editor";
+
+/// 新编辑器窗口的默认尺寸，与 tauri.conf.json 中的主窗口一致
+const EDITOR_WINDOW_SIZE: (f64, f64) = (1280.0, 800.0);
+const EDITOR_WINDOW_MIN_SIZE: (f64, f64) = (800.0, 600.0);
+
+/// 打开新的编辑器窗口并返回其标签；初始文件通过 URL 的 `file` 参数传给前端。
+/// 文件已在某个窗口中打开时不再新建，而是聚焦该窗口并返回其标签
+#[command]
+async fn open_editor_window(
+    app: tauri::AppHandle,
+    editors: State<'_, EditorWindows>,
+    file_path: Option<String>,
+) -> Result<String, AppError> {
+    if let Some(path) = &file_path {
+        let existing = editors
+            .owner(Path::new(path))
+            .and_then(|label| app.get_window(&label));
+        if let Some(window) = existing {
+            window
+                .set_focus()?;
+            return Ok(window.label().to_string());
+        }
+    }
+
+    let label = editors.next_label();
+    if let Some(path) = &file_path {
+        editors.claim(&label, Some(Path::new(path)))?;
+    }
+    let url = match &file_path {
+        Some(path) => format!("index.html?file={}", percent_encode(path)),
+        None => "index.html".to_string(),
     };
-    Ok(file_path.map(|p| p.to_string_lossy().to_string()))
+    let built = WindowBuilder::new(&app, label.clone(), WindowUrl::App(url.into()))
+        .title(APP_NAME)
+        .inner_size(EDITOR_WINDOW_SIZE.0, EDITOR_WINDOW_SIZE.1)
+        .min_inner_size(EDITOR_WINDOW_MIN_SIZE.0, EDITOR_WINDOW_MIN_SIZE.1)
+        .theme(theme::native_theme(&theme_preference(&app.state::<SettingsStore>())))
+        .build();
+    let window = match built {
+        Ok(window) => window,
+        Err(e) => {
+            editors.release(&label);
+            return Err(e.into());
+        }
+    };
+    let recent = app.state::<RecentFiles>().list().unwrap_or_default();
+    if let Err(e) = menu::refresh_recent_window(&window, &recent) {
+        log::error!("Failed to refresh recent menu: {}", e);
+    }
+    if let Err(e) = zoom::apply(&window, zoom::saved(&app.state::<SettingsStore>())) {
+        log::error!("Failed to apply zoom: {}", e);
+    }
+    Ok(label)
+}
+
+/// 登记当前窗口绑定的文件，`None` 表示解除绑定；文件已在另一个窗口中打开时返回错误
+#[command]
+async fn set_window_file(
+    window: Window,
+    editors: State<'_, EditorWindows>,
+    path: Option<String>,
+) -> Result<(), AppError> {
+    editors.claim(window.label(), path.as_deref().map(Path::new))
+}
+
+/// 窗口置顶状态在设置中的键名
+const ALWAYS_ON_TOP_KEY: &str = "alwaysOnTop";
+
+/// 当前置顶的窗口标签；Tauri 不提供查询置顶状态的接口，由后端自行记录
+#[derive(Default)]
+struct PinnedWindows(Mutex<HashSet<String>>);
+
+#[derive(Serialize, Clone)]
+struct AlwaysOnTopChanged {
+    label: String,
+    enabled: bool,
+}
+
+/// 设置窗口置顶；状态会被保存并在下次启动时恢复，并通过 `window:always-on-top` 事件通知所有窗口
+#[command]
+async fn set_always_on_top(
+    window: Window,
+    store: State<'_, SettingsStore>,
+    pinned: State<'_, PinnedWindows>,
+    enabled: bool,
+) -> Result<(), AppError> {
+    window.set_always_on_top(enabled)?;
+    {
+        let mut labels = pinned.0.lock().unwrap();
+        if enabled {
+            labels.insert(window.label().to_string());
+        } else {
+            labels.remove(window.label());
+        }
+    }
+    store.set(ALWAYS_ON_TOP_KEY, Value::Bool(enabled))?;
+    let payload = AlwaysOnTopChanged {
+        label: window.label().to_string(),
+        enabled,
+    };
+    Ok(window.emit_all("window:always-on-top", payload)?)
+}
+
+/// 查询当前窗口是否置顶
+#[command]
+async fn get_always_on_top(window: Window, pinned: State<'_, PinnedWindows>) -> Result<bool, AppError> {
+    Ok(pinned.0.lock().unwrap().contains(window.label()))
+}
+
+/// 可以作为公式集打开的文件扩展名（小写）；`mlformulas` 内容同样是 JSON，用于文件关联；
+/// 带 `.gz` 的是两者的 gzip 压缩版本。只认这几个完整的后缀，`.tar.gz` 之类不算公式集
+const COLLECTION_EXTENSIONS: &[&str] = &["json", "mlformulas", "json.gz", "mlformulas.gz"];
+
+/// 文件名以 `.ext` 结尾（不区分大小写）时返回去掉后缀的部分
+fn strip_extension<'a>(name: &'a str, ext: &str) -> Option<&'a str> {
+    let split = name.len().checked_sub(ext.len() + 1)?;
+    let (stem, suffix) = (name.get(..split)?, name.get(split..)?);
+    (suffix.starts_with('.') && suffix[1..].eq_ignore_ascii_case(ext)).then_some(stem)
+}
+
+/// 公式集文件的完整扩展名，如 `foo.json.gz` 的 `json.gz`；不是公式集时返回 `None`
+fn collection_extension(path: &Path) -> Option<&'static str> {
+    let name = path.file_name()?.to_str()?;
+    COLLECTION_EXTENSIONS
+        .iter()
+        .copied()
+        .find(|ext| strip_extension(name, ext).is_some_and(|stem| !stem.is_empty()))
+}
+
+/// 启动参数中的文件（双击关联的文件或“打开方式”），等前端加载完成后取走
+#[derive(Default)]
+struct StartupFile(Mutex<Option<String>>);
+
+impl StartupFile {
+    /// 取启动参数中第一个可读取的公式集文件。macOS 通过 Apple Event 传递打开的文件，
+    /// Tauri 1.8 的 `RunEvent` 没有对应的事件（Tauri 2 才有 `RunEvent::Opened`），因此只处理命令行参数
+    fn from_args() -> Self {
+        let cwd = std::env::current_dir().unwrap_or_default();
+        let file = startup_file(std::env::args_os().skip(1), &cwd);
+        StartupFile(Mutex::new(file.map(|path| path.to_string_lossy().into_owned())))
+    }
+}
+
+/// 路径的扩展名是否属于可以打开的公式集
+fn is_collection_path(path: &Path) -> bool {
+    collection_extension(path).is_some()
+}
+
+/// 先按扩展名筛选参数，再取第一个可读取的文件；前面的目录或其他参数不会挡住后面的公式集文件
+fn startup_file(args: impl IntoIterator<Item = OsString>, cwd: &Path) -> Option<PathBuf> {
+    args.into_iter()
+        .map(|arg| cwd.join(arg))
+        .filter(|path| is_collection_path(path))
+        .find(|path| {
+            let readable = path.is_file() && fs::File::open(path).is_ok();
+            if !readable {
+                log::warn!("Ignoring startup argument {}: not a readable formula file", path.display());
+            }
+            readable
+        })
+}
+
+/// 取走启动参数中的文件，只有第一次调用返回路径
+#[command]
+async fn take_startup_file(file: State<'_, StartupFile>) -> Result<Option<String>, AppError> {
+    Ok(file.0.lock().unwrap().take())
+}
+
+/// 把 `.mlformulas` 文件关联到本程序，只修改当前用户的设置
+#[command]
+async fn register_file_association() -> Result<(), AppError> {
+    tokio::task::spawn_blocking(file_association::register).await?
+}
+
+/// 把 `mathlive-formula://` 协议重新登记到本程序；启动时只在第一次运行时自动登记
+#[command]
+async fn register_url_scheme() -> Result<(), AppError> {
+    tokio::task::spawn_blocking(deep_link::register_scheme).await?
+}
+
+/// 撤销 `.mlformulas` 文件关联
+#[command]
+async fn unregister_file_association() -> Result<(), AppError> {
+    tokio::task::spawn_blocking(file_association::unregister).await?
+}
+
+/// 前端加载完成后调用，之后才发送启动时缓存的深度链接和其他实例转交的文件
+#[command]
+async fn frontend_ready(app: tauri::AppHandle, links: State<'_, DeepLinks>) -> Result<(), AppError> {
+    links.mark_ready(&app);
+    if let Some(instance) = app.try_state::<Instance>() {
+        instance.attach(&app);
+    }
+    Ok(())
+}
+
+/// 删除保存的窗口位置和大小，并把窗口恢复为默认大小居中显示
+#[command]
+async fn reset_window_state(window: Window, state: State<'_, WindowState>) -> Result<(), AppError> {
+    state.reset(&window)
+}
+
+/// 窗口尺寸限制在设置中的键名
+const SIZE_CONSTRAINTS_KEY: &str = "windowSizeConstraints";
+
+/// 只限制一个方向时，另一方向的最大尺寸
+const UNBOUNDED_SIZE: f64 = 100_000.0;
+
+/// 窗口最小/最大尺寸限制，`None` 表示不限制
+#[derive(Serialize, Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+struct SizeConstraints {
+    min_width: Option<f64>,
+    min_height: Option<f64>,
+    max_width: Option<f64>,
+    max_height: Option<f64>,
+}
+
+impl SizeConstraints {
+    /// 将 0 视为不限制，并校验最小值不大于最大值
+    fn validated(self) -> Result<Self, AppError> {
+        let clean = |value: Option<f64>, name: &str| -> Result<Option<f64>, AppError> {
+            match value {
+                Some(v) if !v.is_finite() || v < 0.0 => Err(AppError::InvalidOption {
+                    name: name.to_string(),
+                    value: v.to_string(),
+                }),
+                other => Ok(other.filter(|v| *v > 0.0)),
+            }
+        };
+        let constraints = SizeConstraints {
+            min_width: clean(self.min_width, "minWidth")?,
+            min_height: clean(self.min_height, "minHeight")?,
+            max_width: clean(self.max_width, "maxWidth")?,
+            max_height: clean(self.max_height, "maxHeight")?,
+        };
+        if let (Some(min), Some(max)) = (constraints.min_width, constraints.max_width) {
+            if min > max {
+                return Err(AppError::InvalidOption {
+                    name: "minWidth".to_string(),
+                    value: format!("{} > {}", min, max),
+                });
+            }
+        }
+        if let (Some(min), Some(max)) = (constraints.min_height, constraints.max_height) {
+            if min > max {
+                return Err(AppError::InvalidOption {
+                    name: "minHeight".to_string(),
+                    value: format!("{} > {}", min, max),
+                });
+            }
+        }
+        Ok(constraints)
+    }
+
+    fn apply(&self, window: &Window) -> tauri::Result<()> {
+        let min = (self.min_width.is_some() || self.min_height.is_some()).then(|| {
+            LogicalSize::new(self.min_width.unwrap_or(0.0), self.min_height.unwrap_or(0.0))
+        });
+        let max = (self.max_width.is_some() || self.max_height.is_some()).then(|| {
+            LogicalSize::new(
+                self.max_width.unwrap_or(UNBOUNDED_SIZE),
+                self.max_height.unwrap_or(UNBOUNDED_SIZE),
+            )
+        });
+        window.set_min_size(min)?;
+        window.set_max_size(max)
+    }
+}
+
+/// 设置窗口最小/最大尺寸，`None` 或 0 表示不限制；设置会被保存并在下次启动时恢复
+#[command]
+async fn set_size_constraints(
+    window: Window,
+    store: State<'_, SettingsStore>,
+    min_width: Option<f64>,
+    min_height: Option<f64>,
+    max_width: Option<f64>,
+    max_height: Option<f64>,
+) -> Result<(), AppError> {
+    let constraints = SizeConstraints {
+        min_width,
+        min_height,
+        max_width,
+        max_height,
+    }
+    .validated()?;
+    constraints.apply(&window)?;
+    let value = serde_json::to_value(constraints).map_err(AppError::encode_json)?;
+    store.set(SIZE_CONSTRAINTS_KEY, value)
+}
+
+/// 前端未响应关闭确认时，等待多久后强制关闭窗口
+const CLOSE_CONFIRM_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// 窗口关闭守卫：记录已确认可关闭的窗口和等待前端确认的关闭请求
+#[derive(Default)]
+struct CloseGuard {
+    allowed: Mutex<HashSet<String>>,
+    pending: Mutex<HashMap<String, u64>>,
+}
+
+impl CloseGuard {
+    fn allow(&self, label: &str) {
+        self.pending.lock().unwrap().remove(label);
+        self.allowed.lock().unwrap().insert(label.to_string());
+    }
+
+    fn is_allowed(&self, label: &str) -> bool {
+        self.allowed.lock().unwrap().contains(label)
+    }
+
+    fn take_allowed(&self, label: &str) -> bool {
+        self.allowed.lock().unwrap().remove(label)
+    }
+
+    /// 登记一次新的关闭请求，返回其序号
+    fn begin(&self, label: &str) -> u64 {
+        let mut pending = self.pending.lock().unwrap();
+        let ticket = pending.get(label).map_or(1, |t| t + 1);
+        pending.insert(label.to_string(), ticket);
+        ticket
+    }
+
+    /// 前端已接管关闭请求，取消超时强制关闭
+    fn settle(&self, label: &str) {
+        self.pending.lock().unwrap().remove(label);
+    }
+
+    /// 若该序号的请求仍未得到响应，则将其移除并返回 true
+    fn expire(&self, label: &str, ticket: u64) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+        if pending.get(label) == Some(&ticket) {
+            pending.remove(label);
+            true
+        } else {
+            false
+        }
+    }
+}
+
+/// 拦截窗口关闭：通知前端检查未保存内容，超时未响应则直接关闭
+fn handle_close_requested(window: &Window, api: &tauri::CloseRequestApi) {
+    let guard = window.state::<CloseGuard>();
+    let label = window.label().to_string();
+    if guard.take_allowed(&label) {
+        return;
+    }
+    api.prevent_close();
+    let ticket = guard.begin(&label);
+    if window.emit("confirm-close", ()).is_err() {
+        guard.allow(&label);
+        let _ = window.close();
+        return;
+    }
+    let window = window.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(CLOSE_CONFIRM_TIMEOUT).await;
+        let guard = window.state::<CloseGuard>();
+        if guard.expire(&label, ticket) {
+            guard.allow(&label);
+            let _ = window.close();
+        }
+    });
+}
+
+/// 用户确认后关闭窗口
+#[command]
+async fn force_close(window: Window, guard: State<'_, CloseGuard>) -> Result<(), AppError> {
+    guard.allow(window.label());
+    Ok(window.close()?)
+}
+
+/// 前端已接管关闭请求（例如正在询问用户），取消超时关闭；用户选择不关闭时也应调用
+#[command]
+async fn cancel_close(
+    window: Window,
+    guard: State<'_, CloseGuard>,
+    tray: State<'_, Tray>,
+) -> Result<(), AppError> {
+    guard.settle(window.label());
+    tray.cancel_quit();
+    Ok(())
+}
+
+/// 主题在设置中的键名
+const THEME_KEY: &str = "theme";
+
+/// 支持的主题取值，需与前端 `Theme` 类型保持一致；"system" 表示跟随系统
+const THEMES: &[&str] = &[
+    "system", "light", "dark", "blue", "pink", "green", "purple", "paper", "sunset",
+];
+
+/// 主题设置（存储到本地），同时让所有窗口的原生标题栏跟随主题
+#[command]
+async fn set_theme_preference(
+    app: tauri::AppHandle,
+    store: State<'_, SettingsStore>,
+    theme: String,
+) -> Result<(), AppError> {
+    if !THEMES.contains(&theme.as_str()) {
+        return Err(AppError::InvalidOption {
+            name: "theme".to_string(),
+            value: theme,
+        });
+    }
+    theme::apply_all(&app, theme::native_theme(&theme))?;
+    store.set(THEME_KEY, Value::String(theme))
+}
+
+/// 设置后端消息（错误信息）使用的语言并保存，返回实际采用的语言，如 `zh-CN`、`en`
+#[command]
+async fn set_backend_locale(store: State<'_, SettingsStore>, locale: String) -> Result<String, AppError> {
+    let Some(resolved) = locale::Locale::parse(&locale) else {
+        return Err(AppError::InvalidOption {
+            name: "locale".to_string(),
+            value: locale,
+        });
+    };
+    store.set(locale::LOCALE_KEY, Value::String(resolved.tag().to_string()))?;
+    locale::set(resolved);
+    Ok(resolved.tag().to_string())
+}
+
+fn theme_preference(store: &SettingsStore) -> String {
+    store
+        .get(THEME_KEY)
+        .and_then(|value| value.as_str().map(str::to_string))
+        .filter(|theme| THEMES.contains(&theme.as_str()))
+        .unwrap_or_else(|| "system".to_string())
+}
+
+/// 读取已保存的主题，缺失或无效时返回 "system"
+#[command]
+async fn get_theme_preference(store: State<'_, SettingsStore>) -> Result<String, AppError> {
+    Ok(theme_preference(&store))
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EffectiveTheme {
+    /// 保存的主题设置，可能是 "system"
+    preference: String,
+    /// 实际使用的主题；设置为 "system" 时按系统外观取 "light" 或 "dark"
+    theme: String,
+    /// 原生窗口是否为深色外观
+    dark: bool,
+}
+
+/// 结合主题设置和系统当前的外观，得到窗口实际使用的主题
+#[command]
+async fn get_effective_theme(window: Window, store: State<'_, SettingsStore>) -> Result<EffectiveTheme, AppError> {
+    let preference = theme_preference(&store);
+    let native = match theme::native_theme(&preference) {
+        Some(native) => native,
+        None => window.theme()?,
+    };
+    let theme = if preference == "system" {
+        native.to_string()
+    } else {
+        preference.clone()
+    };
+    Ok(EffectiveTheme {
+        preference,
+        theme,
+        dark: native == tauri::Theme::Dark,
+    })
+}
+
+/// 将所有持久化设置恢复为默认值，返回被重置的文件列表
+#[command]
+async fn reset_settings(store: State<'_, SettingsStore>) -> Result<Vec<String>, AppError> {
+    store.reset()
+}
+
+/// 读取单个设置项，不存在时返回 null
+#[command]
+async fn get_setting(store: State<'_, SettingsStore>, key: String) -> Result<Option<Value>, AppError> {
+    Ok(store.get(&key))
+}
+
+/// 写入单个设置项
+#[command]
+async fn set_setting(
+    store: State<'_, SettingsStore>,
+    key: String,
+    value: Value,
+) -> Result<(), AppError> {
+    let key = key.trim();
+    if key.is_empty() {
+        return Err(AppError::InvalidOption {
+            name: "key".to_string(),
+            value: key.to_string(),
+        });
+    }
+    store.set(key, value)
+}
+
+/// 一次性读取全部设置，供前端初始化
+#[command]
+async fn get_all_settings(
+    store: State<'_, SettingsStore>,
+) -> Result<serde_json::Map<String, Value>, AppError> {
+    Ok(store.all())
+}
+
+/// 弹出导出用的保存对话框，用户取消时返回 `None`
+async fn pick_export_path(filter_name: &str, extensions: &[&str], file_name: &str) -> Option<PathBuf> {
+    use tauri::api::dialog::FileDialogBuilder;
+
+    let (tx, rx) = oneshot::channel();
+    FileDialogBuilder::new()
+        .add_filter(filter_name, extensions)
+        .set_file_name(file_name)
+        .save_file(move |file_path| {
+            let _ = tx.send(file_path);
+        });
+    rx.await.unwrap_or_default()
+}
+
+/// 没有扩展名或扩展名不在过滤器中时（如 Linux 上输入 `formulas`），补上第一个扩展名；
+/// 补上后与已有文件重名时返回冲突，不静默覆盖
+fn ensure_extension(path: PathBuf, extensions: &[&str]) -> Result<PathBuf, AppError> {
+    let matches = path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| extensions.iter().any(|e| strip_extension(name, e).is_some()));
+    let Some(expected) = extensions.first().filter(|_| !matches) else {
+        return Ok(path);
+    };
+    let mut name = path.clone().into_os_string();
+    name.push(".");
+    name.push(expected);
+    let path = PathBuf::from(name);
+    if path.exists() {
+        return Err(AppError::conflict(&path));
+    }
+    Ok(path)
+}
+
+/// 导出的写入选项
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct WriteOptions {
+    /// 为 false 时目标已存在且内容不同则返回 `Conflict`，由前端确认后再以 true 重试
+    overwrite: bool,
+    /// 直接写入该路径、不弹出对话框，用于确认覆盖后的重试
+    path: Option<String>,
+    /// 文本导出的编码，默认 UTF-8
+    encoding: TextEncoding,
+    /// 文本导出的换行符，默认跟随系统
+    line_endings: LineEndings,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        WriteOptions {
+            overwrite: true,
+            path: None,
+            encoding: TextEncoding::default(),
+            line_endings: LineEndings::default(),
+        }
+    }
+}
+
+/// 弹出保存对话框并写入导出内容；`options.path` 已给出时直接写入该路径
+async fn save_export(
+    filter_name: &str,
+    extensions: &[&str],
+    file_name: &str,
+    bytes: &[u8],
+    options: WriteOptions,
+) -> Result<FileOutcome, AppError> {
+    let path = match options.path {
+        Some(path) => PathBuf::from(path),
+        None => match pick_export_path(filter_name, extensions, file_name).await {
+            Some(path) => ensure_extension(path, extensions)?,
+            None => return Ok(FileOutcome::Cancelled),
+        },
+    };
+    write_file_checked(&path, bytes, options.overwrite)?;
+    Ok(FileOutcome::Saved {
+        path: path.to_string_lossy().to_string(),
+        warnings: Vec::new(),
+    })
+}
+
+/// 按 `options` 中的编码和换行符转换文本后保存；无法编码的字符列在结果的 `warnings` 中。
+/// 文本导出命令都应经过这里写入
+async fn save_text_export(
+    filter_name: &str,
+    extensions: &[&str],
+    file_name: &str,
+    content: &str,
+    options: WriteOptions,
+) -> Result<FileOutcome, AppError> {
+    let (bytes, encode_warnings) = encoding::encode_text(content, options.encoding, options.line_endings);
+    let mut outcome = save_export(filter_name, extensions, file_name, &bytes, options).await?;
+    if let FileOutcome::Saved { warnings, .. } = &mut outcome {
+        *warnings = encode_warnings;
+    }
+    Ok(outcome)
+}
+
+/// 导出LaTeX文件
+#[command]
+async fn export_latex_file(content: String, options: Option<WriteOptions>) -> Result<FileOutcome, AppError> {
+    let options = options.unwrap_or_default();
+    save_text_export("LaTeX Files", &["tex"], "formulas.tex", &content, options).await
+}
+
+/// 在系统文件管理器中显示文件，例如导出完成后的“在文件夹中显示”
+#[command]
+async fn reveal_in_file_manager(path: String) -> Result<(), AppError> {
+    opener::reveal(Path::new(&path))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct OpenPathOptions {
+    /// 允许打开可执行文件（`.exe`、`.sh` 等），默认拒绝
+    allow_executables: bool,
+}
+
+/// 用系统关联的默认程序打开文件，例如导出后直接查看 PDF
+#[command]
+async fn open_path_with_default_app(path: String, options: Option<OpenPathOptions>) -> Result<(), AppError> {
+    let allow_executables = options.unwrap_or_default().allow_executables;
+    tokio::task::spawn_blocking(move || opener::open_default(Path::new(&path), allow_executables))
+        .await?
+}
+
+/// 导出Markdown文件
+#[command]
+async fn export_markdown_file(content: String, options: Option<WriteOptions>) -> Result<FileOutcome, AppError> {
+    let options = options.unwrap_or_default();
+    save_text_export("Markdown Files", &["md"], "formulas.md", &content, options).await
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct PdfOptions {
+    include_tags: bool,
+    /// 覆盖确认和重试路径，与文本导出相同；编码和换行符对 PDF 无效
+    #[serde(flatten)]
+    write: WriteOptions,
+}
+
+/// 把公式编译为 PDF 并保存；选择路径后作为后台任务编译，返回 `started` 和任务 id，
+/// 任务结果为写入的路径。没有公式或用户取消时返回 `cancelled`
+#[command]
+async fn export_pdf_file(
+    window: Window,
+    jobs: State<'_, JobRegistry>,
+    formulas: Vec<FormulaItem>,
+    options: Option<PdfOptions>,
+) -> Result<FileOutcome, AppError> {
+    let options = options.unwrap_or_default();
+    let latex = format_latex(formulas, Some(options.include_tags), None)
+        .await?
+        .content;
+    if latex.is_empty() {
+        return Ok(FileOutcome::Cancelled);
+    }
+    let path = match options.write.path {
+        Some(path) => PathBuf::from(path),
+        None => match pick_export_path("PDF Files", &["pdf"], "formulas.pdf").await {
+            Some(path) => ensure_extension(path, &["pdf"])?,
+            None => return Ok(FileOutcome::Cancelled),
+        },
+    };
+    let overwrite = options.write.overwrite;
+
+    let job_id = jobs.start(&window, "pdf", move |job| {
+        job.progress(0, 2, "compiling");
+        let bytes = pdf::compile(&latex, &|| job.is_cancelled())?;
+        job.check_cancelled()?;
+        job.progress(1, 2, "writing");
+        write_file_checked(&path, &bytes, overwrite)?;
+        job.progress(2, 2, "done");
+        Ok(path.to_string_lossy().to_string())
+    });
+    Ok(FileOutcome::Started { job_id })
+}
+
+/// 请求取消后台任务；任务不存在或已结束时返回 false
+#[command]
+async fn cancel_job(jobs: State<'_, JobRegistry>, job_id: String) -> Result<bool, AppError> {
+    Ok(jobs.cancel(&job_id))
+}
+
+/// 弹出选择文件夹对话框，用户取消时返回 `None`
+async fn pick_export_directory() -> Option<PathBuf> {
+    use tauri::api::dialog::FileDialogBuilder;
+
+    let (tx, rx) = oneshot::channel();
+    FileDialogBuilder::new().pick_folder(move |dir| {
+        let _ = tx.send(dir);
+    });
+    rx.await
+        .unwrap_or_default()
+        .map(|dir| PathBuf::from(normalize_dialog_path(&dir)))
+}
+
+/// 规范化对话框返回的路径：去掉 Windows 的 `\\?\` 扩展前缀和末尾多余的分隔符
+fn normalize_dialog_path(path: &Path) -> String {
+    let mut text = path.to_string_lossy().to_string();
+    if cfg!(windows) {
+        if let Some(rest) = text.strip_prefix(r"\\?\UNC\") {
+            text = format!(r"\\{}", rest);
+        } else if let Some(rest) = text.strip_prefix(r"\\?\") {
+            text = rest.to_string();
+        }
+    }
+    // 根目录（`/`、`C:\`）本身以分隔符结尾，需要保留
+    while text.ends_with(std::path::is_separator) && Path::new(&text).parent().is_some() {
+        text.pop();
+    }
+    text
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct PickDirectoryOptions {
+    /// 对话框初始显示的目录
+    default_path: Option<String>,
+    title: Option<String>,
+}
+
+/// 选择文件夹；只有用户取消时返回 `None`，对话框无法打开时返回错误
+#[command]
+async fn pick_directory(options: Option<PickDirectoryOptions>) -> Result<Option<String>, AppError> {
+    use tauri::api::dialog::FileDialogBuilder;
+
+    let options = options.unwrap_or_default();
+    let mut builder = FileDialogBuilder::new();
+    if let Some(dir) = options.default_path.as_deref().filter(|d| !d.is_empty()) {
+        builder = builder.set_directory(dir);
+    }
+    if let Some(title) = options.title.as_deref() {
+        builder = builder.set_title(title);
+    }
+    let (tx, rx) = oneshot::channel();
+    builder.pick_folder(move |dir| {
+        let _ = tx.send(dir);
+    });
+    // 回调未被调用就被丢弃，说明对话框没有成功打开
+    let dir = rx
+        .await
+        .map_err(|_| AppError::WindowFailed {
+            message: "folder dialog closed without a result".to_string(),
+        })?;
+    Ok(dir.map(|d| normalize_dialog_path(&d)))
+}
+
+/// 是否包含中日韩文字，决定导出的 LaTeX 是否需要 ctex
+fn contains_cjk(text: &str) -> bool {
+    text.chars().any(|ch| {
+        matches!(ch as u32,
+            0x3040..=0x30FF      // 平假名、片假名
+            | 0x3400..=0x4DBF    // 扩展 A
+            | 0x4E00..=0x9FFF    // 基本汉字
+            | 0xAC00..=0xD7AF    // 谚文
+            | 0xF900..=0xFAFF    // 兼容汉字
+            | 0xFF00..=0xFFEF    // 全角符号
+            | 0x20000..=0x2FA1F) // 扩展 B 及以后
+    })
+}
+
+/// 由备注生成文件名片段：保留字母数字（含汉字），其余字符折叠为 `-`
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for ch in text.trim().chars() {
+        if ch.is_alphanumeric() {
+            slug.extend(ch.to_lowercase());
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= 40 {
+            break;
+        }
+    }
+    slug.trim_end_matches('-').to_string()
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct LatexDirectoryOptions {
+    /// 文件名模板，支持 `{index}`（三位序号）和 `{slug}`（备注转换的片段）
+    pattern: String,
+    /// 在文件开头以注释写入备注
+    include_note: bool,
+    /// 覆盖同名文件；为 false 时跳过已存在且内容不同的文件
+    overwrite: bool,
+}
+
+impl Default for LatexDirectoryOptions {
+    fn default() -> Self {
+        Self {
+            pattern: "formula-{index}".to_string(),
+            include_note: true,
+            overwrite: false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct LatexDirectoryExport {
+    /// 写入完成时为 `saved`，`path` 为选择的文件夹；没有公式或用户取消时为 `cancelled`
+    #[serde(flatten)]
+    outcome: FileOutcome,
+    written: Vec<String>,
+    /// 因同名文件已存在且内容不同而跳过的路径
+    skipped: Vec<String>,
+}
+
+/// 单个公式的可独立编译的文档
+fn standalone_document(item: &FormulaItem, include_note: bool) -> String {
+    let note = item.note.as_deref().map(str::trim).filter(|n| !n.is_empty());
+    let mut doc = String::new();
+    if let Some(note) = note.filter(|_| include_note) {
+        for line in note.lines() {
+            doc.push_str(&format!("% {}\n", line));
+        }
+    }
+    doc.push_str("\\documentclass[varwidth,border=2pt]{standalone}\n\\usepackage{amsmath}\n");
+    if contains_cjk(&item.latex) || note.is_some_and(contains_cjk) {
+        doc.push_str("\\usepackage{ctex}\n");
+    }
+    doc.push_str(&format!(
+        "\\begin{{document}}\n\\[\n{}\n\\]\n\\end{{document}}\n",
+        item.latex.trim()
+    ));
+    doc
+}
+
+/// 按模板生成带 `extension` 扩展名的文件名；结果中不允许出现路径分隔符，同一批次内重名时追加序号
+fn directory_file_name(
+    pattern: &str,
+    extension: &str,
+    idx: usize,
+    item: &FormulaItem,
+    used: &mut HashSet<String>,
+) -> String {
+    let slug = item.note.as_deref().map(slugify).unwrap_or_default();
+    let name = pattern
+        .replace("{index}", &format!("{:03}", idx + 1))
+        .replace("{slug}", if slug.is_empty() { "formula" } else { &slug });
+    let name: String = name
+        .trim()
+        .chars()
+        .map(|ch| match ch {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '-',
+            _ => ch,
+        })
+        .collect();
+    let stem = name.strip_suffix(&format!(".{}", extension)).unwrap_or(&name).to_string();
+    let stem = if stem.is_empty() { format!("formula-{:03}", idx + 1) } else { stem };
+    let mut candidate = format!("{}.{}", stem, extension);
+    let mut n = 2;
+    while !used.insert(candidate.to_lowercase()) {
+        candidate = format!("{}-{}.{}", stem, n, extension);
+        n += 1;
+    }
+    candidate
+}
+
+/// 把每个公式导出为单独的 standalone 文档，写入用户选择的文件夹
+#[command]
+async fn export_latex_directory(
+    formulas: Vec<FormulaItem>,
+    options: Option<LatexDirectoryOptions>,
+) -> Result<LatexDirectoryExport, AppError> {
+    let options = options.unwrap_or_default();
+    let mut result = LatexDirectoryExport {
+        outcome: FileOutcome::Cancelled,
+        written: Vec::new(),
+        skipped: Vec::new(),
+    };
+    if formulas.is_empty() {
+        return Ok(result);
+    }
+    let Some(dir) = pick_export_directory().await else {
+        return Ok(result);
+    };
+
+    let mut used = HashSet::new();
+    for (idx, item) in formulas.iter().enumerate() {
+        let path = dir.join(directory_file_name(&options.pattern, "tex", idx, item, &mut used));
+        let document = standalone_document(item, options.include_note);
+        match write_file_checked(&path, document.as_bytes(), options.overwrite) {
+            Ok(()) => result.written.push(path.to_string_lossy().to_string()),
+            Err(AppError::Conflict { .. }) => result.skipped.push(path.to_string_lossy().to_string()),
+            Err(e) => return Err(e),
+        }
+    }
+    result.outcome = FileOutcome::Saved {
+        path: dir.to_string_lossy().to_string(),
+        warnings: Vec::new(),
+    };
+    Ok(result)
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct BundleOptions {
+    title: Option<String>,
+    include_tags: bool,
+}
+
+/// 压缩包中写入失败的条目
+#[derive(Serialize)]
+struct BundleFailure {
+    entry: &'static str,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct BundleExport {
+    /// 写入的路径；用户取消时为空字符串
+    path: String,
+    failures: Vec<BundleFailure>,
+}
+
+fn write_zip_entry<W: Write + std::io::Seek>(
+    zip: &mut zip::ZipWriter<W>,
+    name: &str,
+    content: &[u8],
+) -> zip::result::ZipResult<()> {
+    let options = zip::write::FileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    zip.start_file(name, options)?;
+    zip.write_all(content)?;
+    Ok(())
+}
+
+/// 依次写入压缩包的各条目，最后写入带条目列表的清单。LaTeX、Markdown 格式化失败时跳过该条目并记录在
+/// 返回的失败列表中；写入压缩包本身出错时立即返回错误，不再继续写入
+fn write_bundle(
+    file: fs::File,
+    json: &str,
+    documents: [(&'static str, Result<String, AppError>); 2],
+    mut manifest: Map<String, Value>,
+) -> Result<(fs::File, Vec<BundleFailure>), AppError> {
+    let zip_error = |e: zip::result::ZipError| AppError::EncodeFailed {
+        format: "ZIP".to_string(),
+        message: e.to_string(),
+    };
+    let mut zip = zip::ZipWriter::new(file);
+    write_zip_entry(&mut zip, "formulas.json", json.as_bytes()).map_err(zip_error)?;
+    let mut entries = vec!["formulas.json"];
+    let mut failures = Vec::new();
+    for (entry, content) in documents {
+        match content {
+            Ok(content) => {
+                write_zip_entry(&mut zip, entry, content.as_bytes()).map_err(zip_error)?;
+                entries.push(entry);
+            }
+            Err(e) => failures.push(BundleFailure {
+                entry,
+                message: e.to_string(),
+            }),
+        }
+    }
+    manifest.insert("entries".to_string(), serde_json::json!(entries));
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(AppError::encode_json)?;
+    write_zip_entry(&mut zip, "manifest.json", &manifest).map_err(zip_error)?;
+    Ok((zip.finish().map_err(zip_error)?, failures))
+}
+
+/// 把 JSON、LaTeX、Markdown 三种格式连同清单打包成一个 zip，方便整体分享
+///
+/// 各条目依次直接写入磁盘上的临时文件，完成后再替换目标文件。LaTeX、Markdown 格式化失败时
+/// 记录在 `failures` 中，写入压缩包出错时放弃整个压缩包。
+#[command]
+async fn export_bundle_zip(
+    app: tauri::AppHandle,
+    formulas: Vec<FormulaEntry>,
+    options: Option<BundleOptions>,
+) -> Result<BundleExport, AppError> {
+    let options = options.unwrap_or_default();
+    let Some(path) = pick_export_path("Zip Archives", &["zip"], "formulas-bundle.zip").await else {
+        return Ok(BundleExport {
+            path: String::new(),
+            failures: Vec::new(),
+        });
+    };
+
+    let items = formula_items(&formulas);
+    let count = formulas.len();
+    let json = serialize_formulas(formulas, options.title).await?;
+    let latex = format_latex(items.clone(), Some(options.include_tags), None)
+        .await
+        .map(|doc| doc.content);
+    let markdown = format_markdown(items, None, Some(options.include_tags), None, None).await;
+
+    let mut manifest = Map::new();
+    manifest.insert("appVersion".to_string(), Value::from(app.package_info().version.to_string()));
+    manifest.insert("exportedAt".to_string(), Value::from(now_millis()));
+    manifest.insert("formulaCount".to_string(), Value::from(count));
+
+    let tmp_path = path.with_extension("zip.tmp");
+    let file = fs::File::create(&tmp_path)
+        .map_err(|e| AppError::io(&tmp_path, "Failed to create archive", e))?;
+    let documents = [("formulas.tex", latex), ("formulas.md", markdown)];
+    let written = write_bundle(file, &json, documents, manifest).and_then(|(file, failures)| {
+        file.sync_all()
+            .map_err(|e| AppError::io(&tmp_path, "Failed to flush archive", e))?;
+        replace_file(&tmp_path, &path).map_err(|e| AppError::io(&path, "Failed to save archive", e))?;
+        sync_parent_dir(&path);
+        Ok(failures)
+    });
+    let failures = match written {
+        Ok(failures) => failures,
+        Err(e) => {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(e);
+        }
+    };
+    Ok(BundleExport {
+        path: path.to_string_lossy().to_string(),
+        failures,
+    })
+}
+
+/// 导出时未能完整转换的公式
+#[derive(Serialize)]
+struct ExportWarning {
+    position: usize,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct DocxExport {
+    #[serde(flatten)]
+    outcome: FileOutcome,
+    /// 未能转换为 Word 公式、按原始 LaTeX 写入的公式
+    failures: Vec<ExportWarning>,
+}
+
+/// 导出 Word 文档，公式转换为 Word 原生公式；无法转换的公式保留为原始 LaTeX 并列入 `failures`
+#[command]
+async fn export_docx_file(formulas: Vec<FormulaItem>) -> Result<DocxExport, AppError> {
+    let entries: Vec<docx::Entry> = formulas
+        .iter()
+        .map(|item| docx::Entry {
+            latex: &item.latex,
+            note: item.note.as_deref(),
+        })
+        .collect();
+    let (bytes, failures) = docx::build(&entries)?;
+    let outcome = save_export("Word Documents", &["docx"], "formulas.docx", &bytes, WriteOptions::default()).await?;
+    let failures = failures
+        .into_iter()
+        .map(|f| ExportWarning {
+            position: f.position,
+            message: f.message,
+        })
+        .collect();
+    Ok(DocxExport { outcome, failures })
+}
+
+/// 导出Typst文件
+#[command]
+async fn export_typst_file(content: String, options: Option<WriteOptions>) -> Result<FileOutcome, AppError> {
+    save_text_export("Typst Files", &["typ"], "formulas.typ", &content, options.unwrap_or_default()).await
+}
+
+/// 导出Org文件
+#[command]
+async fn export_org_file(content: String, options: Option<WriteOptions>) -> Result<FileOutcome, AppError> {
+    save_text_export("Org Files", &["org"], "formulas.org", &content, options.unwrap_or_default()).await
+}
+
+/// 导出reStructuredText文件
+#[command]
+async fn export_rst_file(content: String, options: Option<WriteOptions>) -> Result<FileOutcome, AppError> {
+    save_text_export("reStructuredText Files", &["rst"], "formulas.rst", &content, options.unwrap_or_default()).await
+}
+
+/// 导出CSV文件
+#[command]
+async fn export_csv_file(content: String, options: Option<WriteOptions>) -> Result<FileOutcome, AppError> {
+    save_text_export("CSV Files", &["csv"], "formulas.csv", &content, options.unwrap_or_default()).await
+}
+
+/// 导出MathML文件
+#[command]
+async fn export_mathml_file(content: String, options: Option<WriteOptions>) -> Result<FileOutcome, AppError> {
+    save_text_export("XHTML Files", &["xhtml", "html"], "formulas.xhtml", &content, options.unwrap_or_default()).await
+}
+
+/// 导出HTML文件
+#[command]
+async fn export_html_file(content: String, options: Option<WriteOptions>) -> Result<FileOutcome, AppError> {
+    save_text_export("HTML Files", &["html", "htm"], "formulas.html", &content, options.unwrap_or_default()).await
+}
+
+#[derive(Deserialize, Clone)]
+struct FormulaItem {
+    latex: String,
+    note: Option<String>,
+    #[serde(default)]
+    tags: Vec<String>,
+    /// 仅 LaTeX 导出使用，覆盖导出选项中的环境
+    #[serde(default)]
+    environment: Option<MathEnvironment>,
+    #[serde(default)]
+    id: Option<String>,
+    /// 显式指定的 `\label` 名称（不含 `eq:` 前缀）
+    #[serde(default)]
+    label: Option<String>,
+    #[serde(default)]
+    section: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FormulaEntry {
+    id: String,
+    index: u32,
+    latex: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    note: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// 所属章节，导出时据此分组
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    section: Option<String>,
+    /// 其他工具写入的未知字段，原样保留以免打开再保存时丢失
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+/// 把规范化后的条目转为导出用的公式
+fn formula_items(entries: &[FormulaEntry]) -> Vec<FormulaItem> {
+    entries
+        .iter()
+        .map(|f| FormulaItem {
+            latex: f.latex.clone(),
+            note: f.note.clone(),
+            tags: f.tags.clone(),
+            environment: f
+                .extra
+                .get("environment")
+                .and_then(Value::as_str)
+                .and_then(MathEnvironment::from_name),
+            id: Some(f.id.clone()),
+            label: None,
+            section: f.section.clone(),
+        })
+        .collect()
+}
+
+/// 文本模式下只需加反斜杠转义的字符
+const LATEX_TEXT_SPECIALS: &[char] = &['#', '%', '&', '_', '$', '{', '}'];
+
+/// 文本模式下需用命令表示的字符；直接加反斜杠会变成重音、换行或倒置标点
+const LATEX_TEXT_SYMBOLS: &[(char, &str)] = &[
+    ('\\', "\\textbackslash{}"),
+    ('~', "\\textasciitilde{}"),
+    ('^', "\\textasciicircum{}"),
+    ('<', "\\textless{}"),
+    ('>', "\\textgreater{}"),
+];
+
+/// 转义备注等纯文本；已经转义过的 `\%`、`\textbackslash{}` 等原样保留，重复转义结果不变
+fn escape_latex_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(ch) = rest.chars().next() {
+        let mut len = ch.len_utf8();
+        if ch == '\\' {
+            let after = &rest[1..];
+            if after.starts_with(LATEX_TEXT_SPECIALS) {
+                len += 1;
+            } else if let Some((_, command)) =
+                LATEX_TEXT_SYMBOLS.iter().find(|(_, c)| rest.starts_with(c))
+            {
+                len = command.len();
+            } else {
+                escaped.push_str("\\textbackslash{}");
+                rest = after;
+                continue;
+            }
+            escaped.push_str(&rest[..len]);
+        } else if LATEX_TEXT_SPECIALS.contains(&ch) {
+            escaped.push('\\');
+            escaped.push(ch);
+        } else if let Some((_, command)) = LATEX_TEXT_SYMBOLS.iter().find(|(c, _)| *c == ch) {
+            escaped.push_str(command);
+        } else {
+            escaped.push(ch);
+        }
+        rest = &rest[len..];
+    }
+    escaped
+}
+
+/// `escape_latex_text` 的逆操作；也接受旧版本导出的 `\\`、`\^` 写法
+fn unescape_latex_text(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(ch) = rest.chars().next() {
+        if ch == '\\' {
+            if let Some((symbol, command)) =
+                LATEX_TEXT_SYMBOLS.iter().find(|(_, c)| rest.starts_with(c))
+            {
+                result.push(*symbol);
+                rest = &rest[command.len()..];
+                continue;
+            }
+            if let Some(next) = rest[1..].chars().next() {
+                if LATEX_TEXT_SPECIALS.contains(&next) || matches!(next, '\\' | '^') {
+                    result.push(next);
+                    rest = &rest[1 + next.len_utf8()..];
+                    continue;
+                }
+            }
+        }
+        result.push(ch);
+        rest = &rest[ch.len_utf8()..];
+    }
+    result
+}
+
+/// LaTeX 导出时包裹公式的数学环境；`align` 系列原样输出，`&` 与 `\\` 由用户负责
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+enum MathEnvironment {
+    #[default]
+    #[serde(rename = "equation")]
+    Equation,
+    #[serde(rename = "equation*")]
+    EquationStar,
+    #[serde(rename = "align")]
+    Align,
+    #[serde(rename = "align*")]
+    AlignStar,
+    #[serde(rename = "gather")]
+    Gather,
+    #[serde(rename = "gather*")]
+    GatherStar,
+}
+
+impl MathEnvironment {
+    fn name(self) -> &'static str {
+        match self {
+            MathEnvironment::Equation => "equation",
+            MathEnvironment::EquationStar => "equation*",
+            MathEnvironment::Align => "align",
+            MathEnvironment::AlignStar => "align*",
+            MathEnvironment::Gather => "gather",
+            MathEnvironment::GatherStar => "gather*",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "equation" => Some(MathEnvironment::Equation),
+            "equation*" => Some(MathEnvironment::EquationStar),
+            "align" => Some(MathEnvironment::Align),
+            "align*" => Some(MathEnvironment::AlignStar),
+            "gather" => Some(MathEnvironment::Gather),
+            "gather*" => Some(MathEnvironment::GatherStar),
+            _ => None,
+        }
+    }
+
+    /// 带星号的环境不编号，其中的 `\label` 只会产生 "(??)" 引用
+    fn is_numbered(self) -> bool {
+        !self.name().ends_with('*')
+    }
+}
+
+/// LaTeX 导出的文档类与导言区设置
+#[derive(Deserialize, Clone)]
+#[serde(rename_all = "camelCase", default)]
+struct LatexOptions {
+    document_class: String,
+    /// 公式未指定 `environment` 时使用
+    environment: MathEnvironment,
+    /// 文档类选项，如 `12pt,a4paper`
+    class_options: Option<String>,
+    /// 追加在默认宏包之后，名称只能包含字母、数字、`_` 和 `-`
+    extra_packages: Vec<String>,
+    /// 原样插入导言区末尾，由用户自行保证内容正确
+    preamble_extra: Option<String>,
+    /// 是否引入 ctex；省略时仅在内容含中日韩文字时引入
+    force_ctex: Option<bool>,
+    /// 有分节时在正文开头插入目录
+    table_of_contents: bool,
+    /// 有分节时公式按节编号
+    number_within_section: bool,
+    /// 未指定分节的公式归入的标题，默认为“其他”
+    default_section: Option<String>,
+    /// 保存文件时使用的编码，应与 `export_latex_file` 的写入选项一致；GB18030 时以 GBK 选项引入 ctex
+    encoding: TextEncoding,
+}
+
+#[derive(Serialize)]
+struct LatexDocument {
+    content: String,
+    warnings: Vec<String>,
+}
+
+impl Default for LatexOptions {
+    fn default() -> Self {
+        Self {
+            document_class: "article".to_string(),
+            environment: MathEnvironment::default(),
+            class_options: None,
+            extra_packages: Vec::new(),
+            preamble_extra: None,
+            force_ctex: None,
+            table_of_contents: false,
+            number_within_section: false,
+            default_section: None,
+            encoding: TextEncoding::default(),
+        }
+    }
+}
+
+fn is_valid_latex_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '-')
+}
+
+/// 文档类或自定义导言区已经提供中文支持：ctex 系列文档类，或附加宏包、`preamble_extra` 中
+/// 引入了 ctex、xeCJK、CJK 宏包。此时不再自动引入 ctex，ctex 文档类与 ctex 宏包同时使用会报错
+fn preamble_supports_cjk(options: &LatexOptions) -> bool {
+    const PACKAGES: &[&str] = &["ctex", "xeCJK", "CJKutf8", "CJK"];
+    if options.document_class.trim().starts_with("ctex")
+        || options.extra_packages.iter().any(|p| PACKAGES.contains(&p.trim()))
+    {
+        return true;
+    }
+    let Some(extra) = options.preamble_extra.as_deref() else {
+        return false;
+    };
+    extra.lines().any(|line| {
+        let line = line.split('%').next().unwrap_or_default();
+        line.contains("\\usepackage")
+            && line
+                .split(['{', '}', ','])
+                .any(|name| PACKAGES.contains(&name.trim()))
+    })
+}
+
+/// 生成导言区；文档类与宏包名称不合法时返回错误，避免借此注入任意内容
+/// 名称或选项无效时返回 `InvalidLatex`，消息是出错的那条导言区命令
+fn latex_preamble(
+    options: &LatexOptions,
+    ctex: bool,
+    sectioned: bool,
+) -> Result<String, AppError> {
+    let class = options.document_class.trim();
+    if !is_valid_latex_name(class) {
+        return Err(AppError::InvalidLatex {
+            message: format!("\\documentclass{{{}}}", options.document_class),
+            position: 0,
+        });
+    }
+    let class_options = options
+        .class_options
+        .as_deref()
+        .map(str::trim)
+        .filter(|o| !o.is_empty());
+    if let Some(o) = class_options {
+        if o.contains(['[', ']', '{', '}', '\\', '%', '\n']) {
+            return Err(AppError::InvalidLatex {
+                message: format!("\\documentclass[{}]", o),
+                position: 0,
+            });
+        }
+    }
+
+    let mut preamble = match class_options {
+        Some(o) => format!("\\documentclass[{}]{{{}}}\n", o, class),
+        None => format!("\\documentclass{{{}}}\n", class),
+    };
+    preamble.push_str("\\usepackage{amsmath}\n");
+    if ctex {
+        // ctex 默认按 UTF-8 读取源文件
+        preamble.push_str(match options.encoding {
+            TextEncoding::Gb18030 => "\\usepackage[GBK]{ctex}\n",
+            _ => "\\usepackage{ctex}\n",
+        });
+    }
+    for package in &options.extra_packages {
+        let package = package.trim();
+        if !is_valid_latex_name(package) {
+            return Err(AppError::InvalidLatex {
+                message: format!("\\usepackage{{{}}}", package),
+                position: 0,
+            });
+        }
+        preamble.push_str(&format!("\\usepackage{{{}}}\n", package));
+    }
+    if sectioned && options.number_within_section {
+        preamble.push_str("\\numberwithin{equation}{section}\n");
+    }
+    if let Some(extra) = options.preamble_extra.as_deref().map(str::trim_end) {
+        if !extra.is_empty() {
+            preamble.push_str(extra);
+            preamble.push('\n');
+        }
+    }
+    Ok(preamble)
+}
+
+/// FNV-1a，用于给非 ASCII 文本生成跨平台、跨版本稳定的标签
+fn stable_hash(text: &str) -> u32 {
+    text.bytes().fold(0x811C_9DC5u32, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+/// 生成只含 ASCII 字母数字和 `-` 的标签片段；含汉字等字符时追加原文的哈希以免不同文本撞名
+fn label_slug(text: &str) -> String {
+    let text = text.trim();
+    let mut slug = String::new();
+    let mut has_other = false;
+    for ch in text.chars() {
+        if ch.is_ascii_alphanumeric() {
+            slug.push(ch.to_ascii_lowercase());
+        } else {
+            has_other |= ch.is_alphanumeric();
+            if !slug.is_empty() && !slug.ends_with('-') {
+                slug.push('-');
+            }
+        }
+        if slug.len() >= 40 {
+            break;
+        }
+    }
+    let mut slug = slug.trim_end_matches('-').to_string();
+    if has_other {
+        if !slug.is_empty() {
+            slug.push('-');
+        }
+        slug.push_str(&format!("{:08x}", stable_hash(text)));
+    }
+    slug
+}
+
+/// 依次取显式标签、id、备注生成标签，都为空时退回序号；同一次导出中重名的追加 `-2`、`-3`
+fn equation_labels<'a>(formulas: impl IntoIterator<Item = &'a FormulaItem>) -> Vec<String> {
+    let mut used = HashSet::new();
+    formulas
+        .into_iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let base = [&item.label, &item.id, &item.note]
+                .into_iter()
+                .flatten()
+                .map(|text| label_slug(text))
+                .find(|slug| !slug.is_empty())
+                .unwrap_or_else(|| (idx + 1).to_string());
+            let mut label = base.clone();
+            let mut n = 2;
+            while !used.insert(label.clone()) {
+                label = format!("{}-{}", base, n);
+                n += 1;
+            }
+            label
+        })
+        .collect()
+}
+
+/// 未指定分节的公式默认归入的标题
+const DEFAULT_SECTION: &str = "其他";
+
+/// 按分节分组，各节按首次出现的顺序排列，未分节的公式归入 `default_section` 放在最后；
+/// 没有任何公式指定分节时只返回一个无标题的组，保持原有顺序
+fn group_by_section<'a>(
+    formulas: &'a [FormulaItem],
+    default_section: &'a str,
+) -> Vec<(Option<&'a str>, Vec<&'a FormulaItem>)> {
+    let section_of = |item: &'a FormulaItem| {
+        item.section
+            .as_deref()
+            .map(str::trim)
+            .filter(|s| !s.is_empty())
+    };
+    if formulas.iter().all(|item| section_of(item).is_none()) {
+        return vec![(None, formulas.iter().collect())];
+    }
+    let mut groups: Vec<(Option<&str>, Vec<&FormulaItem>)> = Vec::new();
+    let mut unsectioned = Vec::new();
+    for item in formulas {
+        let Some(section) = section_of(item) else {
+            unsectioned.push(item);
+            continue;
+        };
+        match groups.iter_mut().find(|(name, _)| *name == Some(section)) {
+            Some((_, items)) => items.push(item),
+            None => groups.push((Some(section), vec![item])),
+        }
+    }
+    if !unsectioned.is_empty() {
+        match groups
+            .iter_mut()
+            .find(|(name, _)| *name == Some(default_section))
+        {
+            Some((_, items)) => items.extend(unsectioned),
+            None => groups.push((Some(default_section), unsectioned)),
+        }
+    }
+    groups
+}
+
+/// `include_tags` 为 true 时在每个公式后追加一行标签；`options` 省略时输出 article 文档
+#[command]
+async fn format_latex(
+    formulas: Vec<FormulaItem>,
+    include_tags: Option<bool>,
+    options: Option<LatexOptions>,
+) -> Result<LatexDocument, AppError> {
+    let mut warnings = Vec::new();
+    if formulas.is_empty() {
+        return Ok(LatexDocument {
+            content: String::new(),
+            warnings,
+        });
+    }
+    let options = options.unwrap_or_default();
+    let include_tags = include_tags.unwrap_or(false);
+    let default_section = options.default_section.as_deref().unwrap_or(DEFAULT_SECTION);
+    let sections = group_by_section(&formulas, default_section);
+    let sectioned = sections.iter().any(|(heading, _)| heading.is_some());
+    let mut labels =
+        equation_labels(sections.iter().flat_map(|(_, items)| items.iter().copied())).into_iter();
+    let render = |item: &FormulaItem, label: String| {
+        let note_block = item
+            .note
+            .as_ref()
+            .map(|note| note.trim())
+            .filter(|note| !note.is_empty())
+            .map(|note| format!("\\noindent\\textbf{{{}}}\\\\\n", escape_latex_text(note)))
+            .unwrap_or_default();
+        let tag_block = if include_tags && !item.tags.is_empty() {
+            let tags: Vec<String> = item.tags.iter().map(|t| escape_latex_text(t)).collect();
+            format!("\n\\noindent\\textit{{标签：{}}}\n", tags.join(", "))
+        } else {
+            String::new()
+        };
+        let environment = item.environment.unwrap_or(options.environment);
+        let label = if environment.is_numbered() {
+            format!("\\label{{eq:{}}}", label)
+        } else {
+            String::new()
+        };
+        format!(
+            "{}\\begin{{{env}}}{}\n{}\n\\end{{{env}}}{}",
+            note_block,
+            label,
+            item.latex,
+            tag_block,
+            env = environment.name()
+        )
+    };
+    let mut blocks = Vec::new();
+    if sectioned && options.table_of_contents {
+        blocks.push("\\tableofcontents".to_string());
+    }
+    for (heading, items) in &sections {
+        if let Some(heading) = heading {
+            blocks.push(format!("\\section{{{}}}", escape_latex_text(heading)));
+        }
+        for item in items {
+            blocks.push(render(item, labels.next().unwrap_or_default()));
+        }
+    }
+    let body = blocks.join("\n");
+
+    let has_cjk = contains_cjk(&body);
+    let supported = preamble_supports_cjk(&options);
+    let ctex = options.force_ctex.unwrap_or(has_cjk && !supported);
+    let mut document = String::new();
+    if has_cjk && !ctex && !supported {
+        let warning = "内容包含中日韩文字，但未引入 ctex 宏包，文档可能无法编译".to_string();
+        document.push_str(&format!("% warning: {}\n", warning));
+        warnings.push(warning);
+    }
+    document.push_str(&latex_preamble(&options, ctex, sectioned)?);
+    document.push_str(&format!(
+        "\\begin{{document}}\n{}\n\\end{{document}}\n",
+        body
+    ));
+
+    Ok(LatexDocument {
+        content: document,
+        warnings,
+    })
+}
+
+/// 备注在 Markdown 导出中的位置
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum NotePosition {
+    /// 备注以粗体行置于公式块之前（默认）
+    #[default]
+    Above,
+    /// 备注以斜体说明行置于公式块之后
+    Below,
+}
+
+/// Markdown 导出时包裹公式的定界符
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum MathDelimiter {
+    /// `$$ ... $$`
+    #[default]
+    Dollars,
+    /// `\[ ... \]`
+    Brackets,
+    /// GitHub 的 ```` ```math ```` 代码块
+    Fenced,
+    /// 行内 `\( ... \)`
+    Parentheses,
+    /// 知乎公式图片链接
+    #[serde(rename = "zhihu-image")]
+    ZhihuImage,
+}
+
+/// 目标平台预设，覆盖选项中的定界符等设置
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum MarkdownFlavor {
+    #[default]
+    Github,
+    Obsidian,
+    Zhihu,
+    Wechat,
+    Pandoc,
+}
+
+struct MarkdownPreset {
+    flavor: MarkdownFlavor,
+    /// `None` 表示沿用选项中的定界符
+    delimiter: Option<MathDelimiter>,
+    /// 定界符与公式之间不留空行
+    tight: bool,
+    /// 有备注时以备注作为标题，而不是单独的粗体行
+    note_as_heading: bool,
+}
+
+const MARKDOWN_PRESETS: &[MarkdownPreset] = &[
+    MarkdownPreset {
+        flavor: MarkdownFlavor::Github,
+        delimiter: None,
+        tight: false,
+        note_as_heading: false,
+    },
+    MarkdownPreset {
+        flavor: MarkdownFlavor::Obsidian,
+        delimiter: Some(MathDelimiter::Dollars),
+        tight: true,
+        note_as_heading: false,
+    },
+    MarkdownPreset {
+        flavor: MarkdownFlavor::Zhihu,
+        delimiter: Some(MathDelimiter::ZhihuImage),
+        tight: false,
+        note_as_heading: false,
+    },
+    MarkdownPreset {
+        flavor: MarkdownFlavor::Wechat,
+        delimiter: Some(MathDelimiter::Parentheses),
+        tight: false,
+        note_as_heading: false,
+    },
+    MarkdownPreset {
+        flavor: MarkdownFlavor::Pandoc,
+        delimiter: Some(MathDelimiter::Dollars),
+        tight: true,
+        note_as_heading: true,
+    },
+];
+
+impl MarkdownFlavor {
+    fn preset(self) -> &'static MarkdownPreset {
+        MARKDOWN_PRESETS
+            .iter()
+            .find(|preset| preset.flavor == self)
+            .unwrap_or(&MARKDOWN_PRESETS[0])
+    }
+}
+
+/// 按 RFC 3986 对非保留字符以外的字节做百分号编码
+fn percent_encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'_' | b'.' | b'~') {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+/// 按定界符包裹一条公式；`tight` 为 false 时块级定界符与公式之间留空行
+fn markdown_math(latex: &str, delimiter: MathDelimiter, tight: bool) -> String {
+    let gap = if tight { "\n" } else { "\n\n" };
+    match delimiter {
+        MathDelimiter::Dollars => format!("$${gap}{}{gap}$$", latex),
+        MathDelimiter::Brackets => format!("\\[{gap}{}{gap}\\]", latex),
+        MathDelimiter::Fenced => {
+            let mut fence = "```".to_string();
+            while latex.contains(&fence) {
+                fence.push('`');
+            }
+            format!("{fence}math\n{}\n{fence}", latex)
+        }
+        MathDelimiter::Parentheses => format!("\\({}\\)", latex),
+        MathDelimiter::ZhihuImage => {
+            let single_line = latex.split_whitespace().collect::<Vec<_>>().join(" ");
+            format!(
+                "![{}](https://www.zhihu.com/equation?tex={})",
+                escape_markdown_text(&single_line),
+                percent_encode(&single_line)
+            )
+        }
+    }
+}
+
+/// Markdown 导出的排版方式
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum MarkdownLayout {
+    /// 每个公式一个标题加公式块
+    #[default]
+    Blocks,
+    /// GFM 表格，列为序号、公式、说明；单元格内只能用行内公式
+    Table,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct MarkdownOptions {
+    layout: MarkdownLayout,
+    /// 每个公式标题的级别（1–6），为 `null` 时不输出标题
+    heading_level: Option<u8>,
+    /// 标题文字，`{index}` 替换为序号
+    heading_text: String,
+    math_delimiter: MathDelimiter,
+    /// 为 false 时 `{index}` 替换为空
+    include_numbering: bool,
+    /// 未指定分节的公式归入的标题，默认为“其他”
+    default_section: Option<String>,
+}
+
+impl Default for MarkdownOptions {
+    fn default() -> Self {
+        Self {
+            layout: MarkdownLayout::default(),
+            heading_level: Some(3),
+            heading_text: "公式 {index}".to_string(),
+            math_delimiter: MathDelimiter::default(),
+            include_numbering: true,
+            default_section: None,
+        }
+    }
+}
+
+/// 转义会被 Markdown 解释为格式的字符，避免备注打乱粗体、斜体或表格
+fn escape_markdown_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if import::MARKDOWN_ESCAPED.contains(&ch) {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// 生成一节公式的 GFM 表格；多行内容用 `<br>` 连接，公式每行各自成为行内公式
+fn markdown_table(
+    items: &[&FormulaItem],
+    first_index: usize,
+    include_numbering: bool,
+    include_tags: bool,
+) -> String {
+    let mut columns = Vec::new();
+    if include_numbering {
+        columns.push("序号");
+    }
+    columns.extend(["公式", "说明"]);
+    if include_tags {
+        columns.push("标签");
+    }
+    let mut rows = vec![
+        format!("| {} |", columns.join(" | ")),
+        format!("|{}", " --- |".repeat(columns.len())),
+    ];
+    for (offset, item) in items.iter().enumerate() {
+        let mut cells = Vec::new();
+        if include_numbering {
+            cells.push((first_index + offset).to_string());
+        }
+        let latex = item
+            .latex
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| format!("${}$", line.replace('|', "\\|")))
+            .collect::<Vec<_>>();
+        cells.push(latex.join("<br>"));
+        let note = item
+            .note
+            .as_deref()
+            .unwrap_or_default()
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(escape_markdown_text)
+            .collect::<Vec<_>>();
+        cells.push(note.join("<br>"));
+        if include_tags {
+            let tags: Vec<String> = item.tags.iter().map(|t| escape_markdown_text(t)).collect();
+            cells.push(tags.join(", "));
+        }
+        rows.push(format!("| {} |", cells.join(" | ")));
+    }
+    rows.join("\n")
+}
+
+/// 有公式指定分节时，每节以 `##` 标题开头；`flavor` 省略时为 GitHub 风格
+#[command]
+async fn format_markdown(
+    formulas: Vec<FormulaItem>,
+    note_position: Option<NotePosition>,
+    include_tags: Option<bool>,
+    options: Option<MarkdownOptions>,
+    flavor: Option<MarkdownFlavor>,
+) -> Result<String, AppError> {
+    if formulas.is_empty() {
+        return Ok(String::new());
+    }
+    let options = options.unwrap_or_default();
+    let preset = flavor.unwrap_or_default().preset();
+    let delimiter = preset.delimiter.unwrap_or(options.math_delimiter);
+    if let Some(level) = options.heading_level {
+        if !(1..=6).contains(&level) {
+            return Err(AppError::InvalidOption {
+                name: "headingLevel".to_string(),
+                value: level.to_string(),
+            });
+        }
+    }
+    let note_position = note_position.unwrap_or_default();
+    let include_tags = include_tags.unwrap_or(false);
+    let default_section = options.default_section.as_deref().unwrap_or(DEFAULT_SECTION);
+    let sections = group_by_section(&formulas, default_section);
+    // 分节标题比公式标题高一级
+    let section_level = options
+        .heading_level
+        .map_or(2, |level| level.saturating_sub(1).max(1));
+    let mut blocks = Vec::new();
+    let mut idx = 0;
+    for (heading, items) in &sections {
+        if let Some(heading) = heading {
+            let heading = escape_markdown_text(heading);
+            blocks.push(format!("{} {}", "#".repeat(section_level as usize), heading));
+        }
+        if options.layout == MarkdownLayout::Table {
+            blocks.push(markdown_table(
+                items,
+                idx + 1,
+                options.include_numbering,
+                include_tags,
+            ));
+            idx += items.len();
+            continue;
+        }
+        for item in items {
+            let note = item
+                .note
+                .as_ref()
+                .map(|n| n.trim())
+                .filter(|n| !n.is_empty())
+                .map(escape_markdown_text);
+            idx += 1;
+            let mut parts = Vec::new();
+            let mut note_in_heading = false;
+            if let Some(level) = options.heading_level {
+                let text = match &note {
+                    Some(note) if preset.note_as_heading => {
+                        note_in_heading = true;
+                        note.clone()
+                    }
+                    _ => {
+                        let number = if options.include_numbering {
+                            idx.to_string()
+                        } else {
+                            String::new()
+                        };
+                        options.heading_text.replace("{index}", &number)
+                    }
+                };
+                parts.push(format!("{} {}", "#".repeat(level as usize), text.trim()));
+            }
+            let note = note.filter(|_| !note_in_heading);
+            if let (Some(note), NotePosition::Above) = (&note, note_position) {
+                parts.push(format!("**{}**", note));
+            }
+            parts.push(markdown_math(&item.latex, delimiter, preset.tight));
+            if let (Some(note), NotePosition::Below) = (&note, note_position) {
+                parts.push(format!("*{}*", note));
+            }
+            if include_tags && !item.tags.is_empty() {
+                let tags: Vec<String> = item.tags.iter().map(|t| format!("`{}`", t)).collect();
+                parts.push(format!("标签：{}", tags.join(" ")));
+            }
+            blocks.push(parts.join("\n\n"));
+        }
+    }
+
+    Ok(blocks.join("\n\n"))
+}
+
+/// Typst 导出时 LaTeX 公式的处理方式
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum TypstMode {
+    /// 尽量翻译为 Typst 原生数学语法，无法解析的公式退回 mitex
+    #[default]
+    Translate,
+    /// 全部交给 mitex 包渲染原始 LaTeX
+    Mitex,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct TypstOptions {
+    mode: TypstMode,
+}
+
+#[derive(Serialize)]
+struct TypstDocument {
+    content: String,
+    /// 未能翻译、改用 mitex 的公式
+    warnings: Vec<ExportWarning>,
+}
+
+const MITEX_IMPORT: &str = "#import \"@preview/mitex:0.2.4\": mitex";
+
+/// 转义 Typst 标记模式中的特殊字符
+fn escape_typst_markup(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if matches!(ch, '\\' | '*' | '_' | '#' | '$' | '@' | '<' | '>' | '`' | '[' | ']' | '=' | '-' | '+' | '/' | '~') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// 用 mitex 包装原始 LaTeX；内容中有反引号时加长原始字符串的定界符
+fn mitex_block(latex: &str) -> String {
+    let mut fence = "`".to_string();
+    while latex.contains(&fence) {
+        fence.push('`');
+    }
+    if fence.len() > 1 {
+        fence.push('`');
+        format!("#mitex({} {} {})", fence, latex, fence)
+    } else {
+        format!("#mitex({}{}{})", fence, latex, fence)
+    }
+}
+
+#[command]
+async fn format_typst(
+    formulas: Vec<FormulaItem>,
+    options: Option<TypstOptions>,
+) -> Result<TypstDocument, AppError> {
+    if formulas.is_empty() {
+        return Ok(TypstDocument {
+            content: String::new(),
+            warnings: Vec::new(),
+        });
+    }
+    let mode = options.unwrap_or_default().mode;
+    let mut warnings = Vec::new();
+    let mut uses_mitex = mode == TypstMode::Mitex;
+    let body = formulas
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let note_block = item
+                .note
+                .as_deref()
+                .map(str::trim)
+                .filter(|note| !note.is_empty())
+                .map(|note| format!("*{}*\n\n", escape_typst_markup(note)))
+                .unwrap_or_default();
+            let translated = match mode {
+                TypstMode::Mitex => None,
+                TypstMode::Translate => match math::parse(&item.latex) {
+                    Ok(node) => Some(typst::to_typst(&node)),
+                    Err(error) => {
+                        warnings.push(ExportWarning {
+                            position: idx,
+                            message: error.to_string(),
+                        });
+                        None
+                    }
+                },
+            };
+            let equation = match translated {
+                Some(math) => format!("$ {} $", math),
+                None => {
+                    uses_mitex = true;
+                    mitex_block(item.latex.trim())
+                }
+            };
+            format!("{}{} <eq:{}>", note_block, equation, idx + 1)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+
+    let mut preamble = vec!["#set text(lang: \"zh\")", "#set math.equation(numbering: \"(1)\")"];
+    if uses_mitex {
+        preamble.insert(0, MITEX_IMPORT);
+    }
+    Ok(TypstDocument {
+        content: format!("{}\n\n{}\n", preamble.join("\n"), body),
+        warnings,
+    })
+}
+
+/// Org 导出中公式的包装方式
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum OrgMathStyle {
+    /// `\begin{equation}...\end{equation}`，带编号
+    #[default]
+    Equation,
+    /// `\[...\]`，不编号
+    Brackets,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct OrgOptions {
+    math_style: OrgMathStyle,
+}
+
+/// 行首的 `*`、`|`、`#` 在 Org 中分别表示标题、表格行和关键字，
+/// 在前面插入零宽空格使其按普通文本显示
+fn escape_org_line(line: &str) -> String {
+    let trimmed = line.trim_start();
+    if trimmed.starts_with(['*', '|', '#']) {
+        let indent = &line[..line.len() - trimmed.len()];
+        format!("{}\u{200B}{}", indent, trimmed)
+    } else {
+        line.to_string()
+    }
+}
+
+#[command]
+async fn format_org(
+    formulas: Vec<FormulaItem>,
+    options: Option<OrgOptions>,
+) -> Result<String, AppError> {
+    if formulas.is_empty() {
+        return Ok(String::new());
+    }
+    let style = options.unwrap_or_default().math_style;
+    let sections = formulas
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let mut parts = vec![format!("* 公式 {}", idx + 1)];
+            if let Some(note) = item.note.as_deref().map(str::trim).filter(|n| !n.is_empty()) {
+                parts.push(note.lines().map(escape_org_line).collect::<Vec<_>>().join("\n"));
+            }
+            parts.push(match style {
+                OrgMathStyle::Equation => format!(
+                    "\\begin{{equation}}\n{}\n\\end{{equation}}",
+                    item.latex.trim()
+                ),
+                OrgMathStyle::Brackets => format!("\\[\n{}\n\\]", item.latex.trim()),
+            });
+            parts.join("\n\n")
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Ok(format!("{}\n", sections))
+}
+
+/// 转义 reStructuredText 行内标记字符
+fn escape_rst(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if matches!(ch, '\\' | '*' | '`' | '|' | '_' | '[' | ']') {
+            escaped.push('\\');
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+#[command]
+async fn format_rst(formulas: Vec<FormulaItem>) -> Result<String, AppError> {
+    use unicode_width::UnicodeWidthStr;
+
+    if formulas.is_empty() {
+        return Ok(String::new());
+    }
+    let sections = formulas
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            // 标题只能占一行，备注中的换行折叠为空格
+            let title = item
+                .note
+                .as_deref()
+                .map(|n| n.split_whitespace().collect::<Vec<_>>().join(" "))
+                .filter(|n| !n.is_empty())
+                .map(|n| escape_rst(&n))
+                .unwrap_or_else(|| format!("公式 {}", idx + 1));
+            // 下划线长度按显示宽度计算，中文字符占两列
+            let underline = "=".repeat(title.width().max(1));
+            let body = item
+                .latex
+                .lines()
+                .map(str::trim_end)
+                .filter(|line| !line.trim().is_empty())
+                .map(|line| format!("   {}", line))
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!("{}\n{}\n\n.. math::\n\n{}", title, underline, body)
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    Ok(format!("{}\n", sections))
+}
+
+/// 按 RFC 4180 引用 CSV 字段：含逗号、引号或换行时加引号，内部引号加倍
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct CsvOptions {
+    /// 写入 UTF-8 BOM，Windows 上的 Excel 才能正确识别中文
+    bom: bool,
+}
+
+/// 生成 `index,id,latex,note` 四列的 CSV，行尾为 CRLF
+#[command]
+async fn format_csv(
+    formulas: Vec<FormulaEntry>,
+    options: Option<CsvOptions>,
+) -> Result<String, AppError> {
+    let mut csv = String::new();
+    if options.unwrap_or_default().bom {
+        csv.push('\u{FEFF}');
+    }
+    csv.push_str("index,id,latex,note\r\n");
+    for item in &formulas {
+        let row = [
+            item.index.to_string(),
+            csv_field(&item.id),
+            csv_field(&item.latex),
+            csv_field(item.note.as_deref().unwrap_or_default()),
+        ];
+        csv.push_str(&row.join(","));
+        csv.push_str("\r\n");
+    }
+    Ok(csv)
+}
+
+/// Anki 卡片背面的公式定界符
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum AnkiMath {
+    /// `\(...\)`，Anki 内置 MathJax
+    #[default]
+    Mathjax,
+    /// `[$]...[/$]`，旧版 LaTeX 图片渲染
+    Legacy,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct AnkiOptions {
+    math: AnkiMath,
+    /// 作为正面的字段：`note`、`id`、`tags` 或公式中的其他字段名
+    front_field: String,
+    /// 正面为空时跳过该条；否则用“公式 N”代替
+    skip_empty: bool,
+}
+
+impl Default for AnkiOptions {
+    fn default() -> Self {
+        AnkiOptions {
+            math: AnkiMath::default(),
+            front_field: "note".to_string(),
+            skip_empty: false,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AnkiExport {
+    /// 没有可导出的卡片时为 `cancelled`，不弹出对话框
+    #[serde(flatten)]
+    outcome: FileOutcome,
+    cards: usize,
+}
+
+/// 转为 Anki 的 HTML 字段：转义尖括号，制表符和换行改为 `<br>`，避免破坏导入格式
+fn anki_field(text: &str) -> String {
+    escape_html(text.trim())
+        .replace("\r\n", "<br>")
+        .replace(['\t', '\n', '\r'], "<br>")
+}
+
+fn anki_front(item: &FormulaEntry, field: &str) -> Option<String> {
+    let text = match field {
+        "note" => item.note.clone(),
+        "id" => Some(item.id.clone()),
+        "tags" => Some(item.tags.join(", ")),
+        other => item.extra.get(other).map(|value| match value {
+            Value::String(s) => s.clone(),
+            other => other.to_string(),
+        }),
+    };
+    text.filter(|t| !t.trim().is_empty())
+}
+
+/// 导出可被 Anki 导入的制表符分隔文本，返回写入的卡片数
+#[command]
+async fn export_anki_file(
+    formulas: Vec<FormulaEntry>,
+    options: Option<AnkiOptions>,
+    write_options: Option<WriteOptions>,
+) -> Result<AnkiExport, AppError> {
+    let options = options.unwrap_or_default();
+    let mut lines = vec!["#separator:tab".to_string(), "#html:true".to_string()];
+    for (idx, item) in formulas.iter().enumerate() {
+        let front = match anki_front(item, &options.front_field) {
+            Some(front) => front,
+            None if options.skip_empty => continue,
+            None => format!("公式 {}", idx + 1),
+        };
+        let back = match options.math {
+            AnkiMath::Mathjax => format!("\\({}\\)", item.latex.trim()),
+            AnkiMath::Legacy => format!("[$]{}[/$]", item.latex.trim()),
+        };
+        lines.push(format!("{}\t{}", anki_field(&front), anki_field(&back)));
+    }
+    let cards = lines.len() - 2;
+    if cards == 0 {
+        return Ok(AnkiExport {
+            outcome: FileOutcome::Cancelled,
+            cards,
+        });
+    }
+    let content = lines.join("\n") + "\n";
+    let outcome = save_text_export(
+        "Anki Text Files",
+        &["txt"],
+        "formulas-anki.txt",
+        &content,
+        write_options.unwrap_or_default(),
+    )
+    .await?;
+    Ok(AnkiExport { outcome, cards })
+}
+
+/// KaTeX 的 CDN 版本，需与 `resources/katex` 中附带的文件保持一致
+const KATEX_VERSION: &str = "0.16.11";
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct HtmlOptions {
+    /// 为 true 时内联应用附带的 KaTeX 资源（含字体），生成的文件可完全离线打开
+    embed_assets: bool,
+    title: Option<String>,
+}
+
+/// 应用资源目录中附带的 KaTeX 文件所在目录
+fn katex_dir(app: &tauri::AppHandle) -> Option<PathBuf> {
+    app.path_resolver().resolve_resource("resources/katex")
+}
+
+/// 读取 KaTeX 目录中的文件
+fn read_katex_asset(dir: &Path, name: &str) -> Result<Vec<u8>, AppError> {
+    let path = dir.join(name);
+    fs::read(&path).map_err(|e| AppError::io(&path, "Failed to read KaTeX asset", e))
+}
+
+/// 将 KaTeX CSS 中引用的字体替换为 data URI
+fn inline_katex_fonts(dir: &Path, css: &str) -> Result<String, AppError> {
+    use base64::Engine;
+
+    let mut result = String::with_capacity(css.len());
+    let mut rest = css;
+    while let Some(start) = rest.find("url(fonts/") {
+        let Some(len) = rest[start..].find(')') else {
+            break;
+        };
+        let name = &rest[start + "url(fonts/".len()..start + len];
+        let bytes = read_katex_asset(dir, &format!("fonts/{}", name))?;
+        let mime = match Path::new(name).extension().and_then(|e| e.to_str()) {
+            Some("woff2") => "font/woff2",
+            Some("woff") => "font/woff",
+            _ => "font/ttf",
+        };
+        let data = base64::engine::general_purpose::STANDARD.encode(bytes);
+        result.push_str(&rest[..start]);
+        result.push_str(&format!("url(data:{};base64,{})", mime, data));
+        rest = &rest[start + len + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+/// 生成可直接在浏览器中打开的 HTML 文档，公式由 KaTeX 渲染
+///
+/// LaTeX 以转义后的文本写入页面，脚本未加载时仍能看到公式源码。
+#[command]
+async fn format_html(
+    app: tauri::AppHandle,
+    formulas: Vec<FormulaItem>,
+    options: Option<HtmlOptions>,
+) -> Result<String, AppError> {
+    if formulas.is_empty() {
+        return Ok(String::new());
+    }
+    html_document(
+        katex_dir(&app).as_deref(),
+        &formulas,
+        &options.unwrap_or_default(),
+        "",
+        "",
+    )
+}
+
+/// 生成 HTML 文档；`extra_css` 和 `extra_script` 附加在默认样式和渲染脚本之后，
+/// 内联资源时从 `katex_dir` 读取 KaTeX 文件
+fn html_document(
+    katex_dir: Option<&Path>,
+    formulas: &[FormulaItem],
+    options: &HtmlOptions,
+    extra_css: &str,
+    extra_script: &str,
+) -> Result<String, AppError> {
+    let title = options
+        .title
+        .as_deref()
+        .map(str::trim)
+        .filter(|t| !t.is_empty())
+        .unwrap_or("公式集");
+
+    let assets = if options.embed_assets {
+        let dir = katex_dir.ok_or(AppError::LocationUnavailable(Location::ResourceDir))?;
+        let css = String::from_utf8_lossy(&read_katex_asset(dir, "katex.min.css")?).into_owned();
+        let js = String::from_utf8_lossy(&read_katex_asset(dir, "katex.min.js")?).into_owned();
+        format!(
+            "<style>\n{}\n</style>\n<script>\n{}\n</script>",
+            inline_katex_fonts(dir, &css)?,
+            js.replace("</script", "<\\/script")
+        )
+    } else {
+        let base = format!("https://cdn.jsdelivr.net/npm/katex@{}/dist", KATEX_VERSION);
+        format!(
+            "<link rel=\"stylesheet\" href=\"{0}/katex.min.css\">\n<script src=\"{0}/katex.min.js\"></script>",
+            base
+        )
+    };
+
+    // 有分节时每节放在一个 `.group` 中，分节标题为 h2，公式标题降为 h3
+    let groups = group_by_section(formulas, DEFAULT_SECTION);
+    let sectioned = groups.iter().any(|(heading, _)| heading.is_some());
+    let formula_tag = if sectioned { "h3" } else { "h2" };
+    let mut idx = 0;
+    let sections = groups
+        .iter()
+        .map(|(heading, items)| {
+            let mut parts = Vec::new();
+            if let Some(heading) = heading {
+                parts.push(format!("<h2>{}</h2>", escape_html(heading)));
+            }
+            for item in items {
+                idx += 1;
+                let title = item
+                    .note
+                    .as_deref()
+                    .map(str::trim)
+                    .filter(|n| !n.is_empty())
+                    .map(escape_html)
+                    .unwrap_or_else(|| format!("公式 {}", idx));
+                parts.push(format!(
+                    "<section id=\"eq-{idx}\">\n<{tag}>{}</{tag}>\n<div class=\"tex\">{}</div>\n</section>",
+                    title,
+                    escape_html(&item.latex),
+                    idx = idx,
+                    tag = formula_tag
+                ));
+            }
+            format!("<div class=\"group\">\n{}\n</div>", parts.join("\n"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(format!(
+        r#"<!DOCTYPE html>
+<html lang="zh-CN">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{title}</title>
+{assets}
+<style>
+body {{ max-width: 860px; margin: 2rem auto; padding: 0 1rem; font-family: system-ui, sans-serif; }}
+section {{ margin: 1.5rem 0; }}
+h2 {{ font-size: 1.1rem; }}
+h3 {{ font-size: 1rem; }}
+.tex {{ overflow-x: auto; white-space: pre-wrap; }}
+{extra_css}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{sections}
+<script>
+document.querySelectorAll('.tex').forEach(function (el) {{
+  if (!window.katex) return;
+  katex.render(el.textContent, el, {{ displayMode: true, throwOnError: false }});
+  el.style.whiteSpace = 'normal';
+}});
+{extra_script}
+</script>
+</body>
+</html>
+"#,
+        title = escape_html(title),
+        assets = assets,
+        sections = sections,
+        extra_css = extra_css,
+        extra_script = extra_script
+    ))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct PrintOptions {
+    title: Option<String>,
+    /// 每个分节从新的一页开始，默认开启；没有分节时不分页
+    page_breaks: bool,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        PrintOptions {
+            title: None,
+            page_breaks: true,
+        }
+    }
+}
+
+/// 打印公式集：生成内联 KaTeX 的 HTML 并交给系统打印，离线时公式也能渲染；打印用白底黑字，
+/// 与界面主题无关
+#[command]
+async fn print_formulas(
+    app: tauri::AppHandle,
+    formulas: Vec<FormulaItem>,
+    options: Option<PrintOptions>,
+) -> Result<(), AppError> {
+    if formulas.is_empty() {
+        return Ok(());
+    }
+    let options = options.unwrap_or_default();
+    let html_options = HtmlOptions {
+        embed_assets: true,
+        title: options.title,
+    };
+    let css = if options.page_breaks {
+        format!("{}\n{}", print::PRINT_CSS, print::PAGE_BREAK_CSS)
+    } else {
+        print::PRINT_CSS.to_string()
+    };
+    let html = html_document(
+        katex_dir(&app).as_deref(),
+        &formulas,
+        &html_options,
+        &css,
+        print::PRINT_SCRIPT,
+    )?;
+    let path = print::write_temp(&html)?;
+    print::print_file(&app, &path)
+}
+
+/// HTML 片段中供 MathJax 识别的定界符
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum HtmlDelimiter {
+    /// 行内 `\( ... \)`
+    #[default]
+    Parentheses,
+    /// 独立成行的 `$$ ... $$`
+    Dollars,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct HtmlFragmentOptions {
+    delimiter: HtmlDelimiter,
+    /// 为 true 时给每个 `div` 加上 `data-id` 属性（公式有 id 时）
+    include_id: bool,
+}
+
+/// 生成可直接粘贴到博客编辑器的 HTML 片段，每条公式一个 `<div class="formula">`，
+/// 不含 `<html>`/`<head>`，由页面自带的 MathJax 渲染
+#[command]
+async fn format_html_fragment(
+    formulas: Vec<FormulaItem>,
+    options: Option<HtmlFragmentOptions>,
+) -> Result<String, AppError> {
+    let options = options.unwrap_or_default();
+    let (open, close) = match options.delimiter {
+        HtmlDelimiter::Parentheses => ("\\(", "\\)"),
+        HtmlDelimiter::Dollars => ("$$", "$$"),
+    };
+    let fragment = formulas
+        .iter()
+        .map(|item| {
+            let id_attr = item
+                .id
+                .as_deref()
+                .filter(|_| options.include_id)
+                .map(|id| format!(" data-id=\"{}\"", escape_html(id)))
+                .unwrap_or_default();
+            let note = item
+                .note
+                .as_deref()
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map(|n| format!("<strong>{}</strong>\n", escape_html(n)))
+                .unwrap_or_default();
+            format!(
+                "<div class=\"formula\"{}>\n{}{}{}{}\n</div>",
+                id_attr,
+                note,
+                open,
+                escape_html(item.latex.trim()),
+                close
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    Ok(fragment)
+}
+
+/// 复制到剪贴板
+#[command]
+async fn copy_text_to_clipboard(
+    clipboard: State<'_, SystemClipboard>,
+    content: String,
+) -> Result<(), AppError> {
+    clipboard.write_text(content).await
+}
+
+/// `copy_export_to_clipboard` 支持的格式，均使用各自的默认选项
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum CopyFormat {
+    Latex,
+    Markdown,
+    Typst,
+    Org,
+    Rst,
+    Mathml,
+}
+
+/// 格式化后直接复制，省去一次 IPC 往返；格式化失败时返回对应格式的错误，写入剪贴板失败返回 `clipboard_failed`
+#[command]
+async fn copy_export_to_clipboard(
+    clipboard: State<'_, SystemClipboard>,
+    formulas: Vec<FormulaItem>,
+    format: CopyFormat,
+) -> Result<(), AppError> {
+    let content = match format {
+        CopyFormat::Latex => format_latex(formulas, None, None).await?.content,
+        CopyFormat::Markdown => format_markdown(formulas, None, None, None, None).await?,
+        CopyFormat::Typst => format_typst(formulas, None).await?.content,
+        CopyFormat::Org => format_org(formulas, None).await?,
+        CopyFormat::Rst => format_rst(formulas).await?,
+        CopyFormat::Mathml => format_mathml(formulas).await?.content,
+    };
+    clipboard.write_text(content).await
+}
+
+impl From<math::ParseError> for AppError {
+    fn from(e: math::ParseError) -> Self {
+        AppError::InvalidLatex {
+            message: e.message,
+            position: e.position,
+        }
+    }
+}
+
+/// 把单个公式转换为 MathML；无法解析时返回第一个不支持的写法及其位置
+#[command]
+async fn latex_to_mathml(latex: String, display: bool) -> Result<String, AppError> {
+    let node = math::parse(&latex)?;
+    Ok(math::to_mathml(&node, &latex, display))
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct SvgOptions {
+    /// 1em 对应的像素数
+    font_size: f64,
+    /// 行间公式（大型运算符上下标居中）或行内公式
+    display: bool,
+    color: String,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        Self {
+            font_size: 20.0,
+            display: true,
+            color: "#000000".to_string(),
+        }
+    }
+}
+
+/// 颜色只允许十六进制或颜色名等简单写法，避免注入到 SVG 属性中
+fn validate_color(color: &str) -> Result<&str, AppError> {
+    let color = color.trim();
+    let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '#' | '(' | ')' | ',' | '.' | '%' | ' ');
+    let valid = !color.is_empty() && color.chars().all(allowed);
+    if valid {
+        Ok(color)
+    } else {
+        Err(AppError::InvalidOption {
+            name: "color".to_string(),
+            value: color.to_string(),
+        })
+    }
+}
+
+fn render_svg(latex: &str, options: &SvgOptions) -> Result<String, AppError> {
+    let node = math::parse(latex)?;
+    let font_size = if options.font_size.is_finite() {
+        options.font_size.clamp(4.0, 512.0)
+    } else {
+        20.0
+    };
+    Ok(svg::render(
+        &node,
+        latex,
+        font_size,
+        options.display,
+        validate_color(&options.color)?,
+    ))
+}
+
+/// 在后端把公式排版为 SVG，不依赖前端的渲染结果和主题
+#[command]
+async fn render_formula_svg(
+    latex: String,
+    options: Option<SvgOptions>,
+) -> Result<String, AppError> {
+    render_svg(&latex, &options.unwrap_or_default())
+}
+
+/// 把公式保存为 SVG 图片
+#[command]
+async fn export_formula_svg_file(
+    latex: String,
+    options: Option<SvgOptions>,
+) -> Result<FileOutcome, AppError> {
+    let svg = render_svg(&latex, &options.unwrap_or_default())?;
+    save_export("SVG Images", &["svg"], "formula.svg", svg.as_bytes(), WriteOptions::default()).await
+}
+
+/// PNG 分辨率上限，更高的请求会被降低并给出警告
+const MAX_PNG_DPI: f32 = 1200.0;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct PngOptions {
+    dpi: f32,
+    /// 四周留白，单位为输出图片的像素
+    padding: u32,
+    /// 透明背景；为 false 时填充白色
+    transparent: bool,
+    color: String,
+    font_size: f64,
+    display: bool,
+}
+
+impl Default for PngOptions {
+    fn default() -> Self {
+        Self {
+            dpi: 300.0,
+            padding: 8,
+            transparent: true,
+            color: "#000000".to_string(),
+            font_size: 20.0,
+            display: true,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct PngRender {
+    /// base64 编码的 PNG
+    data: String,
+    width: u32,
+    height: u32,
+    warnings: Vec<String>,
+}
+
+/// 由 PNG 选项得到的光栅化参数
+struct RasterJob {
+    svg: String,
+    /// 请求的分辨率，用于与实际分辨率比较
+    requested_dpi: f32,
+    dpi: f32,
+    padding: u32,
+    background: Option<resvg::tiny_skia::Color>,
+}
+
+impl RasterJob {
+    fn new(latex: &str, options: PngOptions) -> Result<Self, AppError> {
+        let svg = render_svg(
+            latex,
+            &SvgOptions {
+                font_size: options.font_size,
+                display: options.display,
+                color: options.color,
+            },
+        )?;
+        let requested_dpi = if options.dpi.is_finite() && options.dpi > 0.0 {
+            options.dpi
+        } else {
+            300.0
+        };
+        Ok(RasterJob {
+            svg,
+            requested_dpi,
+            dpi: requested_dpi.min(MAX_PNG_DPI),
+            padding: options.padding.min(1000),
+            background: (!options.transparent).then_some(resvg::tiny_skia::Color::WHITE),
+        })
+    }
+
+    /// 分辨率因上限被降低时的提示
+    fn warnings(&self, actual_dpi: f32) -> Vec<String> {
+        if actual_dpi < self.requested_dpi {
+            vec![format!(
+                "分辨率 {} DPI 过高，已降为 {} DPI",
+                self.requested_dpi,
+                actual_dpi.floor()
+            )]
+        } else {
+            Vec::new()
+        }
+    }
+}
+
+fn render_png(latex: &str, options: PngOptions) -> Result<(png::Raster, Vec<String>), AppError> {
+    let job = RasterJob::new(latex, options)?;
+    let raster = png::rasterize(&job.svg, job.dpi, job.padding, job.background)?;
+    let warnings = job.warnings(raster.dpi);
+    Ok((raster, warnings))
+}
+
+/// 把公式渲染为 PNG；光栅化在阻塞线程池中进行
+#[command]
+async fn render_formula_png(
+    latex: String,
+    options: Option<PngOptions>,
+) -> Result<PngRender, AppError> {
+    use base64::Engine;
+
+    let options = options.unwrap_or_default();
+    let (raster, warnings) = tokio::task::spawn_blocking(move || render_png(&latex, options))
+        .await??;
+    Ok(PngRender {
+        data: base64::engine::general_purpose::STANDARD.encode(&raster.png),
+        width: raster.width,
+        height: raster.height,
+        warnings,
+    })
+}
+
+
+/// 把公式渲染为图片并放入剪贴板；平台不支持图片时返回 `clipboard_unsupported`
+#[command]
+async fn copy_formula_image_to_clipboard(
+    clipboard: State<'_, SystemClipboard>,
+    latex: String,
+    options: Option<PngOptions>,
+) -> Result<Vec<String>, AppError> {
+    let options = options.unwrap_or_default();
+    let (width, height, rgba, warnings) = tokio::task::spawn_blocking(move || {
+        let job = RasterJob::new(&latex, options)?;
+        let (width, height, rgba, dpi) =
+            png::rasterize_rgba(&job.svg, job.dpi, job.padding, job.background)?;
+        Ok::<_, AppError>((width, height, rgba, job.warnings(dpi)))
+    })
+    .await??;
+    clipboard.write_image(width, height, rgba).await?;
+    Ok(warnings)
+}
+
+/// 把公式保存为 PNG 图片
+#[command]
+async fn export_formula_png_file(
+    latex: String,
+    options: Option<PngOptions>,
+) -> Result<FileOutcome, AppError> {
+    let options = options.unwrap_or_default();
+    let (raster, render_warnings) = tokio::task::spawn_blocking(move || render_png(&latex, options))
+        .await??;
+    let mut outcome =
+        save_export("PNG Images", &["png"], "formula.png", &raster.png, WriteOptions::default()).await?;
+    if let FileOutcome::Saved { warnings, .. } = &mut outcome {
+        *warnings = render_warnings;
+    }
+    Ok(outcome)
+}
+
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ImageFormat {
+    #[default]
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    fn extension(self) -> &'static str {
+        match self {
+            ImageFormat::Png => "png",
+            ImageFormat::Svg => "svg",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+struct ImageBatchOptions {
+    format: ImageFormat,
+    /// 文件名模板，与 LaTeX 文件夹导出相同
+    pattern: String,
+    /// 覆盖同名文件；为 false 时跳过已存在且内容不同的文件
+    overwrite: bool,
+    /// 排版和光栅化参数；SVG 只使用其中的字号、颜色和行间/行内设置
+    #[serde(flatten)]
+    image: PngOptions,
+}
+
+impl Default for ImageBatchOptions {
+    fn default() -> Self {
+        Self {
+            format: ImageFormat::default(),
+            pattern: "formula-{index}".to_string(),
+            overwrite: false,
+            image: PngOptions::default(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ImageBatchExport {
+    directory: String,
+    written: Vec<String>,
+    /// 因同名文件已存在而跳过的路径
+    skipped: Vec<String>,
+    /// 无法渲染或写入的公式
+    failures: Vec<ExportWarning>,
+    /// 分辨率被降低等提示，同样的提示只保留一条
+    warnings: Vec<String>,
+}
+
+fn render_image(latex: &str, options: &ImageBatchOptions) -> Result<(Vec<u8>, Vec<String>), AppError> {
+    let image = &options.image;
+    match options.format {
+        ImageFormat::Svg => {
+            let svg_options = SvgOptions {
+                font_size: image.font_size,
+                display: image.display,
+                color: image.color.clone(),
+            };
+            Ok((render_svg(latex, &svg_options)?.into_bytes(), Vec::new()))
+        }
+        ImageFormat::Png => {
+            let png_options = PngOptions {
+                color: image.color.clone(),
+                ..*image
+            };
+            let (raster, warnings) = render_png(latex, png_options)?;
+            Ok((raster.png, warnings))
+        }
+    }
+}
+
+/// 把所有公式渲染为图片，写入用户选择的文件夹；作为后台任务运行并返回 `started` 和任务 id，
+/// 每处理完一个公式报告一次进度。没有公式或用户取消时返回 `cancelled`
+#[command]
+async fn export_formula_images(
+    window: Window,
+    jobs: State<'_, JobRegistry>,
+    formulas: Vec<FormulaItem>,
+    options: Option<ImageBatchOptions>,
+) -> Result<FileOutcome, AppError> {
+    let options = options.unwrap_or_default();
+    if formulas.is_empty() {
+        return Ok(FileOutcome::Cancelled);
+    }
+    let Some(dir) = pick_export_directory().await else {
+        return Ok(FileOutcome::Cancelled);
+    };
+
+    let job_id = jobs.start(&window, "images", move |job| {
+        let total = formulas.len();
+        let mut result = ImageBatchExport {
+            directory: dir.to_string_lossy().to_string(),
+            written: Vec::new(),
+            skipped: Vec::new(),
+            failures: Vec::new(),
+            warnings: Vec::new(),
+        };
+        let mut used = HashSet::new();
+        for (idx, item) in formulas.iter().enumerate() {
+            job.check_cancelled()?;
+            job.progress(idx, total, "rendering");
+            let name = directory_file_name(&options.pattern, options.format.extension(), idx, item, &mut used);
+            let path = dir.join(name);
+            let written = render_image(&item.latex, &options).and_then(|(bytes, warnings)| {
+                write_file_checked(&path, &bytes, options.overwrite)?;
+                Ok(warnings)
+            });
+            match written {
+                Ok(warnings) => {
+                    for warning in warnings {
+                        if !result.warnings.contains(&warning) {
+                            result.warnings.push(warning);
+                        }
+                    }
+                    result.written.push(path.to_string_lossy().to_string());
+                }
+                Err(AppError::Conflict { .. }) => {
+                    result.skipped.push(path.to_string_lossy().to_string())
+                }
+                Err(e) => result.failures.push(ExportWarning {
+                    position: idx,
+                    message: e.to_string(),
+                }),
+            }
+        }
+        job.progress(total, total, "done");
+        Ok(result)
+    });
+    Ok(FileOutcome::Started { job_id })
+}
+
+#[derive(Serialize)]
+struct MathmlDocument {
+    content: String,
+    /// 无法转换、以原始 LaTeX 保留的公式
+    warnings: Vec<ExportWarning>,
+}
+
+/// 生成内嵌 MathML 的 XHTML 文档，浏览器无需脚本即可显示公式
+#[command]
+async fn format_mathml(formulas: Vec<FormulaItem>) -> Result<MathmlDocument, AppError> {
+    if formulas.is_empty() {
+        return Ok(MathmlDocument {
+            content: String::new(),
+            warnings: Vec::new(),
+        });
+    }
+    let mut warnings = Vec::new();
+    let sections = formulas
+        .iter()
+        .enumerate()
+        .map(|(idx, item)| {
+            let heading = item
+                .note
+                .as_deref()
+                .map(str::trim)
+                .filter(|n| !n.is_empty())
+                .map(escape_html)
+                .unwrap_or_else(|| format!("公式 {}", idx + 1));
+            let body = match math::parse(&item.latex) {
+                Ok(node) => math::to_mathml(&node, &item.latex, true),
+                Err(error) => {
+                    warnings.push(ExportWarning {
+                        position: idx,
+                        message: error.to_string(),
+                    });
+                    format!("<pre>{}</pre>", escape_html(&item.latex))
+                }
+            };
+            format!(
+                "<section id=\"eq-{}\">\n<h2>{}</h2>\n{}\n</section>",
+                idx + 1,
+                heading,
+                body
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    Ok(MathmlDocument {
+        content: format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" lang="zh-CN" xml:lang="zh-CN">
+<head>
+<meta charset="utf-8"/>
+<title>公式集</title>
+</head>
+<body>
+{}
+</body>
+</html>
+"#,
+            sections
+        ),
+        warnings,
+    })
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+struct TemplateItem {
+    id: String,
+    name: String,
+    latex: String,
+    note: Option<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TemplateCategory {
+    id: String,
+    name: String,
+    templates: Vec<TemplateItem>,
+    #[serde(rename = "parentId")]
+    parent_id: Option<String>,
+    #[serde(flatten)]
+    extra: Map<String, Value>,
+}
+
+/// 模板库文件格式版本。版本 1 没有 `version` 字段，分类可以是顶层数组或嵌套结构；
+/// 版本 2 起为带版本号的对象，分类平铺并以 `parentId` 表示层级
+const TEMPLATE_LIBRARY_VERSION: u64 = 2;
+
+fn legacy_template_version() -> u64 {
+    1
+}
+
+#[derive(Serialize, Deserialize)]
+struct TemplateLibrary {
+    #[serde(default = "legacy_template_version")]
+    version: u64,
+    categories: Vec<TemplateCategory>,
+    #[serde(rename = "selectedCategoryId")]
+    selected_category_id: String,
+}
+
+fn trimmed_string(value: Option<&Value>) -> Option<String> {
+    value
+        .and_then(|v| v.as_str())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+}
+
+/// 收集对象中不属于 `known` 的字段
+fn extra_fields(value: &Value, known: &[&str]) -> Map<String, Value> {
+    value
+        .as_object()
+        .map(|obj| {
+            obj.iter()
+                .filter(|(key, _)| !known.contains(&key.as_str()))
+                .map(|(key, v)| (key.clone(), v.clone()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// 读取标签列表：接受字符串数组或逗号分隔的字符串，去除空白并去重
+fn normalize_tags(value: Option<&Value>) -> Vec<String> {
+    let raw: Vec<&str> = match value {
+        Some(Value::Array(items)) => items.iter().filter_map(|v| v.as_str()).collect(),
+        Some(Value::String(text)) => text.split(',').collect(),
+        _ => Vec::new(),
+    };
+    let mut tags: Vec<String> = Vec::new();
+    for tag in raw.into_iter().map(str::trim).filter(|t| !t.is_empty()) {
+        if !tags.iter().any(|t| t == tag) {
+            tags.push(tag.to_string());
+        }
+    }
+    tags
+}
+
+/// 规范化时被改写的 id
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RewrittenId {
+    /// 条目在原数组中的位置（从 0 开始）
+    position: usize,
+    /// 原来的 id；不是字符串时（如数字）为其 JSON 文本
+    original: String,
+    id: String,
+}
+
+/// 条目被跳过的原因
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum SkipReason {
+    NotAnObject,
+    MissingLatex,
+    EmptyLatex,
+}
+
+impl SkipReason {
+    /// 导入警告中显示的说明
+    fn message(&self) -> &'static str {
+        match self {
+            SkipReason::NotAnObject => "不是公式对象，已跳过",
+            SkipReason::MissingLatex => "缺少 latex，已跳过",
+            SkipReason::EmptyLatex => "latex 为空，已跳过",
+        }
+    }
+}
+
+/// 规范化时被跳过的条目
+#[derive(Serialize)]
+struct NormalizeWarning {
+    /// 条目在原数组中的位置（从 0 开始）
+    position: usize,
+    reason: SkipReason,
+}
+
+/// 当前写出的公式文件格式版本；裸数组视为版本 1
+const FORMULA_FILE_VERSION: u64 = 2;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NormalizeResult {
+    /// 检测到的文件格式版本
+    version: u64,
+    title: Option<String>,
+    entries: Vec<FormulaEntry>,
+    rewritten_ids: Vec<RewrittenId>,
+    warnings: Vec<NormalizeWarning>,
+}
+
+/// 在 `base` 基础上生成未被占用的 id
+fn unique_id(base: String, seen: &HashSet<String>, reserved: &HashSet<String>) -> String {
+    let taken = |id: &String| seen.contains(id) || reserved.contains(id);
+    if !taken(&base) {
+        return base;
+    }
+    let dup = format!("{}-dup", base);
+    if !taken(&dup) {
+        return dup;
+    }
+    (2..)
+        .map(|n| format!("{}-dup-{}", base, n))
+        .find(|id| !taken(id))
+        .unwrap()
+}
+
+/// 将已解析的公式集规范化为 `FormulaEntry` 列表
+///
+/// 同时接受旧版裸数组和 `{ "version": 2, "title": ..., "formulas": [...] }` 包装格式。
+/// 重复或为空的 id 会被重新生成；显式给出的 id 优先保留，自动生成的 id 会避开它们。
+fn normalize_formula_value(value: &Value) -> Result<NormalizeResult, AppError> {
+    let (version, title, value) = match value.get("formulas") {
+        Some(formulas) if value.is_object() => (
+            value.get("version").and_then(|v| v.as_u64()).unwrap_or(FORMULA_FILE_VERSION),
+            trimmed_string(value.get("title")),
+            formulas,
+        ),
+        _ => (1, None, value),
+    };
+    if version > FORMULA_FILE_VERSION {
+        return Err(AppError::UnsupportedVersion {
+            found: version,
+            supported: FORMULA_FILE_VERSION,
+        });
+    }
+    if !value.is_array() {
+        if value.get("categories").is_some() {
+            return Err(AppError::WrongFileType {
+                expected: "formulas".to_string(),
+                found: "templates".to_string(),
+            });
+        }
+        return Err(AppError::NotFormulaList);
+    }
+    let array = value.as_array().unwrap();
+    let reserved: HashSet<String> = array
+        .iter()
+        .filter_map(|item| trimmed_string(item.get("id")))
+        .collect();
+    let mut seen: HashSet<String> = HashSet::new();
+    let mut normalized = Vec::new();
+    let mut rewritten_ids = Vec::new();
+    let mut warnings = Vec::new();
+    for (idx, item) in array.iter().enumerate() {
+        let skip = if !item.is_object() {
+            Some(SkipReason::NotAnObject)
+        } else {
+            match item.get("latex").and_then(|v| v.as_str()) {
+                None => Some(SkipReason::MissingLatex),
+                Some(latex) if latex.trim().is_empty() => Some(SkipReason::EmptyLatex),
+                Some(_) => None,
+            }
+        };
+        if let Some(reason) = skip {
+            warnings.push(NormalizeWarning {
+                position: idx,
+                reason,
+            });
+            continue;
+        }
+        let latex = trimmed_string(item.get("latex"));
+        let original = match item.get("id") {
+            None | Some(Value::Null) => None,
+            Some(Value::String(original)) => Some(original.clone()),
+            Some(other) => Some(other.to_string()),
+        };
+        let id = match trimmed_string(item.get("id")) {
+            Some(id) if !seen.contains(&id) => id,
+            _ => unique_id(format!("formula-{}", idx + 1), &seen, &reserved),
+        };
+        if let Some(original) = original {
+            if original.trim() != id {
+                rewritten_ids.push(RewrittenId {
+                    position: idx,
+                    original,
+                    id: id.clone(),
+                });
+            }
+        }
+        seen.insert(id.clone());
+        let index = item.get("index").and_then(|v| v.as_u64()).unwrap_or((idx + 1) as u64) as u32;
+        let note = trimmed_string(item.get("note"));
+        let tags = normalize_tags(item.get("tags"));
+        normalized.push(FormulaEntry {
+            id,
+            index,
+            latex: latex.unwrap(),
+            note,
+            tags,
+            section: trimmed_string(item.get("section")),
+            extra: extra_fields(item, &["id", "index", "latex", "note", "tags", "section"]),
+        });
+    }
+    Ok(NormalizeResult {
+        version,
+        title,
+        entries: normalized,
+        rewritten_ids,
+        warnings,
+    })
+}
+
+#[command]
+async fn normalize_formulas(content: String) -> Result<NormalizeResult, AppError> {
+    let value: Value = serde_json::from_str(&content)?;
+    normalize_formula_value(&value)
+}
+
+#[derive(Serialize)]
+struct FormulaFile<'a> {
+    version: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<&'a str>,
+    formulas: &'a [FormulaEntry],
+}
+
+/// 以当前版本的包装格式序列化公式集，保存时旧文件会因此自动升级
+#[command]
+async fn serialize_formulas(
+    entries: Vec<FormulaEntry>,
+    title: Option<String>,
+) -> Result<String, AppError> {
+    let title = title.as_deref().map(str::trim).filter(|t| !t.is_empty());
+    let file = FormulaFile {
+        version: FORMULA_FILE_VERSION,
+        title,
+        formulas: &entries,
+    };
+    serde_json::to_string_pretty(&file).map_err(AppError::encode_json)
+}
+
+/// 从 `format_latex` 生成的文档中提取备注：取 `\noindent\textbf{...}` 中的文本
+fn extract_exported_note(preamble: &str) -> Option<String> {
+    const MARKER: &str = "\\noindent\\textbf";
+    let start = preamble.rfind(MARKER)? + MARKER.len();
+    let chars: Vec<char> = preamble[start..].chars().collect();
+    if chars.first() != Some(&'{') {
+        return None;
+    }
+    let end = skip_brace_group(&chars, 0);
+    if end < 2 {
+        return None;
+    }
+    let raw: String = chars[1..end - 1].iter().collect();
+    Some(unescape_latex_text(&raw)).filter(|note| !note.trim().is_empty())
+}
+
+/// 重新导入本应用导出的 LaTeX 文档，还原公式及其备注。数字以外的 `\label` 作为公式 id，
+/// 非默认的数学环境记录在条目的 `environment` 字段中，再次导出时沿用
+#[command]
+async fn reimport_exported_latex(content: String) -> Result<Vec<FormulaEntry>, AppError> {
+    const BEGIN: &str = "\\begin{";
+    const LABEL: &str = "\\label{eq:";
+
+    let mut formulas = Vec::new();
+    let mut rest = content.as_str();
+    while let Some(begin) = rest.find(BEGIN) {
+        let preamble = &rest[..begin];
+        let after_name = &rest[begin + BEGIN.len()..];
+        let environment = after_name
+            .find('}')
+            .and_then(|close| Some((MathEnvironment::from_name(&after_name[..close])?, close)));
+        // `\begin{document}` 等其他环境
+        let Some((environment, close)) = environment else {
+            rest = after_name;
+            continue;
+        };
+        let after_begin = &after_name[close + 1..];
+        let end_marker = format!("\\end{{{}}}", environment.name());
+        let end = after_begin.find(&end_marker).ok_or_else(|| {
+            let offset = content.len() - after_begin.len();
+            AppError::InvalidLatex {
+                position: content[..offset].encode_utf16().count(),
+                message: format!("missing {}", end_marker),
+            }
+        })?;
+        let mut body = after_begin[..end].trim_start();
+        let position = formulas.len() + 1;
+        let mut index = position as u32;
+        let mut id = format!("formula-{}", position);
+        if let Some(label) = body.strip_prefix(LABEL) {
+            if let Some(close) = label.find('}') {
+                let name = label[..close].trim();
+                match name.parse::<u32>() {
+                    Ok(n) => index = n,
+                    Err(_) if !name.is_empty() => id = name.to_string(),
+                    Err(_) => {}
+                }
+                body = &label[close + 1..];
+            }
+        }
+        let mut extra = Map::new();
+        if environment != MathEnvironment::default() {
+            extra.insert(
+                "environment".to_string(),
+                Value::String(environment.name().to_string()),
+            );
+        }
+        let latex = body.trim();
+        if !latex.is_empty() {
+            formulas.push(FormulaEntry {
+                id,
+                index,
+                latex: latex.to_string(),
+                note: extract_exported_note(preamble),
+                tags: Vec::new(),
+                section: None,
+                extra,
+            });
+        }
+        rest = &after_begin[end + end_marker.len()..];
+    }
+    if formulas.is_empty() {
+        return Err(AppError::NothingToImport {
+            format: "LaTeX".to_string(),
+        });
+    }
+    Ok(formulas)
+}
+
+/// 导入时跳过的内容，`line` 从 1 开始
+#[derive(Serialize)]
+struct ImportWarning {
+    line: usize,
+    message: String,
+}
+
+#[derive(Serialize)]
+struct ImportResult {
+    entries: Vec<FormulaEntry>,
+    warnings: Vec<ImportWarning>,
+}
+
+impl ImportResult {
+    fn new(blocks: Vec<import::Block>, skipped: Vec<import::Skipped>) -> Self {
+        let entries = blocks
+            .into_iter()
+            .enumerate()
+            .map(|(idx, block)| FormulaEntry {
+                id: format!("formula-{}", idx + 1),
+                index: idx as u32 + 1,
+                latex: block.latex,
+                note: block.note,
+                tags: block.tags,
+                section: block.section,
+                extra: Map::new(),
+            })
+            .collect();
+        let warnings = skipped
+            .into_iter()
+            .map(|s| ImportWarning {
+                line: s.line,
+                message: s.message,
+            })
+            .collect();
+        ImportResult { entries, warnings }
+    }
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct TexImportOptions {
+    /// `align` 等多行环境按 `\\` 拆分，每行一个公式
+    split_rows: bool,
+}
+
+/// 从 `.tex` 文档中提取行间公式，备注取最近的章节标题或注释行
+#[command]
+async fn import_tex_file(
+    path: String,
+    options: Option<TexImportOptions>,
+) -> Result<ImportResult, AppError> {
+    let content = read_text_file(Path::new(&path), DEFAULT_MAX_READ_BYTES)?;
+    let (blocks, skipped) = import::scan_tex(&content, options.unwrap_or_default().split_rows);
+    Ok(ImportResult::new(blocks, skipped))
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct MarkdownImportOptions {
+    /// 同时提取段落中的 `$...$` 行内公式
+    inline: bool,
+}
+
+/// 从 Markdown 文档中提取行间公式，备注取最近的标题或粗体行；可导回 `format_markdown` 的各种输出
+#[command]
+async fn import_markdown_file(
+    path: String,
+    options: Option<MarkdownImportOptions>,
+) -> Result<ImportResult, AppError> {
+    let content = read_text_file(Path::new(&path), DEFAULT_MAX_READ_BYTES)?;
+    let (blocks, skipped) = import::scan_markdown(&content, options.unwrap_or_default().inline);
+    Ok(ImportResult::new(blocks, skipped))
+}
+
+/// 无法转换的 Word 公式，`omml` 是原始的公式 XML
+#[derive(Serialize)]
+struct DocxImportFailure {
+    index: usize,
+    omml: String,
+    message: String,
+    note: Option<String>,
+}
+
+#[derive(Serialize)]
+struct DocxImport {
+    entries: Vec<FormulaEntry>,
+    failed: Vec<DocxImportFailure>,
+}
+
+/// 从 Word 文档中读取 OMML 公式并转换为 LaTeX，备注取公式周围段落的文字
+#[command]
+async fn import_docx_file(path: String) -> Result<DocxImport, AppError> {
+    let file_path = PathBuf::from(&path);
+    tokio::task::spawn_blocking(move || {
+        let file = fs::File::open(&file_path)
+            .map_err(|e| AppError::io(&file_path, "Failed to read file", e))?;
+        let (imported, failures) = docx::read_equations(std::io::BufReader::new(file))?;
+        let blocks = imported
+            .into_iter()
+            .map(|item| import::Block {
+                latex: item.latex,
+                note: item.note,
+                tags: Vec::new(),
+                section: None,
+            })
+            .collect();
+        let failed = failures
+            .into_iter()
+            .map(|f| DocxImportFailure {
+                index: f.position,
+                omml: f.omml,
+                message: f.message,
+                note: f.note,
+            })
+            .collect();
+        Ok(DocxImport {
+            entries: ImportResult::new(blocks, Vec::new()).entries,
+            failed,
+        })
+    })
+    .await?
+}
+
+/// 粘贴内容被识别为的格式
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum PasteFormat {
+    Json,
+    Latex,
+    Mathml,
+}
+
+/// 一种可能的理解方式及按此导入的结果
+#[derive(Serialize)]
+struct PasteCandidate {
+    format: PasteFormat,
+    entries: Vec<FormulaEntry>,
+    warnings: Vec<String>,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+enum ClipboardImport {
+    Detected {
+        #[serde(rename = "detectedFormat")]
+        detected_format: PasteFormat,
+        entries: Vec<FormulaEntry>,
+        warnings: Vec<String>,
+    },
+    /// 内容可以有多种理解，由用户选择
+    NeedsUserChoice { options: Vec<PasteCandidate> },
+}
+
+fn import_warning_text(warnings: Vec<ImportWarning>) -> Vec<String> {
+    warnings
+        .into_iter()
+        .map(|w| format!("第 {} 行：{}", w.line, w.message))
+        .collect()
+}
+
+/// 没有定界符的文字含有这些记号之一才直接当作 LaTeX，否则 "hello world" 这样的普通文字
+/// 也能通过解析
+fn has_latex_markers(text: &str) -> bool {
+    text.contains(['\\', '^', '_', '{', '}'])
+}
+
+/// 识别粘贴的文字：JSON 公式集、`$$`/`\[`/`equation` 等 LaTeX 公式块或 MathML 片段
+///
+/// 整段是合法的公式集 JSON 时直接采用；LaTeX 与 MathML 同时出现时不做猜测，
+/// 返回所有可能的结果由用户选择。
+fn detect_paste(text: &str) -> Result<ClipboardImport, AppError> {
+    let trimmed = text.trim();
+    let binary = trimmed
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'));
+    if trimmed.is_empty() || binary {
+        return Err(AppError::ClipboardEmpty);
+    }
+
+    if trimmed.starts_with('[') || trimmed.starts_with('{') {
+        if let Ok(value) = serde_json::from_str::<Value>(trimmed) {
+            match normalize_formula_value(&value) {
+                Ok(result) if !result.entries.is_empty() => {
+                    let warnings = result
+                        .warnings
+                        .iter()
+                        .map(|w| format!("第 {} 条公式已跳过", w.position + 1))
+                        .collect();
+                    return Ok(ClipboardImport::Detected {
+                        detected_format: PasteFormat::Json,
+                        entries: result.entries,
+                        warnings,
+                    });
+                }
+                Err(err @ AppError::WrongFileType { .. }) => return Err(err),
+                _ => {}
+            }
+        }
+    }
+
+    let mut candidates = Vec::new();
+    let fragments = mathml::split(text);
+    if !fragments.is_empty() {
+        let mut blocks = Vec::new();
+        let mut warnings = Vec::new();
+        for (line, fragment) in fragments {
+            match mathml::to_latex(fragment) {
+                Ok(converted) => {
+                    warnings.extend(
+                        converted
+                            .warnings
+                            .into_iter()
+                            .map(|w| format!("第 {} 行：{}", line, w)),
+                    );
+                    blocks.push(import::Block {
+                        latex: converted.latex,
+                        note: None,
+                        tags: Vec::new(),
+                        section: None,
+                    });
+                }
+                Err(e) => warnings.push(format!("第 {} 行：{}", line, e)),
+            }
+        }
+        if !blocks.is_empty() {
+            candidates.push(PasteCandidate {
+                format: PasteFormat::Mathml,
+                entries: ImportResult::new(blocks, Vec::new()).entries,
+                warnings,
+            });
+        }
+    }
+
+    let (blocks, skipped) = import::scan_tex(text, false);
+    if !blocks.is_empty() {
+        let result = ImportResult::new(blocks, skipped);
+        candidates.push(PasteCandidate {
+            format: PasteFormat::Latex,
+            entries: result.entries,
+            warnings: import_warning_text(result.warnings),
+        });
+    } else if candidates.is_empty() && !trimmed.contains('\n') && math::parse(trimmed).is_ok() {
+        // 没有定界符的单行公式；不含 LaTeX 记号时可能只是普通文字，由用户确认
+        let block = import::Block {
+            latex: trimmed.to_string(),
+            note: None,
+            tags: Vec::new(),
+            section: None,
+        };
+        let candidate = PasteCandidate {
+            format: PasteFormat::Latex,
+            entries: ImportResult::new(vec![block], Vec::new()).entries,
+            warnings: Vec::new(),
+        };
+        if !has_latex_markers(trimmed) {
+            return Ok(ClipboardImport::NeedsUserChoice {
+                options: vec![candidate],
+            });
+        }
+        candidates.push(candidate);
+    }
+
+    match candidates.len() {
+        0 => Err(AppError::UnrecognizedPaste),
+        1 => {
+            let candidate = candidates.pop().unwrap();
+            Ok(ClipboardImport::Detected {
+                detected_format: candidate.format,
+                entries: candidate.entries,
+                warnings: candidate.warnings,
+            })
+        }
+        _ => Ok(ClipboardImport::NeedsUserChoice {
+            options: candidates,
+        }),
+    }
+}
+
+/// 读取剪贴板中的文字并自动识别格式后导入
+#[command]
+async fn import_from_clipboard(
+    clipboard: State<'_, SystemClipboard>,
+) -> Result<ClipboardImport, AppError> {
+    let text = clipboard.read_text().await?;
+    detect_paste(&text)
+}
+
+#[derive(Serialize)]
+struct MathmlConversion {
+    latex: String,
+    /// 不支持、按文字处理的元素
+    warnings: Vec<String>,
+}
+
+/// 把一段 Presentation MathML 转换为 LaTeX；没有 `<math>` 外层时自动补上。
+/// 含多个 `<math>` 时只转换第一个，并在警告中说明
+#[command]
+async fn mathml_to_latex(mathml: String) -> Result<MathmlConversion, AppError> {
+    let fragments = mathml::split(&mathml);
+    let wrapped;
+    let fragment = match fragments.first() {
+        Some((_, fragment)) => *fragment,
+        None => {
+            wrapped = format!("<math>{}</math>", mathml);
+            &wrapped
+        }
+    };
+    let converted = mathml::to_latex(fragment)?;
+    let mut warnings = converted.warnings;
+    if fragments.len() > 1 {
+        warnings.push(format!(
+            "输入包含 {} 个 <math> 元素，只转换了第一个；导入 MathML 文件可以逐个导入",
+            fragments.len()
+        ));
+    }
+    Ok(MathmlConversion {
+        latex: converted.latex,
+        warnings,
+    })
+}
+
+/// 从 MathML、XHTML 或 EPUB 章节文件中导入公式，每个 `<math>` 元素一条
+#[command]
+async fn import_mathml_file(path: String) -> Result<ImportResult, AppError> {
+    let content = read_text_file(Path::new(&path), DEFAULT_MAX_READ_BYTES)?;
+    let mut blocks = Vec::new();
+    let mut skipped = Vec::new();
+    for (line, fragment) in mathml::split(&content) {
+        match mathml::to_latex(fragment) {
+            Ok(converted) => {
+                skipped.extend(converted.warnings.into_iter().map(|message| import::Skipped {
+                    line,
+                    message,
+                }));
+                blocks.push(import::Block {
+                    latex: converted.latex,
+                    note: None,
+                    tags: Vec::new(),
+                    section: None,
+                });
+            }
+            Err(e) => skipped.push(import::Skipped {
+                line,
+                message: e.to_string(),
+            }),
+        }
+    }
+    if blocks.is_empty() && skipped.is_empty() {
+        return Err(AppError::NothingToImport {
+            format: "MathML".to_string(),
+        });
+    }
+    Ok(ImportResult::new(blocks, skipped))
+}
+
+/// 默认最多导入的 CSV 行数
+const DEFAULT_MAX_CSV_ROWS: usize = 50_000;
+
+/// CSV 列的位置：表头中的名称或从 0 开始的序号
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CsvColumn {
+    Index(usize),
+    Name(String),
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct CsvMapping {
+    latex: Option<CsvColumn>,
+    note: Option<CsvColumn>,
+    tags: Option<CsvColumn>,
+    id: Option<CsvColumn>,
+    /// 第一行是否是表头；不指定时，按名称指定列或第一行含 latex/note 时视为表头
+    has_header: Option<bool>,
+    max_rows: Option<usize>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CsvImport {
+    entries: Vec<FormulaEntry>,
+    rewritten_ids: Vec<RewrittenId>,
+    /// `line` 是 CSV 中的行号（从 1 开始，含表头）
+    warnings: Vec<ImportWarning>,
+}
+
+/// 按名称（不区分大小写）或序号找到列
+fn csv_column(column: &CsvColumn, header: Option<&csv::StringRecord>) -> Result<usize, AppError> {
+    match column {
+        CsvColumn::Index(idx) => Ok(*idx),
+        CsvColumn::Name(name) => header
+            .and_then(|h| h.iter().position(|cell| cell.trim().eq_ignore_ascii_case(name.trim())))
+            .ok_or_else(|| AppError::CsvColumnNotFound { name: name.clone() }),
+    }
+}
+
+/// 从 CSV 导入公式；`mapping` 指定 latex、note、tags 所在的列，
+/// 不指定时要求第一行是含 latex 列的表头
+#[command]
+async fn import_csv_file(path: String, mapping: Option<CsvMapping>) -> Result<CsvImport, AppError> {
+    let content = read_text_file(Path::new(&path), DEFAULT_MAX_READ_BYTES)?;
+    let content = content.trim_start_matches('\u{FEFF}');
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(content.as_bytes());
+    let invalid = |e: csv::Error| AppError::InvalidCsv {
+        message: e.to_string(),
+    };
+    let mut records = reader.records();
+    let first = records.next().transpose().map_err(invalid)?;
+
+    let auto = mapping.is_none();
+    let mapping = mapping.unwrap_or_else(|| CsvMapping {
+        latex: Some(CsvColumn::Name("latex".to_string())),
+        note: Some(CsvColumn::Name("note".to_string())),
+        tags: Some(CsvColumn::Name("tags".to_string())),
+        id: Some(CsvColumn::Name("id".to_string())),
+        ..Default::default()
+    });
+    let by_name = [&mapping.latex, &mapping.note, &mapping.tags, &mapping.id]
+        .iter()
+        .any(|c| matches!(c, Some(CsvColumn::Name(_))));
+    let looks_like_header = first.as_ref().is_some_and(|row| {
+        row.iter()
+            .any(|cell| matches!(cell.trim().to_ascii_lowercase().as_str(), "latex" | "note"))
+    });
+    let has_header = mapping.has_header.unwrap_or(by_name || looks_like_header);
+    if auto && !looks_like_header {
+        return Err(AppError::CsvHeaderMissing);
+    }
+    let header = if has_header { first.clone() } else { None };
+    let latex_column = match &mapping.latex {
+        Some(column) => csv_column(column, header.as_ref())?,
+        None => 0,
+    };
+    // 自动识别时可选列缺失不算错误
+    let optional = |column: &Option<CsvColumn>| match column {
+        Some(column) => match csv_column(column, header.as_ref()) {
+            Ok(idx) => Ok(Some(idx)),
+            Err(_) if auto => Ok(None),
+            Err(err) => Err(err),
+        },
+        None => Ok(None),
+    };
+    let note_column = optional(&mapping.note)?;
+    let tags_column = optional(&mapping.tags)?;
+    let id_column = optional(&mapping.id)?;
+    let max_rows = mapping.max_rows.unwrap_or(DEFAULT_MAX_CSV_ROWS);
+
+    let data = if has_header { None } else { first.map(Ok) };
+    let mut items = Vec::new();
+    // 每个条目所在的 CSV 行号，用于把规范化的警告对应回行
+    let mut lines = Vec::new();
+    let mut warnings = Vec::new();
+    for (count, record) in data.into_iter().chain(records).enumerate() {
+        if count >= max_rows {
+            return Err(AppError::TooManyRows { limit: max_rows });
+        }
+        let record = record.map_err(invalid)?;
+        let line = record.position().map_or(count + 1, |p| p.line() as usize);
+        let cell = |idx: Option<usize>| idx.and_then(|i| record.get(i)).map(str::trim).unwrap_or("");
+        let latex = cell(Some(latex_column));
+        if latex.is_empty() {
+            warnings.push(ImportWarning {
+                line,
+                message: SkipReason::EmptyLatex.message().to_string(),
+            });
+            continue;
+        }
+        let mut item = Map::new();
+        item.insert("latex".to_string(), Value::String(latex.to_string()));
+        let note = cell(note_column);
+        if !note.is_empty() {
+            item.insert("note".to_string(), Value::String(note.to_string()));
+        }
+        let tags = cell(tags_column).replace([';', '；', '，'], ",");
+        if !tags.is_empty() {
+            item.insert("tags".to_string(), Value::String(tags));
+        }
+        let id = cell(id_column);
+        if !id.is_empty() {
+            item.insert("id".to_string(), Value::String(id.to_string()));
+        }
+        items.push(Value::Object(item));
+        lines.push(line);
+    }
+    let result = normalize_formula_value(&Value::Array(items))?;
+    warnings.extend(result.warnings.iter().map(|warning| ImportWarning {
+        line: lines.get(warning.position).copied().unwrap_or_default(),
+        message: warning.reason.message().to_string(),
+    }));
+    warnings.sort_by_key(|warning| warning.line);
+    Ok(CsvImport {
+        entries: result.entries,
+        rewritten_ids: result.rewritten_ids,
+        warnings,
+    })
+}
+
+/// 将 JSON 公式集转换为 YAML 文档
+#[command]
+async fn formulas_to_yaml(content: String) -> Result<String, AppError> {
+    let value: Value = serde_json::from_str(&content)?;
+    let formulas = normalize_formula_value(&value)?.entries;
+    serde_yaml::to_string(&formulas).map_err(|e| AppError::EncodeFailed {
+        format: "YAML".to_string(),
+        message: e.to_string(),
+    })
+}
+
+/// 将 YAML 文档转换回 JSON 公式集
+#[command]
+async fn yaml_to_formulas(content: String) -> Result<String, AppError> {
+    let value: Value = serde_yaml::from_str(&content).map_err(|e| AppError::InvalidYaml {
+        message: e.to_string(),
+    })?;
+    if !value.is_array() && !value.is_object() {
+        return Err(AppError::NotFormulaList);
+    }
+    let formulas = normalize_formula_value(&value)?.entries;
+    serde_json::to_string_pretty(&formulas).map_err(AppError::encode_json)
+}
+
+#[derive(Serialize)]
+struct FormulaRef {
+    id: String,
+    index: u32,
+}
+
+/// 其参数为纯文本、不应参与命令匹配的命令
+const TEXT_COMMANDS: &[&str] = &["text", "textrm", "textbf", "textit", "textsf", "texttt", "mbox"];
+
+/// 从 `start`（指向 `{`）开始跳过一个完整的花括号分组，返回分组之后的位置
+fn skip_brace_group(chars: &[char], start: usize) -> usize {
+    let mut depth = 0usize;
+    let mut i = start;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return i + 1;
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    chars.len()
+}
+
+/// 判断 LaTeX 中是否以完整命令的形式使用了 `\name`，忽略 `\text{}` 等文本块内的内容
+fn latex_uses_command(latex: &str, name: &str) -> bool {
+    let chars: Vec<char> = latex.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '\\' {
+            i += 1;
+            continue;
+        }
+        let start = i + 1;
+        let mut end = start;
+        while end < chars.len() && chars[end].is_ascii_alphabetic() {
+            end += 1;
+        }
+        if end == start && end < chars.len() {
+            // 单字符命令，如 `\,` 或 `\{`
+            end += 1;
+        }
+        let command: String = chars[start..end].iter().collect();
+        if command == name {
+            return true;
+        }
+        i = end;
+        if TEXT_COMMANDS.contains(&command.as_str()) {
+            while i < chars.len() && chars[i].is_whitespace() {
+                i += 1;
+            }
+            if i < chars.len() && chars[i] == '{' {
+                i = skip_brace_group(&chars, i);
+            }
+        }
+    }
+    false
+}
+
+/// 查找使用了指定 LaTeX 命令的公式
+#[command]
+async fn find_formulas_using_command(
+    formulas: Vec<FormulaEntry>,
+    command: String,
+) -> Result<Vec<FormulaRef>, AppError> {
+    let name = command.trim().trim_start_matches('\\');
+    if name.is_empty() {
+        return Err(AppError::InvalidOption {
+            name: "command".to_string(),
+            value: command,
+        });
+    }
+    Ok(formulas
+        .into_iter()
+        .filter(|item| latex_uses_command(&item.latex, name))
+        .map(|item| FormulaRef {
+            id: item.id,
+            index: item.index,
+        })
+        .collect())
+}
+
+/// 按标签筛选公式，`match_all` 为 true 时要求包含全部标签，否则包含任一标签即可
+#[command]
+async fn filter_by_tags(
+    formulas: Vec<FormulaEntry>,
+    tags: Vec<String>,
+    match_all: bool,
+) -> Result<Vec<FormulaEntry>, AppError> {
+    Ok(retain_tagged(formulas, &tags, match_all))
+}
+
+/// 解析公式文件内容后按标签筛选，规则同 `filter_by_tags`
+#[command]
+async fn filter_formulas(
+    content: String,
+    tags: Vec<String>,
+    match_all: bool,
+) -> Result<Vec<FormulaEntry>, AppError> {
+    let value: Value = serde_json::from_str(&content)?;
+    let entries = normalize_formula_value(&value)?.entries;
+    Ok(retain_tagged(entries, &tags, match_all))
+}
+
+/// 读取并规范化磁盘上的公式文件
+fn load_formula_file(path: &str) -> Result<Vec<FormulaEntry>, AppError> {
+    let content = read_text_file(Path::new(path), DEFAULT_MAX_READ_BYTES)?;
+    let value: Value = serde_json::from_str(&content)?;
+    Ok(normalize_formula_value(&value)?.entries)
+}
+
+/// 比较公式时使用的键：连续空白折叠为一个空格
+fn latex_key(latex: &str) -> String {
+    latex.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// 搜索范围
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum SearchField {
+    Latex,
+    Note,
+    #[default]
+    Both,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct SearchOptions {
+    case_sensitive: bool,
+    fields: SearchField,
+    /// 为 true 时把查询当作正则表达式，否则按字面匹配
+    regex: bool,
+}
+
+/// 单处匹配；`start`/`end` 是 UTF-16 偏移，与前端字符串下标一致
+#[derive(Serialize)]
+struct SearchMatch {
+    field: &'static str,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Serialize)]
+struct SearchHit {
+    /// 条目在规范化后列表中的位置
+    position: usize,
+    entry: FormulaEntry,
+    matches: Vec<SearchMatch>,
+}
+
+fn utf16_offset(text: &str, byte_offset: usize) -> usize {
+    text[..byte_offset].encode_utf16().count()
+}
+
+fn find_matches(pattern: &regex::Regex, field: &'static str, text: &str) -> Vec<SearchMatch> {
+    pattern
+        .find_iter(text)
+        .filter(|m| !m.is_empty())
+        .map(|m| SearchMatch {
+            field,
+            start: utf16_offset(text, m.start()),
+            end: utf16_offset(text, m.end()),
+        })
+        .collect()
+}
+
+/// 在公式文件中搜索 LaTeX 和/或备注，结果按文件顺序返回
+#[command]
+async fn search_formulas(
+    path: String,
+    query: String,
+    options: Option<SearchOptions>,
+) -> Result<Vec<SearchHit>, AppError> {
+    let options = options.unwrap_or_default();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let source = if options.regex {
+        query.clone()
+    } else {
+        regex::escape(&query)
+    };
+    let pattern = regex::RegexBuilder::new(&source)
+        .case_insensitive(!options.case_sensitive)
+        .build()
+        .map_err(|e| AppError::InvalidRegex {
+            pattern: query.clone(),
+            message: e.to_string(),
+        })?;
+
+    let entries = load_formula_file(&path)?;
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .filter_map(|(position, entry)| {
+            let mut matches = Vec::new();
+            if options.fields != SearchField::Note {
+                matches.extend(find_matches(&pattern, "latex", &entry.latex));
+            }
+            if options.fields != SearchField::Latex {
+                if let Some(note) = &entry.note {
+                    matches.extend(find_matches(&pattern, "note", note));
+                }
+            }
+            (!matches.is_empty()).then_some(SearchHit {
+                position,
+                entry,
+                matches,
+            })
+        })
+        .collect())
+}
+
+/// `sort_formulas` 的排序依据
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+enum SortKey {
+    Index,
+    Note,
+    LatexLength,
+    /// 取未知字段中的 `createdAt`，支持时间戳数字或 ISO 字符串
+    CreatedAt,
+}
+
+/// 备注比较：先按小写比较，再按原文区分大小写，保证中英文混排时顺序稳定一致
+fn compare_notes(a: &str, b: &str) -> std::cmp::Ordering {
+    a.to_lowercase().cmp(&b.to_lowercase()).then_with(|| a.cmp(b))
+}
+
+/// 可以比较的 `createdAt`
+enum CreatedAt<'a> {
+    Timestamp(f64),
+    Text(&'a str),
+}
+
+/// 取条目的 `createdAt`；缺少、为空或不是数字和字符串时返回 `None`，与缺少一样排在最后
+fn created_at(entry: &FormulaEntry) -> Option<CreatedAt<'_>> {
+    match entry.extra.get("createdAt")? {
+        Value::Number(n) => n.as_f64().map(CreatedAt::Timestamp),
+        Value::String(s) if !s.trim().is_empty() => Some(CreatedAt::Text(s)),
+        _ => None,
+    }
+}
+
+/// 同类之间按值比较，时间戳数字排在字符串之前，保证是全序
+fn compare_created_at(a: &CreatedAt, b: &CreatedAt) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+    match (a, b) {
+        (CreatedAt::Timestamp(x), CreatedAt::Timestamp(y)) => x.total_cmp(y),
+        (CreatedAt::Text(x), CreatedAt::Text(y)) => x.cmp(y),
+        (CreatedAt::Timestamp(_), CreatedAt::Text(_)) => Ordering::Less,
+        (CreatedAt::Text(_), CreatedAt::Timestamp(_)) => Ordering::Greater,
+    }
+}
+
+/// 对公式排序后把 `index` 重写为 1..n，`id` 保持不变
+///
+/// 排序是稳定的；缺少备注或 `createdAt` 的条目无论升降序都排在最后，无法比较的 `createdAt` 视同缺少。
+#[command]
+async fn sort_formulas(
+    content: String,
+    key: SortKey,
+    ascending: bool,
+) -> Result<Vec<FormulaEntry>, AppError> {
+    let value: Value = serde_json::from_str(&content)?;
+    let mut entries = normalize_formula_value(&value)?.entries;
+    let direction = |ordering: std::cmp::Ordering| {
+        if ascending {
+            ordering
+        } else {
+            ordering.reverse()
+        }
+    };
+    match key {
+        SortKey::Index => entries.sort_by(|a, b| direction(a.index.cmp(&b.index))),
+        SortKey::LatexLength => entries.sort_by(|a, b| {
+            direction(a.latex.chars().count().cmp(&b.latex.chars().count()))
+        }),
+        SortKey::Note => entries.sort_by(|a, b| match (&a.note, &b.note) {
+            (Some(x), Some(y)) => direction(compare_notes(x, y)),
+            (x, y) => x.is_none().cmp(&y.is_none()),
+        }),
+        SortKey::CreatedAt => entries.sort_by(|a, b| match (created_at(a), created_at(b)) {
+            (Some(x), Some(y)) => direction(compare_created_at(&x, &y)),
+            (x, y) => x.is_none().cmp(&y.is_none()),
+        }),
+    }
+    for (idx, entry) in entries.iter_mut().enumerate() {
+        entry.index = (idx + 1) as u32;
+    }
+    Ok(entries)
+}
+
+/// 合并两个公式集时对重复公式的处理方式
+#[derive(Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum MergeStrategy {
+    /// 重复的公式也全部保留
+    KeepBoth,
+    /// 丢弃第二个文件中的重复公式
+    SkipDuplicates,
+    /// 丢弃重复公式，但备注不同时采用第二个文件的备注
+    PreferBNote,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct MergeSummary {
+    /// 合并结果中的条目数
+    merged: usize,
+    /// 第二个文件中与第一个文件 LaTeX 相同的条目数
+    duplicates: usize,
+    /// 被丢弃的重复条目数
+    skipped: usize,
+    /// LaTeX 相同但备注不同的条目数
+    conflicting: usize,
+    /// 因与第一个文件冲突而改名的 id 数
+    renamed_ids: usize,
+}
+
+#[derive(Serialize)]
+struct MergeResult {
+    entries: Vec<FormulaEntry>,
+    summary: MergeSummary,
+}
+
+/// 合并两个公式文件：第二个文件的条目追加在后面，`index` 重写为 1..n
+///
+/// 只返回结果，不写盘，前端确认后再保存。
+#[command]
+async fn merge_formula_files(
+    path_a: String,
+    path_b: String,
+    strategy: MergeStrategy,
+) -> Result<MergeResult, AppError> {
+    let mut entries = load_formula_file(&path_a)?;
+    let incoming = load_formula_file(&path_b)?;
+    let mut summary = MergeSummary::default();
+
+    let mut positions: HashMap<String, usize> = HashMap::new();
+    for (idx, entry) in entries.iter().enumerate() {
+        positions.entry(latex_key(&entry.latex)).or_insert(idx);
+    }
+    let mut seen: HashSet<String> = entries.iter().map(|e| e.id.clone()).collect();
+    let reserved: HashSet<String> = incoming.iter().map(|e| e.id.clone()).collect();
+
+    for mut entry in incoming {
+        if let Some(&existing) = positions.get(&latex_key(&entry.latex)) {
+            summary.duplicates += 1;
+            let conflicting = entries[existing].note != entry.note;
+            if conflicting {
+                summary.conflicting += 1;
+            }
+            if strategy != MergeStrategy::KeepBoth {
+                summary.skipped += 1;
+                if conflicting && strategy == MergeStrategy::PreferBNote && entry.note.is_some() {
+                    entries[existing].note = entry.note;
+                }
+                continue;
+            }
+        }
+        if seen.contains(&entry.id) {
+            entry.id = unique_id(entry.id, &seen, &reserved);
+            summary.renamed_ids += 1;
+        }
+        seen.insert(entry.id.clone());
+        positions.entry(latex_key(&entry.latex)).or_insert(entries.len());
+        entries.push(entry);
+    }
+
+    for (idx, entry) in entries.iter_mut().enumerate() {
+        entry.index = (idx + 1) as u32;
+    }
+    summary.merged = entries.len();
+    Ok(MergeResult { entries, summary })
+}
+
+#[derive(Serialize)]
+struct ModifiedEntry {
+    before: FormulaEntry,
+    after: FormulaEntry,
+}
+
+#[derive(Serialize)]
+struct FormulaDiff {
+    added: Vec<FormulaEntry>,
+    removed: Vec<FormulaEntry>,
+    modified: Vec<ModifiedEntry>,
+}
+
+/// 比较两个公式文件：先按 `id` 配对，剩余条目再按折叠空白后的 LaTeX 配对
+///
+/// 仅顺序变化不算修改；`compare_index` 为 true 时 `index` 不同也计入修改。
+#[command]
+async fn diff_formula_files(
+    path_a: String,
+    path_b: String,
+    compare_index: Option<bool>,
+) -> Result<FormulaDiff, AppError> {
+    let before = load_formula_file(&path_a)?;
+    let after = load_formula_file(&path_b)?;
+    let compare_index = compare_index.unwrap_or(false);
+
+    let after_ids: HashMap<&str, usize> = after
+        .iter()
+        .enumerate()
+        .map(|(idx, e)| (e.id.as_str(), idx))
+        .collect();
+    let mut pair_of: Vec<Option<usize>> = before
+        .iter()
+        .map(|e| after_ids.get(e.id.as_str()).copied())
+        .collect();
+    let mut matched: Vec<bool> = vec![false; after.len()];
+    for idx in pair_of.iter().flatten() {
+        matched[*idx] = true;
+    }
+    for (idx, entry) in before.iter().enumerate() {
+        if pair_of[idx].is_some() {
+            continue;
+        }
+        let key = latex_key(&entry.latex);
+        if let Some(found) = (0..after.len()).find(|&j| !matched[j] && latex_key(&after[j].latex) == key) {
+            matched[found] = true;
+            pair_of[idx] = Some(found);
+        }
+    }
+
+    let changed = |a: &FormulaEntry, b: &FormulaEntry| {
+        latex_key(&a.latex) != latex_key(&b.latex)
+            || a.note != b.note
+            || a.tags != b.tags
+            || a.section != b.section
+            || (compare_index && a.index != b.index)
+    };
+    let mut after: Vec<Option<FormulaEntry>> = after.into_iter().map(Some).collect();
+    let mut removed = Vec::new();
+    let mut modified = Vec::new();
+    for (entry, pair) in before.into_iter().zip(pair_of) {
+        match pair {
+            Some(j) => {
+                let other = after[j].take().unwrap();
+                if changed(&entry, &other) {
+                    modified.push(ModifiedEntry {
+                        before: entry,
+                        after: other,
+                    });
+                }
+            }
+            None => removed.push(entry),
+        }
+    }
+    let added = after.into_iter().flatten().collect();
+    Ok(FormulaDiff {
+        added,
+        removed,
+        modified,
+    })
+}
+
+fn retain_tagged(formulas: Vec<FormulaEntry>, tags: &[String], match_all: bool) -> Vec<FormulaEntry> {
+    let wanted: Vec<&str> = tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()).collect();
+    if wanted.is_empty() {
+        return formulas;
+    }
+    formulas
+        .into_iter()
+        .filter(|item| {
+            let has = |tag: &&str| item.tags.iter().any(|t| t == tag);
+            if match_all {
+                wanted.iter().all(has)
+            } else {
+                wanted.iter().any(has)
+            }
+        })
+        .collect()
+}
+
+#[derive(Serialize)]
+struct TagCount {
+    tag: String,
+    count: usize,
+}
+
+/// 列出所有标签及其使用次数，按次数降序、名称升序排列
+#[command]
+async fn list_tags(formulas: Vec<FormulaEntry>) -> Result<Vec<TagCount>, AppError> {
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for item in &formulas {
+        for tag in &item.tags {
+            *counts.entry(tag.clone()).or_default() += 1;
+        }
+    }
+    let mut tags: Vec<TagCount> = counts
+        .into_iter()
+        .map(|(tag, count)| TagCount { tag, count })
+        .collect();
+    tags.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.tag.cmp(&b.tag)));
+    Ok(tags)
+}
+
+/// 检查单条公式的语法问题，不需要 TeX 引擎
+#[command]
+async fn validate_latex(latex: String) -> Result<Vec<validate::Diagnostic>, AppError> {
+    Ok(validate::validate(&latex))
+}
+
+#[derive(Serialize)]
+struct FormulaDiagnostics {
+    /// 公式在列表中的位置，从 0 开始
+    position: usize,
+    id: String,
+    diagnostics: Vec<validate::Diagnostic>,
+}
+
+/// 批量检查公式集，只返回有问题的公式
+#[command]
+async fn validate_formula_set(
+    formulas: Vec<FormulaEntry>,
+) -> Result<Vec<FormulaDiagnostics>, AppError> {
+    Ok(formulas
+        .into_iter()
+        .enumerate()
+        .filter_map(|(position, item)| {
+            let diagnostics = validate::validate(&item.latex);
+            (!diagnostics.is_empty()).then_some(FormulaDiagnostics {
+                position,
+                id: item.id,
+                diagnostics,
+            })
+        })
+        .collect())
+}
+
+/// 公式集检查规则，序列化后的名称作为稳定的规则 id
+#[derive(Serialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+enum LintRule {
+    EmptyLatex,
+    DuplicateFormula,
+    DuplicateNote,
+    RepeatedIndex,
+    NonSequentialIndex,
+    NeedsPackage,
+}
+
+#[derive(Serialize)]
+struct LintFinding {
+    rule: LintRule,
+    /// 涉及的公式在列表中的位置，从 0 开始
+    positions: Vec<usize>,
+    message: String,
+}
+
+/// 需要额外宏包的命令；默认的 LaTeX 导出只引入 amsmath
+const PACKAGE_COMMANDS: &[(&str, &str)] = &[
+    ("SI", "siunitx"),
+    ("si", "siunitx"),
+    ("num", "siunitx"),
+    ("qty", "siunitx"),
+    ("unit", "siunitx"),
+    ("mathscr", "mathrsfs"),
+    ("coloneqq", "mathtools"),
+    ("eqqcolon", "mathtools"),
+    ("Coloneqq", "mathtools"),
+    ("mathbb", "amssymb"),
+    ("mathfrak", "amssymb"),
+    ("varnothing", "amssymb"),
+    ("cancel", "cancel"),
+    ("bcancel", "cancel"),
+    ("ce", "mhchem"),
+];
+
+/// 公式中出现的命令名（不含反斜杠）
+fn command_names(latex: &str) -> impl Iterator<Item = &str> {
+    latex.split('\\').skip(1).filter_map(|rest| {
+        let end = rest
+            .find(|ch: char| !ch.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        (end > 0).then(|| &rest[..end])
+    })
+}
+
+/// 把位置列表按键分组，只保留出现多次的组，按首次出现排序
+fn repeated_groups<K: std::hash::Hash + Eq>(keys: impl Iterator<Item = (usize, K)>) -> Vec<Vec<usize>> {
+    let mut groups: Vec<Vec<usize>> = Vec::new();
+    let mut lookup: HashMap<K, usize> = HashMap::new();
+    for (position, key) in keys {
+        match lookup.get(&key) {
+            Some(&group) => groups[group].push(position),
+            None => {
+                lookup.insert(key, groups.len());
+                groups.push(vec![position]);
+            }
+        }
+    }
+    groups.retain(|group| group.len() > 1);
+    groups
+}
+
+fn format_positions(positions: &[usize]) -> String {
+    positions
+        .iter()
+        .map(|p| format!("第 {} 条", p + 1))
+        .collect::<Vec<_>>()
+        .join("、")
+}
+
+/// 检查公式集层面的问题：空公式、重复公式与备注、序号异常以及需要额外宏包的命令
+#[command]
+async fn lint_formula_set(formulas: Vec<FormulaEntry>) -> Result<Vec<LintFinding>, AppError> {
+    let mut findings = Vec::new();
+
+    let empty: Vec<usize> = formulas
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.latex.trim().is_empty())
+        .map(|(position, _)| position)
+        .collect();
+    if !empty.is_empty() {
+        findings.push(LintFinding {
+            rule: LintRule::EmptyLatex,
+            message: format!("{}的公式为空", format_positions(&empty)),
+            positions: empty,
+        });
+    }
+
+    let keyed = formulas
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| !item.latex.trim().is_empty())
+        .map(|(position, item)| (position, latex_key(&item.latex)));
+    for positions in repeated_groups(keyed) {
+        let first = &formulas[positions[0]].latex;
+        let exact = positions.iter().all(|&p| formulas[p].latex == *first);
+        let kind = if exact { "完全相同" } else { "仅空白不同" };
+        findings.push(LintFinding {
+            rule: LintRule::DuplicateFormula,
+            message: format!("{}的公式{}", format_positions(&positions), kind),
+            positions,
+        });
+    }
+
+    let notes = formulas.iter().enumerate().filter_map(|(position, item)| {
+        let note = item.note.as_deref().map(str::trim).filter(|n| !n.is_empty())?;
+        Some((position, note))
+    });
+    for positions in repeated_groups(notes) {
+        findings.push(LintFinding {
+            rule: LintRule::DuplicateNote,
+            message: format!("{}的备注相同", format_positions(&positions)),
+            positions,
+        });
+    }
+
+    let indices = formulas.iter().enumerate().map(|(position, item)| (position, item.index));
+    for positions in repeated_groups(indices) {
+        findings.push(LintFinding {
+            rule: LintRule::RepeatedIndex,
+            message: format!(
+                "{}的序号都是 {}",
+                format_positions(&positions),
+                formulas[positions[0]].index
+            ),
+            positions,
+        });
+    }
+    let out_of_order: Vec<usize> = formulas
+        .iter()
+        .enumerate()
+        .filter(|(position, item)| item.index as usize != position + 1)
+        .map(|(position, _)| position)
+        .collect();
+    if !out_of_order.is_empty() {
+        findings.push(LintFinding {
+            rule: LintRule::NonSequentialIndex,
+            message: format!("{}的序号与位置不一致", format_positions(&out_of_order)),
+            positions: out_of_order,
+        });
+    }
+
+    let mut packages: Vec<(&str, Vec<&str>, Vec<usize>)> = Vec::new();
+    for (position, item) in formulas.iter().enumerate() {
+        for name in command_names(&item.latex) {
+            let Some(&(command, package)) = PACKAGE_COMMANDS.iter().find(|(c, _)| *c == name) else {
+                continue;
+            };
+            let index = match packages.iter().position(|(p, _, _)| *p == package) {
+                Some(index) => index,
+                None => {
+                    packages.push((package, Vec::new(), Vec::new()));
+                    packages.len() - 1
+                }
+            };
+            let (_, commands, positions) = &mut packages[index];
+            if !commands.contains(&command) {
+                commands.push(command);
+            }
+            if positions.last() != Some(&position) {
+                positions.push(position);
+            }
+        }
+    }
+    for (package, commands, positions) in packages {
+        let commands: Vec<String> = commands.iter().map(|c| format!("\\{}", c)).collect();
+        findings.push(LintFinding {
+            rule: LintRule::NeedsPackage,
+            message: format!(
+                "{}使用了 {}，导出时需要引入 {} 宏包",
+                format_positions(&positions),
+                commands.join("、"),
+                package
+            ),
+            positions,
+        });
+    }
+
+    Ok(findings)
+}
+
+/// 自动修复：去掉空公式并把序号重排为 1..n
+#[command]
+async fn autofix_formula_set(formulas: Vec<FormulaEntry>) -> Result<Vec<FormulaEntry>, AppError> {
+    Ok(formulas
+        .into_iter()
+        .filter(|item| !item.latex.trim().is_empty())
+        .enumerate()
+        .map(|(position, mut item)| {
+            item.index = position as u32 + 1;
+            item
+        })
+        .collect())
+}
+
+/// 合并 id 相同的分类：保留首次出现的位置和名称，模板按 id 去重后追加
+fn merge_duplicate_categories(categories: Vec<TemplateCategory>) -> Vec<TemplateCategory> {
+    let mut merged: Vec<TemplateCategory> = Vec::new();
+    for category in categories {
+        match merged.iter_mut().find(|c| c.id == category.id) {
+            Some(existing) => {
+                for template in category.templates {
+                    if !existing.templates.iter().any(|t| t.id == template.id) {
+                        existing.templates.push(template);
+                    }
+                }
+                if existing.parent_id.is_none() {
+                    existing.parent_id = category.parent_id;
+                }
+            }
+            None => merged.push(category),
+        }
+    }
+    merged
+}
+
+/// 父分类引用被清除的原因
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum ParentRepairReason {
+    /// 父分类链形成环
+    Cycle,
+    /// 父分类 id 在文件中不存在
+    MissingParent,
+}
+
+/// 被移到顶层的分类
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ParentRepair {
+    category_id: String,
+    category_name: String,
+    /// 被清除的原 `parentId`
+    parent_id: String,
+    reason: ParentRepairReason,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct NormalizedTemplates {
+    #[serde(flatten)]
+    library: TemplateLibrary,
+    /// 读入时文件的格式版本；低于 `library.version` 说明已在内存中升级，保存后才会写回磁盘
+    source_version: u64,
+    repairs: Vec<ParentRepair>,
+    warnings: Vec<String>,
+    dedupe: DedupeReport,
+}
+
+/// 清除指向不存在分类的 `parent_id`，并在父分类链成环时断开环上最后一条边，
+/// 保证前端按父子关系建树时一定能终止
+fn repair_parent_refs(categories: &mut [TemplateCategory]) -> Vec<ParentRepair> {
+    let mut repairs = Vec::new();
+    let mut clear = |category: &mut TemplateCategory, reason| {
+        if let Some(parent_id) = category.parent_id.take() {
+            repairs.push(ParentRepair {
+                category_id: category.id.clone(),
+                category_name: category.name.clone(),
+                parent_id,
+                reason,
+            });
+        }
+    };
+
+    let positions: HashMap<String, usize> = categories
+        .iter()
+        .enumerate()
+        .map(|(idx, c)| (c.id.clone(), idx))
+        .collect();
+    for category in categories.iter_mut() {
+        if category
+            .parent_id
+            .as_ref()
+            .is_some_and(|parent| !positions.contains_key(parent))
+        {
+            clear(category, ParentRepairReason::MissingParent);
+        }
+    }
+
+    // 0 未访问，1 在当前路径上，2 已确认不在环上
+    let mut state = vec![0u8; categories.len()];
+    for start in 0..categories.len() {
+        let mut path = Vec::new();
+        let mut current = Some(start);
+        while let Some(idx) = current {
+            match state[idx] {
+                2 => break,
+                1 => {
+                    // 回到当前路径上的分类：路径末尾的父引用闭合了环
+                    let last = *path.last().unwrap_or(&idx);
+                    clear(&mut categories[last], ParentRepairReason::Cycle);
+                    break;
+                }
+                _ => {
+                    state[idx] = 1;
+                    path.push(idx);
+                    current = categories[idx]
+                        .parent_id
+                        .as_ref()
+                        .and_then(|parent| positions.get(parent).copied());
+                }
+            }
+        }
+        for idx in path {
+            state[idx] = 2;
+        }
+    }
+    repairs
+}
+
+/// 默认保留的分类嵌套层数
+const DEFAULT_TEMPLATE_DEPTH: usize = 6;
+/// 嵌套层数上限，调用方传入更大的值时按此截断
+const MAX_TEMPLATE_DEPTH: usize = 32;
+
+fn template_category(
+    cat: &Value,
+    idx: usize,
+    parent_id: Option<String>,
+    depth: usize,
+) -> TemplateCategory {
+    let name = trimmed_string(cat.get("name")).unwrap_or_else(|| format!("分类 {}", idx + 1));
+    let id = trimmed_string(cat.get("id")).unwrap_or_else(|| format!("category-{}-{}", depth, idx + 1));
+    let templates_value = cat.get("templates").and_then(|v| v.as_array()).cloned().unwrap_or_default();
+    let mut templates = Vec::new();
+    for (tidx, tpl) in templates_value.iter().enumerate() {
+        let latex = trimmed_string(tpl.get("latex"));
+        if latex.is_none() {
+            continue;
+        }
+        let name = trimmed_string(tpl.get("name")).unwrap_or_else(|| format!("模板 {}", tidx + 1));
+        let tpl_id = trimmed_string(tpl.get("id"))
+            .unwrap_or_else(|| format!("template-{}-{}", id, tidx + 1));
+        let note = trimmed_string(tpl.get("note"));
+        templates.push(TemplateItem {
+            id: tpl_id,
+            name,
+            latex: latex.unwrap(),
+            note,
+            extra: extra_fields(tpl, &["id", "name", "latex", "note"]),
+        });
+    }
+    let parent_from_json = trimmed_string(cat.get("parentId"));
+    TemplateCategory {
+        id,
+        name,
+        templates,
+        parent_id: parent_from_json.or(parent_id),
+        extra: extra_fields(
+            cat,
+            &["id", "name", "templates", "parentId", "categories", "children"],
+        ),
+    }
+}
+
+/// 按先序展开嵌套的分类树。超过 `max_depth` 层的分类不再单独保留，
+/// 其模板并入最近一个保留的祖先，返回的第二项是这些分类的名称。
+/// 使用显式栈，嵌套再深也不会耗尽调用栈
+fn walk_categories(value: &Value, max_depth: usize) -> (Vec<TemplateCategory>, Vec<String>) {
+    struct Pending<'a> {
+        value: &'a Value,
+        idx: usize,
+        /// 最近一个保留的祖先在结果中的位置
+        parent: Option<usize>,
+        depth: usize,
+    }
+
+    fn push_children<'a>(
+        stack: &mut Vec<Pending<'a>>,
+        value: Option<&'a Value>,
+        parent: Option<usize>,
+        depth: usize,
+    ) {
+        let Some(array) = value.and_then(|v| v.as_array()) else {
+            return;
+        };
+        for (idx, value) in array.iter().enumerate().rev() {
+            stack.push(Pending {
+                value,
+                idx,
+                parent,
+                depth,
+            });
+        }
+    }
+
+    let mut categories: Vec<TemplateCategory> = Vec::new();
+    let mut flattened = Vec::new();
+    let mut stack = Vec::new();
+    push_children(&mut stack, Some(value), None, 1);
+    while let Some(item) = stack.pop() {
+        let cat = item.value;
+        let children = cat.get("categories").or_else(|| cat.get("children"));
+        let parent_id = item.parent.map(|p| categories[p].id.clone());
+        let category = template_category(cat, item.idx, parent_id, item.depth);
+        match item.parent {
+            Some(parent) if item.depth > max_depth => {
+                flattened.push(category.name);
+                categories[parent].templates.extend(category.templates);
+                push_children(&mut stack, children, Some(parent), item.depth + 1);
+            }
+            _ => {
+                categories.push(category);
+                let index = categories.len() - 1;
+                push_children(&mut stack, children, Some(index), item.depth + 1);
+            }
+        }
+    }
+    (categories, flattened)
+}
+
+/// 模板去重的范围
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum DedupeScope {
+    /// 只去掉同一分类内的重复模板，跨分类的重复只计数
+    #[default]
+    Category,
+    /// 整个模板库中只保留首次出现的模板
+    Library,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CategoryDedupe {
+    category_id: String,
+    category_name: String,
+    removed: usize,
+}
+
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct DedupeReport {
+    /// 每个分类中被去掉的模板数，只列出有删除的分类
+    removed: Vec<CategoryDedupe>,
+    /// 与其他分类中的模板重复的数量；`Library` 范围下这些模板已被去掉
+    cross_category: usize,
+}
+
+/// 按空白规范化后的 LaTeX 去重，保留首次出现的位置；
+/// 先出现的模板没有备注而重复项有备注时，用有备注的那一项替换
+fn dedupe_templates(categories: &mut [TemplateCategory], scope: DedupeScope) -> DedupeReport {
+    let mut report = DedupeReport::default();
+    let mut seen_elsewhere: HashSet<String> = HashSet::new();
+    for category in categories.iter_mut() {
+        let before = category.templates.len();
+        let mut kept: Vec<TemplateItem> = Vec::new();
+        let mut keys: HashMap<String, usize> = HashMap::new();
+        for template in std::mem::take(&mut category.templates) {
+            let key = latex_key(&template.latex);
+            if let Some(&index) = keys.get(&key) {
+                let has_note = |t: &TemplateItem| t.note.as_deref().is_some_and(|n| !n.is_empty());
+                if !has_note(&kept[index]) && has_note(&template) {
+                    kept[index] = template;
+                }
+                continue;
+            }
+            if seen_elsewhere.contains(&key) {
+                report.cross_category += 1;
+                if scope == DedupeScope::Library {
+                    continue;
+                }
+            }
+            keys.insert(key, kept.len());
+            kept.push(template);
+        }
+        seen_elsewhere.extend(keys.into_keys());
+        category.templates = kept;
+        let removed = before - category.templates.len();
+        if removed > 0 {
+            report.removed.push(CategoryDedupe {
+                category_id: category.id.clone(),
+                category_name: category.name.clone(),
+                removed,
+            });
+        }
+    }
+    report
 }
 
-/// 获取应用配置目录
+/// 规范化模板库；无效的父分类引用会被清除，对应分类移到顶层并记录在 `repairs` 中。
+/// `max_depth` 默认 6 层，超出的分类并入上级并在 `warnings` 中列出；
+/// `dedupe` 默认开启，去掉同一分类内的重复模板
 #[command]
-async fn get_app_config_dir(app: tauri::AppHandle) -> Result<String, String> {
-    app.path_resolver()
-        .app_config_dir()
-        .map(|p| p.to_string_lossy().to_string())
-        .ok_or_else(|| "Failed to get config directory".to_string())
+async fn normalize_templates(
+    content: String,
+    sort_by_name: Option<bool>,
+    max_depth: Option<usize>,
+    dedupe: Option<bool>,
+) -> Result<NormalizedTemplates, AppError> {
+    let dedupe = dedupe.unwrap_or(true).then_some(DedupeScope::Category);
+    normalize_template_content(&content, sort_by_name.unwrap_or(false), max_depth, dedupe)
 }
 
-/// 检查文件是否存在
+/// 对已加载的模板库按需去重
 #[command]
-async fn file_exists(path: String) -> Result<bool, String> {
-    Ok(PathBuf::from(path).exists())
+async fn dedupe_template_library(
+    content: String,
+    scope: Option<DedupeScope>,
+) -> Result<NormalizedTemplates, AppError> {
+    normalize_template_content(&content, false, None, Some(scope.unwrap_or_default()))
 }
 
-/// 设置窗口标题
-#[command]
-async fn set_window_title(window: Window, title: String) -> Result<(), String> {
-    window
-        .set_title(&title)
-        .map_err(|e| format!("Failed to set title: {}", e))
+/// 合并模板库时同名分类的处理方式
+#[derive(Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum CategoryMerge {
+    /// 同一层级的同名分类合并为一个
+    #[default]
+    Merge,
+    /// 保留两个分类，后者名称追加 ` (2)` 等后缀
+    Rename,
 }
 
-/// 主题设置（存储到本地）
-#[command]
-async fn set_theme_preference(theme: String) -> Result<(), String> {
-    // 可以将主题保存到配置文件
-    println!("Theme changed to: {}", theme);
-    Ok(())
+#[derive(Serialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct TemplateMergeSummary {
+    /// 与第一个模板库中的分类合并的分类数
+    merged_categories: usize,
+    /// 因同名而追加后缀的分类数
+    renamed_categories: usize,
+    /// 因 LaTeX 重复而未加入的模板数
+    deduped_templates: usize,
+    /// 因 id 冲突而重新生成 id 的分类和模板数
+    regenerated_ids: usize,
 }
 
-/// 导出LaTeX文件
-#[command]
-async fn export_latex_file(content: String) -> Result<String, String> {
-    use tauri::api::dialog::FileDialogBuilder;
+#[derive(Serialize)]
+struct TemplateMerge {
+    library: TemplateLibrary,
+    summary: TemplateMergeSummary,
+}
 
-    let (tx, rx) = oneshot::channel();
-    FileDialogBuilder::new()
-        .add_filter("LaTeX Files", &["tex"])
-        .set_file_name("formulas.tex")
-        .save_file(move |file_path| {
-            let _ = tx.send(file_path);
+/// `walk_categories` 为缺少 id 的分类生成的 id 形如 `category-2-3`，不能用来跨文件匹配
+fn is_generated_category_id(id: &str) -> bool {
+    id.strip_prefix("category-")
+        .and_then(|rest| rest.split_once('-'))
+        .is_some_and(|(depth, idx)| {
+            !depth.is_empty()
+                && !idx.is_empty()
+                && depth.bytes().all(|b| b.is_ascii_digit())
+                && idx.bytes().all(|b| b.is_ascii_digit())
+        })
+}
+
+/// 把 `b` 的分类树并入 `a`：稳定 id 相同或同一层级同名的分类视为同一分类，
+/// 模板按 LaTeX 去重后追加，冲突的 id 按 `unique_id` 的规则重新生成
+fn merge_template_trees(
+    a: TemplateLibrary,
+    b: TemplateLibrary,
+    strategy: CategoryMerge,
+) -> TemplateMerge {
+    let mut summary = TemplateMergeSummary::default();
+    let mut categories = a.categories;
+    let mut category_ids: HashSet<String> = categories.iter().map(|c| c.id.clone()).collect();
+    let mut template_ids: HashSet<String> = categories
+        .iter()
+        .flat_map(|c| c.templates.iter().map(|t| t.id.clone()))
+        .collect();
+    let reserved = HashSet::new();
+    // b 中分类 id 到合并结果中分类位置的映射
+    let mut mapped: HashMap<String, usize> = HashMap::new();
+
+    // 父分类先于子分类处理；父分类不在 b 中的按顶层处理
+    let mut pending: Vec<TemplateCategory> = b.categories;
+    let b_ids: HashSet<String> = pending.iter().map(|c| c.id.clone()).collect();
+    while !pending.is_empty() {
+        let (ready, rest): (Vec<_>, Vec<_>) = pending.into_iter().partition(|c| {
+            !c.parent_id
+                .as_ref()
+                .is_some_and(|p| b_ids.contains(p) && !mapped.contains_key(p))
         });
+        // 剩余的只可能是环，修复后的模板库中不会出现；保险起见按顶层处理
+        let (ready, rest) = if ready.is_empty() {
+            (rest, Vec::new())
+        } else {
+            (ready, rest)
+        };
+        pending = rest;
+        for mut category in ready {
+            let parent = category
+                .parent_id
+                .as_ref()
+                .and_then(|p| mapped.get(p))
+                .map(|&idx| categories[idx].id.clone());
+            let by_id = (!is_generated_category_id(&category.id))
+                .then(|| categories.iter().position(|c| c.id == category.id))
+                .flatten();
+            let target = by_id.or_else(|| {
+                let same_name = categories
+                    .iter()
+                    .position(|c| c.parent_id == parent && c.name == category.name);
+                if strategy == CategoryMerge::Rename && same_name.is_some() {
+                    let taken = |name: &str| {
+                        categories
+                            .iter()
+                            .any(|c| c.parent_id == parent && c.name == name)
+                    };
+                    let base = category.name.clone();
+                    category.name = (2..)
+                        .map(|n| format!("{} ({})", base, n))
+                        .find(|name| !taken(name))
+                        .unwrap();
+                    summary.renamed_categories += 1;
+                    None
+                } else {
+                    same_name
+                }
+            });
 
-    let file_path = match rx.await {
-        Ok(path) => path,
-        Err(_) => None,
-    };
-    if let Some(path) = file_path {
-        fs::write(&path, content)
-            .map_err(|e| format!("Failed to write LaTeX file: {}", e))?;
-        Ok(path.to_string_lossy().to_string())
-    } else {
-        Ok(String::new())
+            let original_id = category.id.clone();
+            let templates = std::mem::take(&mut category.templates);
+            let index = match target {
+                Some(index) => {
+                    summary.merged_categories += 1;
+                    index
+                }
+                None => {
+                    if category_ids.contains(&category.id) {
+                        category.id = unique_id(category.id, &category_ids, &reserved);
+                        summary.regenerated_ids += 1;
+                    }
+                    category_ids.insert(category.id.clone());
+                    category.parent_id = parent;
+                    categories.push(category);
+                    categories.len() - 1
+                }
+            };
+            mapped.insert(original_id, index);
+
+            let target = &mut categories[index];
+            let mut keys: HashSet<String> =
+                target.templates.iter().map(|t| latex_key(&t.latex)).collect();
+            for mut template in templates {
+                if !keys.insert(latex_key(&template.latex)) {
+                    summary.deduped_templates += 1;
+                    continue;
+                }
+                if template_ids.contains(&template.id) {
+                    template.id = unique_id(template.id, &template_ids, &reserved);
+                    summary.regenerated_ids += 1;
+                }
+                template_ids.insert(template.id.clone());
+                target.templates.push(template);
+            }
+        }
+    }
+
+    TemplateMerge {
+        library: TemplateLibrary {
+            version: TEMPLATE_LIBRARY_VERSION,
+            categories,
+            selected_category_id: a.selected_category_id,
+        },
+        summary,
     }
 }
 
-/// 导出Markdown文件
+/// 合并两个模板库文件；结果不写入磁盘，由前端沿用现有的保存流程
 #[command]
-async fn export_markdown_file(content: String) -> Result<String, String> {
-    use tauri::api::dialog::FileDialogBuilder;
+async fn merge_template_libraries(
+    path_a: String,
+    path_b: String,
+    strategy: Option<CategoryMerge>,
+) -> Result<TemplateMerge, AppError> {
+    let load = |path: &str| -> Result<TemplateLibrary, AppError> {
+        let content = read_text_file(Path::new(path), DEFAULT_MAX_READ_BYTES)?;
+        Ok(normalize_template_content(&content, false, None, Some(DedupeScope::Category))?.library)
+    };
+    let a = load(&path_a)?;
+    let b = load(&path_b)?;
+    Ok(merge_template_trees(a, b, strategy.unwrap_or_default()))
+}
 
-    let (tx, rx) = oneshot::channel();
-    FileDialogBuilder::new()
-        .add_filter("Markdown Files", &["md"])
-        .set_file_name("formulas.md")
-        .save_file(move |file_path| {
-            let _ = tx.send(file_path);
+/// 取出分类及其所有子孙分类，所选分类成为新的根；
+/// 模板库先经过规范化，无效的父引用和环已按导入时的规则修复
+fn template_subtree(content: &str, category_id: &str) -> Result<(TemplateLibrary, String), AppError> {
+    let library = normalize_template_content(content, false, None, None)?.library;
+    let Some(root) = library.categories.iter().find(|c| c.id == category_id) else {
+        return Err(AppError::CategoryNotFound {
+            id: category_id.to_string(),
         });
-
-    let file_path = match rx.await {
-        Ok(path) => path,
-        Err(_) => None,
     };
-    if let Some(path) = file_path {
-        fs::write(&path, content)
-            .map_err(|e| format!("Failed to write Markdown file: {}", e))?;
-        Ok(path.to_string_lossy().to_string())
-    } else {
-        Ok(String::new())
+    let root_name = root.name.clone();
+    let mut included: HashSet<String> = HashSet::from([category_id.to_string()]);
+    // 子分类可能排在父分类之前，反复扫描直到不再有新分类加入
+    loop {
+        let before = included.len();
+        for category in &library.categories {
+            if category.parent_id.as_ref().is_some_and(|p| included.contains(p)) {
+                included.insert(category.id.clone());
+            }
+        }
+        if included.len() == before {
+            break;
+        }
     }
+    let categories = library
+        .categories
+        .into_iter()
+        .filter(|c| included.contains(&c.id))
+        .map(|mut c| {
+            if c.id == category_id {
+                c.parent_id = None;
+            }
+            c
+        })
+        .collect();
+    Ok((
+        TemplateLibrary {
+            version: TEMPLATE_LIBRARY_VERSION,
+            categories,
+            selected_category_id: category_id.to_string(),
+        },
+        root_name,
+    ))
 }
 
-#[derive(Deserialize)]
-struct FormulaItem {
-    latex: String,
-    note: Option<String>,
+/// 把一个分类及其子分类导出为独立的模板库 JSON，可直接由 `normalize_templates` 导入
+#[command]
+async fn export_template_subtree(
+    library_content: String,
+    category_id: String,
+) -> Result<String, AppError> {
+    let (library, _) = template_subtree(&library_content, &category_id)?;
+    serde_json::to_string_pretty(&library).map_err(AppError::encode_json)
 }
 
-#[derive(Serialize, Deserialize)]
-struct FormulaEntry {
-    id: String,
-    index: u32,
-    latex: String,
-    note: Option<String>,
+/// 导出分类子树并弹出保存对话框，默认文件名为 `templates-分类名.json`
+#[command]
+async fn save_template_subtree(
+    library_content: String,
+    category_id: String,
+) -> Result<FileOutcome, AppError> {
+    let (library, name) = template_subtree(&library_content, &category_id)?;
+    let content = serde_json::to_string_pretty(&library).map_err(AppError::encode_json)?;
+    let slug = slugify(&name);
+    let file_name = if slug.is_empty() {
+        "templates.json".to_string()
+    } else {
+        format!("templates-{}.json", slug)
+    };
+    save_export("JSON Files", &["json"], &file_name, content.as_bytes(), WriteOptions::default()).await
 }
 
-fn escape_latex_text(text: &str) -> String {
-    text.chars()
-        .map(|ch| match ch {
-            '\\' | '#' | '%' | '&' | '_' | '$' | '^' | '{' | '}' => format!("\\{}", ch),
-            _ => ch.to_string(),
+/// 把公式集包装成只有一个分类的模板库：备注作为模板名称，同时保留在 `note` 中；
+/// 标签保存在模板的 `tags` 字段，转换回公式集时还原
+#[command]
+async fn formulas_to_template_library(
+    formulas: Vec<FormulaEntry>,
+    category_name: String,
+) -> Result<TemplateLibrary, AppError> {
+    let name = category_name.trim();
+    let name = if name.is_empty() { "公式集" } else { name };
+    let slug = label_slug(name);
+    let category_id = if slug.is_empty() {
+        "category-formulas".to_string()
+    } else {
+        format!("category-{}", slug)
+    };
+    let mut seen = HashSet::new();
+    let reserved = HashSet::new();
+    let templates = formulas
+        .into_iter()
+        .enumerate()
+        .map(|(idx, entry)| {
+            let base = if entry.id.trim().is_empty() {
+                format!("template-{}", idx + 1)
+            } else {
+                entry.id
+            };
+            let id = unique_id(base, &seen, &reserved);
+            seen.insert(id.clone());
+            let note = entry.note.filter(|n| !n.trim().is_empty());
+            let mut extra = entry.extra;
+            if !entry.tags.is_empty() {
+                extra.insert("tags".to_string(), Value::from(entry.tags));
+            }
+            TemplateItem {
+                id,
+                name: note
+                    .as_deref()
+                    .map(|n| n.trim().to_string())
+                    .unwrap_or_else(|| format!("模板 {}", idx + 1)),
+                latex: entry.latex,
+                note,
+                extra,
+            }
         })
-        .collect::<Vec<_>>()
-        .join("")
+        .collect();
+    Ok(TemplateLibrary {
+        version: TEMPLATE_LIBRARY_VERSION,
+        categories: vec![TemplateCategory {
+            id: category_id.clone(),
+            name: name.to_string(),
+            templates,
+            parent_id: None,
+            extra: Map::new(),
+        }],
+        selected_category_id: category_id,
+    })
 }
 
+/// 把整个模板库或 `category_id` 所在的子树展开为公式集，序号从 1 重新编排，
+/// 分类名写入 `section`，重复的 id 重新生成
 #[command]
-async fn format_latex(formulas: Vec<FormulaItem>) -> Result<String, String> {
-    if formulas.is_empty() {
-        return Ok(String::new());
+async fn template_library_to_formulas(
+    library_content: String,
+    category_id: Option<String>,
+) -> Result<Vec<FormulaEntry>, AppError> {
+    let library = match category_id {
+        Some(id) => template_subtree(&library_content, &id)?.0,
+        None => normalize_template_content(&library_content, false, None, None)?.library,
+    };
+    let mut seen = HashSet::new();
+    let reserved = HashSet::new();
+    let mut entries = Vec::new();
+    for category in library.categories {
+        for mut template in category.templates {
+            let id = unique_id(template.id, &seen, &reserved);
+            seen.insert(id.clone());
+            let tags = normalize_tags(template.extra.remove("tags").as_ref());
+            entries.push(FormulaEntry {
+                id,
+                index: entries.len() as u32 + 1,
+                latex: template.latex,
+                note: template.note.or(Some(template.name)),
+                tags,
+                section: Some(category.name.clone()),
+                extra: template.extra,
+            });
+        }
     }
-    let body = formulas
-        .iter()
-        .enumerate()
-        .map(|(idx, item)| {
-            let note_block = item
-                .note
-                .as_ref()
-                .map(|note| note.trim())
-                .filter(|note| !note.is_empty())
-                .map(|note| format!("\\noindent\\textbf{{{}}}\\\\\n", escape_latex_text(note)))
-                .unwrap_or_default();
-            format!(
-                "{}\\begin{{equation}}\\label{{eq:{}}}\n{}\n\\end{{equation}}",
-                note_block,
-                idx + 1,
-                item.latex
-            )
-        })
-        .collect::<Vec<_>>()
-        .join("\n");
+    Ok(entries)
+}
 
-    let document = format!(
-        "\\documentclass{{article}}\n\\usepackage{{amsmath}}\n\\usepackage{{ctex}}\n\\begin{{document}}\n{}\n\\end{{document}}\n",
-        body
-    );
+/// 模板搜索默认返回的结果数
+const DEFAULT_TEMPLATE_HITS: usize = 50;
 
-    Ok(document)
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TemplateHit {
+    category_id: String,
+    /// 从根分类到所在分类的名称，如 `高数 / 积分`
+    category_path: String,
+    template: TemplateItem,
+    /// 得分最高的字段：`name`、`note` 或 `latex`
+    field: &'static str,
+    score: u32,
 }
 
-#[command]
-async fn format_markdown(formulas: Vec<FormulaItem>) -> Result<String, String> {
-    if formulas.is_empty() {
-        return Ok(String::new());
-    }
-    let segments = formulas
-        .iter()
-        .enumerate()
-        .map(|(idx, item)| {
-            let mut parts = vec![format!("### 公式 {}", idx + 1)];
-            if let Some(note) = item.note.as_ref().map(|n| n.trim()).filter(|n| !n.is_empty()) {
-                parts.push(format!("**{}**", note));
-            }
-            parts.push("$$".to_string());
-            parts.push(item.latex.clone());
-            parts.push("$$".to_string());
-            parts.join("\n\n")
-        })
-        .collect::<Vec<_>>()
-        .join("\n\n");
+fn fold_case(text: &str) -> Vec<char> {
+    text.chars().flat_map(char::to_lowercase).collect()
+}
 
-    Ok(segments)
+/// 连续子串匹配得分最高，越靠前越好；否则按子序列匹配，间隔越大得分越低。
+/// 大小写折叠按 Unicode 规则进行，汉字等无大小写的字符原样比较
+fn fuzzy_score(query: &[char], text: &str) -> Option<u32> {
+    if query.is_empty() {
+        return None;
+    }
+    let text = fold_case(text);
+    if let Some(start) = text.windows(query.len()).position(|w| w == query) {
+        let prefix = if start == 0 { 200 } else { 0 };
+        return Some(1000 + prefix - start.min(200) as u32);
+    }
+    let mut gaps = 0usize;
+    let mut next = 0usize;
+    let mut last: Option<usize> = None;
+    for ch in query {
+        let found = text[next..].iter().position(|c| c == ch)? + next;
+        if let Some(last) = last {
+            gaps += found - last - 1;
+        }
+        last = Some(found);
+        next = found + 1;
+    }
+    Some(500u32.saturating_sub(gaps.min(499) as u32))
 }
 
-#[derive(Serialize, Deserialize)]
-struct TemplateItem {
-    id: String,
-    name: String,
-    latex: String,
-    note: Option<String>,
+fn category_path(categories: &[TemplateCategory], index: usize) -> String {
+    let mut names = vec![categories[index].name.as_str()];
+    let mut current = &categories[index];
+    // 规范化后不会有环，计数只是防御
+    for _ in 0..categories.len() {
+        let Some(parent) = current
+            .parent_id
+            .as_ref()
+            .and_then(|p| categories.iter().find(|c| &c.id == p))
+        else {
+            break;
+        };
+        names.push(&parent.name);
+        current = parent;
+    }
+    names.reverse();
+    names.join(" / ")
 }
 
-#[derive(Serialize, Deserialize)]
-struct TemplateCategory {
-    id: String,
-    name: String,
-    templates: Vec<TemplateItem>,
-    #[serde(rename = "parentId")]
-    parent_id: Option<String>,
+/// 在模板库中模糊搜索名称、备注和 LaTeX。名称匹配的权重最高，
+/// 同分时按模板在库中的顺序排列，保证输入过程中结果顺序稳定
+#[command]
+async fn search_templates(
+    library_content: String,
+    query: String,
+    limit: Option<usize>,
+) -> Result<Vec<TemplateHit>, AppError> {
+    let query: Vec<char> = fold_case(&query)
+        .into_iter()
+        .filter(|c| !c.is_whitespace())
+        .collect();
+    if query.is_empty() {
+        return Ok(Vec::new());
+    }
+    let library = normalize_template_content(&library_content, false, None, None)?.library;
+    let categories = library.categories;
+
+    let mut scored = Vec::new();
+    for (cidx, category) in categories.iter().enumerate() {
+        for (tidx, template) in category.templates.iter().enumerate() {
+            let fields = [
+                ("name", Some(template.name.as_str()), 3),
+                ("note", template.note.as_deref(), 2),
+                ("latex", Some(template.latex.as_str()), 1),
+            ];
+            let best = fields
+                .iter()
+                .filter_map(|&(field, text, weight)| {
+                    fuzzy_score(&query, text?).map(|score| (score * weight, field))
+                })
+                .max_by_key(|&(score, _)| score);
+            if let Some((score, field)) = best {
+                scored.push((score, cidx, tidx, field));
+            }
+        }
+    }
+    scored.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)).then(a.2.cmp(&b.2)));
+    scored.truncate(limit.unwrap_or(DEFAULT_TEMPLATE_HITS));
+
+    let mut paths: HashMap<usize, String> = HashMap::new();
+    Ok(scored
+        .into_iter()
+        .map(|(score, cidx, tidx, field)| TemplateHit {
+            category_id: categories[cidx].id.clone(),
+            category_path: paths
+                .entry(cidx)
+                .or_insert_with(|| category_path(&categories, cidx))
+                .clone(),
+            template: categories[cidx].templates[tidx].clone(),
+            field,
+            score,
+        })
+        .collect())
 }
 
-#[derive(Serialize, Deserialize)]
-struct TemplateLibrary {
-    categories: Vec<TemplateCategory>,
-    #[serde(rename = "selectedCategoryId")]
-    selected_category_id: String,
+/// 读取模板库文件的格式版本；没有版本号的旧文件视为版本 1，
+/// 高于当前支持的版本时报错，避免按旧规则解析后写回时丢失新字段
+fn template_file_version(value: &Value) -> Result<u64, AppError> {
+    let version = match value.get("version") {
+        None => legacy_template_version(),
+        Some(v) => v.as_u64().filter(|&v| v >= 1).ok_or_else(|| {
+            AppError::InvalidOption {
+                name: "version".to_string(),
+                value: v.to_string(),
+            }
+        })?,
+    };
+    if version > TEMPLATE_LIBRARY_VERSION {
+        return Err(AppError::UnsupportedVersion {
+            found: version,
+            supported: TEMPLATE_LIBRARY_VERSION,
+        });
+    }
+    Ok(version)
 }
 
-fn trimmed_string(value: Option<&Value>) -> Option<String> {
-    value
-        .and_then(|v| v.as_str())
-        .map(|s| s.trim().to_string())
-        .filter(|s| !s.is_empty())
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct TemplateMigration {
+    library: TemplateLibrary,
+    from_version: u64,
+    to_version: u64,
+    /// 升级时做的改动，供前端在保存前向用户说明
+    changes: Vec<String>,
 }
 
+/// 把模板库升级到当前格式版本。结果不写入磁盘，前端征得用户同意后再保存，
+/// 这样磁盘上的文件只会被升级一次
 #[command]
-async fn normalize_formulas(content: String) -> Result<Vec<FormulaEntry>, String> {
-    let value: Value = serde_json::from_str(&content)
-        .map_err(|_| "文件内容不是有效的 JSON 格式".to_string())?;
-    if !value.is_array() {
-        if value.get("categories").is_some() {
-            return Err("这是模板库文件，请使用“绑定模板”功能导入".to_string());
-        }
-        return Err("文件格式错误：公式集必须是 JSON 数组".to_string());
+async fn migrate_template_library(content: String) -> Result<TemplateMigration, AppError> {
+    let value: Value = serde_json::from_str(&content)?;
+    let normalized = normalize_template_content(&content, false, None, None)?;
+    let from_version = normalized.source_version;
+
+    let mut changes = Vec::new();
+    if from_version < TEMPLATE_LIBRARY_VERSION {
+        changes.push(format!(
+            "格式版本从 {} 升级到 {}",
+            from_version, TEMPLATE_LIBRARY_VERSION
+        ));
     }
-    let array = value.as_array().unwrap();
-    let mut normalized = Vec::new();
-    for (idx, item) in array.iter().enumerate() {
-        let latex = trimmed_string(item.get("latex"));
-        if latex.is_none() {
-            continue;
-        }
-        let id = trimmed_string(item.get("id")).unwrap_or_else(|| format!("formula-{}", idx + 1));
-        let index = item.get("index").and_then(|v| v.as_u64()).unwrap_or((idx + 1) as u64) as u32;
-        let note = trimmed_string(item.get("note"));
-        normalized.push(FormulaEntry {
-            id,
-            index,
-            latex: latex.unwrap(),
-            note,
+    if value.is_array() {
+        changes.push("顶层分类数组改为带版本号的对象".to_string());
+    }
+    let nested = value
+        .get("categories")
+        .unwrap_or(&value)
+        .as_array()
+        .is_some_and(|cats| {
+            cats.iter()
+                .any(|c| c.get("categories").or_else(|| c.get("children")).is_some())
         });
+    if nested {
+        changes.push("嵌套的子分类改为以 parentId 表示层级".to_string());
+    }
+    if !normalized.repairs.is_empty() {
+        changes.push(format!(
+            "修复了 {} 个分类的无效上级引用",
+            normalized.repairs.len()
+        ));
     }
-    Ok(normalized)
+    changes.extend(normalized.warnings);
+
+    Ok(TemplateMigration {
+        library: normalized.library,
+        from_version,
+        to_version: TEMPLATE_LIBRARY_VERSION,
+        changes,
+    })
 }
 
-#[command]
-async fn normalize_templates(content: String) -> Result<TemplateLibrary, String> {
-    let value: Value = serde_json::from_str(&content)
-        .map_err(|_| "文件内容不是有效的 JSON 格式".to_string())?;
+fn normalize_template_content(
+    content: &str,
+    sort_by_name: bool,
+    max_depth: Option<usize>,
+    dedupe: Option<DedupeScope>,
+) -> Result<NormalizedTemplates, AppError> {
+    let value: Value = serde_json::from_str(content)?;
+    let source_version = template_file_version(&value)?;
+    let requested_selection = trimmed_string(value.get("selectedCategoryId"));
     let categories_value = if let Some(categories) = value.get("categories") {
         categories.clone()
     } else {
         value
     };
 
-    fn walk_categories(
-        value: &Value,
-        parent_id: Option<String>,
-        depth: usize,
-        acc: &mut Vec<TemplateCategory>,
-    ) {
-        if depth > 6 {
-            return;
-        }
-        let array = match value.as_array() {
-            Some(arr) => arr,
-            None => return,
-        };
-        for (idx, cat) in array.iter().enumerate() {
-            let name = trimmed_string(cat.get("name")).unwrap_or_else(|| format!("分类 {}", idx + 1));
-            let id = trimmed_string(cat.get("id")).unwrap_or_else(|| format!("category-{}-{}", depth, idx + 1));
-            let templates_value = cat.get("templates").and_then(|v| v.as_array()).cloned().unwrap_or_default();
-            let mut templates = Vec::new();
-            for (tidx, tpl) in templates_value.iter().enumerate() {
-                let latex = trimmed_string(tpl.get("latex"));
-                if latex.is_none() {
-                    continue;
-                }
-                let name = trimmed_string(tpl.get("name")).unwrap_or_else(|| format!("模板 {}", tidx + 1));
-                let tpl_id = trimmed_string(tpl.get("id"))
-                    .unwrap_or_else(|| format!("template-{}-{}", id, tidx + 1));
-                let note = trimmed_string(tpl.get("note"));
-                templates.push(TemplateItem {
-                    id: tpl_id,
-                    name,
-                    latex: latex.unwrap(),
-                    note,
-                });
-            }
-            let parent_from_json = trimmed_string(cat.get("parentId"));
-            acc.push(TemplateCategory {
-                id: id.clone(),
-                name,
-                templates,
-                parent_id: parent_from_json.or_else(|| parent_id.clone()),
-            });
+    let max_depth = max_depth
+        .unwrap_or(DEFAULT_TEMPLATE_DEPTH)
+        .clamp(1, MAX_TEMPLATE_DEPTH);
+    let (categories, flattened) = walk_categories(&categories_value, max_depth);
+    let mut warnings = Vec::new();
+    if !flattened.is_empty() {
+        warnings.push(format!(
+            "以下分类超过 {} 层嵌套，模板已并入上级分类：{}",
+            max_depth,
+            flattened.join("、")
+        ));
+    }
+    let mut categories = merge_duplicate_categories(categories);
+    let repairs = repair_parent_refs(&mut categories);
+    let dedupe = dedupe
+        .map(|scope| dedupe_templates(&mut categories, scope))
+        .unwrap_or_default();
 
-            let child = cat.get("categories").or_else(|| cat.get("children"));
-            if let Some(child_value) = child {
-                walk_categories(child_value, Some(id), depth + 1, acc);
-            }
+    // 先在原始顺序下确定选中分类，排序不应改变选中项
+    let selected_category_id = requested_selection
+        .filter(|id| categories.iter().any(|c| &c.id == id))
+        .or_else(|| categories.first().map(|c| c.id.clone()))
+        .unwrap_or_default();
+    if sort_by_name {
+        categories.sort_by(|a, b| {
+            a.name
+                .to_lowercase()
+                .cmp(&b.name.to_lowercase())
+                .then_with(|| a.name.cmp(&b.name))
+        });
+    }
+    Ok(NormalizedTemplates {
+        library: TemplateLibrary {
+            version: TEMPLATE_LIBRARY_VERSION,
+            categories,
+            selected_category_id,
+        },
+        source_version,
+        repairs,
+        warnings,
+        dedupe,
+    })
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+enum LogLevel {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+impl From<LogLevel> for log::Level {
+    fn from(level: LogLevel) -> Self {
+        match level {
+            LogLevel::Error => log::Level::Error,
+            LogLevel::Warn => log::Level::Warn,
+            LogLevel::Info => log::Level::Info,
+            LogLevel::Debug => log::Level::Debug,
         }
     }
+}
 
-    let mut categories = Vec::new();
-    walk_categories(&categories_value, None, 1, &mut categories);
+/// 单条前端日志的长度上限（字符数），超出部分被截断
+const MAX_LOG_MESSAGE_CHARS: usize = 8 * 1024;
 
-    let selected_category_id = categories.first().map(|c| c.id.clone()).unwrap_or_default();
-    Ok(TemplateLibrary { categories, selected_category_id })
+/// 把前端的日志写入后端日志文件，`level` 默认为 `info`
+#[command]
+async fn log_message(level: Option<LogLevel>, message: String) -> Result<(), AppError> {
+    let level = level.map_or(log::Level::Info, log::Level::from);
+    let message: String = match message.char_indices().nth(MAX_LOG_MESSAGE_CHARS) {
+        Some((end, _)) => format!("{}…", &message[..end]),
+        None => message,
+    };
+    log::log!(target: logging::FRONTEND_TARGET, level, "{}", message);
+    Ok(())
 }
 
-/// 获取系统信息
+/// 当前日志文件的路径，便于界面提供“打开日志文件夹”
 #[command]
-async fn get_system_info() -> Result<String, String> {
-    Ok(format!(
-        "OS: {}, Arch: {}",
-        std::env::consts::OS,
-        std::env::consts::ARCH
-    ))
+async fn get_log_file_path() -> Result<String, AppError> {
+    logging::file_path()
+        .map(|path| path.to_string_lossy().into_owned())
+        .ok_or(AppError::LocationUnavailable(Location::LogFile))
+}
+
+/// 获取系统信息，用于问题反馈；无法确定的项为 `null`
+#[command]
+async fn get_system_info(app: tauri::AppHandle) -> Result<SystemInfo, AppError> {
+    Ok(tokio::task::spawn_blocking(move || system_info::collect(&app)).await?)
+}
+
+/// 以可直接粘贴到问题报告中的文本形式返回系统信息
+#[command]
+async fn get_system_info_text(app: tauri::AppHandle) -> Result<String, AppError> {
+    let info = get_system_info(app).await?;
+    Ok(info.to_text(APP_NAME))
 }
 
 fn main() {
+    logging::install();
+    let context = tauri::generate_context!();
+    // 路径可能不是有效的 Unicode，`std::env::args` 遇到这种参数会直接 panic
+    let args: Vec<String> = std::env::args_os()
+        .skip(1)
+        .map(|arg| arg.to_string_lossy().into_owned())
+        .collect();
+    if let Some(code) = cli::run(&args, context.package_info()) {
+        std::process::exit(code);
+    }
+    // 默认只运行一个实例，再次启动时把参数转交给已运行的实例
+    let instance = match tauri::api::path::app_config_dir(context.config()) {
+        Some(dir) if !single_instance::allows_multiple(&dir) => match single_instance::acquire(&dir) {
+            Ok(Acquired::Primary(instance)) => Some(instance),
+            Ok(Acquired::Forwarded) => return,
+            Err(e) => {
+                log::error!("Single instance check failed: {}", e);
+                None
+            }
+        },
+        _ => None,
+    };
     tauri::Builder::default()
+        .manage(CloseGuard::default())
+        .manage(Autosave::default())
+        .manage(FileWatcher::default())
+        .manage(SystemClipboard::default())
+        .manage(EditorWindows::default())
+        .manage(PinnedWindows::default())
+        .manage(Tray::default())
+        .manage(RecentMenu::default())
+        .manage(GlobalShortcut::default())
+        .manage(DeepLinks::default())
+        .manage(JobRegistry::default())
+        .manage(StartupFile::from_args())
+        .menu(menu::build(APP_NAME))
+        .on_menu_event(menu::handle_event)
+        .on_window_event(|event| match event.event() {
+            WindowEvent::CloseRequested { api, .. } => {
+                if let Some(state) = event.window().try_state::<WindowState>() {
+                    state.save_now(event.window());
+                }
+                let window = event.window();
+                if !window.state::<CloseGuard>().is_allowed(window.label())
+                    && tray::should_hide_on_close(window)
+                {
+                    api.prevent_close();
+                    if let Err(e) = window.hide() {
+                        log::error!("Failed to hide window: {}", e);
+                    }
+                    return;
+                }
+                handle_close_requested(window, api);
+            }
+            WindowEvent::ThemeChanged(os_theme) => {
+                let window = event.window();
+                let follows_system = window
+                    .try_state::<SettingsStore>()
+                    .is_some_and(|store| theme_preference(&store) == "system");
+                if follows_system {
+                    if let Err(e) = window.emit("theme:os-changed", os_theme.to_string()) {
+                        log::warn!("Failed to emit theme event: {}", e);
+                    }
+                }
+            }
+            WindowEvent::Moved(_) | WindowEvent::Resized(_) => {
+                if let Some(state) = event.window().try_state::<WindowState>() {
+                    state.schedule_save(event.window());
+                }
+            }
+            WindowEvent::FileDrop(FileDropEvent::Dropped(paths)) => {
+                file_drop::handle(event.window(), paths);
+            }
+            WindowEvent::Destroyed => {
+                let label = event.window().label();
+                event.window().state::<Autosave>().disable(&event.window().app_handle(), label);
+                event.window().state::<FileWatcher>().unwatch(label);
+                event.window().state::<EditorWindows>().release(event.window().label());
+                event.window().state::<PinnedWindows>().0.lock().unwrap().remove(event.window().label());
+            }
+            _ => {}
+        })
         .invoke_handler(tauri::generate_handler![
             read_json_file,
+            read_json_file_head,
+            compute_file_checksum,
             write_json_file,
+            list_backups,
+            restore_backup,
+            enable_autosave,
+            disable_autosave,
+            queue_autosave_content,
+            watch_file,
+            unwatch_file,
+            write_recovery_snapshot,
+            list_recovery_snapshots,
+            recover_snapshot,
+            discard_snapshot,
             open_file_dialog,
+            pick_files,
+            pick_directory,
             save_file_dialog,
+            save_copy_dialog,
+            add_recent_file,
+            get_recent_files,
+            clear_recent_files,
+            refresh_recent_menu,
+            set_tray_enabled,
+            set_minimize_to_tray,
+            register_global_shortcut,
+            unregister_global_shortcut,
+            get_global_shortcut,
+            set_zoom_preference,
+            get_zoom_preference,
             get_app_config_dir,
             file_exists,
             set_window_title,
+            open_editor_window,
+            set_window_file,
+            set_size_constraints,
+            reset_window_state,
+            frontend_ready,
+            take_startup_file,
+            register_file_association,
+            unregister_file_association,
+            register_url_scheme,
+            set_always_on_top,
+            get_always_on_top,
+            force_close,
+            cancel_close,
             set_theme_preference,
+            get_theme_preference,
+            get_effective_theme,
+            reset_settings,
+            get_setting,
+            set_setting,
+            get_all_settings,
             export_latex_file,
+            reveal_in_file_manager,
+            open_path_with_default_app,
+            export_latex_directory,
             export_markdown_file,
+            export_html_file,
+            export_mathml_file,
+            export_pdf_file,
+            export_docx_file,
+            export_typst_file,
+            export_org_file,
+            export_rst_file,
+            export_csv_file,
+            export_anki_file,
+            export_bundle_zip,
+            export_formula_images,
+            cancel_job,
+            copy_text_to_clipboard,
+            copy_export_to_clipboard,
             format_latex,
             format_markdown,
+            format_html,
+            print_formulas,
+            format_html_fragment,
+            format_mathml,
+            latex_to_mathml,
+            render_formula_svg,
+            export_formula_svg_file,
+            render_formula_png,
+            export_formula_png_file,
+            copy_formula_image_to_clipboard,
+            format_typst,
+            format_org,
+            format_rst,
+            format_csv,
             normalize_formulas,
+            serialize_formulas,
+            formulas_to_yaml,
+            yaml_to_formulas,
+            reimport_exported_latex,
+            import_tex_file,
+            import_markdown_file,
+            import_docx_file,
+            import_from_clipboard,
+            import_csv_file,
+            mathml_to_latex,
+            import_mathml_file,
+            find_formulas_using_command,
+            filter_by_tags,
+            filter_formulas,
+            search_formulas,
+            sort_formulas,
+            merge_formula_files,
+            diff_formula_files,
+            list_tags,
+            validate_latex,
+            validate_formula_set,
+            lint_formula_set,
+            autofix_formula_set,
             normalize_templates,
+            dedupe_template_library,
+            migrate_template_library,
+            merge_template_libraries,
+            export_template_subtree,
+            save_template_subtree,
+            search_templates,
+            formulas_to_template_library,
+            template_library_to_formulas,
             get_system_info,
+            get_system_info_text,
+            set_backend_locale,
+            log_message,
+            get_log_file_path,
         ])
-        .setup(|_app| {
+        .on_page_load(|window, _| {
+            // 启动时发现的遗留快照要等前端加载完成后再通知，否则事件会丢失
+            if let Some(snapshots) = window.state::<Recovery>().take_startup_snapshots() {
+                if let Err(e) = window.emit("recovery:available", snapshots) {
+                    log::warn!("Failed to emit recovery event: {}", e);
+                }
+            }
+        })
+        .setup(move |app| {
             // 初始化应用
-            println!("MathLive Formula Editor - Rust Backend Started");
+            if let Some(data_dir) = app.path_resolver().app_data_dir() {
+                logging::init(&data_dir.join("logs"));
+            }
+            log::info!("{} {} started", APP_NAME, app.package_info().version);
+            let config_dir = app
+                .path_resolver()
+                .app_config_dir()
+                .ok_or("Failed to get config directory")?;
+            let store = SettingsStore::open(config_dir);
+            locale::set(locale::preferred(&store));
+            let saved_constraints = store
+                .get(SIZE_CONSTRAINTS_KEY)
+                .and_then(|value| serde_json::from_value::<SizeConstraints>(value).ok())
+                .and_then(|constraints| constraints.validated().ok());
+            if let Some(constraints) = saved_constraints {
+                for window in app.windows().values() {
+                    if let Err(e) = constraints.apply(window) {
+                        log::error!("Failed to restore size constraints: {}", e);
+                    }
+                }
+            }
+            let data_dir = app
+                .path_resolver()
+                .app_data_dir()
+                .ok_or("Failed to get data directory")?;
+            let retention_days = store
+                .get(RECOVERY_RETENTION_KEY)
+                .and_then(|value| value.as_u64())
+                .unwrap_or(recovery::DEFAULT_RETENTION_DAYS);
+            app.manage(Recovery::open(data_dir.join("recovery"), retention_days));
+            let recent = RecentFiles::new(store.config_file(settings::RECENT_FILE));
+            if let Err(e) = menu::refresh_recent(&app.handle(), &recent.list().unwrap_or_default()) {
+                log::error!("Failed to refresh recent menu: {}", e);
+            }
+            app.manage(recent);
+            if tray::enabled_in(&store) {
+                if let Err(e) = tray::enable(&app.handle()) {
+                    log::error!("Failed to create tray: {}", e);
+                }
+            }
+            if let Some(accelerator) = shortcut::saved(&store) {
+                if let Err(e) = app.state::<GlobalShortcut>().register(&app.handle(), &accelerator) {
+                    log::error!("Failed to register global shortcut: {}", e);
+                }
+            }
+            // 主窗口在配置中默认隐藏，恢复位置后再显示，避免先在默认位置闪现
+            let window_state = WindowState::new(store.config_file(settings::WINDOW_STATE_FILE));
+            if let Some(window) = app.get_window(window_state::MAIN_WINDOW) {
+                if let Err(e) = window_state.restore(&window) {
+                    log::error!("Failed to restore window state: {}", e);
+                }
+                if let Err(e) = zoom::apply(&window, zoom::saved(&store)) {
+                    log::error!("Failed to apply zoom: {}", e);
+                }
+                if let Some(native) = theme::native_theme(&theme_preference(&store)) {
+                    if let Err(e) = theme::apply(&window, Some(native)) {
+                        log::error!("Failed to apply window theme: {}", e);
+                    }
+                }
+                if store.get(ALWAYS_ON_TOP_KEY).and_then(|v| v.as_bool()) == Some(true) {
+                    match window.set_always_on_top(true) {
+                        Ok(()) => {
+                            app.state::<PinnedWindows>().0.lock().unwrap().insert(window.label().to_string());
+                        }
+                        Err(e) => log::error!("Failed to restore always on top: {}", e),
+                    }
+                }
+                window.show()?;
+            }
+            app.manage(window_state);
+            app.manage(store);
+            if let Some(instance) = instance {
+                app.manage(instance);
+            }
+            let handle = app.handle();
+            std::thread::spawn(move || {
+                deep_link::register_on_first_run(&handle.state::<SettingsStore>());
+            });
+            if let Some(link) = deep_link::find_in_args(args) {
+                app.state::<DeepLinks>().handle(&app.handle(), &link);
+            }
             Ok(())
         })
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(context)
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            if let tauri::RunEvent::Exit = event {
+                if let Some(instance) = app.try_state::<Instance>() {
+                    instance.release();
+                }
+            }
+        });
 }
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,1263 @@
+//! LaTeX 数学公式的解析
+//!
+//! 只覆盖公式集中常见的子集：希腊字母、运算符、上下标、分式、根式、重音、
+//! `\left...\right` 定界符以及矩阵类环境。遇到不认识的命令时返回错误，
+//! 由调用方决定是否退回原始 LaTeX。
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::locale::{self, localized};
+
+/// 解析失败的说明，按当前的后端语言选择中文或英文
+macro_rules! msg {
+    ($zh:literal, $en:literal $(, $arg:expr)* $(,)?) => {
+        localized!(locale::current(), zh: $zh, en: $en $(, $arg)*)
+    };
+}
+
+/// 公式语法树节点
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    /// 标识符；多字母时是函数名（如 `sin`），以正体显示
+    Ident(String),
+    Number(String),
+    Operator(String),
+    /// 大型运算符（求和、积分等），上下标位置与普通符号不同
+    LargeOp(String),
+    Text(String),
+    Space(&'static str),
+    Row(Vec<Node>),
+    Frac {
+        num: Box<Node>,
+        den: Box<Node>,
+        /// `\binom` 等无分数线的形式
+        bar: bool,
+    },
+    Sqrt {
+        body: Box<Node>,
+        index: Option<Box<Node>>,
+    },
+    Scripts {
+        base: Box<Node>,
+        sub: Option<Box<Node>>,
+        sup: Option<Box<Node>>,
+    },
+    Accent {
+        body: Box<Node>,
+        accent: Accent,
+    },
+    Fenced {
+        open: String,
+        close: String,
+        body: Box<Node>,
+    },
+    Matrix {
+        rows: Vec<Vec<Node>>,
+        open: String,
+        close: String,
+        /// `aligned` 等对齐环境，单元格之间不留间距
+        aligned: bool,
+    },
+    Styled {
+        variant: Variant,
+        body: Box<Node>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Accent {
+    Hat,
+    Bar,
+    Overline,
+    Underline,
+    Vec,
+    Dot,
+    Ddot,
+    Tilde,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Variant {
+    Normal,
+    Bold,
+    Italic,
+    DoubleStruck,
+    Script,
+    Fraktur,
+    SansSerif,
+    Monospace,
+}
+
+const GREEK: &[(&str, &str)] = &[
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ϵ"),
+    ("varepsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("vartheta", "ϑ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("varpi", "ϖ"),
+    ("rho", "ρ"),
+    ("varrho", "ϱ"),
+    ("sigma", "σ"),
+    ("varsigma", "ς"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "ϕ"),
+    ("varphi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Xi", "Ξ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Upsilon", "Υ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+    ("hbar", "ℏ"),
+    ("ell", "ℓ"),
+    ("Re", "ℜ"),
+    ("Im", "ℑ"),
+    ("aleph", "ℵ"),
+    ("infty", "∞"),
+    ("partial", "∂"),
+    ("nabla", "∇"),
+    ("emptyset", "∅"),
+    ("varnothing", "∅"),
+];
+
+const OPERATORS: &[(&str, &str)] = &[
+    ("times", "×"),
+    ("cdot", "⋅"),
+    ("pm", "±"),
+    ("mp", "∓"),
+    ("div", "÷"),
+    ("ast", "∗"),
+    ("star", "⋆"),
+    ("circ", "∘"),
+    ("bullet", "∙"),
+    ("oplus", "⊕"),
+    ("otimes", "⊗"),
+    ("leq", "≤"),
+    ("le", "≤"),
+    ("geq", "≥"),
+    ("ge", "≥"),
+    ("neq", "≠"),
+    ("ne", "≠"),
+    ("ll", "≪"),
+    ("gg", "≫"),
+    ("approx", "≈"),
+    ("equiv", "≡"),
+    ("sim", "∼"),
+    ("simeq", "≃"),
+    ("cong", "≅"),
+    ("propto", "∝"),
+    ("to", "→"),
+    ("rightarrow", "→"),
+    ("leftarrow", "←"),
+    ("Rightarrow", "⇒"),
+    ("Leftarrow", "⇐"),
+    ("leftrightarrow", "↔"),
+    ("Leftrightarrow", "⇔"),
+    ("implies", "⟹"),
+    ("iff", "⟺"),
+    ("mapsto", "↦"),
+    ("in", "∈"),
+    ("notin", "∉"),
+    ("ni", "∋"),
+    ("subset", "⊂"),
+    ("subseteq", "⊆"),
+    ("supset", "⊃"),
+    ("supseteq", "⊇"),
+    ("cup", "∪"),
+    ("cap", "∩"),
+    ("setminus", "∖"),
+    ("forall", "∀"),
+    ("exists", "∃"),
+    ("neg", "¬"),
+    ("lnot", "¬"),
+    ("land", "∧"),
+    ("wedge", "∧"),
+    ("lor", "∨"),
+    ("vee", "∨"),
+    ("perp", "⊥"),
+    ("parallel", "∥"),
+    ("mid", "∣"),
+    ("angle", "∠"),
+    ("triangle", "△"),
+    ("prime", "′"),
+    ("cdots", "⋯"),
+    ("ldots", "…"),
+    ("dots", "…"),
+    ("vdots", "⋮"),
+    ("ddots", "⋱"),
+    ("langle", "⟨"),
+    ("rangle", "⟩"),
+    ("lfloor", "⌊"),
+    ("rfloor", "⌋"),
+    ("lceil", "⌈"),
+    ("rceil", "⌉"),
+    ("lbrace", "{"),
+    ("rbrace", "}"),
+    ("vert", "|"),
+    ("Vert", "‖"),
+    ("colon", ":"),
+];
+
+const LARGE_OPERATORS: &[(&str, &str)] = &[
+    ("sum", "∑"),
+    ("prod", "∏"),
+    ("coprod", "∐"),
+    ("int", "∫"),
+    ("iint", "∬"),
+    ("iiint", "∭"),
+    ("oint", "∮"),
+    ("bigcup", "⋃"),
+    ("bigcap", "⋂"),
+    ("bigoplus", "⨁"),
+    ("bigotimes", "⨂"),
+];
+
+const FUNCTIONS: &[&str] = &[
+    "sin", "cos", "tan", "cot", "sec", "csc", "arcsin", "arccos", "arctan", "sinh", "cosh", "tanh",
+    "coth", "log", "ln", "lg", "exp", "lim", "limsup", "liminf", "max", "min", "sup", "inf", "det",
+    "gcd", "deg", "dim", "ker", "arg", "hom", "Pr",
+];
+
+const SPACES: &[(&str, &str)] = &[
+    (",", "0.167em"),
+    (":", "0.222em"),
+    (";", "0.278em"),
+    ("!", "-0.167em"),
+    (" ", "0.333em"),
+    ("quad", "1em"),
+    ("qquad", "2em"),
+];
+
+/// 不影响 MathML 输出、直接忽略的命令；值为需要跳过的花括号参数个数
+const IGNORED: &[(&str, usize)] = &[
+    ("displaystyle", 0),
+    ("textstyle", 0),
+    ("scriptstyle", 0),
+    ("limits", 0),
+    ("nolimits", 0),
+    ("nonumber", 0),
+    ("notag", 0),
+    ("label", 1),
+    ("tag", 1),
+];
+
+/// 是否是有预定义排版的函数名（`\sin`、`\lim` 等）
+pub fn is_function(name: &str) -> bool {
+    FUNCTIONS.contains(&name)
+}
+
+/// 是否是求和、积分等大型运算符
+pub fn is_large_operator(symbol: &str) -> bool {
+    LARGE_OPERATORS.iter().any(|(_, v)| *v == symbol)
+}
+
+fn lookup(table: &[(&str, &'static str)], name: &str) -> Option<&'static str> {
+    table.iter().find(|(k, _)| *k == name).map(|(_, v)| *v)
+}
+
+/// 解析失败的原因及出错位置
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub message: String,
+    /// 出错处在原始公式中的偏移（UTF-16 单位，与前端字符串下标一致）
+    pub position: usize,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&msg!(
+            "{}（位置 {}）",
+            "{} (at position {})",
+            self.message,
+            self.position
+        ))
+    }
+}
+
+/// 解析 LaTeX 公式
+pub fn parse(latex: &str) -> Result<Node, ParseError> {
+    let mut parser = Parser {
+        chars: Cursor {
+            chars: latex.chars().peekable(),
+            position: 0,
+        },
+        command_start: 0,
+    };
+    let mut nodes = Vec::new();
+    loop {
+        let (mut part, stop) = parser.parse_sequence(false)?;
+        nodes.append(&mut part);
+        match stop {
+            Stop::End => return Ok(Node::Row(nodes)),
+            // 环境之外的 `&` 和 `\\` 不影响显示，直接忽略
+            Stop::Ampersand | Stop::NewRow => {}
+            Stop::CloseBrace | Stop::CloseBracket => {
+                return Err(parser.error(msg!("多余的右花括号", "unmatched closing brace")))
+            }
+            Stop::Right(_) => {
+                return Err(parser.error(msg!(
+                    "\\right 缺少对应的 \\left",
+                    "\\right without matching \\left"
+                )))
+            }
+            Stop::EndEnv(name) => {
+                return Err(parser.error(msg!(
+                    "\\end{{{}}} 缺少对应的 \\begin",
+                    "\\end{{{}}} without matching \\begin",
+                    name
+                )))
+            }
+        }
+    }
+}
+
+/// 记录已读取位置的字符流
+struct Cursor<'a> {
+    chars: Peekable<Chars<'a>>,
+    position: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn peek(&mut self) -> Option<&char> {
+        self.chars.peek()
+    }
+}
+
+impl<'a> Iterator for Cursor<'a> {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        self.position += c.len_utf16();
+        Some(c)
+    }
+}
+
+struct Parser<'a> {
+    chars: Cursor<'a>,
+    /// 最近读取的命令的反斜杠位置，用于报告不支持的命令
+    command_start: usize,
+}
+
+/// 解析到达的结束标记
+#[derive(Debug, PartialEq)]
+enum Stop {
+    End,
+    CloseBrace,
+    CloseBracket,
+    Right(String),
+    EndEnv(String),
+    Ampersand,
+    NewRow,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: impl Into<String>) -> ParseError {
+        self.error_at(message, self.chars.position)
+    }
+
+    fn error_at(&self, message: impl Into<String>, position: usize) -> ParseError {
+        ParseError {
+            message: message.into(),
+            position,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.peek().is_some_and(|c| c.is_whitespace()) {
+            self.chars.next();
+        }
+    }
+
+    /// 读取 `\` 之后的命令名：一串字母，或单个非字母字符
+    fn read_command(&mut self) -> Result<String, ParseError> {
+        self.command_start = self.chars.position.saturating_sub(1);
+        let mut name = String::new();
+        while let Some(&c) = self.chars.peek() {
+            if c.is_ascii_alphabetic() {
+                name.push(c);
+                self.chars.next();
+            } else {
+                break;
+            }
+        }
+        if name.is_empty() {
+            match self.chars.next() {
+                Some(c) => name.push(c),
+                None => {
+                    return Err(self.error(msg!(
+                        "公式以单独的反斜杠结尾",
+                        "formula ends with a lone backslash"
+                    )))
+                }
+            }
+        }
+        Ok(name)
+    }
+
+    /// 读取 `{...}` 中的原始文本，用于 `\text` 和环境名
+    fn read_raw_group(&mut self) -> Result<String, ParseError> {
+        self.skip_whitespace();
+        if self.chars.next() != Some('{') {
+            return Err(self.error(msg!("缺少左花括号", "missing opening brace")));
+        }
+        let mut depth = 0;
+        let mut text = String::new();
+        for c in self.chars.by_ref() {
+            match c {
+                '{' => depth += 1,
+                '}' if depth == 0 => return Ok(text),
+                '}' => depth -= 1,
+                _ => {}
+            }
+            text.push(c);
+        }
+        Err(self.error(msg!("花括号不匹配", "unbalanced braces")))
+    }
+
+    /// 读取单个参数：花括号组或单个记号
+    fn parse_argument(&mut self) -> Result<Node, ParseError> {
+        self.skip_whitespace();
+        match self.chars.peek() {
+            Some('{') => {
+                self.chars.next();
+                let (nodes, stop) = self.parse_sequence(false)?;
+                if stop != Stop::CloseBrace {
+                    return Err(self.error(msg!("花括号不匹配", "unbalanced braces")));
+                }
+                Ok(single(nodes))
+            }
+            Some(_) => match self.parse_atom()? {
+                Some(node) => Ok(node),
+                None => Err(self.error(msg!("缺少参数", "missing argument"))),
+            },
+            None => Err(self.error(msg!("缺少参数", "missing argument"))),
+        }
+    }
+
+    /// 读取可选的 `[...]` 参数
+    fn parse_optional(&mut self) -> Result<Option<Node>, ParseError> {
+        self.skip_whitespace();
+        if self.chars.peek() != Some(&'[') {
+            return Ok(None);
+        }
+        self.chars.next();
+        let (nodes, stop) = self.parse_sequence(true)?;
+        if stop != Stop::CloseBracket {
+            return Err(self.error(msg!("方括号不匹配", "unbalanced brackets")));
+        }
+        Ok(Some(single(nodes)))
+    }
+
+    /// 读取定界符，`.` 表示空
+    fn parse_delimiter(&mut self) -> Result<String, ParseError> {
+        self.skip_whitespace();
+        match self.chars.next() {
+            Some('.') => Ok(String::new()),
+            Some('\\') => {
+                let name = self.read_command()?;
+                match name.as_str() {
+                    "{" => Ok("{".to_string()),
+                    "}" => Ok("}".to_string()),
+                    "|" => Ok("‖".to_string()),
+                    _ => lookup(OPERATORS, &name).map(str::to_string).ok_or_else(|| {
+                        self.error(msg!(
+                            "不支持的定界符 \\{}",
+                            "unsupported delimiter \\{}",
+                            name
+                        ))
+                    }),
+                }
+            }
+            Some(c) => Ok(c.to_string()),
+            None => Err(self.error(msg!("缺少定界符", "missing delimiter"))),
+        }
+    }
+
+    /// 解析一串节点直到遇到结束标记，并处理上下标；`]` 只在可选参数中作为结束标记
+    fn parse_sequence(&mut self, in_optional: bool) -> Result<(Vec<Node>, Stop), ParseError> {
+        let mut nodes: Vec<Node> = Vec::new();
+        loop {
+            self.skip_whitespace();
+            let Some(&c) = self.chars.peek() else {
+                return Ok((nodes, Stop::End));
+            };
+            match c {
+                '}' => {
+                    self.chars.next();
+                    return Ok((nodes, Stop::CloseBrace));
+                }
+                ']' if in_optional => {
+                    self.chars.next();
+                    return Ok((nodes, Stop::CloseBracket));
+                }
+                '&' => {
+                    self.chars.next();
+                    return Ok((nodes, Stop::Ampersand));
+                }
+                '^' | '_' => {
+                    self.chars.next();
+                    let arg = self.parse_argument()?;
+                    let base = nodes.pop().unwrap_or(Node::Row(Vec::new()));
+                    nodes.push(attach(base, c == '^', arg).map_err(|m| self.error(m))?);
+                }
+                '\'' => {
+                    self.chars.next();
+                    let base = nodes.pop().unwrap_or(Node::Row(Vec::new()));
+                    let prime = Node::Operator("′".to_string());
+                    nodes.push(attach(base, true, prime).map_err(|m| self.error(m))?);
+                }
+                '\\' => {
+                    self.chars.next();
+                    let name = self.read_command()?;
+                    match name.as_str() {
+                        "\\" | "cr" => {
+                            self.parse_optional()?;
+                            return Ok((nodes, Stop::NewRow));
+                        }
+                        "right" => {
+                            let delim = self.parse_delimiter()?;
+                            return Ok((nodes, Stop::Right(delim)));
+                        }
+                        "end" => {
+                            let env = self.read_raw_group()?;
+                            return Ok((nodes, Stop::EndEnv(env)));
+                        }
+                        _ => {
+                            if let Some(node) = self.parse_command(&name)? {
+                                nodes.push(node);
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    if let Some(node) = self.parse_atom()? {
+                        nodes.push(node);
+                    }
+                }
+            }
+        }
+    }
+
+    /// 解析单个原子记号；纯格式命令返回 `None`
+    fn parse_atom(&mut self) -> Result<Option<Node>, ParseError> {
+        self.skip_whitespace();
+        let Some(c) = self.chars.next() else {
+            return Ok(None);
+        };
+        let node = match c {
+            '\\' => {
+                let name = self.read_command()?;
+                return self.parse_command(&name);
+            }
+            '{' => {
+                let (nodes, stop) = self.parse_sequence(false)?;
+                if stop != Stop::CloseBrace {
+                    return Err(self.error(msg!("花括号不匹配", "unbalanced braces")));
+                }
+                single(nodes)
+            }
+            '0'..='9' | '.' => {
+                let mut number = c.to_string();
+                while let Some(&d) = self.chars.peek() {
+                    if d.is_ascii_digit() || (d == '.' && !number.contains('.')) {
+                        number.push(d);
+                        self.chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                if number == "." {
+                    Node::Operator(number)
+                } else {
+                    Node::Number(number)
+                }
+            }
+            c if c.is_alphabetic() => Node::Ident(c.to_string()),
+            '~' => Node::Space("0.333em"),
+            '}' | '&' | '^' | '_' => {
+                return Err(self.error_at(
+                    msg!("意外的字符 {}", "unexpected character {}", c),
+                    self.chars.position - 1,
+                ))
+            }
+            c => Node::Operator(c.to_string()),
+        };
+        Ok(Some(node))
+    }
+
+    fn parse_command(&mut self, name: &str) -> Result<Option<Node>, ParseError> {
+        let start = self.command_start;
+        if let Some(symbol) = lookup(GREEK, name) {
+            return Ok(Some(Node::Ident(symbol.to_string())));
+        }
+        if let Some(symbol) = lookup(OPERATORS, name) {
+            return Ok(Some(Node::Operator(symbol.to_string())));
+        }
+        if let Some(symbol) = lookup(LARGE_OPERATORS, name) {
+            return Ok(Some(Node::LargeOp(symbol.to_string())));
+        }
+        if let Some(width) = lookup(SPACES, name) {
+            return Ok(Some(Node::Space(width)));
+        }
+        if FUNCTIONS.contains(&name) {
+            return Ok(Some(Node::Ident(name.to_string())));
+        }
+        if let Some((_, args)) = IGNORED.iter().find(|(k, _)| *k == name) {
+            for _ in 0..*args {
+                self.read_raw_group()?;
+            }
+            return Ok(None);
+        }
+        let node = match name {
+            "{" | "}" | "%" | "#" | "&" | "_" | "$" => Node::Operator(name.to_string()),
+            "|" => Node::Operator("‖".to_string()),
+            "frac" | "dfrac" | "tfrac" | "cfrac" => Node::Frac {
+                num: Box::new(self.parse_argument()?),
+                den: Box::new(self.parse_argument()?),
+                bar: true,
+            },
+            "binom" | "dbinom" | "tbinom" => Node::Fenced {
+                open: "(".to_string(),
+                close: ")".to_string(),
+                body: Box::new(Node::Frac {
+                    num: Box::new(self.parse_argument()?),
+                    den: Box::new(self.parse_argument()?),
+                    bar: false,
+                }),
+            },
+            "sqrt" => {
+                let index = self.parse_optional()?.map(Box::new);
+                Node::Sqrt {
+                    body: Box::new(self.parse_argument()?),
+                    index,
+                }
+            }
+            "hat" | "widehat" => self.accent(Accent::Hat)?,
+            "bar" => self.accent(Accent::Bar)?,
+            "overline" => self.accent(Accent::Overline)?,
+            "underline" => self.accent(Accent::Underline)?,
+            "vec" | "overrightarrow" => self.accent(Accent::Vec)?,
+            "dot" => self.accent(Accent::Dot)?,
+            "ddot" => self.accent(Accent::Ddot)?,
+            "tilde" | "widetilde" => self.accent(Accent::Tilde)?,
+            "mathbf" | "boldsymbol" | "bm" => self.styled(Variant::Bold)?,
+            "mathrm" => self.styled(Variant::Normal)?,
+            "mathit" => self.styled(Variant::Italic)?,
+            "mathbb" => self.styled(Variant::DoubleStruck)?,
+            "mathcal" | "mathscr" => self.styled(Variant::Script)?,
+            "mathfrak" => self.styled(Variant::Fraktur)?,
+            "mathsf" => self.styled(Variant::SansSerif)?,
+            "mathtt" => self.styled(Variant::Monospace)?,
+            "operatorname" => Node::Ident(self.read_raw_group()?.trim().to_string()),
+            "text" | "textrm" | "textnormal" | "mbox" | "textit" | "textbf" => {
+                Node::Text(self.read_raw_group()?)
+            }
+            "big" | "Big" | "bigg" | "Bigg" | "bigl" | "bigr" | "Bigl" | "Bigr" | "biggl"
+            | "biggr" | "Biggl" | "Biggr" => Node::Operator(self.parse_delimiter()?),
+            "left" => {
+                let open = self.parse_delimiter()?;
+                let (nodes, stop) = self.parse_sequence(false)?;
+                let Stop::Right(close) = stop else {
+                    return Err(self.error(msg!(
+                        "\\left 缺少对应的 \\right",
+                        "\\left without matching \\right"
+                    )));
+                };
+                Node::Fenced {
+                    open,
+                    close,
+                    body: Box::new(Node::Row(nodes)),
+                }
+            }
+            "begin" => {
+                let env = self.read_raw_group()?;
+                self.parse_environment(&env, start)?
+            }
+            _ => {
+                return Err(self.error_at(
+                    msg!("不支持的命令 \\{}", "unsupported command \\{}", name),
+                    start,
+                ));
+            }
+        };
+        Ok(Some(node))
+    }
+
+    fn accent(&mut self, accent: Accent) -> Result<Node, ParseError> {
+        Ok(Node::Accent {
+            body: Box::new(self.parse_argument()?),
+            accent,
+        })
+    }
+
+    fn styled(&mut self, variant: Variant) -> Result<Node, ParseError> {
+        Ok(Node::Styled {
+            variant,
+            body: Box::new(self.parse_argument()?),
+        })
+    }
+
+    fn parse_environment(&mut self, env: &str, start: usize) -> Result<Node, ParseError> {
+        let (open, close, aligned) = match env {
+            "matrix" | "smallmatrix" | "array" => ("", "", false),
+            "pmatrix" => ("(", ")", false),
+            "bmatrix" => ("[", "]", false),
+            "Bmatrix" => ("{", "}", false),
+            "vmatrix" => ("|", "|", false),
+            "Vmatrix" => ("‖", "‖", false),
+            "cases" => ("{", "", false),
+            "aligned" | "align" | "align*" | "gathered" | "gather" | "gather*" | "split"
+            | "equation" | "equation*" | "alignat" | "alignat*" => ("", "", true),
+            _ => {
+                return Err(self.error_at(
+                    msg!("不支持的环境 {}", "unsupported environment {}", env),
+                    start,
+                ))
+            }
+        };
+        if env == "array" || env.starts_with("alignat") {
+            self.read_raw_group()?;
+        }
+        let mut rows = Vec::new();
+        let mut row = Vec::new();
+        loop {
+            let (nodes, stop) = self.parse_sequence(false)?;
+            row.push(Node::Row(nodes));
+            match stop {
+                Stop::Ampersand => {}
+                Stop::NewRow => rows.push(std::mem::take(&mut row)),
+                Stop::EndEnv(name) if name == env => {
+                    // 末尾的 `\\` 会留下一个空行
+                    if !(row.len() == 1 && row[0] == Node::Row(Vec::new())) || rows.is_empty() {
+                        rows.push(row);
+                    }
+                    break;
+                }
+                _ => return Err(self.error(format!("环境 {} 没有正确结束", env))),
+            }
+        }
+        Ok(Node::Matrix {
+            rows,
+            open: open.to_string(),
+            close: close.to_string(),
+            aligned,
+        })
+    }
+}
+
+/// 只有一个节点时去掉外层 `Row`
+fn single(mut nodes: Vec<Node>) -> Node {
+    if nodes.len() == 1 {
+        nodes.pop().unwrap()
+    } else {
+        Node::Row(nodes)
+    }
+}
+
+/// 把上标或下标附加到前一个节点
+fn attach(base: Node, is_sup: bool, script: Node) -> Result<Node, String> {
+    match base {
+        Node::Scripts { base, sub, sup } => {
+            let (sub, sup) = if is_sup {
+                if sup.is_some() {
+                    return Err(msg!("重复的上标", "double superscript"));
+                }
+                (sub, Some(Box::new(script)))
+            } else {
+                if sub.is_some() {
+                    return Err(msg!("重复的下标", "double subscript"));
+                }
+                (Some(Box::new(script)), sup)
+            };
+            Ok(Node::Scripts { base, sub, sup })
+        }
+        base => {
+            let script = Some(Box::new(script));
+            let (sub, sup) = if is_sup {
+                (None, script)
+            } else {
+                (script, None)
+            };
+            Ok(Node::Scripts {
+                base: Box::new(base),
+                sub,
+                sup,
+            })
+        }
+    }
+}
+
+impl Accent {
+    /// 显示用的重音字符
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Accent::Hat => "^",
+            Accent::Bar | Accent::Overline => "¯",
+            Accent::Underline => "_",
+            Accent::Vec => "→",
+            Accent::Dot => "˙",
+            Accent::Ddot => "¨",
+            Accent::Tilde => "~",
+        }
+    }
+}
+
+impl Variant {
+    pub fn mathml_name(self) -> &'static str {
+        match self {
+            Variant::Normal => "normal",
+            Variant::Bold => "bold",
+            Variant::Italic => "italic",
+            Variant::DoubleStruck => "double-struck",
+            Variant::Script => "script",
+            Variant::Fraktur => "fraktur",
+            Variant::SansSerif => "sans-serif",
+            Variant::Monospace => "monospace",
+        }
+    }
+}
+
+/// 转换为 MathML；`semantics` 中附带原始 LaTeX，便于再次导入
+pub fn to_mathml(node: &Node, latex: &str, display: bool) -> String {
+    let mut out = String::new();
+    write_mathml(node, None, &mut out);
+    format!(
+        "<math xmlns=\"http://www.w3.org/1998/Math/MathML\" display=\"{}\"><semantics><mrow>{}</mrow><annotation encoding=\"application/x-tex\">{}</annotation></semantics></math>",
+        if display { "block" } else { "inline" },
+        out,
+        escape_xml(latex)
+    )
+}
+
+fn token(tag: &str, text: &str, variant: Option<Variant>, out: &mut String) {
+    match variant {
+        Some(v) => out.push_str(&format!(
+            "<{0} mathvariant=\"{1}\">{2}</{0}>",
+            tag,
+            v.mathml_name(),
+            escape_xml(text)
+        )),
+        None => out.push_str(&format!("<{0}>{1}</{0}>", tag, escape_xml(text))),
+    }
+}
+
+fn wrap(node: &Node, variant: Option<Variant>, out: &mut String) {
+    out.push_str("<mrow>");
+    write_mathml(node, variant, out);
+    out.push_str("</mrow>");
+}
+
+fn fence(symbol: &str, out: &mut String) {
+    if !symbol.is_empty() {
+        out.push_str(&format!("<mo fence=\"true\">{}</mo>", escape_xml(symbol)));
+    }
+}
+
+/// 求和、极限等在行间公式中把上下标放在正上方和正下方
+fn uses_limits(base: &Node) -> bool {
+    match base {
+        Node::LargeOp(op) => !matches!(op.as_str(), "∫" | "∬" | "∭" | "∮"),
+        Node::Ident(name) => matches!(
+            name.as_str(),
+            "lim" | "max" | "min" | "sup" | "inf" | "limsup" | "liminf"
+        ),
+        _ => false,
+    }
+}
+
+fn write_mathml(node: &Node, variant: Option<Variant>, out: &mut String) {
+    match node {
+        Node::Ident(name) => token("mi", name, variant, out),
+        Node::Number(n) => token("mn", n, variant, out),
+        Node::Operator(op) => token("mo", op, None, out),
+        Node::LargeOp(op) => out.push_str(&format!("<mo largeop=\"true\">{}</mo>", escape_xml(op))),
+        Node::Text(text) => token("mtext", text, None, out),
+        Node::Space(width) => out.push_str(&format!("<mspace width=\"{}\"/>", width)),
+        Node::Row(nodes) => {
+            for n in nodes {
+                write_mathml(n, variant, out);
+            }
+        }
+        Node::Frac { num, den, bar } => {
+            out.push_str(if *bar {
+                "<mfrac>"
+            } else {
+                "<mfrac linethickness=\"0\">"
+            });
+            wrap(num, variant, out);
+            wrap(den, variant, out);
+            out.push_str("</mfrac>");
+        }
+        Node::Sqrt { body, index } => match index {
+            Some(index) => {
+                out.push_str("<mroot>");
+                wrap(body, variant, out);
+                wrap(index, variant, out);
+                out.push_str("</mroot>");
+            }
+            None => {
+                out.push_str("<msqrt>");
+                write_mathml(body, variant, out);
+                out.push_str("</msqrt>");
+            }
+        },
+        Node::Scripts { base, sub, sup } => {
+            let (under, over, both) = if uses_limits(base) {
+                ("munder", "mover", "munderover")
+            } else {
+                ("msub", "msup", "msubsup")
+            };
+            let tag = match (sub, sup) {
+                (Some(_), Some(_)) => both,
+                (Some(_), None) => under,
+                _ => over,
+            };
+            out.push_str(&format!("<{}>", tag));
+            wrap(base, variant, out);
+            if let Some(sub) = sub {
+                wrap(sub, variant, out);
+            }
+            if let Some(sup) = sup {
+                wrap(sup, variant, out);
+            }
+            out.push_str(&format!("</{}>", tag));
+        }
+        Node::Accent { body, accent } => {
+            let (tag, attr) = if *accent == Accent::Underline {
+                ("munder", "accentunder")
+            } else {
+                ("mover", "accent")
+            };
+            out.push_str(&format!("<{}>", tag));
+            wrap(body, variant, out);
+            out.push_str(&format!(
+                "<mo {}=\"true\">{}</mo></{}>",
+                attr,
+                escape_xml(accent.symbol()),
+                tag
+            ));
+        }
+        Node::Fenced { open, close, body } => {
+            out.push_str("<mrow>");
+            fence(open, out);
+            write_mathml(body, variant, out);
+            fence(close, out);
+            out.push_str("</mrow>");
+        }
+        Node::Matrix {
+            rows,
+            open,
+            close,
+            aligned,
+        } => {
+            out.push_str("<mrow>");
+            fence(open, out);
+            out.push_str(if *aligned {
+                "<mtable columnalign=\"right left\" columnspacing=\"0\">"
+            } else {
+                "<mtable>"
+            });
+            for row in rows {
+                out.push_str("<mtr>");
+                for cell in row {
+                    out.push_str("<mtd>");
+                    write_mathml(cell, variant, out);
+                    out.push_str("</mtd>");
+                }
+                out.push_str("</mtr>");
+            }
+            out.push_str("</mtable>");
+            fence(close, out);
+            out.push_str("</mrow>");
+        }
+        Node::Styled { variant, body } => write_mathml(body, Some(*variant), out),
+    }
+}
+
+/// 按符号反查命令名，如 `α` → `alpha`
+fn command_for(table: &[(&'static str, &str)], symbol: &str) -> Option<&'static str> {
+    table.iter().find(|(_, v)| *v == symbol).map(|(k, _)| *k)
+}
+
+/// 转换回 LaTeX，用于从 OMML、MathML 等格式导入的公式
+pub fn to_latex(node: &Node) -> String {
+    let mut out = String::new();
+    write_latex(node, &mut out);
+    out.trim().to_string()
+}
+
+/// 追加一段 LaTeX；字母紧跟在命令名或不带花括号的上下标之后时补一个空格，
+/// 避免 `\alpha x` 变成 `\alphax`、`x^n y` 变成 `x^ny`
+fn push_latex(out: &mut String, piece: &str) {
+    if piece.starts_with(|c: char| c.is_ascii_alphanumeric()) {
+        let mut tail = out.chars().rev();
+        if tail.next().is_some_and(|c| c.is_alphanumeric())
+            && tail.next().is_some_and(|c| c == '^' || c == '_')
+        {
+            out.push(' ');
+            out.push_str(piece);
+            return;
+        }
+    }
+    if piece.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        let letters = out.len()
+            - out
+                .trim_end_matches(|c: char| c.is_ascii_alphabetic())
+                .len();
+        if letters > 0 && out[..out.len() - letters].ends_with('\\') {
+            out.push(' ');
+        }
+    }
+    out.push_str(piece);
+}
+
+/// 单个字符的参数不需要花括号
+fn latex_group(node: &Node) -> String {
+    let inner = to_latex(node);
+    if inner.chars().count() == 1 {
+        inner
+    } else {
+        format!("{{{}}}", inner)
+    }
+}
+
+fn latex_symbol(symbol: &str) -> String {
+    match symbol {
+        "{" | "}" | "%" | "#" | "&" | "_" | "$" => format!("\\{}", symbol),
+        "‖" => "\\|".to_string(),
+        _ => match command_for(OPERATORS, symbol).or_else(|| command_for(GREEK, symbol)) {
+            Some(name) => format!("\\{}", name),
+            None => symbol.to_string(),
+        },
+    }
+}
+
+/// 导入的文字是纯文本，写入 `\text{}` 前转义 LaTeX 特殊字符
+fn escape_text(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '{' | '}' | '%' | '#' | '$' | '&' | '_' => {
+                escaped.push('\\');
+                escaped.push(ch);
+            }
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+fn latex_delimiter(symbol: &str) -> String {
+    if symbol.is_empty() {
+        ".".to_string()
+    } else {
+        latex_symbol(symbol)
+    }
+}
+
+fn write_latex(node: &Node, out: &mut String) {
+    match node {
+        Node::Ident(name) if name.chars().count() > 1 => {
+            if is_function(name) {
+                push_latex(out, &format!("\\{}", name));
+            } else {
+                push_latex(out, &format!("\\operatorname{{{}}}", name));
+            }
+        }
+        Node::Ident(name) => match command_for(GREEK, name) {
+            Some(command) => push_latex(out, &format!("\\{}", command)),
+            None => push_latex(out, name),
+        },
+        Node::Number(n) => push_latex(out, n),
+        Node::Operator(op) => push_latex(out, &latex_symbol(op)),
+        Node::LargeOp(op) => match command_for(LARGE_OPERATORS, op) {
+            Some(command) => push_latex(out, &format!("\\{}", command)),
+            None => push_latex(out, op),
+        },
+        Node::Text(text) => push_latex(out, &format!("\\text{{{}}}", escape_text(text))),
+        Node::Space(width) => match *width {
+            "0.333em" => out.push_str("\\ "),
+            w => push_latex(out, &format!("\\{}", command_for(SPACES, w).unwrap_or(","))),
+        },
+        Node::Row(nodes) => {
+            for n in nodes {
+                write_latex(n, out);
+            }
+        }
+        Node::Frac { num, den, bar } => {
+            let command = if *bar {
+                "\\frac"
+            } else {
+                "\\genfrac{}{}{0pt}{}"
+            };
+            push_latex(
+                out,
+                &format!("{}{{{}}}{{{}}}", command, to_latex(num), to_latex(den)),
+            );
+        }
+        Node::Sqrt { body, index } => match index {
+            Some(index) => push_latex(
+                out,
+                &format!("\\sqrt[{}]{{{}}}", to_latex(index), to_latex(body)),
+            ),
+            None => push_latex(out, &format!("\\sqrt{{{}}}", to_latex(body))),
+        },
+        Node::Scripts { base, sub, sup } => {
+            match base.as_ref() {
+                Node::Row(nodes) if nodes.len() != 1 => {
+                    push_latex(out, &format!("{{{}}}", to_latex(base)))
+                }
+                Node::Scripts { .. } => push_latex(out, &format!("{{{}}}", to_latex(base))),
+                _ => write_latex(base, out),
+            }
+            if let Some(sub) = sub {
+                out.push_str(&format!("_{}", latex_group(sub)));
+            }
+            if let Some(sup) = sup {
+                match latex_group(sup).as_str() {
+                    "{\\prime}" => out.push('\''),
+                    group => out.push_str(&format!("^{}", group)),
+                }
+            }
+        }
+        Node::Accent { body, accent } => {
+            let command = match accent {
+                Accent::Hat => "hat",
+                Accent::Bar => "bar",
+                Accent::Overline => "overline",
+                Accent::Underline => "underline",
+                Accent::Vec => "vec",
+                Accent::Dot => "dot",
+                Accent::Ddot => "ddot",
+                Accent::Tilde => "tilde",
+            };
+            push_latex(out, &format!("\\{}{{{}}}", command, to_latex(body)));
+        }
+        Node::Fenced { open, close, body } => match body.as_ref() {
+            Node::Frac {
+                num,
+                den,
+                bar: false,
+            } if open == "(" && close == ")" => push_latex(
+                out,
+                &format!("\\binom{{{}}}{{{}}}", to_latex(num), to_latex(den)),
+            ),
+            _ => push_latex(
+                out,
+                &format!(
+                    "\\left{} {} \\right{}",
+                    latex_delimiter(open),
+                    to_latex(body),
+                    latex_delimiter(close)
+                ),
+            ),
+        },
+        Node::Matrix {
+            rows,
+            open,
+            close,
+            aligned,
+        } => {
+            let env = match (open.as_str(), close.as_str()) {
+                _ if *aligned => Some("aligned"),
+                ("", "") => Some("matrix"),
+                ("(", ")") => Some("pmatrix"),
+                ("[", "]") => Some("bmatrix"),
+                ("{", "}") => Some("Bmatrix"),
+                ("|", "|") => Some("vmatrix"),
+                ("‖", "‖") => Some("Vmatrix"),
+                ("{", "") => Some("cases"),
+                _ => None,
+            };
+            let body = rows
+                .iter()
+                .map(|row| row.iter().map(to_latex).collect::<Vec<_>>().join(" & "))
+                .collect::<Vec<_>>()
+                .join(" \\\\ ");
+            let latex = match env {
+                Some(env) => format!("\\begin{{{0}}} {1} \\end{{{0}}}", env, body),
+                None => format!(
+                    "\\left{} \\begin{{matrix}} {} \\end{{matrix}} \\right{}",
+                    latex_delimiter(open),
+                    body,
+                    latex_delimiter(close)
+                ),
+            };
+            push_latex(out, &latex);
+        }
+        Node::Styled { variant, body } => {
+            let command = match variant {
+                Variant::Normal => "mathrm",
+                Variant::Bold => "mathbf",
+                Variant::Italic => "mathit",
+                Variant::DoubleStruck => "mathbb",
+                Variant::Script => "mathcal",
+                Variant::Fraktur => "mathfrak",
+                Variant::SansSerif => "mathsf",
+                Variant::Monospace => "mathtt",
+            };
+            push_latex(out, &format!("\\{}{{{}}}", command, to_latex(body)));
+        }
+    }
+}
+
+pub fn escape_xml(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn imported_text_is_escaped() {
+        let node = Node::Text("50% of a_b & {c} #1 $2 ~x^y \\z".to_string());
+        assert_eq!(
+            to_latex(&node),
+            "\\text{50\\% of a\\_b \\& \\{c\\} \\#1 \\$2 \\textasciitilde{}x\\textasciicircum{}y \\textbackslash{}z}"
+        );
+    }
+
+    #[test]
+    fn plain_text_is_unchanged() {
+        let node = Node::Text("速度 speed".to_string());
+        assert_eq!(to_latex(&node), "\\text{速度 speed}");
+    }
+}
@@ -0,0 +1,525 @@
+//! 把 Presentation MathML 转换回 LaTeX
+//!
+//! 先转换为 `math` 模块的语法树，再由 `math::to_latex` 输出。带有
+//! `application/x-tex` 注释的公式（如本程序导出的 MathML）直接使用注释中的原始 LaTeX。
+//! 不认识的元素按文字处理并记录警告。
+
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use regex::Regex;
+
+use crate::error::AppError;
+use crate::math::{self, Accent, Node, Variant};
+
+/// 转换结果；`warnings` 列出按文字处理的元素
+pub struct Converted {
+    pub latex: String,
+    pub warnings: Vec<String>,
+}
+
+enum Child {
+    Element(Element),
+    Text(String),
+}
+
+struct Element {
+    name: String,
+    attrs: Vec<(String, String)>,
+    children: Vec<Child>,
+}
+
+impl Element {
+    fn attr(&self, key: &str) -> Option<&str> {
+        self.attrs
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn elements(&self) -> impl Iterator<Item = &Element> {
+        self.children.iter().filter_map(|c| match c {
+            Child::Element(e) => Some(e),
+            Child::Text(_) => None,
+        })
+    }
+
+    /// 所有后代文字，首尾空白去掉
+    fn text(&self) -> String {
+        fn collect(element: &Element, out: &mut String) {
+            for child in &element.children {
+                match child {
+                    Child::Element(e) => collect(e, out),
+                    Child::Text(t) => out.push_str(t),
+                }
+            }
+        }
+        let mut out = String::new();
+        collect(self, &mut out);
+        out.trim().to_string()
+    }
+}
+
+/// 找出文本中所有 `<math>...</math>` 片段（允许 `mml:` 等前缀），返回起始行号和片段
+pub fn split(content: &str) -> Vec<(usize, &str)> {
+    let start = Regex::new(r"<((?:[A-Za-z_][\w.-]*:)?math)[\s>/]").unwrap();
+    let mut found = Vec::new();
+    let mut pos = 0;
+    while let Some(caps) = start.captures(&content[pos..]) {
+        let whole = caps.get(0).unwrap();
+        let begin = pos + whole.start();
+        let close = format!("</{}>", &caps[1]);
+        let end = match content[begin..].find(&close) {
+            Some(offset) => begin + offset + close.len(),
+            // 自闭合的 `<math/>` 或没有结束标签
+            None => match content[begin..].find('>') {
+                Some(offset) if content[..begin + offset].ends_with('/') => begin + offset + 1,
+                _ => break,
+            },
+        };
+        let line = content[..begin].matches('\n').count() + 1;
+        found.push((line, &content[begin..end]));
+        pos = end;
+    }
+    found
+}
+
+/// XML 预定义实体之外，网页中 MathML 常见的命名实体
+fn entity(name: &str) -> Option<&'static str> {
+    Some(match name {
+        "nbsp" => "\u{00A0}",
+        "InvisibleTimes" | "it" => "\u{2062}",
+        "ApplyFunction" | "af" => "\u{2061}",
+        "InvisibleComma" | "ic" => "\u{2063}",
+        "times" => "×",
+        "minus" => "−",
+        "plusmn" | "PlusMinus" => "±",
+        "sdot" | "middot" => "⋅",
+        "le" | "leq" => "≤",
+        "ge" | "geq" => "≥",
+        "ne" => "≠",
+        "infin" | "infty" => "∞",
+        "int" | "Integral" => "∫",
+        "sum" | "Sum" => "∑",
+        "prod" | "Product" => "∏",
+        "rarr" | "rightarrow" | "RightArrow" => "→",
+        "larr" | "leftarrow" | "LeftArrow" => "←",
+        "part" | "PartialD" => "∂",
+        "nabla" | "Del" => "∇",
+        "lang" | "LeftAngleBracket" => "⟨",
+        "rang" | "RightAngleBracket" => "⟩",
+        "prime" => "′",
+        "pi" => "π",
+        "alpha" => "α",
+        "beta" => "β",
+        "gamma" => "γ",
+        "theta" => "θ",
+        "lambda" => "λ",
+        "mu" => "μ",
+        "sigma" => "σ",
+        "omega" => "ω",
+        _ => return None,
+    })
+}
+
+fn invalid(err: impl std::fmt::Display) -> AppError {
+    AppError::InvalidDocument {
+        format: "MathML".to_string(),
+        message: err.to_string(),
+    }
+}
+
+fn parse(fragment: &str) -> Result<Element, AppError> {
+    let mut reader = Reader::from_str(fragment);
+    let mut stack: Vec<Element> = vec![Element {
+        name: String::new(),
+        attrs: Vec::new(),
+        children: Vec::new(),
+    }];
+    loop {
+        let event = reader.read_event().map_err(invalid)?;
+        match event {
+            Event::Start(ref e) | Event::Empty(ref e) => {
+                let attrs = e
+                    .attributes()
+                    .filter_map(|a| a.ok())
+                    .map(|a| {
+                        let key = String::from_utf8_lossy(a.key.local_name().as_ref()).to_string();
+                        let value = a
+                            .unescape_value()
+                            .map(|v| v.to_string())
+                            .unwrap_or_else(|_| String::from_utf8_lossy(&a.value).to_string());
+                        (key, value)
+                    })
+                    .collect();
+                let element = Element {
+                    name: String::from_utf8_lossy(e.local_name().as_ref()).to_string(),
+                    attrs,
+                    children: Vec::new(),
+                };
+                if matches!(event, Event::Empty(_)) {
+                    stack
+                        .last_mut()
+                        .unwrap()
+                        .children
+                        .push(Child::Element(element));
+                } else {
+                    stack.push(element);
+                }
+            }
+            Event::End(_) if stack.len() > 1 => {
+                let element = stack.pop().unwrap();
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .children
+                    .push(Child::Element(element));
+            }
+            Event::Text(t) => {
+                let text = t.unescape_with(entity).map_err(invalid)?;
+                stack
+                    .last_mut()
+                    .unwrap()
+                    .children
+                    .push(Child::Text(text.to_string()));
+            }
+            Event::CData(t) => {
+                let text = String::from_utf8_lossy(&t).to_string();
+                stack.last_mut().unwrap().children.push(Child::Text(text));
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    while stack.len() > 1 {
+        let element = stack.pop().unwrap();
+        stack
+            .last_mut()
+            .unwrap()
+            .children
+            .push(Child::Element(element));
+    }
+    let root = stack.pop().unwrap();
+    root.children
+        .into_iter()
+        .find_map(|c| match c {
+            Child::Element(e) if e.name == "math" => Some(e),
+            _ => None,
+        })
+        .ok_or_else(|| AppError::NothingToImport {
+            format: "MathML".to_string(),
+        })
+}
+
+/// `semantics` 中附带的原始 LaTeX
+fn tex_annotation(element: &Element) -> Option<String> {
+    element
+        .elements()
+        .find_map(|child| match child.name.as_str() {
+            "semantics" => tex_annotation(child),
+            "annotation" => {
+                let encoding = child.attr("encoding")?;
+                let is_tex = matches!(encoding, "application/x-tex" | "TeX" | "LaTeX");
+                let text = child.text();
+                (is_tex && !text.is_empty()).then_some(text)
+            }
+            _ => None,
+        })
+}
+
+/// 转换一个 `<math>` 片段；XML 无效时返回 `InvalidDocument`，转换结果为空时返回 `NothingToImport`
+pub fn to_latex(fragment: &str) -> Result<Converted, AppError> {
+    let root = parse(fragment)?;
+    if let Some(latex) = tex_annotation(&root) {
+        return Ok(Converted {
+            latex,
+            warnings: Vec::new(),
+        });
+    }
+    let mut converter = Converter {
+        warnings: Vec::new(),
+    };
+    let node = converter.row(&root);
+    let latex = math::to_latex(&node);
+    if latex.is_empty() {
+        return Err(AppError::NothingToImport {
+            format: "MathML".to_string(),
+        });
+    }
+    Ok(Converted {
+        latex,
+        warnings: converter.warnings,
+    })
+}
+
+fn variant(name: &str) -> Option<Variant> {
+    Some(match name {
+        "normal" => Variant::Normal,
+        "bold" | "bold-italic" => Variant::Bold,
+        "italic" => Variant::Italic,
+        "double-struck" => Variant::DoubleStruck,
+        "script" | "bold-script" => Variant::Script,
+        "fraktur" | "bold-fraktur" => Variant::Fraktur,
+        "sans-serif" | "bold-sans-serif" | "sans-serif-italic" => Variant::SansSerif,
+        "monospace" => Variant::Monospace,
+        _ => return None,
+    })
+}
+
+fn styled(node: Node, variant: Option<Variant>) -> Node {
+    match variant {
+        Some(variant) => Node::Styled {
+            variant,
+            body: Box::new(node),
+        },
+        None => node,
+    }
+}
+
+/// 上方重音字符
+fn over_accent(symbol: &str) -> Option<Accent> {
+    Some(match symbol {
+        "^" | "ˆ" | "\u{0302}" => Accent::Hat,
+        "¯" | "‾" | "―" | "_" | "\u{0305}" => Accent::Overline,
+        "→" | "\u{20D7}" => Accent::Vec,
+        "˙" | "\u{0307}" | "." => Accent::Dot,
+        "¨" | "\u{0308}" | ".." => Accent::Ddot,
+        "~" | "˜" | "\u{0303}" => Accent::Tilde,
+        _ => return None,
+    })
+}
+
+fn space(width: Option<&str>) -> &'static str {
+    let em = width
+        .and_then(|w| w.trim().strip_suffix("em"))
+        .and_then(|w| w.parse::<f32>().ok())
+        .unwrap_or(0.167);
+    match em {
+        w if w >= 1.9 => "2em",
+        w if w >= 0.9 => "1em",
+        w if w >= 0.25 => "0.278em",
+        w if w >= 0.2 => "0.222em",
+        w if w < 0.0 => "-0.167em",
+        _ => "0.167em",
+    }
+}
+
+fn opening(symbol: &str) -> Option<&'static str> {
+    Some(match symbol {
+        "(" => ")",
+        "[" => "]",
+        "{" => "}",
+        "|" => "|",
+        "‖" => "‖",
+        "⟨" => "⟩",
+        _ => return None,
+    })
+}
+
+struct Converter {
+    warnings: Vec<String>,
+}
+
+impl Converter {
+    fn row(&mut self, element: &Element) -> Node {
+        let mut nodes = Vec::new();
+        for child in element.elements() {
+            match self.convert(child) {
+                Node::Row(mut inner) => nodes.append(&mut inner),
+                node => nodes.push(node),
+            }
+        }
+        // `( <mtable> )` 这样的组合转换为带定界符的矩阵
+        if let [Node::Operator(open), Node::Matrix {
+            rows,
+            open: o,
+            close: c,
+            aligned: false,
+        }, rest @ ..] = nodes.as_slice()
+        {
+            let close = match rest {
+                [Node::Operator(close)] => Some(close.as_str()),
+                [] => Some(""),
+                _ => None,
+            };
+            if let Some(close) = close {
+                let paired = opening(open) == Some(close) || (open == "{" && close.is_empty());
+                if paired && o.is_empty() && c.is_empty() {
+                    return Node::Matrix {
+                        rows: rows.clone(),
+                        open: open.clone(),
+                        close: close.to_string(),
+                        aligned: false,
+                    };
+                }
+            }
+        }
+        Node::Row(nodes)
+    }
+
+    /// 第 `idx` 个子元素，缺失时为空
+    fn nth(&mut self, element: &Element, idx: usize) -> Node {
+        match element.elements().nth(idx) {
+            Some(child) => self.convert(child),
+            None => Node::Row(Vec::new()),
+        }
+    }
+
+    fn convert(&mut self, element: &Element) -> Node {
+        let variant = element.attr("mathvariant").and_then(variant);
+        match element.name.as_str() {
+            "math" | "mrow" | "mstyle" | "mpadded" | "menclose" | "merror" | "mtd"
+            | "semantics" => styled(self.row(element), variant),
+            "annotation" | "annotation-xml" | "mphantom" | "none" | "mprescripts" => {
+                Node::Row(Vec::new())
+            }
+            "mi" => {
+                let text = element.text();
+                let node = Node::Ident(text.clone());
+                if text.chars().count() > 1 {
+                    node
+                } else {
+                    styled(node, variant)
+                }
+            }
+            "mn" => styled(Node::Number(element.text()), variant),
+            "mo" => {
+                let text = element.text();
+                match text.as_str() {
+                    "" | "\u{2061}" | "\u{2062}" | "\u{2063}" | "\u{2064}" => Node::Row(Vec::new()),
+                    "−" => Node::Operator("-".to_string()),
+                    op if math::is_large_operator(op) => Node::LargeOp(text),
+                    _ => Node::Operator(text),
+                }
+            }
+            "mtext" | "ms" => {
+                let text = element.text();
+                if text.trim().is_empty() {
+                    Node::Space("0.333em")
+                } else {
+                    Node::Text(text)
+                }
+            }
+            "mspace" => Node::Space(space(element.attr("width"))),
+            "mfrac" => {
+                let thickness = element.attr("linethickness").unwrap_or("1");
+                let zero = thickness
+                    .trim_end_matches(|c: char| c.is_ascii_alphabetic() || c == '%')
+                    .trim()
+                    .parse::<f32>()
+                    .is_ok_and(|t| t == 0.0);
+                Node::Frac {
+                    num: Box::new(self.nth(element, 0)),
+                    den: Box::new(self.nth(element, 1)),
+                    bar: !zero,
+                }
+            }
+            "msqrt" => Node::Sqrt {
+                body: Box::new(self.row(element)),
+                index: None,
+            },
+            "mroot" => Node::Sqrt {
+                body: Box::new(self.nth(element, 0)),
+                index: Some(Box::new(self.nth(element, 1))),
+            },
+            "msub" | "munder" => {
+                let base = self.nth(element, 0);
+                let under = self.nth(element, 1);
+                if element.name == "munder" && element.attr("accentunder") == Some("true") {
+                    return Node::Accent {
+                        body: Box::new(base),
+                        accent: Accent::Underline,
+                    };
+                }
+                Node::Scripts {
+                    base: Box::new(base),
+                    sub: Some(Box::new(under)),
+                    sup: None,
+                }
+            }
+            "msup" | "mover" => {
+                let base = self.nth(element, 0);
+                let over = self.nth(element, 1);
+                if element.name == "mover" {
+                    let accent = match &over {
+                        Node::Operator(op) => over_accent(op),
+                        _ => None,
+                    };
+                    if let Some(accent) = accent {
+                        return Node::Accent {
+                            body: Box::new(base),
+                            accent,
+                        };
+                    }
+                }
+                Node::Scripts {
+                    base: Box::new(base),
+                    sub: None,
+                    sup: Some(Box::new(over)),
+                }
+            }
+            "msubsup" | "munderover" => Node::Scripts {
+                base: Box::new(self.nth(element, 0)),
+                sub: Some(Box::new(self.nth(element, 1))),
+                sup: Some(Box::new(self.nth(element, 2))),
+            },
+            "mtable" => {
+                let aligned = element
+                    .attr("columnalign")
+                    .is_some_and(|a| a.starts_with("right left"));
+                let rows = element
+                    .elements()
+                    .filter(|r| r.name == "mtr" || r.name == "mlabeledtr")
+                    .map(|r| {
+                        let skip = usize::from(r.name == "mlabeledtr");
+                        r.elements()
+                            .filter(|c| c.name == "mtd")
+                            .skip(skip)
+                            .map(|c| self.row(c))
+                            .collect::<Vec<_>>()
+                    })
+                    .collect();
+                Node::Matrix {
+                    rows,
+                    open: String::new(),
+                    close: String::new(),
+                    aligned,
+                }
+            }
+            "mfenced" => {
+                let open = element.attr("open").unwrap_or("(").to_string();
+                let close = element.attr("close").unwrap_or(")").to_string();
+                let separators: Vec<char> = element
+                    .attr("separators")
+                    .unwrap_or(",")
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .collect();
+                let mut body = Vec::new();
+                let children: Vec<&Element> = element.elements().collect();
+                for (idx, child) in children.iter().enumerate() {
+                    if idx > 0 {
+                        if let Some(sep) = separators.get(idx - 1).or(separators.last()) {
+                            body.push(Node::Operator(sep.to_string()));
+                        }
+                    }
+                    body.push(self.convert(child));
+                }
+                Node::Fenced {
+                    open,
+                    close,
+                    body: Box::new(Node::Row(body)),
+                }
+            }
+            name => {
+                self.warnings
+                    .push(format!("不支持的 MathML 元素 <{}>，已按文字处理", name));
+                let text = element.text();
+                if text.is_empty() {
+                    Node::Row(Vec::new())
+                } else {
+                    Node::Text(text)
+                }
+            }
+        }
+    }
+}
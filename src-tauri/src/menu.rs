@@ -0,0 +1,207 @@
+//! 原生应用菜单：菜单项被点击时向所在窗口发送 `menu:<id>` 事件，由前端复用现有逻辑处理
+//!
+//! Tauri 1 不支持在运行时增删菜单项，“最近打开”子菜单使用固定数量的槽位，
+//! 刷新时只修改槽位的标题和可用状态。
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, CustomMenuItem, Manager, Menu, MenuItem, Submenu, Window, WindowMenuEvent};
+
+use crate::recent::{RecentFile, RecentFiles};
+
+/// “最近打开”子菜单的槽位数
+pub const RECENT_SLOTS: usize = 10;
+pub const RECENT_SLOT_PREFIX: &str = "recent-";
+const CLEAR_RECENT_ID: &str = "clear-recent";
+/// 空槽位的标题
+const EMPTY_SLOT_TITLE: &str = "（无）";
+
+/// `menu:open-recent` 事件的内容
+#[derive(Serialize, Clone)]
+pub struct OpenRecent {
+    pub path: String,
+}
+
+/// 各窗口“最近打开”槽位当前显示的路径，作为 `tauri::State` 托管
+///
+/// 点击时按槽位在这里查找，不重新读取列表：`RecentFiles::list` 会移除已不存在的文件，
+/// 槽位随之错位，可能打开与菜单上显示的不同的文件。
+#[derive(Default)]
+pub struct RecentMenu {
+    shown: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl RecentMenu {
+    fn remember(&self, label: &str, files: &[RecentFile]) {
+        let paths = files
+            .iter()
+            .take(RECENT_SLOTS)
+            .map(|file| file.path.clone())
+            .collect();
+        self.shown.lock().unwrap().insert(label.to_string(), paths);
+    }
+
+    fn path(&self, label: &str, slot: usize) -> Option<String> {
+        self.shown.lock().unwrap().get(label)?.get(slot).cloned()
+    }
+}
+
+fn item(id: &str, title: &str) -> CustomMenuItem {
+    CustomMenuItem::new(id.to_string(), title)
+}
+
+fn recent_submenu() -> Submenu {
+    let mut menu = Menu::new();
+    for slot in 0..RECENT_SLOTS {
+        let entry = item(&format!("{}{}", RECENT_SLOT_PREFIX, slot), EMPTY_SLOT_TITLE);
+        menu = menu.add_item(entry.disabled());
+    }
+    menu = menu
+        .add_native_item(MenuItem::Separator)
+        .add_item(item(CLEAR_RECENT_ID, "清除最近文件"));
+    Submenu::new("最近打开", menu)
+}
+
+/// 构建应用菜单；“最近打开”的内容在启动后由 `refresh_recent` 填充
+pub fn build(app_name: &str) -> Menu {
+    let file = Menu::new()
+        .add_item(item("open", "打开…").accelerator("CmdOrCtrl+O"))
+        .add_submenu(recent_submenu())
+        .add_item(item("new-window", "新建窗口").accelerator("CmdOrCtrl+Shift+N"))
+        .add_native_item(MenuItem::Separator)
+        .add_item(item("save", "保存").accelerator("CmdOrCtrl+S"))
+        .add_item(item("save-as", "另存为…").accelerator("CmdOrCtrl+Shift+S"))
+        .add_native_item(MenuItem::Separator)
+        .add_item(item("export-latex", "导出 LaTeX…").accelerator("CmdOrCtrl+E"))
+        .add_item(item("export-markdown", "导出 Markdown…"))
+        .add_native_item(MenuItem::Separator)
+        .add_item(item("print", "打印…").accelerator("CmdOrCtrl+P"))
+        .add_native_item(MenuItem::Separator)
+        .add_native_item(MenuItem::CloseWindow);
+    let edit = Menu::new()
+        .add_native_item(MenuItem::Undo)
+        .add_native_item(MenuItem::Redo)
+        .add_native_item(MenuItem::Separator)
+        .add_native_item(MenuItem::Cut)
+        .add_native_item(MenuItem::Copy)
+        .add_native_item(MenuItem::Paste)
+        .add_native_item(MenuItem::SelectAll);
+    let view = Menu::new()
+        .add_item(item("toggle-theme", "切换主题").accelerator("CmdOrCtrl+Shift+T"))
+        .add_native_item(MenuItem::Separator)
+        .add_native_item(MenuItem::EnterFullScreen);
+    let help = Menu::new()
+        .add_item(item("shortcuts", "快捷键"))
+        .add_item(item("about", format!("关于 {}", app_name).as_str()));
+
+    let mut menu = Menu::new();
+    // macOS 的第一个子菜单固定显示为应用菜单
+    #[cfg(target_os = "macos")]
+    {
+        menu = menu.add_submenu(Submenu::new(
+            app_name,
+            Menu::new()
+                .add_native_item(MenuItem::About(app_name.to_string(), Default::default()))
+                .add_native_item(MenuItem::Separator)
+                .add_native_item(MenuItem::Hide)
+                .add_native_item(MenuItem::HideOthers)
+                .add_native_item(MenuItem::ShowAll)
+                .add_native_item(MenuItem::Separator)
+                .add_native_item(MenuItem::Quit),
+        ));
+    }
+    #[cfg(not(target_os = "macos"))]
+    let file = file.add_native_item(MenuItem::Quit);
+    menu = menu
+        .add_submenu(Submenu::new("文件", file))
+        .add_submenu(Submenu::new("编辑", edit))
+        .add_submenu(Submenu::new("视图", view))
+        .add_submenu(Submenu::new("帮助", help));
+    menu
+}
+
+/// 用最近文件列表更新窗口菜单中的槽位；窗口没有菜单时跳过
+pub fn refresh_recent_window(window: &Window, files: &[RecentFile]) -> tauri::Result<()> {
+    window.state::<RecentMenu>().remember(window.label(), files);
+    let handle = window.menu_handle();
+    for slot in 0..RECENT_SLOTS {
+        let Some(entry) = handle.try_get_item(&format!("{}{}", RECENT_SLOT_PREFIX, slot)) else {
+            return Ok(());
+        };
+        match files.get(slot) {
+            Some(file) => {
+                entry.set_title(&file.name)?;
+                entry.set_enabled(true)?;
+            }
+            None => {
+                entry.set_title(EMPTY_SLOT_TITLE)?;
+                entry.set_enabled(false)?;
+            }
+        }
+    }
+    if let Some(clear) = handle.try_get_item(CLEAR_RECENT_ID) {
+        clear.set_enabled(!files.is_empty())?;
+    }
+    Ok(())
+}
+
+/// 更新所有窗口和托盘的“最近打开”子菜单
+pub fn refresh_recent(app: &AppHandle, files: &[RecentFile]) -> tauri::Result<()> {
+    for window in app.windows().values() {
+        refresh_recent_window(window, files)?;
+    }
+    crate::tray::refresh_recent(app, files)
+}
+
+/// 处理菜单点击：最近文件和清除列表在后端完成，其余转发给所在窗口
+pub fn handle_event(event: WindowMenuEvent) {
+    let window = event.window();
+    let id = event.menu_item_id();
+    let result = if let Some(slot) = id.strip_prefix(RECENT_SLOT_PREFIX) {
+        let path = slot
+            .parse::<usize>()
+            .ok()
+            .and_then(|slot| window.state::<RecentMenu>().path(window.label(), slot));
+        match path {
+            Some(path) => window.emit("menu:open-recent", OpenRecent { path }),
+            None => Ok(()),
+        }
+    } else if id == CLEAR_RECENT_ID {
+        let recent = window.state::<RecentFiles>();
+        if let Err(e) = recent.clear() {
+            log::error!("Failed to clear recent files: {}", e);
+        }
+        refresh_recent(&window.app_handle(), &[])
+    } else {
+        window.emit(&format!("menu:{}", id), ())
+    };
+    if let Err(e) = result {
+        log::error!("Failed to handle menu event {}: {}", id, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str) -> RecentFile {
+        RecentFile {
+            path: path.to_string(),
+            name: path.to_string(),
+            last_opened: 0,
+        }
+    }
+
+    #[test]
+    fn slots_resolve_to_the_paths_shown_in_each_window() {
+        let menu = RecentMenu::default();
+        menu.remember("main", &[file("/a.json"), file("/b.json")]);
+        menu.remember("editor-1", &[file("/b.json")]);
+        assert_eq!(menu.path("main", 1).as_deref(), Some("/b.json"));
+        assert_eq!(menu.path("editor-1", 0).as_deref(), Some("/b.json"));
+        assert_eq!(menu.path("editor-1", 1), None);
+        assert_eq!(menu.path("other", 0), None);
+    }
+}
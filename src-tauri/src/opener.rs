@@ -0,0 +1,157 @@
+//! 调用系统文件管理器显示文件，或用系统关联的默认程序打开文件
+//!
+//! 路径作为独立参数传给程序，不经过 shell 拼接，含空格或中文的路径也能正确处理。
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use crate::error::AppError;
+
+/// 在文件管理器中显示并选中文件；Linux 上打开所在的文件夹
+pub fn reveal(path: &Path) -> Result<(), AppError> {
+    let metadata =
+        fs::metadata(path).map_err(|e| AppError::io(path, "Failed to locate file", e))?;
+    spawn(reveal_command(path, metadata.is_dir()))
+}
+
+#[cfg(windows)]
+fn reveal_command(path: &Path, _is_dir: bool) -> Command {
+    use std::os::windows::process::CommandExt;
+
+    // explorer 只认 `/select,"路径"` 这种写法，整体加引号时会忽略选择；
+    // Windows 路径不能包含双引号，因此直接加引号是安全的
+    let mut command = Command::new("explorer");
+    command.raw_arg(format!("/select,\"{}\"", path.display()));
+    command
+}
+
+#[cfg(target_os = "macos")]
+fn reveal_command(path: &Path, _is_dir: bool) -> Command {
+    let mut command = Command::new("open");
+    command.arg("-R").arg(path);
+    command
+}
+
+#[cfg(all(unix, not(target_os = "macos")))]
+fn reveal_command(path: &Path, is_dir: bool) -> Command {
+    let dir = match path.parent() {
+        _ if is_dir => path,
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut command = Command::new("xdg-open");
+    command.arg(dir);
+    command
+}
+
+/// 默认拒绝打开的可执行文件扩展名（小写）；该功能可从网页视图调用，避免被用来运行程序
+const EXECUTABLE_EXTENSIONS: &[&str] = &[
+    "exe", "bat", "cmd", "com", "msi", "scr", "ps1", "vbs", "js", "jar", "lnk", "sh", "bash",
+    "command", "app", "desktop", "appimage", "run",
+];
+
+/// 用系统关联的默认程序打开文件；会等待系统的打开程序返回，需在 `spawn_blocking` 中调用
+pub fn open_default(path: &Path, allow_executables: bool) -> Result<(), AppError> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if !allow_executables && EXECUTABLE_EXTENSIONS.contains(&extension.as_str()) {
+        return Err(AppError::ExecutableBlocked {
+            path: path.to_string_lossy().into_owned(),
+            extension,
+        });
+    }
+    let metadata =
+        fs::metadata(path).map_err(|e| AppError::io(path, "Failed to locate file", e))?;
+    if !metadata.is_file() {
+        return Err(AppError::WrongFileType {
+            expected: "文件".to_string(),
+            found: "文件夹".to_string(),
+        });
+    }
+    open_native(path)
+}
+
+#[cfg(windows)]
+fn open_native(path: &Path) -> Result<(), AppError> {
+    /// SW_SHOWNORMAL
+    const SHOW_NORMAL: i32 = 1;
+    shell_execute("open", path, SHOW_NORMAL)
+}
+
+#[cfg(not(windows))]
+fn open_native(path: &Path) -> Result<(), AppError> {
+    // xdg-open 和 open 在找不到关联程序时以非零状态退出
+    let program = if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    let status = Command::new(program)
+        .arg(path)
+        .status()
+        .map_err(|e| AppError::SpawnFailed {
+            program: program.to_string(),
+            message: e.to_string(),
+        })?;
+    if status.success() {
+        Ok(())
+    } else {
+        Err(AppError::NoAssociation {
+            path: path.to_string_lossy().into_owned(),
+        })
+    }
+}
+
+/// 对文件执行系统的 Shell 动作（如 `open`、`print`），`show` 是窗口显示方式
+#[cfg(windows)]
+pub fn shell_execute(verb: &str, path: &Path, show: i32) -> Result<(), AppError> {
+    use windows::core::PCWSTR;
+    use windows::Win32::Foundation::HWND;
+    use windows::Win32::UI::Shell::ShellExecuteW;
+
+    /// 返回值不大于此值表示失败
+    const MAX_ERROR_CODE: isize = 32;
+    /// SE_ERR_NOASSOC：没有程序能处理该动作
+    const NO_ASSOCIATION: isize = 31;
+
+    let wide = |text: &str| text.encode_utf16().chain([0]).collect::<Vec<u16>>();
+    let verb = wide(verb);
+    let file = wide(&path.to_string_lossy());
+    let code = unsafe {
+        ShellExecuteW(
+            HWND(0),
+            PCWSTR(verb.as_ptr()),
+            PCWSTR(file.as_ptr()),
+            PCWSTR::null(),
+            PCWSTR::null(),
+            show,
+        )
+    }
+    .0;
+    match code {
+        NO_ASSOCIATION => Err(AppError::NoAssociation {
+            path: path.to_string_lossy().into_owned(),
+        }),
+        code if code <= MAX_ERROR_CODE => Err(AppError::SpawnFailed {
+            program: "ShellExecute".to_string(),
+            message: format!("error code {}", code),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// 启动程序后立即返回，在后台线程中等待其退出，避免留下僵尸进程
+fn spawn(mut command: Command) -> Result<(), AppError> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let mut child = command.spawn().map_err(|e| AppError::SpawnFailed {
+        program,
+        message: e.to_string(),
+    })?;
+    std::thread::spawn(move || {
+        let _ = child.wait();
+    });
+    Ok(())
+}
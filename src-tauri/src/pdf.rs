@@ -0,0 +1,152 @@
+//! 调用本机 LaTeX 引擎把导出的 `.tex` 编译成 PDF
+//!
+//! 优先使用 Tectonic（自动下载缺失的宏包），找不到时退回 XeLaTeX。
+//! 编译在临时目录中进行，结束后无论成功与否都会清理。取消时终止引擎进程。
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use crate::error::AppError;
+
+const SOURCE_NAME: &str = "formulas.tex";
+const PDF_NAME: &str = "formulas.pdf";
+const LOG_NAME: &str = "formulas.log";
+/// 引擎的标准输出和标准错误，没有生成日志文件时用作日志
+const OUTPUT_NAME: &str = "engine-output.txt";
+
+/// 检查引擎是否结束、是否已取消的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+/// 日志摘要最多保留的行数
+const MAX_LOG_LINES: usize = 40;
+
+#[derive(Clone, Copy)]
+enum Engine {
+    Tectonic,
+    XeLatex,
+}
+
+impl Engine {
+    fn program(self) -> &'static str {
+        match self {
+            Engine::Tectonic => "tectonic",
+            Engine::XeLatex => "xelatex",
+        }
+    }
+
+    fn command(self, dir: &Path) -> Command {
+        let mut command = Command::new(self.program());
+        command.current_dir(dir);
+        match self {
+            Engine::Tectonic => command.args(["--keep-logs", "--outdir", "."]),
+            Engine::XeLatex => command.args(["-interaction=nonstopmode", "-halt-on-error"]),
+        };
+        command.arg(SOURCE_NAME);
+        command
+    }
+}
+
+const ENGINES: &[Engine] = &[Engine::Tectonic, Engine::XeLatex];
+
+/// 编译 LaTeX 文档并返回 PDF 内容；会阻塞，需在 `spawn_blocking` 中调用。
+/// `cancelled` 返回 true 时终止编译并返回 `AppError::Cancelled`
+pub fn compile(latex: &str, cancelled: &dyn Fn() -> bool) -> Result<Vec<u8>, AppError> {
+    let dir = std::env::temp_dir().join(format!(
+        "mathlive-pdf-{}-{}",
+        std::process::id(),
+        crate::now_millis()
+    ));
+    fs::create_dir_all(&dir)
+        .map_err(|e| AppError::io(&dir, "Failed to create build directory", e))?;
+    let result = compile_in(&dir, latex, cancelled);
+    let _ = fs::remove_dir_all(&dir);
+    result
+}
+
+fn compile_in(dir: &Path, latex: &str, cancelled: &dyn Fn() -> bool) -> Result<Vec<u8>, AppError> {
+    let source = dir.join(SOURCE_NAME);
+    fs::write(&source, latex)
+        .map_err(|e| AppError::io(&source, "Failed to write LaTeX source", e))?;
+
+    // 输出写入文件而不是管道，引擎输出很多时不会因管道写满而卡住
+    let output_path = dir.join(OUTPUT_NAME);
+    for engine in ENGINES {
+        let start_error = |e| {
+            AppError::io(
+                Path::new(engine.program()),
+                "Failed to start LaTeX engine",
+                e,
+            )
+        };
+        let output = fs::File::create(&output_path)
+            .map_err(|e| AppError::io(&output_path, "Failed to create output file", e))?;
+        let mut command = engine.command(dir);
+        command
+            .stdout(output.try_clone().map_err(start_error)?)
+            .stderr(output);
+        let mut child = match command.spawn() {
+            Ok(child) => child,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(start_error(e)),
+        };
+        let status = loop {
+            if let Some(status) = child.try_wait().map_err(start_error)? {
+                break status;
+            }
+            if cancelled() {
+                let _ = child.kill();
+                let _ = child.wait();
+                return Err(AppError::Cancelled);
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        };
+        let pdf: PathBuf = dir.join(PDF_NAME);
+        if status.success() {
+            return fs::read(&pdf)
+                .map_err(|e| AppError::io(&pdf, "Failed to read compiled PDF", e));
+        }
+        let log = fs::read_to_string(dir.join(LOG_NAME))
+            .or_else(|_| fs::read(&output_path).map(|b| String::from_utf8_lossy(&b).into_owned()))
+            .unwrap_or_default();
+        return Err(AppError::CompileFailed {
+            engine: engine.program().to_string(),
+            log: log_excerpt(&log),
+        });
+    }
+    Err(AppError::EngineNotFound {
+        tried: ENGINES.iter().map(|e| e.program().to_string()).collect(),
+    })
+}
+
+/// 从编译日志中摘出错误行：`!` 开头的 TeX 错误及其后两行，以及 Tectonic 的 `error:` 行；
+/// 没有识别到错误时返回日志末尾
+fn log_excerpt(log: &str) -> Vec<String> {
+    let lines: Vec<&str> = log.lines().collect();
+    let mut excerpt = Vec::new();
+    let mut idx = 0;
+    while idx < lines.len() && excerpt.len() < MAX_LOG_LINES {
+        let line = lines[idx];
+        if line.starts_with('!') {
+            excerpt.extend(
+                lines[idx..lines.len().min(idx + 3)]
+                    .iter()
+                    .map(|l| l.to_string()),
+            );
+            idx += 3;
+            continue;
+        }
+        if line.trim_start().starts_with("error:") {
+            excerpt.push(line.to_string());
+        }
+        idx += 1;
+    }
+    if excerpt.is_empty() {
+        let start = lines.len().saturating_sub(20);
+        excerpt = lines[start..].iter().map(|l| l.to_string()).collect();
+    }
+    excerpt.truncate(MAX_LOG_LINES);
+    excerpt
+}
@@ -0,0 +1,155 @@
+//! 把 `svg` 模块输出的 SVG 光栅化为 PNG
+//!
+//! 字体从系统中加载一次后缓存。渲染会阻塞，需在 `spawn_blocking` 中调用。
+
+use std::sync::{Arc, OnceLock};
+
+use resvg::tiny_skia::{Color, Pixmap, Transform};
+use resvg::usvg::{self, fontdb};
+
+use crate::error::AppError;
+
+/// SVG 中的 1px 对应 96 DPI
+const CSS_DPI: f32 = 96.0;
+/// 单边像素数上限，超过时按比例缩小
+const MAX_SIDE: f32 = 16384.0;
+/// 总像素数上限（约 160MB 的 RGBA 缓冲区）
+const MAX_PIXELS: f32 = 40_000_000.0;
+
+fn font_database() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+    FONTS
+        .get_or_init(|| {
+            let mut db = fontdb::Database::new();
+            db.load_system_fonts();
+            Arc::new(db)
+        })
+        .clone()
+}
+
+pub struct Raster {
+    pub png: Vec<u8>,
+    pub width: u32,
+    pub height: u32,
+    /// 实际使用的分辨率，超出上限时小于请求值
+    pub dpi: f32,
+}
+
+/// 按 `dpi` 光栅化为 PNG，四周加 `padding` 像素留白；`background` 为 `None` 时背景透明
+pub fn rasterize(
+    svg: &str,
+    dpi: f32,
+    padding: u32,
+    background: Option<Color>,
+) -> Result<Raster, AppError> {
+    let (pixmap, dpi) = render(svg, dpi, padding, background)?;
+    let png = pixmap
+        .encode_png()
+        .map_err(|e| AppError::EncodeFailed {
+            format: "PNG".to_string(),
+            message: e.to_string(),
+        })?;
+    Ok(Raster {
+        png: with_resolution(png, dpi),
+        width: pixmap.width(),
+        height: pixmap.height(),
+        dpi,
+    })
+}
+
+/// 光栅化为非预乘的 RGBA 像素，返回宽、高、像素和实际分辨率
+pub fn rasterize_rgba(
+    svg: &str,
+    dpi: f32,
+    padding: u32,
+    background: Option<Color>,
+) -> Result<(u32, u32, Vec<u8>, f32), AppError> {
+    let (pixmap, dpi) = render(svg, dpi, padding, background)?;
+    let rgba = pixmap
+        .pixels()
+        .iter()
+        .flat_map(|p| {
+            let c = p.demultiply();
+            [c.red(), c.green(), c.blue(), c.alpha()]
+        })
+        .collect();
+    Ok((pixmap.width(), pixmap.height(), rgba, dpi))
+}
+
+fn render(
+    svg: &str,
+    dpi: f32,
+    padding: u32,
+    background: Option<Color>,
+) -> Result<(Pixmap, f32), AppError> {
+    let options = usvg::Options {
+        fontdb: font_database(),
+        ..Default::default()
+    };
+    let tree = usvg::Tree::from_str(svg, &options)
+        .map_err(|e| AppError::InvalidDocument {
+            format: "SVG".to_string(),
+            message: e.to_string(),
+        })?;
+    let size = tree.size();
+    let pad = padding as f32;
+    let longest = size.width().max(size.height());
+    let area = (size.width() * size.height()).max(1.0);
+    let dpi = dpi
+        .min((MAX_SIDE - 2.0 * pad).max(1.0) / longest * CSS_DPI)
+        .min((MAX_PIXELS / area).sqrt() * CSS_DPI);
+    let scale = dpi / CSS_DPI;
+    let width = (size.width() * scale + 2.0 * pad).ceil() as u32;
+    let height = (size.height() * scale + 2.0 * pad).ceil() as u32;
+
+    let mut pixmap = Pixmap::new(width.max(1), height.max(1))
+        .ok_or_else(|| AppError::EncodeFailed {
+            format: "PNG".to_string(),
+            message: format!("cannot allocate a {}x{} image", width, height),
+        })?;
+    if let Some(color) = background {
+        pixmap.fill(color);
+    }
+    let transform = Transform::from_scale(scale, scale).post_translate(pad, pad);
+    resvg::render(&tree, transform, &mut pixmap.as_mut());
+    Ok((pixmap, dpi))
+}
+
+/// 在 IHDR 之后插入 pHYs 块，使 Office 等软件按指定分辨率确定图片的物理尺寸
+fn with_resolution(png: Vec<u8>, dpi: f32) -> Vec<u8> {
+    // 8 字节签名 + IHDR（4 长度 + 4 类型 + 13 数据 + 4 CRC）
+    const IHDR_END: usize = 33;
+    if png.len() < IHDR_END {
+        return png;
+    }
+    let ppm = (dpi / 0.0254).round() as u32;
+    let mut chunk = Vec::with_capacity(21);
+    chunk.extend_from_slice(&9u32.to_be_bytes());
+    chunk.extend_from_slice(b"pHYs");
+    chunk.extend_from_slice(&ppm.to_be_bytes());
+    chunk.extend_from_slice(&ppm.to_be_bytes());
+    chunk.push(1); // 单位：米
+    let crc = crc32(&chunk[4..]);
+    chunk.extend_from_slice(&crc.to_be_bytes());
+
+    let mut out = Vec::with_capacity(png.len() + chunk.len());
+    out.extend_from_slice(&png[..IHDR_END]);
+    out.extend_from_slice(&chunk);
+    out.extend_from_slice(&png[IHDR_END..]);
+    out
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
@@ -0,0 +1,118 @@
+//! 打印公式集：把导出的 HTML 写入临时文件后交给打印
+//!
+//! Windows 上使用系统的 “打印” 动作处理临时文件；其他平台在隐藏窗口中加载该文件，
+//! 页面渲染完成后调用 `window.print()`，对话框关闭后跳转到 `mathlive-print:` 地址，
+//! 后端据此关闭窗口并删除临时文件。用户在对话框中取消打印同样视为完成，不会报错。
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tauri::AppHandle;
+
+use crate::error::AppError;
+
+/// 打印页面的附加样式：不论当前主题都使用白底黑字，公式不跨页断开
+pub const PRINT_CSS: &str = r#":root { color-scheme: light; }
+html, body { background: #fff !important; color: #000 !important; }
+body * { color: #000 !important; background: transparent !important; }
+@page { margin: 1.5cm; }
+@media print {
+  body { max-width: none; margin: 0; }
+  section { break-inside: avoid; }
+}"#;
+
+/// 分节之间强制分页的样式，默认附加，调用方可以通过 `pageBreaks: false` 关闭
+pub const PAGE_BREAK_CSS: &str = ".group + .group { break-before: page; }";
+
+/// 页面加载后打印，结束后通知后端
+#[cfg(not(windows))]
+pub const PRINT_SCRIPT: &str = r#"window.addEventListener('load', function () {
+  window.print();
+  location.href = 'mathlive-print:done';
+});"#;
+
+/// Windows 上由系统的 “打印” 动作负责打印，页面中再调用 `window.print()` 会打印两次
+#[cfg(windows)]
+pub const PRINT_SCRIPT: &str = "";
+
+/// 打印页面结束时跳转的地址协议
+#[cfg(not(windows))]
+const DONE_SCHEME: &str = "mathlive-print";
+
+/// 把打印用的 HTML 写入临时文件
+pub fn write_temp(html: &str) -> Result<PathBuf, AppError> {
+    let path = std::env::temp_dir().join(format!(
+        "mathlive-print-{}-{}.html",
+        std::process::id(),
+        crate::now_millis()
+    ));
+    fs::write(&path, html).map_err(|e| AppError::io(&path, "Failed to write print file", e))?;
+    Ok(path)
+}
+
+/// 打印临时文件；无论成功与否，临时文件最终都会被删除
+pub fn print_file(app: &AppHandle, path: &Path) -> Result<(), AppError> {
+    let result = print_native(app, path);
+    if result.is_err() {
+        let _ = fs::remove_file(path);
+    }
+    result
+}
+
+#[cfg(windows)]
+fn print_native(_app: &AppHandle, path: &Path) -> Result<(), AppError> {
+    /// SW_HIDE
+    const HIDE: i32 = 0;
+    /// 打印程序在后台读取文件，延迟删除临时文件
+    const CLEANUP_DELAY: std::time::Duration = std::time::Duration::from_secs(120);
+
+    crate::opener::shell_execute("print", path, HIDE)?;
+    let path = path.to_path_buf();
+    std::thread::spawn(move || {
+        std::thread::sleep(CLEANUP_DELAY);
+        let _ = fs::remove_file(&path);
+    });
+    Ok(())
+}
+
+#[cfg(not(windows))]
+fn print_native(app: &AppHandle, path: &Path) -> Result<(), AppError> {
+    use tauri::{Url, WindowBuilder, WindowUrl};
+
+    let url = Url::from_file_path(path)
+        .map_err(|_| AppError::InvalidOption {
+            name: "path".to_string(),
+            value: path.display().to_string(),
+        })?;
+    let label = format!("print-{}", crate::now_millis());
+    let handle = app.clone();
+    let window_label = label.clone();
+    let file = path.to_path_buf();
+    WindowBuilder::new(app, label, WindowUrl::External(url))
+        .title("打印")
+        .visible(false)
+        .on_navigation(move |url| {
+            if url.scheme() != DONE_SCHEME {
+                return true;
+            }
+            finish(&handle, &window_label, &file);
+            false
+        })
+        .build()
+        .map_err(AppError::from)?;
+    Ok(())
+}
+
+/// 打印结束：删除临时文件并关闭隐藏窗口，跳过前端的关闭确认
+#[cfg(not(windows))]
+fn finish(app: &AppHandle, label: &str, file: &Path) {
+    use tauri::Manager;
+
+    let _ = fs::remove_file(file);
+    if let Some(window) = app.get_window(label) {
+        app.state::<crate::CloseGuard>().allow(label);
+        if let Err(e) = window.close() {
+            log::error!("Failed to close print window: {}", e);
+        }
+    }
+}
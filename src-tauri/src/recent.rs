@@ -0,0 +1,103 @@
+//! 最近打开的文件列表：保存在配置目录下的 recent.json 中
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::{now_millis, write_file_atomic};
+
+/// 最多保留的条目数
+const MAX_RECENT_FILES: usize = 15;
+
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentFile {
+    pub path: String,
+    pub name: String,
+    /// 最近打开时间（Unix 毫秒时间戳）
+    pub last_opened: u64,
+}
+
+/// 最近文件列表，作为 `tauri::State` 托管；每次操作都读写磁盘，锁用于串行化并发修改
+pub struct RecentFiles {
+    file: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl RecentFiles {
+    pub fn new(file: PathBuf) -> Self {
+        RecentFiles {
+            file,
+            lock: Mutex::new(()),
+        }
+    }
+
+    fn load(&self) -> Vec<RecentFile> {
+        fs::read_to_string(&self.file)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+
+    fn save(&self, entries: &[RecentFile]) -> Result<(), AppError> {
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| AppError::io(dir, "Failed to create config directory", e))?;
+        }
+        let content = serde_json::to_string_pretty(entries).map_err(AppError::encode_json)?;
+        write_file_atomic(&self.file, content.as_bytes())
+    }
+
+    /// 记录一次打开，已存在的条目移到最前
+    pub fn add(&self, path: &str) -> Result<Vec<RecentFile>, AppError> {
+        let _guard = self.lock.lock().unwrap();
+        let canonical = canonical_path(Path::new(path));
+        let name = canonical
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_string());
+        let path = canonical.to_string_lossy().to_string();
+
+        let mut entries = self.load();
+        entries.retain(|entry| canonical_path(Path::new(&entry.path)) != canonical);
+        entries.insert(
+            0,
+            RecentFile {
+                path,
+                name,
+                last_opened: now_millis(),
+            },
+        );
+        entries.truncate(MAX_RECENT_FILES);
+        self.save(&entries)?;
+        Ok(entries)
+    }
+
+    /// 返回最近文件列表，并移除已不存在的文件
+    pub fn list(&self) -> Result<Vec<RecentFile>, AppError> {
+        let _guard = self.lock.lock().unwrap();
+        let entries = self.load();
+        let count = entries.len();
+        let existing: Vec<RecentFile> = entries
+            .into_iter()
+            .filter(|entry| Path::new(&entry.path).exists())
+            .collect();
+        if existing.len() != count {
+            self.save(&existing)?;
+        }
+        Ok(existing)
+    }
+
+    pub fn clear(&self) -> Result<(), AppError> {
+        let _guard = self.lock.lock().unwrap();
+        self.save(&[])
+    }
+}
+
+/// 规范化路径用于去重；文件不存在时退回原始路径
+fn canonical_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
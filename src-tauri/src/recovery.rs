@@ -0,0 +1,161 @@
+//! 崩溃恢复：在数据目录中保存未保存编辑的快照，每个源文件一份
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::AppError;
+use crate::{now_millis, write_file_atomic};
+
+/// 默认保留快照的天数
+pub const DEFAULT_RETENTION_DAYS: u64 = 7;
+
+const DAY_MILLIS: u64 = 24 * 60 * 60 * 1000;
+
+/// 未关联文件的编辑内容使用的快照 id
+const UNTITLED_ID: &str = "untitled";
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Snapshot {
+    id: String,
+    source_path: Option<String>,
+    saved_at: u64,
+    content: String,
+}
+
+#[derive(Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotInfo {
+    pub id: String,
+    pub source_path: Option<String>,
+    /// 快照时间（Unix 毫秒时间戳）
+    pub saved_at: u64,
+    pub size: usize,
+}
+
+impl From<&Snapshot> for SnapshotInfo {
+    fn from(snapshot: &Snapshot) -> Self {
+        SnapshotInfo {
+            id: snapshot.id.clone(),
+            source_path: snapshot.source_path.clone(),
+            saved_at: snapshot.saved_at,
+            size: snapshot.content.len(),
+        }
+    }
+}
+
+/// 恢复快照存储，作为 `tauri::State` 托管
+pub struct Recovery {
+    dir: PathBuf,
+    /// 启动时发现的遗留快照，页面加载后通知前端一次
+    startup: Mutex<Option<Vec<SnapshotInfo>>>,
+}
+
+impl Recovery {
+    /// 打开快照目录并清理过期快照
+    pub fn open(dir: PathBuf, retention_days: u64) -> Self {
+        let recovery = Recovery {
+            dir,
+            startup: Mutex::new(None),
+        };
+        recovery.prune(retention_days);
+        let leftover = recovery.list();
+        if !leftover.is_empty() {
+            *recovery.startup.lock().unwrap() = Some(leftover);
+        }
+        recovery
+    }
+
+    /// 取出启动时发现的遗留快照（只返回一次）
+    pub fn take_startup_snapshots(&self) -> Option<Vec<SnapshotInfo>> {
+        self.startup.lock().unwrap().take()
+    }
+
+    fn snapshot_path(&self, id: &str) -> Result<PathBuf, AppError> {
+        let valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err(AppError::SnapshotNotFound { id: id.to_string() });
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    fn read(&self, id: &str) -> Result<Snapshot, AppError> {
+        let path = self.snapshot_path(id)?;
+        let content = fs::read_to_string(&path).map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => AppError::SnapshotNotFound { id: id.to_string() },
+            _ => AppError::io(&path, "Failed to read snapshot", e),
+        })?;
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    /// 写入（替换）源文件对应的快照，返回快照 id
+    pub fn write(&self, source_path: Option<String>, content: String) -> Result<String, AppError> {
+        let id = source_path
+            .as_deref()
+            .map(snapshot_id)
+            .unwrap_or_else(|| UNTITLED_ID.to_string());
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| AppError::io(&self.dir, "Failed to create recovery directory", e))?;
+        let snapshot = Snapshot {
+            id: id.clone(),
+            source_path,
+            saved_at: now_millis(),
+            content,
+        };
+        let data = serde_json::to_string(&snapshot).map_err(AppError::encode_json)?;
+        write_file_atomic(&self.snapshot_path(&id)?, data.as_bytes())?;
+        Ok(id)
+    }
+
+    /// 列出全部快照，按时间从新到旧排列；无法解析的文件会被忽略
+    pub fn list(&self) -> Vec<SnapshotInfo> {
+        let mut snapshots: Vec<SnapshotInfo> = fs::read_dir(&self.dir)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|entry| fs::read_to_string(entry.path()).ok())
+            .filter_map(|content| serde_json::from_str::<Snapshot>(&content).ok())
+            .map(|snapshot| SnapshotInfo::from(&snapshot))
+            .collect();
+        snapshots.sort_by_key(|s| std::cmp::Reverse(s.saved_at));
+        snapshots
+    }
+
+    /// 读取快照内容
+    pub fn recover(&self, id: &str) -> Result<String, AppError> {
+        self.read(id).map(|snapshot| snapshot.content)
+    }
+
+    /// 删除快照；快照不存在时视为成功
+    pub fn discard(&self, id: &str) -> Result<(), AppError> {
+        let path = self.snapshot_path(id)?;
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                Err(AppError::io(&path, "Failed to delete snapshot", e))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 删除超过保留天数的快照
+    fn prune(&self, retention_days: u64) {
+        let cutoff = now_millis().saturating_sub(retention_days.saturating_mul(DAY_MILLIS));
+        for info in self.list() {
+            if info.saved_at < cutoff {
+                let _ = self.discard(&info.id);
+            }
+        }
+    }
+}
+
+/// 由源文件路径生成稳定的快照 id（FNV-1a 64 位哈希）
+fn snapshot_id(source_path: &str) -> String {
+    let hash = source_path.bytes().fold(0xcbf2_9ce4_8422_2325u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    });
+    format!("{:016x}", hash)
+}
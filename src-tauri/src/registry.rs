@@ -0,0 +1,83 @@
+//! 读写当前用户的注册表（`HKEY_CURRENT_USER`），用于登记 URL 协议和文件关联，以及读取用户的区域设置
+//!
+//! 通过系统自带的 `reg.exe` 完成，参数逐个传递，不经过 shell 拼接。reg.exe 按 OEM 代码页输出，
+//! 中文系统上为 GBK，解码后才能识别其中的提示。
+
+use std::io;
+use std::os::windows::process::CommandExt;
+use std::process::Command;
+
+/// CREATE_NO_WINDOW：不为 reg.exe 弹出控制台窗口
+const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+
+/// reg.exe 拒绝访问时的提示，分别对应英文、简体中文和繁体中文系统
+const ACCESS_DENIED_HINTS: &[&str] = &["Access is denied", "拒绝访问", "拒絕存取"];
+
+/// 按 OEM 代码页解码 reg.exe 的输出；未列出的代码页按 UTF-8 宽松解码，英文提示不受影响
+fn decode_output(bytes: &[u8]) -> String {
+    use encoding_rs::{BIG5, EUC_KR, GBK, SHIFT_JIS, UTF_8};
+    use windows::Win32::Globalization::GetOEMCP;
+
+    let encoding = match unsafe { GetOEMCP() } {
+        936 => GBK,
+        950 => BIG5,
+        932 => SHIFT_JIS,
+        949 => EUC_KR,
+        _ => UTF_8,
+    };
+    encoding.decode_without_bom_handling(bytes).0.into_owned()
+}
+
+fn run(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("reg")
+        .args(args)
+        .creation_flags(CREATE_NO_WINDOW)
+        .output()?;
+    if output.status.success() {
+        return Ok(decode_output(&output.stdout));
+    }
+    let message = decode_output(&output.stderr).trim().to_string();
+    let kind = if ACCESS_DENIED_HINTS
+        .iter()
+        .any(|hint| message.contains(hint))
+    {
+        io::ErrorKind::PermissionDenied
+    } else {
+        io::ErrorKind::Other
+    };
+    Err(io::Error::new(kind, message))
+}
+
+/// 设置 `HKCU\<key>` 下的值，`name` 为 `None` 时设置默认值；键不存在时自动创建
+pub fn set_value(key: &str, name: Option<&str>, value: &str) -> io::Result<()> {
+    let key = format!("HKCU\\{}", key);
+    match name {
+        Some(name) => run(&["add", &key, "/v", name, "/d", value, "/f"]),
+        None => run(&["add", &key, "/ve", "/d", value, "/f"]),
+    }
+    .map(drop)
+}
+
+/// 读取 `HKCU\<key>` 下名为 `name` 的字符串值；键或值不存在时返回 `None`
+pub fn query_value(key: &str, name: &str) -> Option<String> {
+    let key = format!("HKCU\\{}", key);
+    let output = run(&["query", &key, "/v", name]).ok()?;
+    // 输出形如 `    LocaleName    REG_SZ    zh-CN`
+    output.lines().find_map(|line| {
+        let mut fields = line.split_whitespace();
+        if fields.next()? != name {
+            return None;
+        }
+        fields.next().filter(|kind| kind.starts_with("REG_"))?;
+        Some(fields.collect::<Vec<_>>().join(" "))
+    })
+}
+
+/// 删除 `HKCU\<key>` 及其所有子键；键不存在时不做任何事
+pub fn delete_key(key: &str) -> io::Result<()> {
+    let key = format!("HKCU\\{}", key);
+    if run(&["query", &key]).is_err() {
+        return Ok(());
+    }
+    run(&["delete", &key, "/f"]).map(drop)
+}
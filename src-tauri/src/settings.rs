@@ -0,0 +1,113 @@
+//! 应用设置：保存在配置目录下的 settings.json 中，内存中保留一份缓存
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use serde_json::{Map, Value};
+
+use crate::error::AppError;
+use crate::write_file_atomic;
+
+pub const SETTINGS_FILE: &str = "settings.json";
+pub const RECENT_FILE: &str = "recent.json";
+pub const WINDOW_STATE_FILE: &str = "window-state.json";
+
+/// 重置设置时会删除的文件
+const RESETTABLE_FILES: &[&str] = &[SETTINGS_FILE, RECENT_FILE, WINDOW_STATE_FILE];
+
+/// 键值设置存储，作为 `tauri::State` 托管
+///
+/// 读取只访问内存缓存；写入时持有锁完成原子写盘，保证并发写入不会互相覆盖或损坏文件。
+pub struct SettingsStore {
+    dir: PathBuf,
+    cache: Mutex<Map<String, Value>>,
+}
+
+impl SettingsStore {
+    /// 从配置目录加载设置；文件不存在或已损坏时以空设置开始
+    pub fn open(dir: PathBuf) -> Self {
+        let cache = fs::read_to_string(dir.join(SETTINGS_FILE))
+            .ok()
+            .and_then(|content| serde_json::from_str::<Value>(&content).ok())
+            .and_then(|value| match value {
+                Value::Object(map) => Some(map),
+                _ => None,
+            })
+            .unwrap_or_default();
+        SettingsStore {
+            dir,
+            cache: Mutex::new(cache),
+        }
+    }
+
+    /// 配置目录下指定文件的路径
+    pub fn config_file(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+
+    /// 读取单个设置项
+    pub fn get(&self, key: &str) -> Option<Value> {
+        self.cache.lock().unwrap().get(key).cloned()
+    }
+
+    /// 读取全部设置
+    pub fn all(&self) -> Map<String, Value> {
+        self.cache.lock().unwrap().clone()
+    }
+
+    /// 更新单个设置项并写回磁盘；写盘失败时缓存保持不变
+    pub fn set(&self, key: &str, value: Value) -> Result<(), AppError> {
+        let mut cache = self.cache.lock().unwrap();
+        let mut next = cache.clone();
+        next.insert(key.to_string(), value);
+        self.persist(&next)?;
+        *cache = next;
+        Ok(())
+    }
+
+    fn persist(&self, settings: &Map<String, Value>) -> Result<(), AppError> {
+        fs::create_dir_all(&self.dir)
+            .map_err(|e| AppError::io(&self.dir, "Failed to create config directory", e))?;
+        let content = serde_json::to_string_pretty(settings).map_err(AppError::encode_json)?;
+        write_file_atomic(&self.config_file(SETTINGS_FILE), content.as_bytes())
+    }
+
+    /// 删除配置目录中的设置文件并清空缓存，返回实际被删除的文件路径；文件不存在时跳过
+    pub fn reset(&self) -> Result<Vec<String>, AppError> {
+        let mut cache = self.cache.lock().unwrap();
+        cache.clear();
+        if !self.dir.exists() {
+            return Ok(Vec::new());
+        }
+        let dir = self
+            .dir
+            .canonicalize()
+            .map_err(|e| AppError::io(&self.dir, "Failed to resolve config directory", e))?;
+
+        let mut removed = Vec::new();
+        for name in RESETTABLE_FILES {
+            let path = dir.join(name);
+            if fs::symlink_metadata(&path).is_err() {
+                continue;
+            }
+            if !is_inside(&dir, &path) {
+                log::warn!(
+                    "Refusing to delete file outside config directory: {}",
+                    path.display()
+                );
+                return Err(AppError::PermissionDenied {
+                    path: path.to_string_lossy().into_owned(),
+                });
+            }
+            fs::remove_file(&path).map_err(|e| AppError::io(&path, "Failed to delete file", e))?;
+            removed.push(path.to_string_lossy().to_string());
+        }
+        Ok(removed)
+    }
+}
+
+/// 判断路径是否直接位于目录内；符号链接只会删除链接本身，不会影响其指向的文件
+fn is_inside(dir: &Path, path: &Path) -> bool {
+    path.parent().and_then(|p| p.canonicalize().ok()).as_deref() == Some(dir)
+}
@@ -0,0 +1,85 @@
+//! 全局快捷键：在任何应用中按下即可把主窗口调到最前
+//!
+//! 设置中没有该项时使用默认组合；值为 `null` 表示用户已关闭快捷键。
+
+use std::sync::Mutex;
+
+use serde_json::Value;
+use tauri::{AppHandle, GlobalShortcutManager};
+
+use crate::error::AppError;
+use crate::settings::SettingsStore;
+use crate::window_state;
+
+/// 全局快捷键在设置中的键名
+pub const GLOBAL_SHORTCUT_KEY: &str = "globalShortcut";
+
+pub const DEFAULT_SHORTCUT: &str = "CmdOrCtrl+Shift+M";
+
+/// 当前注册的快捷键，作为 `tauri::State` 托管
+#[derive(Default)]
+pub struct GlobalShortcut {
+    current: Mutex<Option<String>>,
+}
+
+impl GlobalShortcut {
+    pub fn current(&self) -> Option<String> {
+        self.current.lock().unwrap().clone()
+    }
+
+    /// 注册新的快捷键并替换之前的；新快捷键注册失败时保留原有的快捷键
+    pub fn register(&self, app: &AppHandle, accelerator: &str) -> Result<(), AppError> {
+        let accelerator = accelerator.trim();
+        let mut current = self.current.lock().unwrap();
+        if current.as_deref() == Some(accelerator) {
+            return Ok(());
+        }
+        let mut manager = app.global_shortcut_manager();
+        let handle = app.clone();
+        manager
+            .register(accelerator, move || summon(&handle))
+            .map_err(|e| AppError::ShortcutUnavailable {
+                accelerator: accelerator.to_string(),
+                message: e.to_string(),
+            })?;
+        if let Some(previous) = current.replace(accelerator.to_string()) {
+            if let Err(e) = manager.unregister(&previous) {
+                log::error!("Failed to unregister shortcut {}: {}", previous, e);
+            }
+        }
+        Ok(())
+    }
+
+    /// 注销当前的快捷键；没有注册时不做任何事
+    pub fn unregister(&self, app: &AppHandle) -> Result<(), AppError> {
+        let mut current = self.current.lock().unwrap();
+        if let Some(accelerator) = current.as_deref() {
+            app.global_shortcut_manager()
+                .unregister(accelerator)
+                .map_err(|e| AppError::ShortcutUnavailable {
+                    accelerator: accelerator.to_string(),
+                    message: e.to_string(),
+                })?;
+        }
+        *current = None;
+        Ok(())
+    }
+}
+
+/// 设置中保存的快捷键；未设置时返回默认值，用户关闭时返回 `None`
+pub fn saved(store: &SettingsStore) -> Option<String> {
+    match store.get(GLOBAL_SHORTCUT_KEY) {
+        None => Some(DEFAULT_SHORTCUT.to_string()),
+        Some(Value::String(accelerator)) => Some(accelerator),
+        Some(_) => None,
+    }
+}
+
+/// 显示并聚焦主窗口，通知前端快捷键被触发
+fn summon(app: &AppHandle) {
+    if let Some(window) = window_state::show_main(app) {
+        if let Err(e) = window.emit("shortcut:summon", ()) {
+            log::warn!("Failed to emit shortcut event: {}", e);
+        }
+    }
+}
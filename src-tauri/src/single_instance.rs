@@ -0,0 +1,228 @@
+//! 单实例：再次启动时把命令行参数转交给已运行的实例，然后退出
+//!
+//! 第一个实例用 `create_new` 创建配置目录下的 `instance.lock`，在本机回环地址上监听，
+//! 并把端口写入锁文件；之后启动的实例读取端口、发送参数并等待确认。锁文件存在但无法连接时
+//! 视为上次异常退出留下的，删除后重新竞争。已运行的实例可能还在启动，收到的参数先缓存，
+//! 等前端调用 `frontend_ready` 后再处理。
+
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{Ipv4Addr, SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use crate::deep_link::{self, DeepLinks};
+use crate::settings::SettingsStore;
+use crate::window_state::show_main;
+
+/// 是否允许同时运行多个实例在设置中的键名
+pub const ALLOW_MULTIPLE_KEY: &str = "allowMultipleInstances";
+
+const LOCK_FILE: &str = "instance.lock";
+const ACK: &str = "ok";
+/// 锁文件已创建但端口尚未写入时，最多等待已有实例完成启动的时间
+const STARTUP_WAIT: Duration = Duration::from_secs(5);
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const IO_TIMEOUT: Duration = Duration::from_secs(5);
+/// 清理残留锁文件后重新竞争的次数
+const MAX_ATTEMPTS: usize = 3;
+
+/// 转交给已运行实例的启动参数（不含程序路径）和工作目录
+#[derive(Serialize, Deserialize)]
+struct Launch {
+    args: Vec<String>,
+    cwd: PathBuf,
+}
+
+#[derive(Serialize, Clone)]
+struct OpenFile {
+    path: String,
+}
+
+pub enum Acquired {
+    /// 当前进程是第一个实例
+    Primary(Instance),
+    /// 参数已转交给已运行的实例，当前进程应退出
+    Forwarded,
+}
+
+#[derive(Default)]
+struct Inbox {
+    app: Option<AppHandle>,
+    pending: Vec<Launch>,
+}
+
+/// 第一个实例持有的锁，作为 `tauri::State` 托管
+pub struct Instance {
+    lock: PathBuf,
+    inbox: Arc<Mutex<Inbox>>,
+}
+
+impl Instance {
+    /// 开始处理转交来的参数，并处理启动期间缓存的参数
+    pub fn attach(&self, app: &AppHandle) {
+        let pending = {
+            let mut inbox = self.inbox.lock().unwrap();
+            if inbox.app.is_some() {
+                return;
+            }
+            inbox.app = Some(app.clone());
+            std::mem::take(&mut inbox.pending)
+        };
+        for launch in pending {
+            handle_launch(app, launch);
+        }
+    }
+
+    /// 退出时删除锁文件
+    pub fn release(&self) {
+        let _ = fs::remove_file(&self.lock);
+    }
+}
+
+/// 设置中是否允许同时运行多个实例
+pub fn allows_multiple(config_dir: &Path) -> bool {
+    SettingsStore::open(config_dir.to_path_buf())
+        .get(ALLOW_MULTIPLE_KEY)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false)
+}
+
+/// 成为第一个实例，或把当前进程的参数转交给已运行的实例
+pub fn acquire(config_dir: &Path) -> Result<Acquired, String> {
+    fs::create_dir_all(config_dir).map_err(|e| e.to_string())?;
+    let lock = config_dir.join(LOCK_FILE);
+    for _ in 0..MAX_ATTEMPTS {
+        match OpenOptions::new().write(true).create_new(true).open(&lock) {
+            Ok(mut file) => {
+                let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))
+                    .and_then(|listener| {
+                        let port = listener.local_addr()?.port();
+                        file.write_all(port.to_string().as_bytes())?;
+                        Ok(listener)
+                    })
+                    .map_err(|e| {
+                        let _ = fs::remove_file(&lock);
+                        format!("Failed to listen for other instances: {}", e)
+                    })?;
+                let inbox = Arc::new(Mutex::new(Inbox::default()));
+                let shared = inbox.clone();
+                std::thread::spawn(move || serve(listener, shared));
+                return Ok(Acquired::Primary(Instance { lock, inbox }));
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                if forward(&lock).is_ok() {
+                    return Ok(Acquired::Forwarded);
+                }
+                // 已有实例没有响应，视为残留的锁文件
+                let _ = fs::remove_file(&lock);
+            }
+            Err(e) => return Err(format!("Failed to create {}: {}", lock.display(), e)),
+        }
+    }
+    Err("Failed to acquire the instance lock".to_string())
+}
+
+/// 读取锁文件中的端口；已有实例还在启动时等待其写入
+fn read_port(lock: &Path) -> io::Result<u16> {
+    let started = Instant::now();
+    loop {
+        if let Ok(port) = fs::read_to_string(lock)?.trim().parse() {
+            return Ok(port);
+        }
+        if started.elapsed() > STARTUP_WAIT {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "no port in lock file",
+            ));
+        }
+        std::thread::sleep(POLL_INTERVAL);
+    }
+}
+
+fn forward(lock: &Path) -> io::Result<()> {
+    let port = read_port(lock)?;
+    let address = SocketAddr::from((Ipv4Addr::LOCALHOST, port));
+    let mut stream = TcpStream::connect_timeout(&address, IO_TIMEOUT)?;
+    stream.set_read_timeout(Some(IO_TIMEOUT))?;
+    let launch = Launch {
+        args: std::env::args_os()
+            .skip(1)
+            .map(|arg| arg.to_string_lossy().into_owned())
+            .collect(),
+        cwd: std::env::current_dir().unwrap_or_default(),
+    };
+    let mut message = serde_json::to_string(&launch)?;
+    message.push('\n');
+    stream.write_all(message.as_bytes())?;
+    let mut reply = String::new();
+    BufReader::new(stream).read_line(&mut reply)?;
+    if reply.trim() == ACK {
+        Ok(())
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "unexpected reply",
+        ))
+    }
+}
+
+fn serve(listener: TcpListener, inbox: Arc<Mutex<Inbox>>) {
+    for stream in listener.incoming() {
+        let launch = stream.and_then(|stream| {
+            stream.set_read_timeout(Some(IO_TIMEOUT))?;
+            let mut line = String::new();
+            BufReader::new(&stream).read_line(&mut line)?;
+            let launch: Launch = serde_json::from_str(&line)?;
+            (&stream).write_all(format!("{}\n", ACK).as_bytes())?;
+            Ok(launch)
+        });
+        let launch = match launch {
+            Ok(launch) => launch,
+            Err(e) => {
+                log::warn!("Ignoring message from another instance: {}", e);
+                continue;
+            }
+        };
+        let mut guard = inbox.lock().unwrap();
+        match guard.app.clone() {
+            Some(app) => {
+                drop(guard);
+                handle_launch(&app, launch);
+            }
+            None => guard.pending.push(launch),
+        }
+    }
+}
+
+/// 参数中第一个存在的公式集文件，相对路径按 `cwd` 解析；扩展名不是公式集的文件会被忽略
+pub fn file_argument(args: &[String], cwd: &Path) -> Option<PathBuf> {
+    args.iter()
+        .filter(|arg| !arg.starts_with('-'))
+        .map(|arg| cwd.join(arg))
+        .filter(|path| crate::is_collection_path(path))
+        .find(|path| path.is_file())
+}
+
+/// 聚焦主窗口，转发深度链接，或通过 `instance:open-file` 事件通知前端打开文件
+fn handle_launch(app: &AppHandle, launch: Launch) {
+    let Some(window) = show_main(app) else {
+        return;
+    };
+    if let Some(link) = deep_link::find_in_args(launch.args.iter().cloned()) {
+        app.state::<DeepLinks>().handle(app, &link);
+        return;
+    }
+    if let Some(path) = file_argument(&launch.args, &launch.cwd) {
+        let payload = OpenFile {
+            path: path.to_string_lossy().into_owned(),
+        };
+        if let Err(e) = window.emit("instance:open-file", payload) {
+            log::warn!("Failed to emit open file event: {}", e);
+        }
+    }
+}
@@ -0,0 +1,735 @@
+//! 在后端把公式排版为 SVG
+//!
+//! 排版基于 `math` 模块的语法树：按近似的字符宽度计算每个盒子的宽度、
+//! 基线以上和以下的高度，再以 `<text>`、`<rect>` 和折线输出。
+//! 尺寸均以 em 为单位，最终通过 `viewBox` 缩放到指定字号。
+
+use crate::math::{escape_xml, Accent, Node, Variant};
+
+const FONT_FAMILY: &str =
+    "'Latin Modern Math', 'STIX Two Math', 'Cambria Math', 'Times New Roman', 'DejaVu Serif', serif";
+
+/// 数学轴（分数线、运算符中心）相对基线的高度
+const AXIS: f64 = 0.25;
+/// 分数线、根号横线的粗细
+const RULE: f64 = 0.05;
+const ASCENT: f64 = 0.72;
+const DESCENT: f64 = 0.22;
+const SCRIPT_SCALE: f64 = 0.7;
+const MIN_SCALE: f64 = 0.5;
+/// 整个公式四周的留白
+const PADDING: f64 = 0.1;
+
+#[derive(Clone, Copy)]
+struct Style {
+    scale: f64,
+    variant: Option<Variant>,
+    display: bool,
+}
+
+impl Style {
+    fn script(self) -> Style {
+        Style {
+            scale: (self.scale * SCRIPT_SCALE).max(MIN_SCALE),
+            display: false,
+            ..self
+        }
+    }
+
+    fn text(self) -> Style {
+        Style {
+            display: false,
+            ..self
+        }
+    }
+}
+
+struct Glyphs {
+    text: String,
+    italic: bool,
+    bold: bool,
+    family: Option<&'static str>,
+}
+
+enum Item {
+    /// `y` 为基线；`stretch` 是竖直方向的拉伸倍数，用于可伸缩的定界符
+    Text {
+        x: f64,
+        y: f64,
+        size: f64,
+        glyphs: Glyphs,
+        stretch: f64,
+    },
+    /// 以 `y` 为中心线的水平线
+    Rule {
+        x: f64,
+        y: f64,
+        width: f64,
+        thickness: f64,
+    },
+    Path {
+        points: Vec<(f64, f64)>,
+        thickness: f64,
+    },
+}
+
+impl Item {
+    fn shift(&mut self, dx: f64, dy: f64) {
+        match self {
+            Item::Text { x, y, .. } | Item::Rule { x, y, .. } => {
+                *x += dx;
+                *y += dy;
+            }
+            Item::Path { points, .. } => {
+                for (x, y) in points {
+                    *x += dx;
+                    *y += dy;
+                }
+            }
+        }
+    }
+}
+
+/// 排版后的盒子，原点在左侧基线上，`y` 轴向下
+struct Layout {
+    width: f64,
+    ascent: f64,
+    descent: f64,
+    items: Vec<Item>,
+}
+
+impl Layout {
+    fn empty() -> Self {
+        Layout {
+            width: 0.0,
+            ascent: 0.0,
+            descent: 0.0,
+            items: Vec::new(),
+        }
+    }
+
+    /// 把另一个盒子的内容平移后放入，不改变本盒子的尺寸
+    fn place(&mut self, other: Layout, dx: f64, dy: f64) {
+        for mut item in other.items {
+            item.shift(dx, dy);
+            self.items.push(item);
+        }
+    }
+
+    /// 在宽度为 `width` 的区域中水平居中
+    fn shifted_center(self, width: f64) -> Layout {
+        let dx = (width - self.width) / 2.0;
+        let mut out = Layout {
+            width,
+            ascent: self.ascent,
+            descent: self.descent,
+            items: Vec::new(),
+        };
+        out.place(self, dx, 0.0);
+        out
+    }
+
+    /// 水平拼接
+    fn append(&mut self, other: Layout) {
+        let dx = self.width;
+        self.width += other.width;
+        self.ascent = self.ascent.max(other.ascent);
+        self.descent = self.descent.max(other.descent);
+        self.place(other, dx, 0.0);
+    }
+}
+
+fn char_width(ch: char) -> f64 {
+    match ch {
+        ' ' => 0.25,
+        'i' | 'j' | 'l' | '!' | '.' | ',' | ';' | ':' | '\'' | '′' | '|' => 0.28,
+        'f' | 't' | 'r' | '(' | ')' | '[' | ']' | '{' | '}' | '⟨' | '⟩' | '‖' | '⌊' | '⌋' | '⌈'
+        | '⌉' => 0.39,
+        'm' | 'w' | 'M' | 'W' => 0.85,
+        'A'..='Z' => 0.72,
+        'a'..='z' | '0'..='9' => 0.5,
+        'α'..='ω' => 0.55,
+        'Α'..='Ω' => 0.7,
+        c if crate::contains_cjk(&c.to_string()) => 1.0,
+        c if c.is_alphanumeric() => 0.6,
+        _ => 0.78,
+    }
+}
+
+fn text_width(text: &str) -> f64 {
+    text.chars().map(char_width).sum()
+}
+
+fn map_alphanumeric(ch: char, variant: Variant) -> char {
+    let special = match (variant, ch) {
+        (Variant::DoubleStruck, 'C') => Some('ℂ'),
+        (Variant::DoubleStruck, 'H') => Some('ℍ'),
+        (Variant::DoubleStruck, 'N') => Some('ℕ'),
+        (Variant::DoubleStruck, 'P') => Some('ℙ'),
+        (Variant::DoubleStruck, 'Q') => Some('ℚ'),
+        (Variant::DoubleStruck, 'R') => Some('ℝ'),
+        (Variant::DoubleStruck, 'Z') => Some('ℤ'),
+        (Variant::Script, 'B') => Some('ℬ'),
+        (Variant::Script, 'E') => Some('ℰ'),
+        (Variant::Script, 'F') => Some('ℱ'),
+        (Variant::Script, 'H') => Some('ℋ'),
+        (Variant::Script, 'I') => Some('ℐ'),
+        (Variant::Script, 'L') => Some('ℒ'),
+        (Variant::Script, 'M') => Some('ℳ'),
+        (Variant::Script, 'R') => Some('ℛ'),
+        (Variant::Script, 'e') => Some('ℯ'),
+        (Variant::Script, 'g') => Some('ℊ'),
+        (Variant::Script, 'o') => Some('ℴ'),
+        (Variant::Fraktur, 'C') => Some('ℭ'),
+        (Variant::Fraktur, 'H') => Some('ℌ'),
+        (Variant::Fraktur, 'I') => Some('ℑ'),
+        (Variant::Fraktur, 'R') => Some('ℜ'),
+        (Variant::Fraktur, 'Z') => Some('ℨ'),
+        _ => None,
+    };
+    if let Some(c) = special {
+        return c;
+    }
+    let (upper, lower, digit) = match variant {
+        Variant::DoubleStruck => (0x1D538, 0x1D552, Some(0x1D7D8)),
+        Variant::Script => (0x1D49C, 0x1D4B6, None),
+        Variant::Fraktur => (0x1D504, 0x1D51E, None),
+        _ => return ch,
+    };
+    let code = match ch {
+        'A'..='Z' => upper + (ch as u32 - 'A' as u32),
+        'a'..='z' => lower + (ch as u32 - 'a' as u32),
+        '0'..='9' => match digit {
+            Some(base) => base + (ch as u32 - '0' as u32),
+            None => return ch,
+        },
+        _ => return ch,
+    };
+    char::from_u32(code).unwrap_or(ch)
+}
+
+fn glyphs(text: &str, variant: Option<Variant>, default_italic: bool) -> Glyphs {
+    let mut g = Glyphs {
+        text: text.to_string(),
+        italic: default_italic,
+        bold: false,
+        family: None,
+    };
+    match variant {
+        None => {}
+        Some(Variant::Normal) => g.italic = false,
+        Some(Variant::Bold) => {
+            g.italic = false;
+            g.bold = true;
+        }
+        Some(Variant::Italic) => g.italic = true,
+        Some(Variant::SansSerif) => {
+            g.italic = false;
+            g.family = Some("sans-serif");
+        }
+        Some(Variant::Monospace) => {
+            g.italic = false;
+            g.family = Some("monospace");
+        }
+        Some(v) => {
+            g.italic = false;
+            g.text = text.chars().map(|c| map_alphanumeric(c, v)).collect();
+        }
+    }
+    g
+}
+
+fn text_box(glyphs: Glyphs, style: Style) -> Layout {
+    let s = style.scale;
+    let width = text_width(&glyphs.text) * s;
+    Layout {
+        width,
+        ascent: ASCENT * s,
+        descent: DESCENT * s,
+        items: vec![Item::Text {
+            x: 0.0,
+            y: 0.0,
+            size: s,
+            glyphs,
+            stretch: 1.0,
+        }],
+    }
+}
+
+/// 二元运算符和关系符两侧的间距
+fn operator_spacing(op: &str) -> (f64, f64) {
+    match op {
+        "+" | "−" | "-" | "×" | "÷" | "±" | "∓" | "·" | "⋅" | "∘" | "∪" | "∩" | "∧" | "∨" | "⊕"
+        | "⊗" | "∖" | "*" => (0.222, 0.222),
+        "=" | "<" | ">" | "≤" | "≥" | "≠" | "≈" | "≡" | "∼" | "≃" | "≅" | "→" | "←" | "↔" | "⇒"
+        | "⇐" | "⇔" | "↦" | "∈" | "∉" | "∋" | "⊂" | "⊆" | "⊃" | "⊇" | "∝" | "≪" | "≫" | ":"
+        | "∣" | "⟹" | "⟸" | "⟺" | "⟶" | "⟵" => (0.278, 0.278),
+        "," | ";" => (0.0, 0.167),
+        _ => (0.0, 0.0),
+    }
+}
+
+/// 大型运算符的字形宽度；积分号是窄的斜体字形
+fn large_op_width(op: &str) -> f64 {
+    match op {
+        "∫" | "∮" => 0.55,
+        "∬" => 0.8,
+        "∭" => 1.05,
+        _ => 0.9,
+    }
+}
+
+/// 在行间公式中把上下标放在正上方和正下方的底数
+fn uses_limits(base: &Node) -> bool {
+    match base {
+        Node::LargeOp(op) => !matches!(op.as_str(), "∫" | "∬" | "∭" | "∮"),
+        Node::Ident(name) => matches!(
+            name.as_str(),
+            "lim" | "max" | "min" | "sup" | "inf" | "limsup" | "liminf"
+        ),
+        _ => false,
+    }
+}
+
+/// 以数学轴为中心、高度为 `2 * half` 的定界符
+fn delimiter(symbol: &str, half: f64, style: Style) -> Layout {
+    if symbol.is_empty() {
+        return Layout::empty();
+    }
+    let s = style.scale;
+    let stretch = (2.0 * half / s).max(1.0);
+    Layout {
+        width: text_width(symbol) * s,
+        ascent: AXIS * s + half.max(0.5 * s),
+        descent: half.max(0.5 * s) - AXIS * s,
+        items: vec![Item::Text {
+            x: 0.0,
+            // 字形中心约在基线以上 0.25em，拉伸后仍对齐到数学轴
+            y: 0.25 * s * stretch - AXIS * s,
+            size: s,
+            glyphs: glyphs(symbol, None, false),
+            stretch,
+        }],
+    }
+}
+
+fn fenced(open: &str, close: &str, body: Layout, style: Style) -> Layout {
+    let s = style.scale;
+    let half = (body.ascent - AXIS * s).max(body.descent + AXIS * s) + 0.05 * s;
+    let mut out = delimiter(open, half, style);
+    out.append(body);
+    out.append(delimiter(close, half, style));
+    out
+}
+
+fn layout(node: &Node, style: Style) -> Layout {
+    let s = style.scale;
+    match node {
+        Node::Ident(name) => {
+            // 单个字母按数学惯例用斜体，函数名和 `\infty` 等符号用正体
+            let mut chars = name.chars();
+            let italic =
+                matches!((chars.next(), chars.next()), (Some(c), None) if c.is_alphabetic());
+            text_box(glyphs(name, style.variant, italic), style)
+        }
+        Node::Number(n) => text_box(glyphs(n, style.variant, false), style),
+        Node::Operator(op) => {
+            let (left, right) = if s >= 0.85 {
+                operator_spacing(op)
+            } else {
+                (0.0, 0.0)
+            };
+            let mut out = Layout {
+                width: left * s,
+                ..Layout::empty()
+            };
+            out.append(text_box(glyphs(op, None, false), style));
+            out.width += right * s;
+            out
+        }
+        Node::LargeOp(op) => {
+            let size = s * if style.display { 1.6 } else { 1.2 };
+            let dy = 0.25 * size - AXIS * s;
+            Layout {
+                width: large_op_width(op) * size + 0.1 * s,
+                ascent: ASCENT * size - dy,
+                descent: DESCENT * size + dy,
+                items: vec![Item::Text {
+                    x: 0.05 * s,
+                    y: dy,
+                    size,
+                    glyphs: glyphs(op, None, false),
+                    stretch: 1.0,
+                }],
+            }
+        }
+        Node::Text(text) => text_box(glyphs(text, None, false), style),
+        Node::Space(width) => Layout {
+            width: width.trim_end_matches("em").parse::<f64>().unwrap_or(0.0) * s,
+            ..Layout::empty()
+        },
+        Node::Row(nodes) => {
+            let mut out = Layout::empty();
+            for n in nodes {
+                out.append(layout(n, style));
+            }
+            out
+        }
+        Node::Frac { num, den, bar } => {
+            let inner = if style.display {
+                style.text()
+            } else {
+                style.script()
+            };
+            let (num, den) = (layout(num, inner), layout(den, inner));
+            let gap = 0.12 * s;
+            let width = num.width.max(den.width) + 0.2 * s;
+            let axis = -AXIS * s;
+            let num_y = axis - RULE * s / 2.0 - gap - num.descent;
+            let den_y = axis + RULE * s / 2.0 + gap + den.ascent;
+            let mut out = Layout {
+                width,
+                ascent: num.ascent - num_y,
+                descent: den_y + den.descent,
+                items: Vec::new(),
+            };
+            if *bar {
+                out.items.push(Item::Rule {
+                    x: 0.05 * s,
+                    y: axis,
+                    width: width - 0.1 * s,
+                    thickness: RULE * s,
+                });
+            }
+            out.place(num.shifted_center(width), 0.0, num_y);
+            out.place(den.shifted_center(width), 0.0, den_y);
+            out
+        }
+        Node::Sqrt { body, index } => {
+            let body = layout(body, style);
+            let top = -(body.ascent + 0.12 * s);
+            let bottom = body.descent + 0.05 * s;
+            let mid = bottom - (bottom - top) * 0.45;
+            let index = index.as_deref().map(|i| layout(i, style.script().script()));
+            let offset = index
+                .as_ref()
+                .map_or(0.0, |i| (i.width - 0.25 * s).max(0.0));
+            let sign = 0.55 * s;
+            let mut out = Layout {
+                width: offset + sign + body.width + 0.1 * s,
+                ascent: -top + RULE * s + 0.05 * s,
+                descent: bottom,
+                items: Vec::new(),
+            };
+            out.items.push(Item::Path {
+                points: vec![
+                    (offset, mid),
+                    (offset + 0.12 * s, mid - 0.06 * s),
+                    (offset + 0.28 * s, bottom),
+                    (offset + 0.5 * s, top),
+                    (out.width, top),
+                ],
+                thickness: RULE * s,
+            });
+            out.place(body, offset + sign, 0.0);
+            if let Some(index) = index {
+                let y = mid - 0.1 * s - index.descent;
+                out.ascent = out.ascent.max(index.ascent - y);
+                out.place(index, 0.0, y);
+            }
+            out
+        }
+        Node::Scripts { base, sub, sup } => {
+            let limits = style.display && uses_limits(base);
+            let base_box = layout(base, style);
+            let script = style.script();
+            let sub = sub.as_deref().map(|n| layout(n, script));
+            let sup = sup.as_deref().map(|n| layout(n, script));
+            if limits {
+                let width = [
+                    Some(base_box.width),
+                    sub.as_ref().map(|b| b.width),
+                    sup.as_ref().map(|b| b.width),
+                ]
+                .into_iter()
+                .flatten()
+                .fold(0.0, f64::max);
+                let mut out = Layout {
+                    width,
+                    ascent: base_box.ascent,
+                    descent: base_box.descent,
+                    items: Vec::new(),
+                };
+                if let Some(sup) = sup {
+                    let y = -(base_box.ascent + 0.1 * s + sup.descent);
+                    out.ascent = sup.ascent - y;
+                    out.place(sup.shifted_center(width), 0.0, y);
+                }
+                if let Some(sub) = sub {
+                    let y = base_box.descent + 0.1 * s + sub.ascent;
+                    out.descent = y + sub.descent;
+                    out.place(sub.shifted_center(width), 0.0, y);
+                }
+                out.place(base_box.shifted_center(width), 0.0, 0.0);
+                return out;
+            }
+            let mut up = (base_box.ascent - 0.3 * s).max(0.42 * s);
+            let mut down = base_box.descent.max(0.22 * s);
+            if let (Some(sup), Some(sub)) = (&sup, &sub) {
+                let gap = (up - sup.descent) - (sub.ascent - down);
+                if gap < 0.1 * s {
+                    down += 0.1 * s - gap;
+                }
+            }
+            let x = base_box.width;
+            let mut out = base_box;
+            let mut extra: f64 = 0.0;
+            if let Some(sup) = sup {
+                up = up.max(sup.descent + 0.1 * s);
+                out.ascent = out.ascent.max(sup.ascent + up);
+                extra = extra.max(sup.width + 0.03 * s);
+                out.place(sup, x + 0.03 * s, -up);
+            }
+            if let Some(sub) = sub {
+                out.descent = out.descent.max(sub.descent + down);
+                extra = extra.max(sub.width);
+                out.place(sub, x, down);
+            }
+            out.width = x + extra + 0.05 * s;
+            out
+        }
+        Node::Accent { body, accent } => {
+            let body = layout(body, style);
+            let width = body.width;
+            let mut out = Layout {
+                width,
+                ascent: body.ascent,
+                descent: body.descent,
+                items: Vec::new(),
+            };
+            match accent {
+                Accent::Bar | Accent::Overline => {
+                    let y = -(body.ascent + 0.08 * s);
+                    out.ascent = -y + RULE * s;
+                    out.items.push(Item::Rule {
+                        x: 0.0,
+                        y,
+                        width,
+                        thickness: RULE * s,
+                    });
+                }
+                Accent::Underline => {
+                    let y = body.descent + 0.08 * s;
+                    out.descent = y + RULE * s;
+                    out.items.push(Item::Rule {
+                        x: 0.0,
+                        y,
+                        width,
+                        thickness: RULE * s,
+                    });
+                }
+                _ => {
+                    let (symbol, lift) = match accent {
+                        Accent::Hat => ("ˆ", 0.35),
+                        Accent::Vec => ("→", 0.15),
+                        Accent::Dot => ("˙", 0.35),
+                        Accent::Ddot => ("¨", 0.35),
+                        _ => ("˜", 0.35),
+                    };
+                    let mark = text_box(glyphs(symbol, None, false), style);
+                    let y = -(body.ascent - lift * s);
+                    out.ascent = body.ascent + 0.3 * s;
+                    out.place(mark.shifted_center(width), 0.0, y);
+                }
+            }
+            out.place(body, 0.0, 0.0);
+            out
+        }
+        Node::Fenced { open, close, body } => fenced(open, close, layout(body, style), style),
+        Node::Matrix {
+            rows,
+            open,
+            close,
+            aligned,
+        } => {
+            let inner = style.text();
+            let cells: Vec<Vec<Layout>> = rows
+                .iter()
+                .map(|row| row.iter().map(|cell| layout(cell, inner)).collect())
+                .collect();
+            let columns = cells.iter().map(Vec::len).max().unwrap_or(0);
+            let mut widths = vec![0.0f64; columns];
+            for row in &cells {
+                for (col, cell) in row.iter().enumerate() {
+                    widths[col] = widths[col].max(cell.width);
+                }
+            }
+            let is_cases = open == "{" && close.is_empty();
+            let col_gap = if *aligned {
+                0.0
+            } else if is_cases {
+                1.0 * s
+            } else {
+                0.8 * s
+            };
+            let row_gap = 0.3 * s;
+            let heights: Vec<(f64, f64)> = cells
+                .iter()
+                .map(|row| {
+                    row.iter().fold((0.6 * s, 0.2 * s), |(a, d), c| {
+                        (a.max(c.ascent), d.max(c.descent))
+                    })
+                })
+                .collect();
+            let total: f64 = heights.iter().map(|(a, d)| a + d).sum::<f64>()
+                + row_gap * heights.len().saturating_sub(1) as f64;
+            let pad = if open.is_empty() { 0.0 } else { 0.1 * s };
+            let mut table = Layout {
+                width: widths.iter().sum::<f64>()
+                    + col_gap * columns.saturating_sub(1) as f64
+                    + 2.0 * pad,
+                ascent: total / 2.0 + AXIS * s,
+                descent: total / 2.0 - AXIS * s,
+                items: Vec::new(),
+            };
+            let mut y = -AXIS * s - total / 2.0;
+            for (row, (ascent, descent)) in cells.into_iter().zip(heights) {
+                y += ascent;
+                let mut x = pad;
+                for (col, cell) in row.into_iter().enumerate() {
+                    // 对齐环境中奇数列右对齐、偶数列左对齐；cases 左对齐；矩阵居中
+                    let dx = if *aligned {
+                        if col % 2 == 0 {
+                            widths[col] - cell.width
+                        } else {
+                            0.0
+                        }
+                    } else if is_cases {
+                        0.0
+                    } else {
+                        (widths[col] - cell.width) / 2.0
+                    };
+                    table.place(cell, x + dx, y);
+                    x += widths[col] + col_gap;
+                }
+                y += descent + row_gap;
+            }
+            if open.is_empty() && close.is_empty() {
+                table
+            } else {
+                fenced(open, close, table, style)
+            }
+        }
+        Node::Styled { variant, body } => layout(
+            body,
+            Style {
+                variant: Some(*variant),
+                ..style
+            },
+        ),
+    }
+}
+
+fn num(value: f64) -> String {
+    let text = format!("{:.3}", value);
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    if text == "-0" {
+        "0".to_string()
+    } else {
+        text.to_string()
+    }
+}
+
+/// 排版并输出 SVG；`font_size` 是 1em 对应的像素数
+pub fn render(node: &Node, latex: &str, font_size: f64, display: bool, color: &str) -> String {
+    let style = Style {
+        scale: 1.0,
+        variant: None,
+        display,
+    };
+    let layout = layout(node, style);
+    let width = layout.width + 2.0 * PADDING;
+    let height = layout.ascent + layout.descent + 2.0 * PADDING;
+
+    let mut body = String::new();
+    for item in &layout.items {
+        match item {
+            Item::Text {
+                x,
+                y,
+                size,
+                glyphs,
+                stretch,
+            } => {
+                let mut attrs = format!("font-size=\"{}\"", num(*size));
+                if glyphs.italic {
+                    attrs.push_str(" font-style=\"italic\"");
+                }
+                if glyphs.bold {
+                    attrs.push_str(" font-weight=\"bold\"");
+                }
+                if let Some(family) = glyphs.family {
+                    attrs.push_str(&format!(" font-family=\"{}\"", family));
+                }
+                if glyphs.text.contains(' ') {
+                    attrs.push_str(" xml:space=\"preserve\"");
+                }
+                let position = if (*stretch - 1.0).abs() > 1e-3 {
+                    format!(
+                        "transform=\"translate({} {}) scale(1 {})\"",
+                        num(*x),
+                        num(*y),
+                        num(*stretch)
+                    )
+                } else {
+                    format!("x=\"{}\" y=\"{}\"", num(*x), num(*y))
+                };
+                body.push_str(&format!(
+                    "<text {} {}>{}</text>",
+                    position,
+                    attrs,
+                    escape_xml(&glyphs.text)
+                ));
+            }
+            Item::Rule {
+                x,
+                y,
+                width,
+                thickness,
+            } => body.push_str(&format!(
+                "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+                num(*x),
+                num(*y - thickness / 2.0),
+                num(*width),
+                num(*thickness)
+            )),
+            Item::Path { points, thickness } => {
+                let points = points
+                    .iter()
+                    .map(|(x, y)| format!("{},{}", num(*x), num(*y)))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                body.push_str(&format!(
+                    "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\" stroke-width=\"{}\" stroke-linejoin=\"round\"/>",
+                    points,
+                    escape_xml(color),
+                    num(*thickness)
+                ));
+            }
+        }
+    }
+
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"{x} {y} {vw} {vh}\"><title>{title}</title><g fill=\"{color}\" font-family=\"{family}\">{body}</g></svg>",
+        w = num(width * font_size),
+        h = num(height * font_size),
+        x = num(-PADDING),
+        y = num(-layout.ascent - PADDING),
+        vw = num(width),
+        vh = num(height),
+        title = escape_xml(latex),
+        color = escape_xml(color),
+        family = FONT_FAMILY,
+        body = body
+    )
+}
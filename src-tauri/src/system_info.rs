@@ -0,0 +1,241 @@
+//! 收集用于问题反馈的系统信息
+//!
+//! 各项信息分别获取，某一项在当前平台上无法确定时为 `None`（前端收到 `null`），
+//! 不影响其他项。可能需要启动 `sw_vers`、`cmd` 等系统命令，需在阻塞线程池中调用。
+
+use std::fmt::Write as _;
+
+use serde::Serialize;
+use tauri::AppHandle;
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemInfo {
+    /// `windows`、`macos`、`linux` 等
+    os: &'static str,
+    /// 发行版或产品名称，例如 `Ubuntu 22.04.3 LTS`
+    os_name: Option<String>,
+    os_version: Option<String>,
+    arch: &'static str,
+    app_version: String,
+    tauri_version: &'static str,
+    /// 实际使用的 WebView2 或 WebKitGTK 版本
+    webview_version: Option<String>,
+    /// 物理内存总量，单位为字节
+    total_memory: Option<u64>,
+    available_memory: Option<u64>,
+    /// BCP 47 格式，例如 `zh-CN`
+    locale: Option<String>,
+    config_dir: Option<String>,
+    data_dir: Option<String>,
+}
+
+pub fn collect(app: &AppHandle) -> SystemInfo {
+    let resolver = app.path_resolver();
+    let path_text =
+        |path: Option<std::path::PathBuf>| path.map(|p| p.to_string_lossy().into_owned());
+    let (os_name, os_version) = os_release();
+    let (total_memory, available_memory) = memory();
+    SystemInfo {
+        os: std::env::consts::OS,
+        os_name,
+        os_version,
+        arch: std::env::consts::ARCH,
+        app_version: app.package_info().version.to_string(),
+        tauri_version: tauri::VERSION,
+        webview_version: tauri::webview_version().ok(),
+        total_memory,
+        available_memory,
+        locale: locale(),
+        config_dir: path_text(resolver.app_config_dir()),
+        data_dir: path_text(resolver.app_data_dir()),
+    }
+}
+
+impl SystemInfo {
+    /// 便于粘贴到问题报告中的文本，无法确定的项显示为 `unknown`
+    pub fn to_text(&self, app_name: &str) -> String {
+        let unknown =
+            |value: &Option<String>| value.clone().unwrap_or_else(|| "unknown".to_string());
+        let memory = match (self.available_memory, self.total_memory) {
+            (Some(available), Some(total)) => {
+                format!(
+                    "{} available / {} total",
+                    gigabytes(available),
+                    gigabytes(total)
+                )
+            }
+            (None, Some(total)) => format!("{} total", gigabytes(total)),
+            _ => "unknown".to_string(),
+        };
+        let mut text = String::new();
+        let mut line = |label: &str, value: String| {
+            let _ = writeln!(text, "- {}: {}", label, value);
+        };
+        line("App", format!("{} {}", app_name, self.app_version));
+        line("Tauri", self.tauri_version.to_string());
+        line(
+            "OS",
+            format!(
+                "{} {} ({}, {})",
+                self.os_name.as_deref().unwrap_or(self.os),
+                unknown(&self.os_version),
+                self.os,
+                self.arch
+            ),
+        );
+        line("WebView", unknown(&self.webview_version));
+        line("Memory", memory);
+        line("Locale", unknown(&self.locale));
+        line("Config dir", unknown(&self.config_dir));
+        line("Data dir", unknown(&self.data_dir));
+        text
+    }
+}
+
+fn gigabytes(bytes: u64) -> String {
+    format!("{:.1} GB", bytes as f64 / 1_073_741_824.0)
+}
+
+/// 运行命令并返回去掉首尾空白的标准输出；命令失败或输出为空时返回 `None`
+#[cfg(not(target_os = "linux"))]
+fn command_output(command: &mut std::process::Command) -> Option<String> {
+    let output = command.output().ok()?;
+    let text = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    (output.status.success() && !text.is_empty()).then_some(text)
+}
+
+#[cfg(target_os = "linux")]
+fn os_release() -> (Option<String>, Option<String>) {
+    let content = std::fs::read_to_string("/etc/os-release")
+        .or_else(|_| std::fs::read_to_string("/usr/lib/os-release"))
+        .unwrap_or_default();
+    let field = |key: &str| {
+        content.lines().find_map(|line| {
+            let value = line.strip_prefix(key)?.strip_prefix('=')?;
+            Some(value.trim_matches('"').to_string()).filter(|v| !v.is_empty())
+        })
+    };
+    let name = field("PRETTY_NAME").or_else(|| field("NAME"));
+    // 发行版没有版本号（如 Arch Linux）时用内核版本
+    let version = field("VERSION_ID").or_else(|| {
+        std::fs::read_to_string("/proc/sys/kernel/osrelease")
+            .ok()
+            .map(|v| v.trim().to_string())
+    });
+    (name, version)
+}
+
+#[cfg(target_os = "macos")]
+fn os_release() -> (Option<String>, Option<String>) {
+    use std::process::Command;
+
+    let name = command_output(Command::new("sw_vers").arg("-productName"));
+    let version = command_output(Command::new("sw_vers").arg("-productVersion"));
+    (name, version)
+}
+
+#[cfg(windows)]
+fn os_release() -> (Option<String>, Option<String>) {
+    use std::os::windows::process::CommandExt;
+    use std::process::Command;
+
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    // 输出形如 `Microsoft Windows [Version 10.0.22631.2861]`，中文系统为 `[版本 ...]`
+    let version = command_output(
+        Command::new("cmd")
+            .args(["/C", "ver"])
+            .creation_flags(CREATE_NO_WINDOW),
+    )
+    .and_then(|text| {
+        let inner = text.rsplit_once('[')?.1.trim_end_matches(']');
+        inner.split_whitespace().last().map(str::to_string)
+    });
+    (Some("Windows".to_string()), version)
+}
+
+/// 物理内存的总量和可用量
+#[cfg(target_os = "linux")]
+fn memory() -> (Option<u64>, Option<u64>) {
+    let content = std::fs::read_to_string("/proc/meminfo").unwrap_or_default();
+    let field = |key: &str| {
+        content.lines().find_map(|line| {
+            let value = line.strip_prefix(key)?.strip_prefix(':')?;
+            let kilobytes: u64 = value.trim().trim_end_matches("kB").trim().parse().ok()?;
+            Some(kilobytes * 1024)
+        })
+    };
+    (field("MemTotal"), field("MemAvailable"))
+}
+
+#[cfg(target_os = "macos")]
+fn memory() -> (Option<u64>, Option<u64>) {
+    use std::process::Command;
+
+    let total = command_output(Command::new("sysctl").args(["-n", "hw.memsize"]))
+        .and_then(|text| text.parse().ok());
+    // `vm_stat` 以页为单位，首行给出页大小：`... (page size of 16384 bytes)`
+    let available = command_output(&mut Command::new("vm_stat")).and_then(|text| {
+        let page_size: u64 = text
+            .split("page size of ")
+            .nth(1)?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()?;
+        let pages = |key: &str| -> Option<u64> {
+            text.lines().find_map(|line| {
+                line.strip_prefix(key)?
+                    .trim()
+                    .trim_end_matches('.')
+                    .parse()
+                    .ok()
+            })
+        };
+        Some((pages("Pages free:")? + pages("Pages inactive:").unwrap_or(0)) * page_size)
+    });
+    (total, available)
+}
+
+#[cfg(windows)]
+fn memory() -> (Option<u64>, Option<u64>) {
+    use windows::Win32::System::SystemInformation::{GlobalMemoryStatusEx, MEMORYSTATUSEX};
+
+    let mut status = MEMORYSTATUSEX {
+        dwLength: std::mem::size_of::<MEMORYSTATUSEX>() as u32,
+        ..Default::default()
+    };
+    if unsafe { GlobalMemoryStatusEx(&mut status) }.as_bool() {
+        (Some(status.ullTotalPhys), Some(status.ullAvailPhys))
+    } else {
+        (None, None)
+    }
+}
+
+/// 把 `zh_CN.UTF-8`、`en_US@euro` 之类的写法转换为 `zh-CN`
+#[cfg(not(windows))]
+fn normalize_locale(value: &str) -> Option<String> {
+    let tag = value.split(['.', '@']).next()?.trim().replace('_', "-");
+    (!tag.is_empty() && tag != "C" && tag != "POSIX").then_some(tag)
+}
+
+#[cfg(target_os = "linux")]
+pub fn locale() -> Option<String> {
+    ["LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|key| std::env::var(key).ok())
+        .find_map(|value| normalize_locale(&value))
+}
+
+/// 从 Finder 启动的应用没有 `LANG`，读取系统的区域设置
+#[cfg(target_os = "macos")]
+pub fn locale() -> Option<String> {
+    command_output(std::process::Command::new("defaults").args(["read", "-g", "AppleLocale"]))
+        .or_else(|| std::env::var("LANG").ok())
+        .and_then(|value| normalize_locale(&value))
+}
+
+#[cfg(windows)]
+pub fn locale() -> Option<String> {
+    crate::registry::query_value("Control Panel\\International", "LocaleName")
+}
@@ -0,0 +1,1460 @@
+//! 主模块中命令和辅助函数的单元测试
+
+use super::*;
+use serde_json::json;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tauri::async_runtime::block_on;
+
+/// 测试用的临时目录，离开作用域时删除
+struct TempDir(PathBuf);
+
+impl TempDir {
+    fn new() -> Self {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "mathlive-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = fs::remove_dir_all(&path);
+        TempDir(path)
+    }
+
+    fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+fn item(latex: &str, note: Option<&str>) -> FormulaItem {
+    serde_json::from_value(json!({ "latex": latex, "note": note })).unwrap()
+}
+
+// format_markdown 的 note_position
+
+fn markdown_with_note(position: NotePosition) -> String {
+    block_on(format_markdown(
+        vec![item("E = mc^2", Some("质能方程"))],
+        Some(position),
+        None,
+        None,
+        None,
+    ))
+    .unwrap()
+}
+
+#[test]
+fn note_above_precedes_math_block() {
+    let output = markdown_with_note(NotePosition::Above);
+    assert_eq!(output, "### 公式 1\n\n**质能方程**\n\n$$\n\nE = mc^2\n\n$$");
+}
+
+#[test]
+fn note_below_follows_math_block_as_caption() {
+    let output = markdown_with_note(NotePosition::Below);
+    assert_eq!(output, "### 公式 1\n\n$$\n\nE = mc^2\n\n$$\n\n*质能方程*");
+}
+
+#[test]
+fn empty_note_adds_no_lines_in_either_position() {
+    for position in [NotePosition::Above, NotePosition::Below] {
+        let output = block_on(format_markdown(
+            vec![item("a + b", Some("  "))],
+            Some(position),
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(output, "### 公式 1\n\n$$\n\na + b\n\n$$");
+    }
+}
+
+// find_formulas_using_command
+
+fn entry(id: &str, latex: &str) -> FormulaEntry {
+    serde_json::from_value(json!({ "id": id, "index": 0, "latex": latex, "note": null })).unwrap()
+}
+
+fn formulas_using(command: &str, formulas: &[(&str, &str)]) -> Vec<String> {
+    let entries = formulas
+        .iter()
+        .map(|(id, latex)| entry(id, latex))
+        .collect();
+    block_on(find_formulas_using_command(entries, command.to_string()))
+        .unwrap()
+        .into_iter()
+        .map(|found| found.id)
+        .collect()
+}
+
+#[test]
+fn command_search_matches_whole_token() {
+    let found = formulas_using("\\frac", &[("a", "\\frac{1}{2}"), ("b", "x^2")]);
+    assert_eq!(found, ["a"]);
+}
+
+#[test]
+fn command_search_skips_longer_commands() {
+    let found = formulas_using("\\frac", &[("a", "\\fracx"), ("b", "\\vector{v}")]);
+    assert!(found.is_empty());
+    let found = formulas_using("vec", &[("a", "\\vector{v}"), ("b", "\\vec{v}")]);
+    assert_eq!(found, ["b"]);
+}
+
+#[test]
+fn command_search_ignores_text_blocks() {
+    let found = formulas_using(
+        "\\vec",
+        &[
+            ("a", "\\text{use \\vec here}"),
+            ("b", "\\text{x} + \\vec{y}"),
+        ],
+    );
+    assert_eq!(found, ["b"]);
+}
+
+// 关闭确认：CloseGuard 的状态转换
+
+#[test]
+fn force_close_lets_the_next_close_through_once() {
+    let guard = CloseGuard::default();
+    guard.begin("main");
+    guard.allow("main");
+    assert!(guard.is_allowed("main"));
+    assert!(guard.take_allowed("main"));
+    assert!(!guard.take_allowed("main"));
+}
+
+#[test]
+fn unanswered_close_request_expires() {
+    let guard = CloseGuard::default();
+    let ticket = guard.begin("main");
+    assert!(guard.expire("main", ticket));
+    assert!(!guard.expire("main", ticket));
+}
+
+#[test]
+fn settled_close_request_does_not_time_out() {
+    let guard = CloseGuard::default();
+    let ticket = guard.begin("main");
+    guard.settle("main");
+    assert!(!guard.expire("main", ticket));
+}
+
+#[test]
+fn newer_close_request_supersedes_older_timeout() {
+    let guard = CloseGuard::default();
+    let first = guard.begin("main");
+    let second = guard.begin("main");
+    assert!(!guard.expire("main", first));
+    assert!(guard.expire("main", second));
+}
+
+#[test]
+fn close_requests_are_tracked_per_window() {
+    let guard = CloseGuard::default();
+    let main = guard.begin("main");
+    guard.begin("editor-1");
+    guard.allow("editor-1");
+    assert!(!guard.is_allowed("main"));
+    assert!(guard.expire("main", main));
+}
+
+// normalize_templates：合并重复分类与按名称排序
+
+fn normalized_templates(library: Value, sort_by_name: bool) -> NormalizedTemplates {
+    block_on(normalize_templates(
+        library.to_string(),
+        Some(sort_by_name),
+        None,
+        None,
+    ))
+    .unwrap()
+}
+
+fn template(id: &str) -> Value {
+    json!({ "id": id, "name": id, "latex": format!("\\{}", id) })
+}
+
+#[test]
+fn categories_with_same_id_are_merged() {
+    let library = json!({
+        "categories": [
+            { "id": "alg", "name": "代数", "templates": [template("a"), template("b")] },
+            { "id": "alg", "name": "代数", "templates": [template("b"), template("c")] },
+        ],
+        "selectedCategoryId": "alg",
+    });
+    let normalized = normalized_templates(library, false);
+    let categories = &normalized.library.categories;
+    assert_eq!(categories.len(), 1);
+    let ids: Vec<&str> = categories[0]
+        .templates
+        .iter()
+        .map(|t| t.id.as_str())
+        .collect();
+    assert_eq!(ids, ["a", "b", "c"]);
+    assert_eq!(normalized.library.selected_category_id, "alg");
+}
+
+#[test]
+fn sorting_by_name_keeps_selected_category() {
+    let library = json!({
+        "categories": [
+            { "id": "z", "name": "Zeta", "templates": [] },
+            { "id": "a", "name": "Alpha", "templates": [] },
+            { "id": "m", "name": "Mu", "templates": [] },
+        ],
+        "selectedCategoryId": "m",
+    });
+    let normalized = normalized_templates(library, true);
+    let names: Vec<&str> = normalized
+        .library
+        .categories
+        .iter()
+        .map(|c| c.name.as_str())
+        .collect();
+    assert_eq!(names, ["Alpha", "Mu", "Zeta"]);
+    assert_eq!(normalized.library.selected_category_id, "m");
+}
+
+// 主题设置的持久化
+
+#[test]
+fn theme_preference_survives_restart() {
+    let dir = TempDir::new();
+    let config = dir.path().join("config");
+    let store = SettingsStore::open(config.clone());
+    assert_eq!(theme_preference(&store), "system");
+    store.set(THEME_KEY, json!("dark")).unwrap();
+    drop(store);
+
+    let reopened = SettingsStore::open(config);
+    assert_eq!(theme_preference(&reopened), "dark");
+}
+
+#[test]
+fn corrupt_settings_file_falls_back_to_system_theme() {
+    let dir = TempDir::new();
+    fs::create_dir_all(dir.path()).unwrap();
+    fs::write(dir.path().join(settings::SETTINGS_FILE), "{ not json").unwrap();
+    let store = SettingsStore::open(dir.path().to_path_buf());
+    assert_eq!(theme_preference(&store), "system");
+}
+
+#[test]
+fn unknown_saved_theme_falls_back_to_system() {
+    let dir = TempDir::new();
+    let store = SettingsStore::open(dir.path().to_path_buf());
+    store.set(THEME_KEY, json!("neon")).unwrap();
+    assert_eq!(theme_preference(&store), "system");
+}
+
+// 未确认覆盖时的冲突检查
+
+#[test]
+fn overwrite_check_rejects_only_different_content() {
+    let dir = TempDir::new();
+    fs::create_dir_all(dir.path()).unwrap();
+    let path = dir.path().join("a.json");
+    assert!(check_overwrite(&path, b"[]").is_ok());
+    fs::write(&path, b"[]").unwrap();
+    assert!(check_overwrite(&path, b"[]").is_ok());
+    let err = check_overwrite(&path, b"[1]").unwrap_err();
+    assert_eq!(err.code(), "conflict");
+}
+
+// 规范化时保留未知字段
+
+#[test]
+fn unknown_formula_fields_round_trip_unchanged() {
+    let file = json!([
+        {
+            "id": "a",
+            "index": 1,
+            "latex": "x^2",
+            "createdAt": "2024-05-01T08:00:00Z",
+            "source": { "tool": "other", "page": 12 },
+        },
+        {
+            "id": "b",
+            "index": 2,
+            "latex": "\\sqrt{2}",
+            "note": "无理数",
+            "tags": ["代数"],
+            "section": "第一章",
+            "rating": 4.5,
+        },
+    ]);
+    let normalized = normalize_formula_value(&file).unwrap();
+    let saved = serde_json::to_value(&normalized.entries).unwrap();
+    assert_eq!(saved, file);
+}
+
+#[test]
+fn non_string_ids_are_reported_as_rewritten() {
+    let file = json!([
+        { "id": 7, "latex": "x" },
+        { "id": null, "latex": "y" },
+        { "id": " b ", "latex": "z" },
+    ]);
+    let normalized = normalize_formula_value(&file).unwrap();
+    let rewritten: Vec<_> = normalized
+        .rewritten_ids
+        .iter()
+        .map(|r| (r.position, r.original.as_str(), r.id.as_str()))
+        .collect();
+    assert_eq!(rewritten, [(0, "7", "formula-1")]);
+}
+
+#[test]
+fn generated_id_avoids_later_explicit_id() {
+    let file = json!([
+        { "id": "a", "latex": "x" },
+        { "latex": "y" },
+        { "latex": "z" },
+        { "id": "formula-3", "latex": "w" },
+    ]);
+    let normalized = normalize_formula_value(&file).unwrap();
+    let ids: Vec<_> = normalized.entries.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(ids, ["a", "formula-2", "formula-3-dup", "formula-3"]);
+    assert!(normalized.rewritten_ids.is_empty());
+}
+
+#[test]
+fn newer_formula_file_version_is_rejected() {
+    let file = json!({ "version": 3, "formulas": [{ "latex": "x" }] });
+    assert!(matches!(
+        normalize_formula_value(&file),
+        Err(AppError::UnsupportedVersion {
+            found: 3,
+            supported: 2
+        })
+    ));
+    let current = json!({ "version": 2, "formulas": [{ "latex": "x" }] });
+    assert_eq!(normalize_formula_value(&current).unwrap().version, 2);
+}
+
+#[test]
+fn unknown_template_fields_are_kept() {
+    let library = json!({
+        "version": 2,
+        "categories": [{
+            "id": "alg",
+            "name": "代数",
+            "parentId": null,
+            "color": "#ff0000",
+            "templates": [{
+                "id": "t1",
+                "name": "平方",
+                "latex": "x^2",
+                "note": null,
+                "author": "someone",
+            }],
+        }],
+        "selectedCategoryId": "alg",
+    });
+    let normalized = normalized_templates(library.clone(), false);
+    let saved = serde_json::to_value(&normalized.library).unwrap();
+    assert_eq!(saved, library);
+}
+
+// CSV 导出
+
+fn csv_entry(id: &str, latex: &str, note: Option<&str>) -> FormulaEntry {
+    serde_json::from_value(json!({ "id": id, "index": 1, "latex": latex, "note": note })).unwrap()
+}
+
+#[test]
+fn csv_quotes_commas_quotes_and_newlines() {
+    let latex = "\\begin{cases} a, & b \\\\\n c \\end{cases}";
+    let formulas = vec![csv_entry("f1", latex, Some("分段 \"函数\""))];
+    let csv = block_on(format_csv(formulas, None)).unwrap();
+    assert_eq!(
+        csv,
+        "index,id,latex,note\r\n\
+         1,f1,\"\\begin{cases} a, & b \\\\\n c \\end{cases}\",\"分段 \"\"函数\"\"\"\r\n"
+    );
+
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+    let record = reader.records().next().unwrap().unwrap();
+    assert_eq!(&record[2], latex);
+    assert_eq!(&record[3], "分段 \"函数\"");
+}
+
+#[test]
+fn csv_bom_is_optional() {
+    let formulas = || vec![csv_entry("f1", "x", None)];
+    let plain = block_on(format_csv(formulas(), None)).unwrap();
+    assert_eq!(plain, "index,id,latex,note\r\n1,f1,x,\r\n");
+    let options: CsvOptions = serde_json::from_value(json!({ "bom": true })).unwrap();
+    let with_bom = block_on(format_csv(formulas(), Some(options))).unwrap();
+    assert_eq!(with_bom, format!("\u{FEFF}{}", plain));
+}
+
+// Markdown 导入
+
+fn markdown_pairs(content: &str, inline: bool) -> Vec<(String, Option<String>)> {
+    let (blocks, skipped) = import::scan_markdown(content, inline);
+    assert!(skipped.is_empty());
+    blocks
+        .into_iter()
+        .map(|block| (block.latex, block.note))
+        .collect()
+}
+
+fn sample_formulas() -> Vec<FormulaItem> {
+    vec![
+        item("E = mc^2", Some("质能方程")),
+        item("\\int_0^1 x \\, dx", None),
+        item(
+            "\\begin{aligned}\na &= b \\\\\nc &= d\n\\end{aligned}",
+            Some("方程组"),
+        ),
+    ]
+}
+
+fn expected_pairs() -> Vec<(String, Option<String>)> {
+    sample_formulas()
+        .into_iter()
+        .map(|item| (item.latex, item.note))
+        .collect()
+}
+
+#[test]
+fn markdown_export_imports_back_unchanged() {
+    for position in [NotePosition::Above, NotePosition::Below] {
+        let markdown = block_on(format_markdown(
+            sample_formulas(),
+            Some(position),
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(markdown_pairs(&markdown, false), expected_pairs());
+    }
+}
+
+#[test]
+fn markdown_import_ignores_fenced_code() {
+    let markdown = "```\n$$\nx\n$$\n```\n\n**备注**\n\n$$\ny\n$$\n";
+    assert_eq!(
+        markdown_pairs(markdown, false),
+        [("y".to_string(), Some("备注".to_string()))]
+    );
+}
+
+#[test]
+fn markdown_inline_math_is_opt_in() {
+    let markdown = "勾股定理 $a^2 + b^2 = c^2$，以及 `$code$`。\n";
+    assert!(markdown_pairs(markdown, false).is_empty());
+    assert_eq!(
+        markdown_pairs(markdown, true),
+        [("a^2 + b^2 = c^2".to_string(), None)]
+    );
+}
+
+#[test]
+fn unterminated_display_math_is_reported() {
+    let (blocks, skipped) = import::scan_markdown("$$\nx\n", false);
+    assert!(blocks.is_empty());
+    assert_eq!(skipped.len(), 1);
+    assert_eq!(skipped[0].line, 1);
+}
+
+fn export_markdown(
+    formulas: Vec<FormulaItem>,
+    options: Value,
+    flavor: Option<MarkdownFlavor>,
+) -> String {
+    let options = serde_json::from_value(options).unwrap();
+    block_on(format_markdown(formulas, None, None, Some(options), flavor)).unwrap()
+}
+
+#[test]
+fn every_delimiter_imports_back_unchanged() {
+    for delimiter in ["dollars", "brackets", "fenced", "parentheses"] {
+        let markdown = export_markdown(
+            sample_formulas(),
+            json!({ "mathDelimiter": delimiter }),
+            None,
+        );
+        assert_eq!(
+            markdown_pairs(&markdown, false),
+            expected_pairs(),
+            "{}",
+            delimiter
+        );
+    }
+}
+
+#[test]
+fn zhihu_images_import_back() {
+    let formulas = vec![
+        item("a^2 + b^2 = c^2", Some("勾股定理")),
+        item("E = mc^2", None),
+    ];
+    let markdown = export_markdown(formulas, json!({}), Some(MarkdownFlavor::Zhihu));
+    assert_eq!(
+        markdown_pairs(&markdown, false),
+        [
+            ("a^2 + b^2 = c^2".to_string(), Some("勾股定理".to_string())),
+            ("E = mc^2".to_string(), None),
+        ]
+    );
+}
+
+#[test]
+fn escaped_notes_are_restored() {
+    let note = "a*b_c #1 [x] <y> ~z \\ `q` |";
+    for position in [NotePosition::Above, NotePosition::Below] {
+        let markdown = block_on(format_markdown(
+            vec![item("x", Some(note))],
+            Some(position),
+            None,
+            None,
+            None,
+        ))
+        .unwrap();
+        assert_eq!(
+            markdown_pairs(&markdown, false),
+            [("x".to_string(), Some(note.to_string()))]
+        );
+    }
+}
+
+#[test]
+fn section_headings_are_not_notes() {
+    let formulas: Vec<FormulaItem> = serde_json::from_value(json!([
+        { "latex": "F = ma", "section": "力学" },
+        { "latex": "E = mc^2", "note": "质能方程", "section": "相对论" },
+        { "latex": "p = mv", "section": "力学" },
+    ]))
+    .unwrap();
+    for flavor in [MarkdownFlavor::Github, MarkdownFlavor::Pandoc] {
+        let markdown = export_markdown(formulas.clone(), json!({}), Some(flavor));
+        let (blocks, skipped) = import::scan_markdown(&markdown, false);
+        assert!(skipped.is_empty());
+        let imported: Vec<_> = blocks
+            .iter()
+            .map(|b| (b.latex.as_str(), b.note.as_deref(), b.section.as_deref()))
+            .collect();
+        assert_eq!(
+            imported,
+            [
+                ("F = ma", None, Some("力学")),
+                ("p = mv", None, Some("力学")),
+                ("E = mc^2", Some("质能方程"), Some("相对论")),
+            ]
+        );
+    }
+}
+
+#[test]
+fn paragraph_starting_with_inline_parentheses_is_not_a_block() {
+    let markdown = "\\(x\\) 是变量\n\n$$\ny\n$$\n";
+    assert_eq!(markdown_pairs(markdown, false), [("y".to_string(), None)]);
+}
+
+// 保存时补全扩展名
+
+#[test]
+fn missing_extension_is_appended() {
+    let dir = TempDir::new();
+    let path = ensure_extension(dir.path().join("foo"), &["json"]).unwrap();
+    assert_eq!(path, dir.path().join("foo.json"));
+}
+
+#[test]
+fn matching_extension_is_case_insensitive() {
+    let dir = TempDir::new();
+    let path = ensure_extension(dir.path().join("foo.JSON"), &["json"]).unwrap();
+    assert_eq!(path, dir.path().join("foo.JSON"));
+}
+
+#[test]
+fn other_extension_gets_expected_one_appended() {
+    let dir = TempDir::new();
+    let path = ensure_extension(dir.path().join("foo.backup"), &["tex"]).unwrap();
+    assert_eq!(path, dir.path().join("foo.backup.tex"));
+}
+
+#[test]
+fn compressed_collection_keeps_its_double_extension() {
+    let dir = TempDir::new();
+    let path = ensure_extension(dir.path().join("foo.json.gz"), COLLECTION_EXTENSIONS).unwrap();
+    assert_eq!(path, dir.path().join("foo.json.gz"));
+    let path = ensure_extension(dir.path().join("foo.tar.gz"), COLLECTION_EXTENSIONS).unwrap();
+    assert_eq!(path, dir.path().join("foo.tar.gz.json"));
+}
+
+#[test]
+fn only_collection_double_extensions_are_collections() {
+    for name in ["a.json", "a.MLFORMULAS", "a.json.gz", "a.mlformulas.GZ"] {
+        assert!(is_collection_path(Path::new(name)), "{}", name);
+    }
+    for name in ["a.tar.gz", "a.gz", ".json", "json.gz", "a.jsonl"] {
+        assert!(!is_collection_path(Path::new(name)), "{}", name);
+    }
+}
+
+#[test]
+fn copy_name_strips_the_whole_extension() {
+    let copy = |path: &str| copy_file_name(Some(Path::new(path)));
+    assert_eq!(copy("/d/foo.json.gz"), "foo-copy.json.gz");
+    assert_eq!(copy("/d/foo.mlformulas"), "foo-copy.mlformulas");
+    assert_eq!(copy("/d/foo.txt"), "foo-copy.json");
+    assert_eq!(copy_file_name(None), "formulas-copy.json");
+}
+
+#[test]
+fn appended_extension_colliding_with_existing_file_is_a_conflict() {
+    let dir = TempDir::new();
+    fs::create_dir_all(dir.path()).unwrap();
+    fs::write(dir.path().join("foo.md"), "# existing").unwrap();
+    let err = ensure_extension(dir.path().join("foo"), &["md"]).unwrap_err();
+    assert_eq!(err.code(), "conflict");
+}
+
+// 导出压缩包
+
+#[test]
+fn bundle_skips_failed_documents_and_lists_written_entries() {
+    let dir = TempDir::new();
+    fs::create_dir_all(dir.path()).unwrap();
+    let path = dir.path().join("bundle.zip");
+    let documents = [
+        ("formulas.tex", Err(AppError::TaskFailed)),
+        ("formulas.md", Ok("$$x$$".to_string())),
+    ];
+    let file = fs::File::create(&path).unwrap();
+    let (_, failures) = write_bundle(file, "[]", documents, Map::new()).unwrap();
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0].entry, "formulas.tex");
+
+    let mut archive = zip::ZipArchive::new(fs::File::open(&path).unwrap()).unwrap();
+    let mut names: Vec<_> = archive.file_names().map(str::to_string).collect();
+    names.sort();
+    assert_eq!(names, ["formulas.json", "formulas.md", "manifest.json"]);
+    let manifest: Value =
+        serde_json::from_reader(archive.by_name("manifest.json").unwrap()).unwrap();
+    assert_eq!(manifest["entries"], json!(["formulas.json", "formulas.md"]));
+}
+
+// 按创建时间排序
+
+#[test]
+fn created_at_sort_puts_uncomparable_values_last() {
+    let content = json!([
+        { "id": "iso", "latex": "a", "createdAt": "2024-05-01T08:00:00Z" },
+        { "id": "bool", "latex": "b", "createdAt": true },
+        { "id": "late", "latex": "c", "createdAt": 1_700_000_000_000u64 },
+        { "id": "none", "latex": "d" },
+        { "id": "early", "latex": "e", "createdAt": 1_600_000_000_000u64 },
+        { "id": "blank", "latex": "f", "createdAt": " " },
+    ])
+    .to_string();
+    let ids = |ascending| {
+        block_on(sort_formulas(
+            content.clone(),
+            SortKey::CreatedAt,
+            ascending,
+        ))
+        .unwrap()
+        .into_iter()
+        .map(|entry| entry.id)
+        .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(true), ["early", "late", "iso", "bool", "none", "blank"]);
+    assert_eq!(
+        ids(false),
+        ["iso", "late", "early", "bool", "none", "blank"]
+    );
+}
+
+// 文件对话框与导出命令的结果
+
+#[test]
+fn selected_outcome_serializes_path() {
+    let outcome = FileOutcome::Selected {
+        path: "/tmp/a.json".to_string(),
+    };
+    assert_eq!(
+        serde_json::to_value(outcome).unwrap(),
+        json!({ "status": "selected", "path": "/tmp/a.json" })
+    );
+}
+
+#[test]
+fn saved_outcome_omits_empty_warnings() {
+    let outcome = FileOutcome::Saved {
+        path: "/tmp/a.tex".to_string(),
+        warnings: Vec::new(),
+    };
+    assert_eq!(
+        serde_json::to_value(outcome).unwrap(),
+        json!({ "status": "saved", "path": "/tmp/a.tex" })
+    );
+    let outcome = FileOutcome::Saved {
+        path: "/tmp/a.tex".to_string(),
+        warnings: vec!["w".to_string()],
+    };
+    assert_eq!(
+        serde_json::to_value(outcome).unwrap(),
+        json!({ "status": "saved", "path": "/tmp/a.tex", "warnings": ["w"] })
+    );
+}
+
+#[test]
+fn cancelled_outcome_has_no_path() {
+    assert_eq!(
+        serde_json::to_value(FileOutcome::Cancelled).unwrap(),
+        json!({ "status": "cancelled" })
+    );
+}
+
+#[test]
+fn started_outcome_carries_job_id() {
+    let outcome = FileOutcome::Started {
+        job_id: "pdf-1".to_string(),
+    };
+    assert_eq!(
+        serde_json::to_value(outcome).unwrap(),
+        json!({ "status": "started", "jobId": "pdf-1" })
+    );
+}
+
+#[test]
+fn pdf_options_accept_write_options() {
+    let options: PdfOptions = serde_json::from_value(json!({ "includeTags": true })).unwrap();
+    assert!(options.include_tags && options.write.overwrite);
+    let options: PdfOptions =
+        serde_json::from_value(json!({ "overwrite": false, "path": "/tmp/a.pdf" })).unwrap();
+    assert!(!options.write.overwrite);
+    assert_eq!(options.write.path.as_deref(), Some("/tmp/a.pdf"));
+}
+
+#[test]
+fn cancelled_export_keeps_extra_fields() {
+    let anki = AnkiExport {
+        outcome: FileOutcome::Cancelled,
+        cards: 0,
+    };
+    assert_eq!(
+        serde_json::to_value(anki).unwrap(),
+        json!({ "status": "cancelled", "cards": 0 })
+    );
+    let docx = DocxExport {
+        outcome: FileOutcome::Cancelled,
+        failures: Vec::new(),
+    };
+    assert_eq!(
+        serde_json::to_value(docx).unwrap(),
+        json!({ "status": "cancelled", "failures": [] })
+    );
+    let directory = LatexDirectoryExport {
+        outcome: FileOutcome::Cancelled,
+        written: Vec::new(),
+        skipped: Vec::new(),
+    };
+    assert_eq!(
+        serde_json::to_value(directory).unwrap(),
+        json!({ "status": "cancelled", "written": [], "skipped": [] })
+    );
+}
+
+// LaTeX 导出选项：默认输出与自定义导言区
+
+fn latex_document(formulas: Vec<FormulaItem>, options: Option<Value>) -> Result<String, AppError> {
+    let options = options.map(|o| serde_json::from_value(o).unwrap());
+    block_on(format_latex(formulas, None, options)).map(|document| document.content)
+}
+
+#[test]
+fn default_latex_export_matches_golden_file() {
+    let formulas = vec![
+        item("a^2 + b^2 = c^2", Some("Pythagoras")),
+        item("E = mc^2", None),
+    ];
+    let expected = include_str!("../tests/fixtures/latex-default.tex");
+    assert_eq!(latex_document(formulas.clone(), None).unwrap(), expected);
+    // 显式传入空选项与省略选项的结果相同
+    assert_eq!(latex_document(formulas, Some(json!({}))).unwrap(), expected);
+}
+
+#[test]
+fn cjk_latex_export_matches_golden_file() {
+    let formulas = vec![item("E = mc^2", Some("质能方程"))];
+    assert_eq!(
+        latex_document(formulas, None).unwrap(),
+        include_str!("../tests/fixtures/latex-cjk.tex")
+    );
+}
+
+#[test]
+fn custom_preamble_matches_golden_file() {
+    let options = json!({
+        "documentClass": "ctexart",
+        "classOptions": "12pt,a4paper",
+        "extraPackages": ["amssymb", "physics"],
+        "preambleExtra": "\\newcommand{\\R}{\\mathbb{R}}\n",
+    });
+    assert_eq!(
+        latex_document(vec![item("x \\in \\R", None)], Some(options)).unwrap(),
+        include_str!("../tests/fixtures/latex-options.tex")
+    );
+}
+
+fn sectioned_item(latex: &str, section: Option<&str>) -> FormulaItem {
+    serde_json::from_value(json!({ "latex": latex, "section": section })).unwrap()
+}
+
+#[test]
+fn unsectioned_formulas_go_under_default_heading_at_end() {
+    let formulas = vec![
+        sectioned_item("E = mc^2", None),
+        sectioned_item("F = ma", Some("Mechanics")),
+        sectioned_item("a^2 + b^2 = c^2", Some("Geometry")),
+    ];
+    let document = latex_document(formulas.clone(), None).unwrap();
+    let mechanics = document.find("\\section{Mechanics}").unwrap();
+    let geometry = document.find("\\section{Geometry}").unwrap();
+    let other = document.find("\\section{其他}").unwrap();
+    assert!(mechanics < geometry && geometry < other, "{}", document);
+    assert!(other < document.find("E = mc^2").unwrap(), "{}", document);
+
+    let options = json!({ "defaultSection": "Other" });
+    let document = latex_document(formulas.clone(), Some(options)).unwrap();
+    assert!(!document.contains("其他"), "{}", document);
+    let other = document.find("\\section{Other}").unwrap();
+    assert!(document.find("\\section{Geometry}").unwrap() < other);
+    assert!(other < document.find("E = mc^2").unwrap());
+
+    let groups = group_by_section(&formulas, "Mechanics");
+    assert_eq!(groups.len(), 2);
+    assert_eq!(groups[0].0, Some("Mechanics"));
+    assert_eq!(groups[0].1.len(), 2);
+}
+
+#[test]
+fn invalid_package_and_class_names_are_rejected() {
+    let formulas = vec![item("x", None)];
+    let package = json!({ "extraPackages": ["amssymb}\\input{/etc/passwd"] });
+    let class = json!({ "documentClass": "article}\\input{x" });
+    let class_options = json!({ "classOptions": "12pt]{article}\\input{x" });
+    for options in [package, class, class_options] {
+        let err = latex_document(formulas.clone(), Some(options)).unwrap_err();
+        assert_eq!(err.code(), "invalid_latex");
+    }
+}
+
+// HTML 导出内联 KaTeX 资源
+
+#[test]
+fn embedded_html_does_not_reference_cdn() {
+    let dir = TempDir::new();
+    fs::create_dir_all(dir.path().join("fonts")).unwrap();
+    fs::write(
+        dir.path().join("katex.min.css"),
+        "@font-face{src:url(fonts/KaTeX_Main-Regular.woff2) format(\"woff2\")}",
+    )
+    .unwrap();
+    fs::write(
+        dir.path().join("katex.min.js"),
+        "window.katex={};\"</script>\";",
+    )
+    .unwrap();
+    fs::write(dir.path().join("fonts/KaTeX_Main-Regular.woff2"), b"wOF2").unwrap();
+
+    let formulas = vec![item("E = mc^2", None)];
+    let options = HtmlOptions {
+        embed_assets: true,
+        title: None,
+    };
+    let html = html_document(Some(dir.path()), &formulas, &options, "", "").unwrap();
+    assert!(!html.contains("cdn.jsdelivr.net"), "{}", html);
+    assert!(
+        html.contains("url(data:font/woff2;base64,d09GMg==)"),
+        "{}",
+        html
+    );
+    assert!(html.contains("\"<\\/script>\""), "{}", html);
+
+    let linked = html_document(None, &formulas, &HtmlOptions::default(), "", "").unwrap();
+    assert!(linked.contains("cdn.jsdelivr.net"));
+}
+
+#[test]
+fn embedding_fails_when_katex_assets_are_missing() {
+    let dir = TempDir::new();
+    let options = HtmlOptions {
+        embed_assets: true,
+        title: None,
+    };
+    let formulas = vec![item("x", None)];
+    assert!(html_document(Some(dir.path()), &formulas, &options, "", "").is_err());
+    let err = html_document(None, &formulas, &options, "", "").unwrap_err();
+    assert_eq!(err.code(), "location_unavailable");
+}
+
+#[test]
+fn printing_breaks_pages_between_sections_by_default() {
+    let options: PrintOptions = serde_json::from_value(json!({ "title": "T" })).unwrap();
+    assert!(options.page_breaks);
+    assert!(PrintOptions::default().page_breaks);
+    let options: PrintOptions = serde_json::from_value(json!({ "pageBreaks": false })).unwrap();
+    assert!(!options.page_breaks);
+}
+
+// 重新导入导出的 LaTeX
+
+#[test]
+fn every_environment_round_trips_through_latex_export() {
+    let environments = [
+        "equation",
+        "equation*",
+        "align",
+        "align*",
+        "gather",
+        "gather*",
+    ];
+    for name in environments {
+        let formulas: Vec<FormulaItem> = serde_json::from_value(json!([
+            { "latex": "a &= b \\\\ c &= d", "note": "Step 1", "id": "derivation" },
+            { "latex": "E = mc^2", "id": "f2" },
+        ]))
+        .unwrap();
+        let options = json!({ "environment": name });
+        let content = latex_document(formulas, Some(options)).unwrap();
+        let entries = block_on(reimport_exported_latex(content)).unwrap();
+        assert_eq!(entries.len(), 2, "{}", name);
+        assert_eq!(entries[0].latex, "a &= b \\\\ c &= d", "{}", name);
+        assert_eq!(entries[0].note.as_deref(), Some("Step 1"), "{}", name);
+        assert_eq!(entries[1].latex, "E = mc^2", "{}", name);
+        let numbered = !name.ends_with('*');
+        let ids: Vec<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+        if numbered {
+            assert_eq!(ids, ["derivation", "f2"], "{}", name);
+        } else {
+            assert_eq!(ids, ["formula-1", "formula-2"], "{}", name);
+        }
+        let environment = entries[0].extra.get("environment").and_then(Value::as_str);
+        let expected = (name != "equation").then_some(name);
+        assert_eq!(environment, expected, "{}", name);
+        // 再次导出时沿用导入的环境
+        let items = formula_items(&entries);
+        assert_eq!(
+            items[0].environment.unwrap_or_default().name(),
+            name,
+            "{}",
+            name
+        );
+    }
+}
+
+#[test]
+fn numeric_labels_restore_index() {
+    let content =
+        "\\begin{document}\n\\begin{equation}\\label{eq:7}\nx\n\\end{equation}\n\\end{document}\n";
+    let entries = block_on(reimport_exported_latex(content.to_string())).unwrap();
+    assert_eq!(entries[0].index, 7);
+    assert_eq!(entries[0].id, "formula-1");
+}
+
+// 按内容决定是否引入 ctex
+
+fn latex_with_warnings(formulas: Vec<FormulaItem>, options: Value) -> LatexDocument {
+    let options = serde_json::from_value(options).unwrap();
+    block_on(format_latex(formulas, None, Some(options))).unwrap()
+}
+
+#[test]
+fn ascii_collection_omits_ctex() {
+    let document = latex_with_warnings(vec![item("E = mc^2", Some("Energy"))], json!({}));
+    assert!(!document.content.contains("ctex"));
+    assert!(document.warnings.is_empty());
+}
+
+#[test]
+fn chinese_note_adds_ctex() {
+    let document = latex_with_warnings(vec![item("E = mc^2", Some("质能方程"))], json!({}));
+    assert!(document.content.contains("\\usepackage{ctex}\n"));
+    assert!(document.warnings.is_empty());
+}
+
+#[test]
+fn chinese_text_in_formula_adds_ctex() {
+    let document = latex_with_warnings(vec![item("v = \\text{速度}", None)], json!({}));
+    assert!(document.content.contains("\\usepackage{ctex}\n"));
+}
+
+#[test]
+fn disabling_ctex_for_chinese_content_warns() {
+    let document = latex_with_warnings(
+        vec![item("v = \\text{速度}", None)],
+        json!({ "forceCtex": false }),
+    );
+    assert!(!document.content.contains("\\usepackage{ctex}"));
+    assert!(document.content.starts_with("% warning: "));
+    assert_eq!(document.warnings.len(), 1);
+}
+
+#[test]
+fn forcing_ctex_adds_it_without_chinese_content() {
+    let document = latex_with_warnings(vec![item("x", None)], json!({ "forceCtex": true }));
+    assert!(document.content.contains("\\usepackage{ctex}\n"));
+}
+
+#[test]
+fn gb18030_export_loads_ctex_with_gbk_option() {
+    let document = latex_with_warnings(
+        vec![item("E = mc^2", Some("质能方程"))],
+        json!({ "encoding": "gb18030" }),
+    );
+    assert!(document.content.contains("\\usepackage[GBK]{ctex}\n"));
+    assert!(!document.content.contains("\\usepackage{ctex}"));
+}
+
+#[test]
+fn cjk_support_in_custom_preamble_is_recognized() {
+    let formulas = vec![item("E = mc^2", Some("质能方程"))];
+    let class = json!({ "documentClass": "ctexart" });
+    let document = latex_with_warnings(formulas.clone(), class);
+    assert!(!document.content.contains("\\usepackage{ctex}"));
+    for options in [
+        json!({ "preambleExtra": "\\usepackage{xeCJK}" }),
+        json!({ "preambleExtra": "\\usepackage[UTF8]{ctex}" }),
+        json!({ "extraPackages": ["ctex"] }),
+        json!({ "documentClass": "ctexart" }),
+    ] {
+        let document = latex_with_warnings(formulas.clone(), options.clone());
+        // 已由用户引入时不会再自动引入一次
+        assert!(
+            document.content.matches("\\usepackage{ctex}").count() <= 1,
+            "{}",
+            options
+        );
+        assert!(document.warnings.is_empty(), "{}", options);
+        assert!(!document.content.starts_with("% warning"), "{}", options);
+    }
+}
+
+// 备注的 LaTeX 转义
+
+#[test]
+fn every_special_character_is_escaped() {
+    let cases = [
+        ("#", "\\#"),
+        ("%", "\\%"),
+        ("&", "\\&"),
+        ("_", "\\_"),
+        ("$", "\\$"),
+        ("{", "\\{"),
+        ("}", "\\}"),
+        ("\\", "\\textbackslash{}"),
+        ("~", "\\textasciitilde{}"),
+        ("^", "\\textasciicircum{}"),
+        ("<", "\\textless{}"),
+        (">", "\\textgreater{}"),
+        ("plain", "plain"),
+        (
+            "勾股定理 a^2 + b^2 = c^2 (约 50% 的情况)",
+            "勾股定理 a\\textasciicircum{}2 + b\\textasciicircum{}2 = c\\textasciicircum{}2 (约 50\\% 的情况)",
+        ),
+        ("C:\\路径\\file_1", "C:\\textbackslash{}路径\\textbackslash{}file\\_1"),
+    ];
+    for (text, expected) in cases {
+        assert_eq!(escape_latex_text(text), expected, "{:?}", text);
+        assert_eq!(unescape_latex_text(expected), text, "{:?}", text);
+    }
+}
+
+#[test]
+fn escaping_is_idempotent() {
+    for text in [
+        "50\\% off",
+        "a\\_b",
+        "\\textbackslash{}",
+        "x < y ~ z",
+        "质能 E=mc^2",
+    ] {
+        let once = escape_latex_text(text);
+        assert_eq!(escape_latex_text(&once), once, "{:?}", text);
+    }
+    assert_eq!(escape_latex_text("50\\% off"), "50\\% off");
+}
+
+// Markdown 导出选项与平台预设的快照
+
+fn markdown_snapshot(options: Option<Value>, flavor: Option<MarkdownFlavor>) -> String {
+    let formulas = vec![
+        item("a^2 + b^2 = c^2", Some("勾股定理")),
+        item("E = mc^2", None),
+    ];
+    let options = options.map(|o| serde_json::from_value(o).unwrap());
+    block_on(format_markdown(formulas, None, None, options, flavor)).unwrap()
+}
+
+#[test]
+fn default_markdown_options_match_golden_file() {
+    let expected = include_str!("../tests/fixtures/markdown-default.md");
+    assert_eq!(markdown_snapshot(None, None), expected);
+    assert_eq!(markdown_snapshot(Some(json!({})), None), expected);
+}
+
+#[test]
+fn fenced_markdown_matches_golden_file() {
+    let options = json!({
+        "mathDelimiter": "fenced",
+        "headingLevel": 2,
+        "headingText": "Formula {index}",
+        "includeNumbering": false,
+    });
+    assert_eq!(
+        markdown_snapshot(Some(options), None),
+        include_str!("../tests/fixtures/markdown-fenced.md")
+    );
+}
+
+#[test]
+fn bracket_markdown_matches_golden_file() {
+    let options = json!({ "mathDelimiter": "brackets", "headingLevel": null });
+    assert_eq!(
+        markdown_snapshot(Some(options), None),
+        include_str!("../tests/fixtures/markdown-brackets.md")
+    );
+}
+
+#[test]
+fn every_markdown_flavor_matches_snapshot() {
+    let cases = [
+        (
+            MarkdownFlavor::Github,
+            include_str!("../tests/fixtures/markdown-default.md"),
+        ),
+        (
+            MarkdownFlavor::Obsidian,
+            include_str!("../tests/fixtures/markdown-flavor-obsidian.md"),
+        ),
+        (
+            MarkdownFlavor::Zhihu,
+            include_str!("../tests/fixtures/markdown-flavor-zhihu.md"),
+        ),
+        (
+            MarkdownFlavor::Wechat,
+            include_str!("../tests/fixtures/markdown-flavor-wechat.md"),
+        ),
+        (
+            MarkdownFlavor::Pandoc,
+            include_str!("../tests/fixtures/markdown-flavor-pandoc.md"),
+        ),
+    ];
+    assert_eq!(cases.len(), MARKDOWN_PRESETS.len());
+    for (flavor, expected) in cases {
+        assert_eq!(markdown_snapshot(None, Some(flavor)), expected);
+    }
+}
+
+// normalize_templates：修复父分类引用
+
+fn category(id: &str, parent: Option<&str>) -> Value {
+    json!({ "id": id, "name": id, "templates": [], "parentId": parent })
+}
+
+fn repaired(categories: Vec<Value>) -> NormalizedTemplates {
+    normalized_templates(json!({ "version": 2, "categories": categories }), false)
+}
+
+/// 从每个分类沿父引用向上走，步数超过分类数即说明仍有环
+fn assert_acyclic(normalized: &NormalizedTemplates) {
+    let categories = &normalized.library.categories;
+    let parent_of = |id: &str| {
+        categories
+            .iter()
+            .find(|c| c.id == id)
+            .and_then(|c| c.parent_id.clone())
+    };
+    for category in categories {
+        let mut current = category.parent_id.clone();
+        let mut steps = 0;
+        while let Some(id) = current {
+            steps += 1;
+            assert!(steps <= categories.len(), "cycle through {}", category.id);
+            current = parent_of(&id);
+        }
+    }
+}
+
+#[test]
+fn direct_parent_cycle_is_broken() {
+    let normalized = repaired(vec![category("a", Some("b")), category("b", Some("a"))]);
+    assert_eq!(normalized.repairs.len(), 1);
+    assert!(matches!(
+        normalized.repairs[0].reason,
+        ParentRepairReason::Cycle
+    ));
+    assert_acyclic(&normalized);
+}
+
+#[test]
+fn longer_parent_cycle_is_broken_once() {
+    let normalized = repaired(vec![
+        category("a", Some("b")),
+        category("b", Some("c")),
+        category("c", Some("a")),
+        category("d", Some("a")),
+    ]);
+    assert_eq!(normalized.repairs.len(), 1);
+    assert!(matches!(
+        normalized.repairs[0].reason,
+        ParentRepairReason::Cycle
+    ));
+    assert_acyclic(&normalized);
+    // 挂在环上的分类保留原父分类
+    let d = normalized.library.categories.iter().find(|c| c.id == "d");
+    assert_eq!(d.unwrap().parent_id.as_deref(), Some("a"));
+}
+
+#[test]
+fn self_parent_is_a_cycle() {
+    let normalized = repaired(vec![category("a", Some("a"))]);
+    assert_eq!(normalized.repairs.len(), 1);
+    assert_eq!(normalized.repairs[0].parent_id, "a");
+    assert_acyclic(&normalized);
+}
+
+#[test]
+fn dangling_parent_moves_category_to_top_level() {
+    let normalized = repaired(vec![category("a", None), category("b", Some("missing"))]);
+    assert_eq!(normalized.repairs.len(), 1);
+    let repair = &normalized.repairs[0];
+    assert_eq!(repair.category_id, "b");
+    assert_eq!(repair.parent_id, "missing");
+    assert!(matches!(repair.reason, ParentRepairReason::MissingParent));
+    let b = normalized.library.categories.iter().find(|c| c.id == "b");
+    assert_eq!(b.unwrap().parent_id, None);
+}
+
+#[test]
+fn valid_parents_are_left_alone() {
+    let normalized = repaired(vec![category("a", None), category("b", Some("a"))]);
+    assert!(normalized.repairs.is_empty());
+}
+
+// 公式集与模板库互相转换
+
+#[test]
+fn formulas_round_trip_through_template_library() {
+    let formulas = vec![
+        FormulaEntry {
+            tags: vec!["几何".to_string()],
+            note: Some("勾股定理".to_string()),
+            ..entry("pythagoras", "a^2 + b^2 = c^2")
+        },
+        FormulaEntry {
+            note: Some("质能方程".to_string()),
+            ..entry("energy", "E = mc^2")
+        },
+    ];
+    let library = block_on(formulas_to_template_library(formulas, "物理".to_string())).unwrap();
+    let content = serde_json::to_string(&library).unwrap();
+    let normalized = normalized_templates(serde_json::from_str(&content).unwrap(), false);
+    assert!(normalized.warnings.is_empty());
+    let library_content = serde_json::to_string(&normalized.library).unwrap();
+    let entries = block_on(template_library_to_formulas(library_content, None)).unwrap();
+    let content = serde_json::to_string(&json!({ "version": 2, "formulas": entries })).unwrap();
+    let normalized = block_on(normalize_formulas(content)).unwrap();
+    assert!(normalized.warnings.is_empty());
+    let restored: Vec<_> = normalized
+        .entries
+        .iter()
+        .map(|e| {
+            (
+                e.id.as_str(),
+                e.latex.as_str(),
+                e.note.as_deref(),
+                e.tags.clone(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        restored,
+        [
+            (
+                "pythagoras",
+                "a^2 + b^2 = c^2",
+                Some("勾股定理"),
+                vec!["几何".to_string()]
+            ),
+            ("energy", "E = mc^2", Some("质能方程"), Vec::new()),
+        ]
+    );
+    assert!(normalized
+        .entries
+        .iter()
+        .all(|e| e.section.as_deref() == Some("物理")));
+}
+
+#[test]
+fn template_library_round_trips_through_formulas() {
+    let library = json!({
+        "version": 2,
+        "categories": [
+            { "id": "alg", "name": "代数", "templates": [template("a"), template("b")] },
+            {
+                "id": "geo",
+                "name": "几何",
+                "parentId": "alg",
+                "templates": [{ "id": "a", "name": "c", "latex": "\\c" }],
+            },
+        ],
+    });
+    let entries = block_on(template_library_to_formulas(library.to_string(), None)).unwrap();
+    // 不同分类中的同名 id 重新生成，序号从 1 连续编排
+    let ids: Vec<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+    assert_eq!(ids.len(), 3);
+    assert_eq!(ids.iter().collect::<HashSet<_>>().len(), 3);
+    let indices: Vec<u32> = entries.iter().map(|e| e.index).collect();
+    assert_eq!(indices, [1, 2, 3]);
+
+    let converted = block_on(formulas_to_template_library(entries, "合并".to_string())).unwrap();
+    let normalized = normalized_templates(serde_json::to_value(&converted).unwrap(), false);
+    let templates = &normalized.library.categories[0].templates;
+    let latex: Vec<&str> = templates.iter().map(|t| t.latex.as_str()).collect();
+    assert_eq!(latex, ["\\a", "\\b", "\\c"]);
+    let names: Vec<&str> = templates.iter().map(|t| t.name.as_str()).collect();
+    assert_eq!(names, ["a", "b", "c"]);
+}
+
+// 粘贴内容的格式识别
+
+fn detected_format(text: &str) -> Option<&'static str> {
+    match detect_paste(text).unwrap() {
+        ClipboardImport::Detected {
+            detected_format, ..
+        } => Some(match detected_format {
+            PasteFormat::Json => "json",
+            PasteFormat::Latex => "latex",
+            PasteFormat::Mathml => "mathml",
+        }),
+        ClipboardImport::NeedsUserChoice { .. } => None,
+    }
+}
+
+#[test]
+fn plain_text_needs_user_choice() {
+    assert_eq!(detected_format("hello world"), None);
+    assert_eq!(detected_format("x = 1"), None);
+}
+
+#[test]
+fn undelimited_latex_with_markers_is_detected() {
+    assert_eq!(detected_format("a^2 + b^2 = c^2"), Some("latex"));
+    assert_eq!(detected_format("\\alpha + x_1"), Some("latex"));
+    assert_eq!(detected_format("$$\nx = 1\n$$"), Some("latex"));
+}
+
+#[test]
+fn json_and_mathml_pastes_are_detected() {
+    let json = json!([{ "id": "f1", "latex": "x" }]).to_string();
+    assert_eq!(detected_format(&json), Some("json"));
+    let mathml = "<math><mi>x</mi><mo>=</mo><mn>1</mn></math>";
+    assert_eq!(detected_format(mathml), Some("mathml"));
+}
+
+#[test]
+fn mathml_with_several_fragments_warns() {
+    let single = block_on(mathml_to_latex("<mi>x</mi>".to_string())).unwrap();
+    assert_eq!(single.latex, "x");
+    assert!(single.warnings.is_empty());
+
+    let mathml = "<math><mi>a</mi></math> <math><mi>b</mi></math>".to_string();
+    let converted = block_on(mathml_to_latex(mathml)).unwrap();
+    assert_eq!(converted.latex, "a");
+    assert_eq!(converted.warnings.len(), 1);
+    assert!(converted.warnings[0].contains("2 个"));
+}
+
+#[test]
+fn empty_paste_is_a_typed_error() {
+    assert!(matches!(
+        detect_paste("  \n"),
+        Err(AppError::ClipboardEmpty)
+    ));
+    assert!(matches!(
+        detect_paste("a\u{0}b"),
+        Err(AppError::ClipboardEmpty)
+    ));
+}
+
+#[test]
+fn startup_file_skips_leading_directory_argument() {
+    let dir = TempDir::new();
+    fs::create_dir_all(dir.path().join("folder")).unwrap();
+    fs::write(dir.path().join("notes.txt"), "x").unwrap();
+    fs::write(dir.path().join("formulas.mlformulas"), "[]").unwrap();
+    let args = ["folder", "notes.txt", "missing.json", "formulas.mlformulas"].map(OsString::from);
+    assert_eq!(
+        startup_file(args, dir.path()),
+        Some(dir.path().join("formulas.mlformulas"))
+    );
+}
+
+#[test]
+fn startup_file_requires_collection_extension() {
+    let dir = TempDir::new();
+    fs::create_dir_all(dir.path().join("library.json")).unwrap();
+    fs::write(dir.path().join("readme.md"), "x").unwrap();
+    let args = ["library.json", "readme.md"].map(OsString::from);
+    assert_eq!(startup_file(args, dir.path()), None);
+}
+
+#[test]
+fn second_instance_ignores_non_collection_files() {
+    let dir = TempDir::new();
+    fs::create_dir_all(dir.path()).unwrap();
+    fs::write(dir.path().join("notes.txt"), "x").unwrap();
+    fs::write(dir.path().join("setup.exe"), "x").unwrap();
+    let args = ["notes.txt", "setup.exe"].map(String::from);
+    assert_eq!(single_instance::file_argument(&args, dir.path()), None);
+
+    fs::write(dir.path().join("formulas.json.gz"), "x").unwrap();
+    let args = ["--flag", "notes.txt", "formulas.json.gz"].map(String::from);
+    assert_eq!(
+        single_instance::file_argument(&args, dir.path()),
+        Some(dir.path().join("formulas.json.gz"))
+    );
+}
+
+#[test]
+fn checksum_matches_the_bytes_that_were_read() {
+    let dir = TempDir::new();
+    fs::create_dir_all(dir.path()).unwrap();
+    let path = dir.path().join("formulas.json.gz");
+    backup::save_with_backup(&path, b"[{\"id\":\"f1\",\"latex\":\"x\"}]", 0).unwrap();
+    let (content, raw) = read_text_file_raw(&path, DEFAULT_MAX_READ_BYTES).unwrap();
+    assert_eq!(content, "[{\"id\":\"f1\",\"latex\":\"x\"}]");
+    assert!(checksum::verify(&path, &raw).is_none());
+
+    let mut truncated = raw.clone();
+    truncated.truncate(raw.len() / 2);
+    let mismatch = checksum::verify(&path, &truncated).unwrap();
+    assert_eq!(mismatch.code, "checksum_mismatch");
+    assert_ne!(mismatch.expected, mismatch.actual);
+}
+
+#[test]
+fn unrecorded_file_has_no_checksum_mismatch() {
+    let dir = TempDir::new();
+    fs::create_dir_all(dir.path()).unwrap();
+    let path = dir.path().join("formulas.json");
+    fs::write(&path, "[]").unwrap();
+    assert!(checksum::verify(&path, b"[]").is_none());
+}
@@ -0,0 +1,103 @@
+//! 原生窗口外观（标题栏等）跟随应用主题
+//!
+//! Tauri 1 不能在运行时修改窗口主题，这里按平台直接调用系统 API；
+//! 这些 API 只能在主线程调用。
+
+use tauri::{AppHandle, Manager, Theme, Window};
+
+/// 使用深色原生外观的应用主题，其余主题均为浅色
+const DARK_THEMES: &[&str] = &["dark"];
+
+/// 应用主题对应的原生外观；"system" 返回 `None`，表示跟随系统
+pub fn native_theme(preference: &str) -> Option<Theme> {
+    match preference {
+        "system" => None,
+        theme if DARK_THEMES.contains(&theme) => Some(Theme::Dark),
+        _ => Some(Theme::Light),
+    }
+}
+
+/// 设置窗口的原生外观，`None` 表示恢复跟随系统
+pub fn apply(window: &Window, theme: Option<Theme>) -> tauri::Result<()> {
+    let target = window.clone();
+    window.run_on_main_thread(move || {
+        if let Err(e) = set_native(&target, theme) {
+            log::error!("Failed to set window theme: {}", e);
+        }
+    })
+}
+
+/// 设置所有窗口的原生外观
+pub fn apply_all(app: &AppHandle, theme: Option<Theme>) -> tauri::Result<()> {
+    for window in app.windows().values() {
+        apply(window, theme)?;
+    }
+    Ok(())
+}
+
+#[cfg(windows)]
+fn set_native(window: &Window, theme: Option<Theme>) -> Result<(), String> {
+    use windows::Win32::Foundation::BOOL;
+    use windows::Win32::Graphics::Dwm::{DwmSetWindowAttribute, DWMWINDOWATTRIBUTE};
+    // DWMWA_USE_IMMERSIVE_DARK_MODE，Windows 10 20H1 起支持
+    const USE_IMMERSIVE_DARK_MODE: DWMWINDOWATTRIBUTE = DWMWINDOWATTRIBUTE(20);
+
+    let dark = match theme {
+        Some(theme) => theme == Theme::Dark,
+        None => window.theme().map_err(|e| e.to_string())? == Theme::Dark,
+    };
+    let hwnd = window.hwnd().map_err(|e| e.to_string())?;
+    let value = BOOL::from(dark);
+    unsafe {
+        DwmSetWindowAttribute(
+            hwnd,
+            USE_IMMERSIVE_DARK_MODE,
+            &value as *const BOOL as *const _,
+            std::mem::size_of::<BOOL>() as u32,
+        )
+    }
+    .map_err(|e| e.to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn set_native(window: &Window, theme: Option<Theme>) -> Result<(), String> {
+    use objc::runtime::Object;
+    use objc::{class, msg_send, sel, sel_impl};
+
+    let ns_window = window.ns_window().map_err(|e| e.to_string())? as *mut Object;
+    unsafe {
+        // 外观为 nil 时窗口跟随系统
+        let appearance: *mut Object = match theme {
+            None => std::ptr::null_mut(),
+            Some(theme) => {
+                let name = if theme == Theme::Dark {
+                    "NSAppearanceNameDarkAqua\0"
+                } else {
+                    "NSAppearanceNameAqua\0"
+                };
+                let name: *mut Object =
+                    msg_send![class!(NSString), stringWithUTF8String: name.as_ptr()];
+                msg_send![class!(NSAppearance), appearanceNamed: name]
+            }
+        };
+        let () = msg_send![ns_window, setAppearance: appearance];
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_native(_window: &Window, theme: Option<Theme>) -> Result<(), String> {
+    use gtk::traits::SettingsExt;
+    use std::sync::OnceLock;
+
+    // GTK 的深色偏好是整个应用共享的；记住启动时的系统值，跟随系统时恢复
+    static SYSTEM_PREFERS_DARK: OnceLock<bool> = OnceLock::new();
+    let settings = gtk::Settings::default().ok_or("GTK settings unavailable")?;
+    let system =
+        *SYSTEM_PREFERS_DARK.get_or_init(|| settings.is_gtk_application_prefer_dark_theme());
+    settings.set_gtk_application_prefer_dark_theme(match theme {
+        Some(theme) => theme == Theme::Dark,
+        None => system,
+    });
+    Ok(())
+}
@@ -0,0 +1,206 @@
+//! 系统托盘：显示/隐藏主窗口、从剪贴板新建公式、打开最近文件和退出
+//!
+//! 托盘可在运行时创建和销毁；部分 Linux 桌面环境对托盘支持不好，因此默认只在
+//! Windows 和 macOS 上启用。Linux 上点击托盘图标不会触发事件，只能使用菜单。
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{
+    AppHandle, CustomMenuItem, Manager, SystemTray, SystemTrayEvent, SystemTrayMenu,
+    SystemTrayMenuItem, SystemTraySubmenu, Window,
+};
+
+use crate::clipboard::SystemClipboard;
+use crate::menu::{OpenRecent, RECENT_SLOTS, RECENT_SLOT_PREFIX};
+use crate::recent::{RecentFile, RecentFiles};
+use crate::settings::SettingsStore;
+use crate::window_state::{show_main, MAIN_WINDOW};
+
+/// 是否显示托盘图标在设置中的键名
+pub const TRAY_ENABLED_KEY: &str = "trayEnabled";
+/// 关闭主窗口时是否隐藏到托盘在设置中的键名
+pub const MINIMIZE_TO_TRAY_KEY: &str = "minimizeToTray";
+
+const TRAY_ID: &str = "main";
+
+#[derive(Serialize, Clone)]
+struct ClipboardFormula {
+    text: String,
+}
+
+/// 托盘状态，作为 `tauri::State` 托管
+#[derive(Default)]
+pub struct Tray {
+    /// 用户从托盘选择了退出，此时关闭窗口不再隐藏到托盘
+    quitting: AtomicBool,
+    /// “最近打开”各槽位显示的路径；点击时按槽位查找，不重新读取会移除失效条目的列表
+    recent: Mutex<Vec<String>>,
+}
+
+impl Tray {
+    fn recent_path(&self, slot: usize) -> Option<String> {
+        self.recent.lock().unwrap().get(slot).cloned()
+    }
+
+    /// 用户在确认对话框中取消了关闭，之后关闭主窗口仍隐藏到托盘
+    pub fn cancel_quit(&self) {
+        self.quitting.store(false, Ordering::SeqCst);
+    }
+}
+
+/// 设置中的托盘开关，未设置时 Linux 默认关闭
+pub fn enabled_in(store: &SettingsStore) -> bool {
+    store
+        .get(TRAY_ENABLED_KEY)
+        .and_then(|v| v.as_bool())
+        .unwrap_or(cfg!(not(target_os = "linux")))
+}
+
+/// 构建托盘菜单，并记录“最近打开”槽位对应的路径
+fn tray_menu(app: &AppHandle, recent: &[RecentFile]) -> SystemTrayMenu {
+    *app.state::<Tray>().recent.lock().unwrap() = recent
+        .iter()
+        .take(RECENT_SLOTS)
+        .map(|file| file.path.clone())
+        .collect();
+    let mut files = SystemTrayMenu::new();
+    for (slot, file) in recent.iter().take(RECENT_SLOTS).enumerate() {
+        let id = format!("{}{}", RECENT_SLOT_PREFIX, slot);
+        files = files.add_item(CustomMenuItem::new(id, &file.name));
+    }
+    if recent.is_empty() {
+        files = files.add_item(CustomMenuItem::new("recent-empty", "（无）").disabled());
+    }
+    SystemTrayMenu::new()
+        .add_item(CustomMenuItem::new("toggle-window", "显示/隐藏窗口"))
+        .add_item(CustomMenuItem::new(
+            "new-from-clipboard",
+            "从剪贴板新建公式",
+        ))
+        .add_submenu(SystemTraySubmenu::new("最近打开", files))
+        .add_native_item(SystemTrayMenuItem::Separator)
+        .add_item(CustomMenuItem::new("quit", "退出"))
+}
+
+/// 创建托盘图标；已存在时不做任何事
+pub fn enable(app: &AppHandle) -> tauri::Result<()> {
+    if app.tray_handle_by_id(TRAY_ID).is_some() {
+        return Ok(());
+    }
+    let recent = app.state::<RecentFiles>().list().unwrap_or_default();
+    let handle = app.clone();
+    SystemTray::new()
+        .with_id(TRAY_ID)
+        .with_tooltip("MathLive Formula Editor")
+        .with_menu(tray_menu(app, &recent))
+        .on_event(move |event| handle_event(&handle, event))
+        .build(app)?;
+    Ok(())
+}
+
+/// 移除托盘图标；不存在时不做任何事
+pub fn disable(app: &AppHandle) -> tauri::Result<()> {
+    match app.tray_handle_by_id(TRAY_ID) {
+        Some(tray) => tray.destroy(),
+        None => Ok(()),
+    }
+}
+
+/// 用最近文件列表重建托盘菜单
+pub fn refresh_recent(app: &AppHandle, files: &[RecentFile]) -> tauri::Result<()> {
+    match app.tray_handle_by_id(TRAY_ID) {
+        Some(tray) => tray.set_menu(tray_menu(app, files)),
+        None => Ok(()),
+    }
+}
+
+/// 关闭主窗口时是否应改为隐藏到托盘
+pub fn should_hide_on_close(window: &Window) -> bool {
+    let app = window.app_handle();
+    window.label() == MAIN_WINDOW
+        && !app.state::<Tray>().quitting.load(Ordering::SeqCst)
+        && app.tray_handle_by_id(TRAY_ID).is_some()
+        && app
+            .try_state::<SettingsStore>()
+            .and_then(|store| store.get(MINIMIZE_TO_TRAY_KEY))
+            .and_then(|v| v.as_bool())
+            .unwrap_or(false)
+}
+
+fn toggle_main(app: &AppHandle) {
+    let Some(window) = app.get_window(MAIN_WINDOW) else {
+        return;
+    };
+    match window.is_visible() {
+        Ok(true) => {
+            if let Err(e) = window.hide() {
+                log::error!("Failed to hide window: {}", e);
+            }
+        }
+        _ => {
+            show_main(app);
+        }
+    }
+}
+
+/// 读取剪贴板的文字，在主窗口中新建公式；读取在后台完成，不阻塞托盘事件
+fn new_from_clipboard(app: AppHandle) {
+    tauri::async_runtime::spawn(async move {
+        let text = match app.state::<SystemClipboard>().read_text().await {
+            Ok(text) => text,
+            Err(e) => {
+                log::error!("Failed to read clipboard: {}", e);
+                return;
+            }
+        };
+        if let Some(window) = show_main(&app) {
+            if let Err(e) = window.emit("tray:new-formula", ClipboardFormula { text }) {
+                log::error!("Failed to emit tray:new-formula: {}", e);
+            }
+        }
+    });
+}
+
+fn handle_event(app: &AppHandle, event: SystemTrayEvent) {
+    let SystemTrayEvent::MenuItemClick { id, .. } = event else {
+        if let SystemTrayEvent::LeftClick { .. } = event {
+            toggle_main(app);
+        }
+        return;
+    };
+    let result = match id.as_str() {
+        "toggle-window" => {
+            toggle_main(app);
+            Ok(())
+        }
+        "new-from-clipboard" => {
+            new_from_clipboard(app.clone());
+            Ok(())
+        }
+        "quit" => {
+            // 逐个关闭窗口以便前端确认未保存的内容，最后一个窗口关闭后应用退出
+            app.state::<Tray>().quitting.store(true, Ordering::SeqCst);
+            for window in app.windows().values() {
+                if let Err(e) = window.close() {
+                    log::error!("Failed to close window: {}", e);
+                }
+            }
+            Ok(())
+        }
+        other => {
+            let path = other
+                .strip_prefix(RECENT_SLOT_PREFIX)
+                .and_then(|slot| slot.parse::<usize>().ok())
+                .and_then(|slot| app.state::<Tray>().recent_path(slot));
+            match path.and_then(|path| Some((show_main(app)?, path))) {
+                Some((window, path)) => window.emit("menu:open-recent", OpenRecent { path }),
+                None => Ok(()),
+            }
+        }
+    };
+    if let Err(e) = result {
+        log::error!("Failed to handle tray event {}: {}", id, e);
+    }
+}
@@ -0,0 +1,206 @@
+//! 把 `math` 模块的语法树转换为 Typst 数学语法
+
+use crate::math::{Accent, Node, Variant};
+
+/// Typst 数学模式中需要转义的字符
+const SPECIAL: &[char] = &[
+    '\\', '/', '_', '^', '&', '#', '$', '"', '{', '}', '@', '<', '>',
+];
+
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if SPECIAL.contains(&ch) {
+            out.push('\\');
+        }
+        out.push(ch);
+    }
+    out
+}
+
+/// 函数参数中顶层的逗号和分号会被当作参数分隔符，需要转义；
+/// 括号内和字符串中的不处理
+fn escape_arg(text: String) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for ch in text.chars() {
+        if !escaped && !in_string && depth == 0 && (ch == ',' || ch == ';') {
+            out.push('\\');
+        }
+        match ch {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '"' => in_string = !in_string,
+            '(' if !in_string => depth += 1,
+            ')' if !in_string => depth = depth.saturating_sub(1),
+            _ => {}
+        }
+        out.push(ch);
+    }
+    out
+}
+
+fn large_op(op: &str) -> &'static str {
+    match op {
+        "∑" => "sum",
+        "∏" => "product",
+        "∐" => "product.co",
+        "∫" => "integral",
+        "∬" => "integral.double",
+        "∭" => "integral.triple",
+        "∮" => "integral.cont",
+        "⋃" => "union.big",
+        "⋂" => "sect.big",
+        "⨁" => "plus.circle.big",
+        _ => "times.circle.big",
+    }
+}
+
+fn matrix_delim(symbol: &str) -> &'static str {
+    match symbol {
+        "(" => "\"(\"",
+        "[" => "\"[\"",
+        "{" => "\"{\"",
+        "|" => "\"|\"",
+        "‖" => "\"||\"",
+        _ => "#none",
+    }
+}
+
+/// 多个节点组成的底数不能直接接上下标，需要用 `attach`
+fn needs_attach(node: &Node) -> bool {
+    matches!(node, Node::Row(_))
+}
+
+pub fn to_typst(node: &Node) -> String {
+    match node {
+        Node::Ident(name) if name.chars().count() > 1 => {
+            if crate::math::is_function(name) {
+                name.clone()
+            } else {
+                format!("op(\"{}\")", name.replace('"', "\\\""))
+            }
+        }
+        Node::Ident(name) | Node::Number(name) | Node::Operator(name) => escape(name),
+        Node::LargeOp(op) => large_op(op).to_string(),
+        Node::Text(text) => format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\"")),
+        Node::Space(width) => match *width {
+            "1em" => "quad".to_string(),
+            "2em" => "wide".to_string(),
+            "0.167em" => "thin".to_string(),
+            "0.222em" => "med".to_string(),
+            w if w.starts_with('-') => String::new(),
+            _ => "thick".to_string(),
+        },
+        Node::Row(nodes) => nodes
+            .iter()
+            .map(to_typst)
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "),
+        Node::Frac { num, den, bar } => {
+            let (num, den) = (escape_arg(to_typst(num)), escape_arg(to_typst(den)));
+            if *bar {
+                format!("frac({}, {})", num, den)
+            } else {
+                format!("binom({}, {})", num, den)
+            }
+        }
+        Node::Sqrt { body, index } => match index {
+            Some(index) => format!(
+                "root({}, {})",
+                escape_arg(to_typst(index)),
+                escape_arg(to_typst(body))
+            ),
+            None => format!("sqrt({})", escape_arg(to_typst(body))),
+        },
+        Node::Scripts { base, sub, sup } => {
+            let sub = sub.as_ref().map(|n| to_typst(n));
+            let sup = sup.as_ref().map(|n| to_typst(n));
+            if !needs_attach(base) {
+                let mut out = to_typst(base);
+                if let Some(sub) = sub {
+                    out.push_str(&format!("_({})", sub));
+                }
+                if let Some(sup) = sup {
+                    out.push_str(&format!("^({})", sup));
+                }
+                out
+            } else {
+                let mut args = vec![escape_arg(to_typst(base))];
+                if let Some(sub) = sub {
+                    args.push(format!("b: {}", escape_arg(sub)));
+                }
+                if let Some(sup) = sup {
+                    args.push(format!("t: {}", escape_arg(sup)));
+                }
+                format!("attach({})", args.join(", "))
+            }
+        }
+        Node::Accent { body, accent } => {
+            let func = match accent {
+                Accent::Hat => "hat",
+                Accent::Bar => "macron",
+                Accent::Overline => "overline",
+                Accent::Underline => "underline",
+                Accent::Vec => "arrow",
+                Accent::Dot => "dot",
+                Accent::Ddot => "dot.double",
+                Accent::Tilde => "tilde",
+            };
+            format!("{}({})", func, escape_arg(to_typst(body)))
+        }
+        // 成对的定界符在 Typst 中会自动缩放
+        Node::Fenced { open, close, body } => [escape(open), to_typst(body), escape(close)]
+            .into_iter()
+            .filter(|s| !s.is_empty())
+            .collect::<Vec<_>>()
+            .join(" "),
+        Node::Matrix {
+            rows,
+            open,
+            close,
+            aligned,
+        } => {
+            if *aligned {
+                return rows
+                    .iter()
+                    .map(|row| row.iter().map(to_typst).collect::<Vec<_>>().join(" & "))
+                    .collect::<Vec<_>>()
+                    .join(" \\\n  ");
+            }
+            // cases 的每行是 `值 & 条件`，矩阵的单元格以逗号分隔
+            let is_cases = open == "{" && close.is_empty();
+            let cell_sep = if is_cases { " & " } else { ", " };
+            let body = rows
+                .iter()
+                .map(|row| {
+                    row.iter()
+                        .map(|cell| escape_arg(to_typst(cell)))
+                        .collect::<Vec<_>>()
+                        .join(cell_sep)
+                })
+                .collect::<Vec<_>>();
+            if is_cases {
+                format!("cases({})", body.join(", "))
+            } else {
+                format!("mat(delim: {}, {})", matrix_delim(open), body.join("; "))
+            }
+        }
+        Node::Styled { variant, body } => {
+            let func = match variant {
+                Variant::Normal => "upright",
+                Variant::Bold => "bold",
+                Variant::Italic => "italic",
+                Variant::DoubleStruck => "bb",
+                Variant::Script => "cal",
+                Variant::Fraktur => "frak",
+                Variant::SansSerif => "sans",
+                Variant::Monospace => "mono",
+            };
+            format!("{}({})", func, escape_arg(to_typst(body)))
+        }
+    }
+}
@@ -0,0 +1,555 @@
+//! 不依赖 TeX 引擎的 LaTeX 公式检查
+//!
+//! 只做宽松的词法扫描，找出未闭合的括号、不匹配的环境和 `\left...\right` 等
+//! 通常要到编译时才暴露的问题。扫描不递归，任何输入都不会 panic。
+
+use serde::Serialize;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// 几乎肯定无法编译
+    Error,
+    /// 可能有意为之，例如半开区间 `[0, 1)`
+    Warning,
+}
+
+#[derive(Serialize, Debug)]
+pub struct Diagnostic {
+    /// 问题在公式中的偏移（UTF-16 单位，与前端字符串下标一致）
+    pub offset: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// 允许 `&` 和 `\\` 的环境
+const ALIGNMENT_ENVS: &[&str] = &[
+    "matrix",
+    "pmatrix",
+    "bmatrix",
+    "Bmatrix",
+    "vmatrix",
+    "Vmatrix",
+    "smallmatrix",
+    "cases",
+    "dcases",
+    "rcases",
+    "array",
+    "subarray",
+    "aligned",
+    "alignedat",
+    "split",
+    "align",
+    "align*",
+    "alignat",
+    "alignat*",
+    "flalign",
+    "flalign*",
+    "eqnarray",
+    "eqnarray*",
+    "tabular",
+];
+
+/// 只允许 `\\` 的环境
+const LINE_BREAK_ENVS: &[&str] = &["gathered", "gather", "gather*", "multline", "multline*"];
+
+/// 参数为文本模式的命令，参数内不检查 `&`、`\\` 和括号
+const TEXT_COMMANDS: &[&str] = &[
+    "text",
+    "textrm",
+    "textbf",
+    "textit",
+    "textsf",
+    "texttt",
+    "mbox",
+    "hbox",
+    "textnormal",
+];
+
+/// 参数中允许 `\\` 换行的命令
+const STACK_COMMANDS: &[&str] = &["substack", "shortstack"];
+
+enum Frame {
+    Brace {
+        offset: usize,
+    },
+    /// 文本命令的参数，记录命令名
+    Text {
+        offset: usize,
+        command: String,
+    },
+    Stack {
+        offset: usize,
+    },
+    Env {
+        offset: usize,
+        name: String,
+    },
+    Left {
+        offset: usize,
+    },
+}
+
+struct Scanner {
+    chars: Vec<(usize, char)>,
+    pos: usize,
+    end: usize,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Scanner {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).map(|&(_, ch)| ch)
+    }
+
+    fn offset(&self) -> usize {
+        self.chars
+            .get(self.pos)
+            .map_or(self.end, |&(offset, _)| offset)
+    }
+
+    fn report(&mut self, offset: usize, severity: Severity, message: String) {
+        self.diagnostics.push(Diagnostic {
+            offset,
+            severity,
+            message,
+        });
+    }
+
+    fn skip_spaces(&mut self) {
+        while self.peek().is_some_and(char::is_whitespace) {
+            self.pos += 1;
+        }
+    }
+
+    /// 读取反斜杠之后的命令名：连续字母，或单个非字母字符
+    fn command_name(&mut self) -> Option<String> {
+        let first = self.peek()?;
+        self.pos += 1;
+        if !first.is_ascii_alphabetic() {
+            return Some(first.to_string());
+        }
+        let mut name = first.to_string();
+        while let Some(ch) = self.peek().filter(char::is_ascii_alphabetic) {
+            name.push(ch);
+            self.pos += 1;
+        }
+        Some(name)
+    }
+
+    /// 读取 `{name}` 形式的环境名；缺少花括号或未闭合时返回 `None`
+    fn env_name(&mut self) -> Option<String> {
+        self.skip_spaces();
+        if self.peek() != Some('{') {
+            return None;
+        }
+        let start = self.pos;
+        self.pos += 1;
+        let mut name = String::new();
+        while let Some(ch) = self.peek() {
+            self.pos += 1;
+            if ch == '}' {
+                return Some(name.trim().to_string());
+            }
+            if ch == '{' || ch == '\\' {
+                break;
+            }
+            name.push(ch);
+        }
+        self.pos = start;
+        None
+    }
+
+    /// 跳过 `\left` / `\right` 之后的定界符，使其不参与括号配对
+    fn skip_delimiter(&mut self) {
+        self.skip_spaces();
+        match self.peek() {
+            Some('\\') => {
+                self.pos += 1;
+                self.command_name();
+            }
+            Some(_) => self.pos += 1,
+            None => {}
+        }
+    }
+}
+
+fn describe(frame: &Frame) -> (usize, String) {
+    match frame {
+        Frame::Brace { offset } | Frame::Stack { offset } => (*offset, "{ 没有闭合".to_string()),
+        Frame::Text { offset, command } => (*offset, format!("\\{}{{ 没有结束", command)),
+        Frame::Env { offset, name } => (
+            *offset,
+            format!("\\begin{{{0}}} 没有对应的 \\end{{{0}}}", name),
+        ),
+        Frame::Left { offset } => (*offset, "\\left 没有对应的 \\right".to_string()),
+    }
+}
+
+/// 把栈中从 `start` 起的帧都报告为未闭合并弹出
+fn unwind(scanner: &mut Scanner, stack: &mut Vec<Frame>, start: usize) {
+    let frames: Vec<Frame> = stack.drain(start..).collect();
+    for frame in frames {
+        let (offset, message) = describe(&frame);
+        scanner.report(offset, Severity::Error, message);
+    }
+}
+
+fn in_text(stack: &[Frame]) -> bool {
+    stack.iter().any(|f| matches!(f, Frame::Text { .. }))
+}
+
+/// 最近的环境或换行命令是否允许 `&`（`ampersand` 为 false 时检查 `\\`）
+fn allows(stack: &[Frame], ampersand: bool) -> bool {
+    stack.iter().rev().any(|frame| match frame {
+        Frame::Env { name, .. } => {
+            ALIGNMENT_ENVS.contains(&name.as_str())
+                || (!ampersand && LINE_BREAK_ENVS.contains(&name.as_str()))
+        }
+        Frame::Stack { .. } => !ampersand,
+        _ => false,
+    })
+}
+
+fn closing_bracket(open: char) -> char {
+    match open {
+        '(' => ')',
+        '[' => ']',
+        _ => open,
+    }
+}
+
+/// 检查一条公式，诊断按出现位置排序
+pub fn validate(latex: &str) -> Vec<Diagnostic> {
+    let mut offset = 0;
+    let chars = latex
+        .chars()
+        .map(|ch| {
+            let at = offset;
+            offset += ch.len_utf16();
+            (at, ch)
+        })
+        .collect();
+    let mut scanner = Scanner {
+        chars,
+        pos: 0,
+        end: offset,
+        diagnostics: Vec::new(),
+    };
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut brackets: Vec<(usize, char)> = Vec::new();
+    // 下一个 `{` 是文本命令或换行命令的参数
+    let mut pending: Option<Frame> = None;
+
+    while let Some(ch) = scanner.peek() {
+        let at = scanner.offset();
+        scanner.pos += 1;
+        if !ch.is_whitespace() && ch != '{' {
+            pending = None;
+        }
+        match ch {
+            '%' => {
+                while scanner.peek().is_some_and(|c| c != '\n') {
+                    scanner.pos += 1;
+                }
+            }
+            '{' => stack.push(match pending.take() {
+                Some(Frame::Text { command, .. }) => Frame::Text {
+                    offset: at,
+                    command,
+                },
+                Some(Frame::Stack { .. }) => Frame::Stack { offset: at },
+                _ => Frame::Brace { offset: at },
+            }),
+            '}' => {
+                let open = stack.iter().rposition(|f| {
+                    matches!(
+                        f,
+                        Frame::Brace { .. } | Frame::Text { .. } | Frame::Stack { .. }
+                    )
+                });
+                match open {
+                    Some(index) => {
+                        unwind(&mut scanner, &mut stack, index + 1);
+                        stack.pop();
+                    }
+                    None => scanner.report(at, Severity::Error, "多余的 }".to_string()),
+                }
+            }
+            // 文本模式中只需跳过转义，避免 `\}` 被当作闭合
+            '\\' if in_text(&stack) => scanner.pos += 1,
+            _ if in_text(&stack) => {}
+            '(' | '[' => brackets.push((at, ch)),
+            ')' | ']' => match brackets.last() {
+                Some(&(_, open)) if closing_bracket(open) == ch => {
+                    brackets.pop();
+                }
+                Some(&(open_at, open)) => {
+                    scanner.report(
+                        at,
+                        Severity::Warning,
+                        format!("{} 与位置 {} 的 {} 不匹配", ch, open_at, open),
+                    );
+                    brackets.pop();
+                }
+                None => scanner.report(at, Severity::Warning, format!("多余的 {}", ch)),
+            },
+            '&' if !allows(&stack, true) => {
+                scanner.report(at, Severity::Warning, "& 不在对齐环境中".to_string())
+            }
+            '\\' => {
+                let Some(name) = scanner.command_name() else {
+                    scanner.report(at, Severity::Error, "公式以单独的反斜杠结尾".to_string());
+                    break;
+                };
+                match name.as_str() {
+                    "\\" if !allows(&stack, false) => {
+                        scanner.report(at, Severity::Warning, "\\\\ 不在对齐环境中".to_string())
+                    }
+                    "begin" => match scanner.env_name() {
+                        Some(name) if !name.is_empty() => {
+                            stack.push(Frame::Env { offset: at, name })
+                        }
+                        _ => scanner.report(at, Severity::Error, "\\begin 缺少环境名".to_string()),
+                    },
+                    "end" => {
+                        let Some(name) = scanner.env_name().filter(|n| !n.is_empty()) else {
+                            scanner.report(at, Severity::Error, "\\end 缺少环境名".to_string());
+                            continue;
+                        };
+                        match stack.iter().rposition(|f| matches!(f, Frame::Env { .. })) {
+                            Some(index) => {
+                                unwind(&mut scanner, &mut stack, index + 1);
+                                if let Some(Frame::Env { name: open, .. }) = stack.pop() {
+                                    if open != name {
+                                        scanner.report(
+                                            at,
+                                            Severity::Error,
+                                            format!(
+                                                "\\end{{{}}} 与 \\begin{{{}}} 不匹配",
+                                                name, open
+                                            ),
+                                        );
+                                    }
+                                }
+                            }
+                            None => scanner.report(
+                                at,
+                                Severity::Error,
+                                format!("\\end{{{}}} 缺少对应的 \\begin", name),
+                            ),
+                        }
+                    }
+                    "left" => {
+                        scanner.skip_delimiter();
+                        stack.push(Frame::Left { offset: at });
+                    }
+                    "right" => {
+                        scanner.skip_delimiter();
+                        match stack.iter().rposition(|f| matches!(f, Frame::Left { .. })) {
+                            Some(index) => {
+                                unwind(&mut scanner, &mut stack, index + 1);
+                                stack.pop();
+                            }
+                            None => scanner.report(
+                                at,
+                                Severity::Error,
+                                "\\right 缺少对应的 \\left".to_string(),
+                            ),
+                        }
+                    }
+                    "middle" => {
+                        scanner.skip_delimiter();
+                        if !stack.iter().any(|f| matches!(f, Frame::Left { .. })) {
+                            scanner.report(
+                                at,
+                                Severity::Error,
+                                "\\middle 必须位于 \\left 和 \\right 之间".to_string(),
+                            );
+                        }
+                    }
+                    // `\big(` 等定界符尺寸命令不参与配对
+                    "big" | "Big" | "bigg" | "Bigg" | "bigl" | "Bigl" | "biggl" | "Biggl"
+                    | "bigr" | "Bigr" | "biggr" | "Biggr" | "bigm" | "Bigm" => {
+                        scanner.skip_delimiter()
+                    }
+                    _ if TEXT_COMMANDS.contains(&name.as_str()) => {
+                        pending = Some(Frame::Text {
+                            offset: at,
+                            command: name,
+                        });
+                    }
+                    _ if STACK_COMMANDS.contains(&name.as_str()) => {
+                        pending = Some(Frame::Stack { offset: at });
+                    }
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    unwind(&mut scanner, &mut stack, 0);
+    for (at, open) in brackets {
+        scanner.report(at, Severity::Warning, format!("{} 没有闭合", open));
+    }
+    scanner.diagnostics.sort_by_key(|d| d.offset);
+    scanner.diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 固定种子的 xorshift，结果可复现，不依赖额外的 crate
+    struct Rng(u64);
+
+    impl Rng {
+        fn next(&mut self) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0
+        }
+
+        fn below(&mut self, n: usize) -> usize {
+            (self.next() % n as u64) as usize
+        }
+    }
+
+    /// 容易打乱扫描状态的片段，随机拼接后大多是不合法的公式
+    const FRAGMENTS: &[&str] = &[
+        "{",
+        "}",
+        "[",
+        "]",
+        "(",
+        ")",
+        "\\",
+        "\\\\",
+        "&",
+        "%",
+        "\n",
+        " ",
+        "x",
+        "公式",
+        "😀",
+        "\\left",
+        "\\right",
+        "\\middle",
+        "\\big",
+        "\\begin",
+        "\\end",
+        "{cases}",
+        "{align*}",
+        "\\begin{",
+        "\\end{",
+        "\\text",
+        "\\text{",
+        "\\substack{",
+        "\\{",
+        "\\}",
+        "\\left.",
+        "\\right\\|",
+        "^",
+        "_",
+    ];
+
+    fn random_input(rng: &mut Rng) -> String {
+        let len = rng.below(40);
+        (0..len)
+            .map(|_| FRAGMENTS[rng.below(FRAGMENTS.len())])
+            .collect()
+    }
+
+    /// 生成结构合法的公式，深度受限
+    fn well_formed(rng: &mut Rng, depth: usize) -> String {
+        if depth == 0 {
+            return ["x", "y^2", "\\alpha", "公式", "1"][rng.below(5)].to_string();
+        }
+        let inner = |rng: &mut Rng| well_formed(rng, depth - 1);
+        match rng.below(7) {
+            0 => format!("{{{}}}", inner(rng)),
+            1 => format!("\\left( {} \\right)", inner(rng)),
+            2 => format!("({} + {})", inner(rng), inner(rng)),
+            3 => format!(
+                "\\begin{{cases}} {} & {} \\\\ {} & {} \\end{{cases}}",
+                inner(rng),
+                inner(rng),
+                inner(rng),
+                inner(rng)
+            ),
+            4 => format!("\\text{{a {{b}} & \\}} c}} {}", inner(rng)),
+            5 => format!("\\frac{{{}}}{{{}}}", inner(rng), inner(rng)),
+            _ => format!("{} {}", inner(rng), inner(rng)),
+        }
+    }
+
+    #[test]
+    fn random_input_never_panics_and_offsets_stay_in_range() {
+        let mut rng = Rng(0x9E37_79B9_7F4A_7C15);
+        for _ in 0..5000 {
+            let input = random_input(&mut rng);
+            let len = input.encode_utf16().count();
+            let diagnostics = validate(&input);
+            assert!(
+                diagnostics.windows(2).all(|w| w[0].offset <= w[1].offset),
+                "{:?}",
+                input
+            );
+            assert!(diagnostics.iter().all(|d| d.offset <= len), "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn well_formed_input_has_no_diagnostics() {
+        let mut rng = Rng(42);
+        for _ in 0..1000 {
+            let input = well_formed(&mut rng, 4);
+            let diagnostics = validate(&input);
+            assert!(diagnostics.is_empty(), "{:?}: {:?}", input, diagnostics);
+        }
+    }
+
+    #[test]
+    fn deleting_a_closing_brace_is_reported() {
+        let mut rng = Rng(7);
+        for _ in 0..500 {
+            let input = format!("{{{}}}", well_formed(&mut rng, 3));
+            let truncated = &input[..input.len() - 1];
+            let diagnostics = validate(truncated);
+            assert!(
+                diagnostics.iter().any(|d| d.severity == Severity::Error),
+                "{:?}",
+                truncated
+            );
+        }
+    }
+
+    #[test]
+    fn reports_each_kind_of_problem() {
+        let cases = [
+            ("{x", 0, Severity::Error),
+            ("x}", 1, Severity::Error),
+            ("\\begin{cases} x \\end{array}", 16, Severity::Error),
+            ("\\left( x", 0, Severity::Error),
+            ("x \\right)", 2, Severity::Error),
+            ("\\text{abc", 5, Severity::Error),
+            ("a & b", 2, Severity::Warning),
+            ("a \\\\ b", 2, Severity::Warning),
+            ("[0, 1)", 5, Severity::Warning),
+        ];
+        for (input, offset, severity) in cases {
+            let diagnostics = validate(input);
+            assert_eq!(diagnostics.len(), 1, "{:?}: {:?}", input, diagnostics);
+            assert_eq!(diagnostics[0].offset, offset, "{:?}", input);
+            assert_eq!(diagnostics[0].severity, severity, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn offsets_count_utf16_units() {
+        let diagnostics = validate("😀{");
+        assert_eq!(diagnostics[0].offset, 2);
+    }
+}
@@ -0,0 +1,152 @@
+//! 监视各窗口打开的公式文件，外部修改、重命名或删除时通知对应窗口
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use notify::event::ModifyKind;
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+use tauri::Window;
+
+use crate::error::AppError;
+
+/// 合并同一次保存触发的多个文件系统事件
+const DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// 本应用自己写入文件后，在这段时间内忽略变更事件
+const SELF_WRITE_GRACE: Duration = Duration::from_millis(1500);
+
+#[derive(Serialize, Clone)]
+struct FileChanged {
+    /// "modified" | "renamed" | "removed"
+    kind: &'static str,
+    path: String,
+}
+
+struct ActiveWatch {
+    path: PathBuf,
+    ignore_until: Arc<Mutex<Option<Instant>>>,
+    /// 持有即保持监视，丢弃后后台线程随通道关闭而退出
+    _watcher: RecommendedWatcher,
+}
+
+/// 文件监视器，作为 `tauri::State` 托管；每个窗口各自监视一个文件，以窗口标签区分
+#[derive(Default)]
+pub struct FileWatcher {
+    active: Mutex<HashMap<String, ActiveWatch>>,
+}
+
+impl FileWatcher {
+    /// 开始监视文件，替换该窗口之前的监视
+    pub fn watch(&self, window: Window, path: PathBuf) -> Result<(), AppError> {
+        let watch_failed = |message: String| AppError::WatchFailed {
+            path: path.to_string_lossy().into_owned(),
+            message,
+        };
+        let file_name = path
+            .file_name()
+            .map(|n| n.to_os_string())
+            .ok_or_else(|| watch_failed("path has no file name".to_string()))?;
+        // 监视所在目录：编辑器常以“写临时文件再重命名”的方式保存，直接监视文件会丢失后续事件
+        let dir = path
+            .parent()
+            .filter(|p| !p.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."))
+            .to_path_buf();
+
+        let (tx, rx) = mpsc::channel::<EventKind>();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            if let Ok(event) = res {
+                if event.paths.iter().any(|p| p.file_name() == Some(file_name.as_os_str())) {
+                    let _ = tx.send(event.kind);
+                }
+            }
+        })
+        .map_err(|e| watch_failed(e.to_string()))?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(|e| watch_failed(e.to_string()))?;
+
+        let ignore_until = Arc::new(Mutex::new(None));
+        let label = window.label().to_string();
+        let target = path.clone();
+        let until = Arc::clone(&ignore_until);
+        std::thread::spawn(move || debounce_loop(rx, window, target, until));
+
+        self.active.lock().unwrap().insert(label, ActiveWatch {
+            path,
+            ignore_until,
+            _watcher: watcher,
+        });
+        Ok(())
+    }
+
+    /// 停止该窗口的监视
+    pub fn unwatch(&self, label: &str) {
+        self.active.lock().unwrap().remove(label);
+    }
+
+    /// 记录本应用即将写入该文件，避免把自己的保存当成外部修改；监视同一文件的窗口都会忽略
+    pub fn note_self_write(&self, path: &Path) {
+        for active in self.active.lock().unwrap().values() {
+            if active.path == path {
+                *active.ignore_until.lock().unwrap() = Some(Instant::now() + SELF_WRITE_GRACE);
+            }
+        }
+    }
+}
+
+fn debounce_loop(
+    rx: mpsc::Receiver<EventKind>,
+    window: Window,
+    path: PathBuf,
+    ignore_until: Arc<Mutex<Option<Instant>>>,
+) {
+    while let Ok(first) = rx.recv() {
+        let mut relevant = is_change(&first);
+        let mut renamed = is_rename(&first);
+        let deadline = Instant::now() + DEBOUNCE;
+        loop {
+            match rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                Ok(kind) => {
+                    relevant |= is_change(&kind);
+                    renamed |= is_rename(&kind);
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        let ignored = ignore_until
+            .lock()
+            .unwrap()
+            .is_some_and(|until| Instant::now() < until);
+        if !relevant || ignored {
+            continue;
+        }
+        let kind = if path.exists() {
+            "modified"
+        } else if renamed {
+            "renamed"
+        } else {
+            "removed"
+        };
+        let payload = FileChanged {
+            kind,
+            path: path.to_string_lossy().to_string(),
+        };
+        if let Err(e) = window.emit("file:changed", payload) {
+            log::warn!("Failed to emit file change event: {}", e);
+        }
+    }
+}
+
+fn is_change(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Create(_) | EventKind::Modify(_) | EventKind::Remove(_))
+}
+
+fn is_rename(kind: &EventKind) -> bool {
+    matches!(kind, EventKind::Modify(ModifyKind::Name(_)))
+}
@@ -0,0 +1,232 @@
+//! 主窗口的位置、大小和最大化状态：保存在配置目录下的 window-state.json 中，启动时恢复
+//!
+//! 移动和缩放事件非常频繁，写盘经过防抖；关闭窗口时立即保存。
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, LogicalSize, Manager, Monitor, PhysicalPosition, PhysicalSize, Window};
+
+use crate::error::AppError;
+use crate::write_file_atomic;
+
+/// 需要保存几何状态的窗口标签
+pub const MAIN_WINDOW: &str = "main";
+
+/// 最后一次移动或缩放之后等待多久再写盘
+const SAVE_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// 与 tauri.conf.json 中的默认窗口尺寸一致，重置时使用
+const DEFAULT_WIDTH: f64 = 1280.0;
+const DEFAULT_HEIGHT: f64 = 800.0;
+
+/// 标题栏附近至少要有这么大的区域落在某个显示器内，才认为窗口可以拖回来
+const VISIBLE_MARGIN: i32 = 64;
+
+/// 窗口几何状态，坐标和尺寸均为物理像素
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowGeometry {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub maximized: bool,
+    /// 保存时窗口所在显示器的名称，仅用于诊断
+    pub monitor: Option<String>,
+}
+
+/// 窗口状态存储，作为 `tauri::State` 托管
+pub struct WindowState {
+    file: PathBuf,
+    /// 每个窗口最近一次防抖保存的序号，只有最新的一次会真正写盘
+    pending: Mutex<HashMap<String, u64>>,
+    /// 最近一次未最大化时的几何状态；最大化时保存它，取消最大化后能回到原来的位置
+    last: Mutex<Option<WindowGeometry>>,
+}
+
+impl WindowState {
+    pub fn new(file: PathBuf) -> Self {
+        WindowState {
+            file,
+            pending: Mutex::new(HashMap::new()),
+            last: Mutex::new(None),
+        }
+    }
+
+    fn load(&self) -> Option<WindowGeometry> {
+        fs::read_to_string(&self.file)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+    }
+
+    fn save(&self, geometry: &WindowGeometry) -> Result<(), AppError> {
+        if let Some(dir) = self.file.parent() {
+            fs::create_dir_all(dir)
+                .map_err(|e| AppError::io(dir, "Failed to create config directory", e))?;
+        }
+        let content = serde_json::to_string_pretty(geometry).map_err(AppError::encode_json)?;
+        write_file_atomic(&self.file, content.as_bytes())
+    }
+
+    /// 读取窗口当前的几何状态；最小化时位置无意义，返回 `None`
+    ///
+    /// 最大化时保存未最大化时的位置和大小：先取最近一次记录的，没有时取上次保存的；
+    /// 两者都没有时无法得知，返回 `None`，不把最大化后的大小当作正常大小保存
+    fn capture(&self, window: &Window) -> tauri::Result<Option<WindowGeometry>> {
+        if window.is_minimized()? {
+            return Ok(None);
+        }
+        let maximized = window.is_maximized()?;
+        let mut last = self.last.lock().unwrap();
+        if maximized {
+            let normal = last.clone().or_else(|| self.load());
+            return Ok(normal.map(|normal| WindowGeometry {
+                maximized: true,
+                ..normal
+            }));
+        }
+        let position = window.outer_position()?;
+        let size = window.inner_size()?;
+        let geometry = WindowGeometry {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+            maximized,
+            monitor: window.current_monitor()?.and_then(|m| m.name().cloned()),
+        };
+        *last = Some(geometry.clone());
+        Ok(Some(geometry))
+    }
+
+    /// 立即保存窗口状态，关闭窗口时调用
+    pub fn save_now(&self, window: &Window) {
+        if window.label() != MAIN_WINDOW {
+            return;
+        }
+        self.pending.lock().unwrap().remove(window.label());
+        match self.capture(window) {
+            Ok(Some(geometry)) => {
+                if let Err(e) = self.save(&geometry) {
+                    log::error!("Failed to save window state: {}", e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => log::error!("Failed to read window state: {}", e),
+        }
+    }
+
+    /// 移动或缩放后延迟保存，期间的后续事件会取消之前的保存
+    pub fn schedule_save(&self, window: &Window) {
+        if window.label() != MAIN_WINDOW {
+            return;
+        }
+        let ticket = {
+            let mut pending = self.pending.lock().unwrap();
+            let ticket = pending.get(window.label()).map_or(1, |t| t + 1);
+            pending.insert(window.label().to_string(), ticket);
+            ticket
+        };
+        let window = window.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(SAVE_DEBOUNCE).await;
+            let state = window.state::<WindowState>();
+            let current = state.pending.lock().unwrap().get(window.label()) == Some(&ticket);
+            if current {
+                state.save_now(&window);
+            }
+        });
+    }
+
+    /// 恢复保存的窗口状态；保存的位置不在任何已连接的显示器上时改为在主显示器居中
+    pub fn restore(&self, window: &Window) -> tauri::Result<()> {
+        let Some(geometry) = self.load() else {
+            // 没有保存的状态时记下初始的位置和大小，之后直接最大化也能保存正常大小
+            self.capture(window)?;
+            return Ok(());
+        };
+        let monitors = window.available_monitors()?;
+        let on_screen = monitors.iter().any(|m| title_bar_visible(&geometry, m));
+        let target = if on_screen {
+            None
+        } else {
+            window
+                .primary_monitor()?
+                .or_else(|| monitors.into_iter().next())
+        };
+
+        let (mut width, mut height) = (geometry.width, geometry.height);
+        if let Some(monitor) = &target {
+            width = width.min(monitor.size().width);
+            height = height.min(monitor.size().height);
+        }
+        if width > 0 && height > 0 {
+            window.set_size(PhysicalSize::new(width, height))?;
+        }
+        match &target {
+            None => window.set_position(PhysicalPosition::new(geometry.x, geometry.y))?,
+            Some(monitor) => {
+                let origin = monitor.position();
+                let x = origin.x + (monitor.size().width.saturating_sub(width) / 2) as i32;
+                let y = origin.y + (monitor.size().height.saturating_sub(height) / 2) as i32;
+                window.set_position(PhysicalPosition::new(x, y))?;
+            }
+        }
+        *self.last.lock().unwrap() = Some(WindowGeometry {
+            maximized: false,
+            ..geometry.clone()
+        });
+        if geometry.maximized {
+            window.maximize()?;
+        }
+        Ok(())
+    }
+
+    /// 删除保存的状态，并把窗口恢复为默认大小并居中
+    pub fn reset(&self, window: &Window) -> Result<(), AppError> {
+        self.pending.lock().unwrap().clear();
+        *self.last.lock().unwrap() = None;
+        if self.file.exists() {
+            fs::remove_file(&self.file)
+                .map_err(|e| AppError::io(&self.file, "Failed to delete window state", e))?;
+        }
+        window.unmaximize()?;
+        window.set_size(LogicalSize::new(DEFAULT_WIDTH, DEFAULT_HEIGHT))?;
+        window.center()?;
+        self.capture(window)?;
+        Ok(())
+    }
+}
+
+/// 显示主窗口并取消最小化、获取焦点；主窗口不存在时返回 `None`
+pub fn show_main(app: &AppHandle) -> Option<Window> {
+    let window = app.get_window(MAIN_WINDOW)?;
+    let shown = window
+        .show()
+        .and_then(|_| window.unminimize())
+        .and_then(|_| window.set_focus());
+    if let Err(e) = shown {
+        log::error!("Failed to show window: {}", e);
+    }
+    Some(window)
+}
+
+/// 窗口左上角附近的标题栏区域是否与显示器相交
+fn title_bar_visible(geometry: &WindowGeometry, monitor: &Monitor) -> bool {
+    let origin = monitor.position();
+    let size = monitor.size();
+    let right = origin.x.saturating_add(size.width as i32);
+    let bottom = origin.y.saturating_add(size.height as i32);
+    let bar_right = geometry
+        .x
+        .saturating_add((geometry.width as i32).min(VISIBLE_MARGIN * 4));
+    geometry.x < right - VISIBLE_MARGIN
+        && bar_right > origin.x + VISIBLE_MARGIN
+        && geometry.y >= origin.y - VISIBLE_MARGIN / 2
+        && geometry.y < bottom - VISIBLE_MARGIN
+}
@@ -0,0 +1,59 @@
+//! 网页视图缩放：Tauri 1 没有跨平台的缩放接口，按平台直接调用 WebView 的 API
+
+use tauri::{AppHandle, Manager, Window};
+
+use crate::settings::SettingsStore;
+
+/// 缩放比例在设置中的键名
+pub const ZOOM_KEY: &str = "zoomFactor";
+
+const MIN_ZOOM: f64 = 0.5;
+const MAX_ZOOM: f64 = 3.0;
+const DEFAULT_ZOOM: f64 = 1.0;
+
+/// 把缩放比例限制在允许范围内；非有限值视为默认比例
+pub fn clamp(factor: f64) -> f64 {
+    if factor.is_finite() {
+        factor.clamp(MIN_ZOOM, MAX_ZOOM)
+    } else {
+        DEFAULT_ZOOM
+    }
+}
+
+/// 设置中保存的缩放比例，缺失或无效时为 1.0
+pub fn saved(store: &SettingsStore) -> f64 {
+    store
+        .get(ZOOM_KEY)
+        .and_then(|v| v.as_f64())
+        .map_or(DEFAULT_ZOOM, clamp)
+}
+
+/// 设置窗口的缩放比例
+pub fn apply(window: &Window, factor: f64) -> tauri::Result<()> {
+    window.with_webview(move |webview| {
+        #[cfg(target_os = "linux")]
+        {
+            use webkit2gtk::WebViewExt;
+            webview.inner().set_zoom_level(factor);
+        }
+        #[cfg(windows)]
+        unsafe {
+            if let Err(e) = webview.controller().SetZoomFactor(factor) {
+                log::error!("Failed to set zoom factor: {}", e);
+            }
+        }
+        #[cfg(target_os = "macos")]
+        unsafe {
+            use objc::{msg_send, sel, sel_impl};
+            let () = msg_send![webview.inner(), setPageZoom: factor];
+        }
+    })
+}
+
+/// 设置所有窗口的缩放比例
+pub fn apply_all(app: &AppHandle, factor: f64) -> tauri::Result<()> {
+    for window in app.windows().values() {
+        apply(window, factor)?;
+    }
+    Ok(())
+}
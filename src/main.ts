@@ -83,10 +83,13 @@ type TemplateLibrary = {
 
 // Centralized UI + persistence state; mutated via helper functions to keep DOM in sync
 const TEMPLATE_STORAGE_KEY = 'mathlive.templateLibrary';
+// 与后端 TEMPLATE_LIBRARY_VERSION 保持一致
+const TEMPLATE_LIBRARY_VERSION = 2;
 const ALL_CATEGORY_ID = '__all__';
 
 const state: {
   formulas: FormulaItem[];
+  collectionTitle: string;
   mode: Mode;
   nextIndex: number;
   editingId: string | null;
@@ -96,6 +99,8 @@ const state: {
   boundFilePath: string;
   lastAutosaveAt: Date | null;
   templateLibrary: TemplateLibrary;
+  // 写入模板文件时使用的格式版本；用户拒绝升级旧版文件时保持原版本
+  templateLibraryVersion: number;
   templateFileHandle: FileSystemFileHandle | null;
   templateFileName: string;
   templateFilePath: string;
@@ -105,6 +110,7 @@ const state: {
   virtualListEnabled: boolean;
 } = {
   formulas: [],
+  collectionTitle: '',
   mode: 'wysiwyg',
   nextIndex: 1,
   editingId: null,
@@ -118,6 +124,7 @@ const state: {
     categories: [],
     selectedCategoryId: '',
   },
+  templateLibraryVersion: TEMPLATE_LIBRARY_VERSION,
   templateFileHandle: null,
   templateFileName: '',
   templateFilePath: '',
@@ -191,6 +198,20 @@ const isElectronShell = Boolean(window?.electronAPI);
 const isTauriEnv = isTauri();
 const tauriApi = getTauriAPI();
 
+// 未捕获的错误写入后端日志文件，便于用户反馈问题时附上
+if (isTauriEnv) {
+  const logError = (message: string) => {
+    tauriApi.logMessage('error', message).catch(() => {});
+  };
+  window.addEventListener('error', (event) => {
+    logError(`${event.message} (${event.filename}:${event.lineno}:${event.colno})`);
+  });
+  window.addEventListener('unhandledrejection', (event) => {
+    const reason = event.reason instanceof Error ? event.reason.stack ?? event.reason.message : String(event.reason);
+    logError(`Unhandled rejection: ${reason}`);
+  });
+}
+
 const extractFileName = (path: string) => {
   if (!path) return '';
   const parts = path.split(/[/\\]/);
@@ -251,9 +272,9 @@ const setTheme = (theme: Theme, { skipPersist = false }: { skipPersist?: boolean
 };
 
 const loadThemePreference = () => {
+  const validThemes: Theme[] = ['light', 'dark', 'blue', 'pink', 'green', 'purple', 'paper', 'sunset'];
   try {
     const stored = window.localStorage?.getItem(THEME_STORAGE_KEY);
-    const validThemes: Theme[] = ['light', 'dark', 'blue', 'pink', 'green', 'purple', 'paper', 'sunset'];
     if (stored && validThemes.includes(stored as Theme)) {
       setTheme(stored as Theme, { skipPersist: true });
       return;
@@ -262,6 +283,19 @@ const loadThemePreference = () => {
     console.info('无法读取主题偏好', error);
   }
   setTheme('light', { skipPersist: true });
+  if (isTauriEnv) {
+    // localStorage 为空时（如 WebView 数据被清理）回退到后端保存的主题
+    tauriApi
+      .getThemePreference()
+      .then((saved) => {
+        if (validThemes.includes(saved as Theme)) {
+          setTheme(saved as Theme, { skipPersist: true });
+        }
+      })
+      .catch((error) => {
+        console.info('无法读取 Tauri 主题偏好', error);
+      });
+  }
 };
 
 loadThemePreference();
@@ -275,7 +309,7 @@ topBar.innerHTML = `
     <span id="lanStatus"></span>
   </div>
 
-  <input type="file" id="importJsonInput" accept="application/json" hidden />
+  <input type="file" id="importJsonInput" accept="application/json,.json,.mlformulas" hidden />
   <input type="file" id="importTemplateInput" accept="application/json" hidden />
 `;
 
@@ -352,6 +386,7 @@ layout.innerHTML = `
         <button id="copyJson" class="secondary-btn" title="复制 JSON 到剪贴板">复制 JSON</button>
         <button id="exportLatex" class="secondary-btn" title="导出为 LaTeX 源码">导出 LaTeX</button>
         <button id="exportMarkdown" class="secondary-btn" title="导出为 Markdown">导出 Markdown</button>
+        <button id="exportPdf" class="secondary-btn" title="编译为 PDF，需要本机安装 LaTeX">导出 PDF</button>
         <button id="clearAll" class="danger">清空全部</button>
       </div>
     </header>
@@ -438,6 +473,7 @@ const bindTemplateButton = assertElement(layout.querySelector<HTMLButtonElement>
 const exportLatexButton = assertElement(layout.querySelector<HTMLButtonElement>('#exportLatex'), '#exportLatex');
 const exportJsonButton = assertElement(layout.querySelector<HTMLButtonElement>('#exportJson'), '#exportJson');
 const exportMarkdownButton = assertElement(layout.querySelector<HTMLButtonElement>('#exportMarkdown'), '#exportMarkdown');
+const exportPdfButton = assertElement(layout.querySelector<HTMLButtonElement>('#exportPdf'), '#exportPdf');
 const copyJsonButton = assertElement(layout.querySelector<HTMLButtonElement>('#copyJson'), '#copyJson');
 const autosaveStatus = assertElement(layout.querySelector<HTMLSpanElement>('#autosaveStatus'), '#autosaveStatus');
 
@@ -851,6 +887,11 @@ const unbindAutosaveFile = (message?: string, variant?: string) => {
   stopAutoSave();
   state.boundFileHandle = null;
   state.boundFileName = '';
+  if (state.boundFileHandleType === 'tauri') {
+    tauriApi.setWindowFile(null).catch((error) => {
+      console.info('无法解除窗口绑定的文件', error);
+    });
+  }
   state.boundFileHandleType = 'none';
   state.boundFilePath = '';
   state.lastAutosaveAt = null;
@@ -864,7 +905,8 @@ const saveToBoundFile = async () => {
   if (!hasBoundFile()) return;
   try {
     if (state.boundFileHandleType === 'tauri' && state.boundFilePath) {
-      await tauriApi.writeJsonFile(state.boundFilePath, JSON.stringify(state.formulas, null, 2));
+      const content = await tauriApi.serializeFormulas(state.formulas, state.collectionTitle || undefined);
+      await tauriApi.writeJsonFile(state.boundFilePath, content);
     } else if (state.boundFileHandleType === 'fsa' && state.boundFileHandle) {
       if (state.boundFileHandle.requestPermission) {
         const permission = await state.boundFileHandle.requestPermission({ mode: 'readwrite' });
@@ -992,20 +1034,82 @@ const importJsonViaElectron = async () => {
 };
 
 const importJsonViaTauri = async () => {
+  const picked = await tauriApi.openFileDialog().catch((error) => {
+    console.error('无法打开文件对话框', error);
+    return null;
+  });
+  if (picked?.status !== 'selected') return false;
+  return openJsonPathViaTauri(picked.path);
+};
+
+// 后端在 readJsonFile 返回之前发送 file:checksum-mismatch 事件，这里按路径记下可用的备份数量
+const checksumMismatches = new Map<string, number>();
+if (isTauriEnv) {
+  tauriApi
+    .onChecksumMismatch((mismatch) => {
+      console.warn('文件校验和不一致', mismatch);
+      checksumMismatches.set(mismatch.path, mismatch.backups);
+    })
+    .catch((error) => {
+      console.info('无法订阅校验和事件', error);
+    });
+}
+
+// 刚读取的文件与记录的校验和不一致时建议从最近的备份恢复；返回要使用的内容和是否已恢复
+const recoverFromChecksumMismatch = async (filePath: string, content: string) => {
+  const backups = checksumMismatches.get(filePath);
+  if (backups === undefined) return { content, mismatch: false };
+  checksumMismatches.delete(filePath);
+  const name = extractFileName(filePath) || filePath;
+  if (!backups) {
+    showToast(`${name} 与上次保存时的内容不一致，可能已被同步服务截断或覆盖，且没有可用的备份`, 'warning');
+    return { content, mismatch: true };
+  }
+  const restore = confirm(
+    `${name} 与上次保存时的内容不一致，可能已被同步服务截断或覆盖。\n\n是否从最近的备份恢复？（共 ${backups} 个备份）`
+  );
+  if (!restore) return { content, mismatch: true };
   try {
-    const filePath = await tauriApi.openFileDialog();
-    if (!filePath) return false;
-    const content = await tauriApi.readJsonFile(filePath);
+    const restored = await tauriApi.restoreBackup(filePath, 1);
+    showToast(`已从备份恢复 ${name}`, 'success');
+    return { content: restored, mismatch: false };
+  } catch (error) {
+    const message = error instanceof Error ? error.message : '未知错误';
+    showToast(`从备份恢复失败：${message}`, 'error');
+    return { content, mismatch: true };
+  }
+};
+
+// 读取指定路径的公式文件并绑定到当前窗口；文件已在其他窗口中打开时改为切换到那个窗口
+const openJsonPathViaTauri = async (filePath: string) => {
+  try {
+    try {
+      await tauriApi.setWindowFile(filePath);
+    } catch (error) {
+      if ((error as { code?: string })?.code === 'open_in_other_window') {
+        await tauriApi.openEditorWindow(filePath);
+        return true;
+      }
+      throw error;
+    }
+    const { content, mismatch } = await recoverFromChecksumMismatch(filePath, await tauriApi.readJsonFile(filePath));
     const success = await importJsonText(content, { silent: true });
     if (!success) {
+      await tauriApi.setWindowFile(state.boundFileHandleType === 'tauri' ? state.boundFilePath : null);
       return false;
     }
+    tauriApi.addRecentFile(filePath).catch((error) => {
+      console.info('无法记录最近文件', error);
+    });
     state.boundFileHandle = null;
     state.boundFileHandleType = 'tauri';
     state.boundFilePath = filePath;
     state.boundFileName = extractFileName(filePath) || 'formulas.json';
     startAutoSave();
-    await saveToBoundFile();
+    // 未恢复的不一致文件先不写回，以免覆盖后只能从更旧的备份恢复
+    if (!mismatch) {
+      await saveToBoundFile();
+    }
     return true;
   } catch (error) {
     const message = error instanceof Error ? error.message : '未知错误';
@@ -1033,8 +1137,8 @@ const bindJsonFile = async () => {
       multiple: false,
       types: [
         {
-          description: 'JSON 文件',
-          accept: { 'application/json': ['.json'] },
+          description: '公式文件',
+          accept: { 'application/json': ['.json', '.mlformulas'] },
         },
       ],
     });
@@ -1251,9 +1355,11 @@ const exportJson = async () => {
   }
   if (isTauriEnv) {
     try {
-      const path = await tauriApi.saveFileDialog();
-      if (!path) return;
-      await tauriApi.writeJsonFile(path, JSON.stringify(state.formulas, null, 2));
+      const picked = await tauriApi.saveFileDialog();
+      if (picked.status !== 'selected') return;
+      const { path } = picked;
+      const content = await tauriApi.serializeFormulas(state.formulas, state.collectionTitle || undefined);
+      await tauriApi.writeJsonFile(path, content);
       showToast('已导出 JSON', 'success');
     } catch (error) {
       console.error('导出 JSON 失败', error);
@@ -1273,13 +1379,18 @@ const exportLatex = async () => {
 
   if (isTauriEnv) {
     try {
-      const documentLatex = await tauriApi.formatLatex(
-        state.formulas.map((item) => ({ latex: item.latex, note: item.note })),
+      const { content: documentLatex, warnings } = await tauriApi.formatLatex(
+        state.formulas.map((item) => ({ id: item.id, latex: item.latex, note: item.note })),
       );
       if (!documentLatex) return;
-      const path = await tauriApi.exportLatexFile(documentLatex);
-      if (!path) return;
-      showToast('已导出 LaTeX', 'success');
+      const result = await tauriApi.exportLatexFile(documentLatex);
+      if (result.status !== 'saved') return;
+      if (warnings.length) {
+        console.warn('导出 LaTeX 时的警告', warnings);
+        showToast(warnings[0], 'warning');
+      } else {
+        showToast('已导出 LaTeX', 'success');
+      }
     } catch (error) {
       console.error('导出 LaTeX 失败', error);
       alert('导出 LaTeX 失败');
@@ -1319,8 +1430,8 @@ const exportMarkdown = async () => {
         state.formulas.map((item) => ({ latex: item.latex, note: item.note })),
       );
       if (!markdown) return;
-      const path = await tauriApi.exportMarkdownFile(markdown);
-      if (!path) return;
+      const result = await tauriApi.exportMarkdownFile(markdown);
+      if (result.status !== 'saved') return;
       showToast('已导出 Markdown', 'success');
     } catch (error) {
       console.error('导出 Markdown 失败', error);
@@ -1342,6 +1453,87 @@ const exportMarkdown = async () => {
   downloadFile('formulas.md', markdown, 'text/markdown');
 };
 
+type JobOutcome = { result?: unknown; error?: { code: string; message: string } };
+
+// 后台任务的结束事件可能早于启动命令返回，没有等待者时按任务 id 暂存
+const finishedJobs = new Map<string, JobOutcome>();
+const jobWaiters = new Map<string, (outcome: JobOutcome) => void>();
+
+const settleJob = (jobId: string, outcome: JobOutcome) => {
+  const waiter = jobWaiters.get(jobId);
+  if (waiter) {
+    jobWaiters.delete(jobId);
+    waiter(outcome);
+  } else {
+    finishedJobs.set(jobId, outcome);
+  }
+};
+
+const waitForJob = (jobId: string) =>
+  new Promise<JobOutcome>((resolve) => {
+    const finished = finishedJobs.get(jobId);
+    if (finished) {
+      finishedJobs.delete(jobId);
+      resolve(finished);
+    } else {
+      jobWaiters.set(jobId, resolve);
+    }
+  });
+
+const pdfStageLabels: Record<string, string> = {
+  compiling: '编译中',
+  writing: '写入中',
+};
+
+// 正在编译的 PDF 任务；运行期间按钮显示进度，再次点击取消
+let pdfJobId: string | null = null;
+
+if (isTauriEnv) {
+  tauriApi.onJobProgress(({ jobId, stage }) => {
+    if (jobId !== pdfJobId) return;
+    exportPdfButton.textContent = `${pdfStageLabels[stage] ?? stage}…（点击取消）`;
+  }).catch((error) => console.warn('订阅任务进度失败', error));
+  tauriApi.onJobDone(({ jobId, result }) => settleJob(jobId, { result }))
+    .catch((error) => console.warn('订阅任务结果失败', error));
+  tauriApi.onJobFailed(({ jobId, error }) => settleJob(jobId, { error }))
+    .catch((error) => console.warn('订阅任务结果失败', error));
+}
+
+const exportPdf = async () => {
+  if (pdfJobId) {
+    await tauriApi.cancelJob(pdfJobId).catch(() => false);
+    return;
+  }
+  if (!isTauriEnv) {
+    alert('PDF 导出仅在桌面版可用');
+    return;
+  }
+  if (!ensureFormulasAvailable()) return;
+  try {
+    const outcome = await tauriApi.exportPdfFile(
+      state.formulas.map((item) => ({ latex: item.latex, note: item.note })),
+    );
+    if (outcome.status !== 'started') return;
+    pdfJobId = outcome.jobId;
+    exportPdfButton.textContent = `${pdfStageLabels.compiling}…（点击取消）`;
+    const { error } = await waitForJob(outcome.jobId);
+    if (!error) {
+      showToast('已导出 PDF', 'success');
+    } else if (error.code === 'cancelled') {
+      showToast('已取消导出 PDF', 'info');
+    } else {
+      console.error('导出 PDF 失败', error);
+      showToast(error.message, 'error');
+    }
+  } catch (error) {
+    console.error('导出 PDF 失败', error);
+    alert('导出 PDF 失败');
+  } finally {
+    pdfJobId = null;
+    exportPdfButton.textContent = '导出 PDF';
+  }
+};
+
 const exportText = () => {
   if (!ensureFormulasAvailable()) return;
   const segments = state.formulas.map((item, idx) => {
@@ -1384,8 +1576,19 @@ const applyImportedFormulas = (sanitized: FormulaItem[], { silent = false }: { s
 const importJsonText = async (content: string, { silent = false }: { silent?: boolean } = {}) => {
   try {
     if (isTauriEnv) {
-      const sanitized = await tauriApi.normalizeFormulas(content);
-      return applyImportedFormulas(sanitized as FormulaItem[], { silent });
+      const result = await tauriApi.normalizeFormulas(content);
+      const applied = applyImportedFormulas(result.entries as FormulaItem[], { silent });
+      if (applied) {
+        state.collectionTitle = result.title ?? '';
+      }
+      if (applied && result.rewrittenIds.length) {
+        showToast(`已修复 ${result.rewrittenIds.length} 个重复或空的公式 ID`, 'warning');
+      }
+      if (applied && result.warnings.length) {
+        console.warn('导入时跳过的条目', result.warnings);
+        showToast(`导入时跳过了 ${result.warnings.length} 个无效条目`, 'warning');
+      }
+      return applied;
     }
     let parsed: unknown;
     try {
@@ -1394,6 +1597,10 @@ const importJsonText = async (content: string, { silent = false }: { silent?: bo
       throw new Error('文件内容不是有效的 JSON 格式');
     }
 
+    // 版本 2 起公式集包装为 { version, title, formulas }
+    if (parsed && typeof parsed === 'object' && Array.isArray((parsed as { formulas?: unknown }).formulas)) {
+      parsed = (parsed as { formulas: unknown[] }).formulas;
+    }
     if (!Array.isArray(parsed)) {
       if (parsed && typeof parsed === 'object' && (parsed as { categories?: unknown }).categories) {
         throw new Error('这是模板库文件，请使用“绑定模板”功能导入');
@@ -1904,14 +2111,14 @@ const persistTemplateLibrary = async ({ skipBoundWrite = false }: { skipBoundWri
   }
 };
 
+const serializeTemplateLibrary = () =>
+  JSON.stringify({ version: state.templateLibraryVersion, categories: state.templateLibrary.categories }, null, 2);
+
 const saveTemplatesToBoundFile = async () => {
   if (!state.templateFileHandle && !state.templateFilePath) return;
   try {
     if (state.templateFilePath && isTauriEnv) {
-      await tauriApi.writeJsonFile(
-        state.templateFilePath,
-        JSON.stringify({ categories: state.templateLibrary.categories }, null, 2),
-      );
+      await tauriApi.writeJsonFile(state.templateFilePath, serializeTemplateLibrary());
     } else if (state.templateFileHandle) {
       if (state.templateFileHandle.requestPermission) {
         const permission = await state.templateFileHandle.requestPermission({ mode: 'readwrite' });
@@ -1920,7 +2127,7 @@ const saveTemplatesToBoundFile = async () => {
         }
       }
       const writable = await state.templateFileHandle.createWritable();
-      await writable.write(JSON.stringify({ categories: state.templateLibrary.categories }, null, 2));
+      await writable.write(serializeTemplateLibrary());
       await writable.close();
     }
     setTemplateStatusText(`已写入 ${state.templateFileName || 'template-library.json'}`);
@@ -2024,23 +2231,60 @@ const removeTemplateCategory = async (categoryId: string) => {
 
 const importTemplateText = async (content: string, { silent = false }: { silent?: boolean } = {}) => {
   try {
+    let repairedCount = 0;
+    let warnings: string[] = [];
+    let legacyVersion: number | null = null;
+    let upgradedFrom: number | null = null;
     if (isTauriEnv) {
       const library = await tauriApi.normalizeTemplates(content);
-      state.templateLibrary.categories = library.categories as TemplateCategory[];
+      repairedCount = library.repairs.length;
+      warnings = library.warnings;
+      let categories = library.categories as TemplateCategory[];
+      let version = library.version;
+      if (library.sourceVersion < library.version) {
+        // 旧版文件只在用户同意后升级；拒绝时继续按原版本写入
+        const migration = await tauriApi.migrateTemplateLibrary(content);
+        const changes = migration.changes.map((change) => `· ${change}`).join('\n');
+        const accepted = confirm(
+          `模板库为旧版格式（版本 ${migration.fromVersion}），是否升级为版本 ${migration.toVersion}？\n\n${changes}\n\n取消则继续按旧版格式保存。`
+        );
+        if (accepted) {
+          categories = migration.library.categories as TemplateCategory[];
+          version = migration.toVersion;
+          upgradedFrom = migration.fromVersion;
+        } else {
+          version = migration.fromVersion;
+          legacyVersion = migration.fromVersion;
+        }
+      }
+      state.templateLibrary.categories = categories;
       state.templateLibrary.selectedCategoryId = ALL_CATEGORY_ID;
+      state.templateLibraryVersion = version;
     } else {
-      const parsed = JSON.parse(content) as { categories?: unknown } | unknown[];
+      const parsed = JSON.parse(content) as { categories?: unknown; version?: unknown } | unknown[];
       const categories = Array.isArray((parsed as { categories?: unknown }).categories)
         ? (parsed as { categories?: unknown }).categories
         : parsed;
       const normalized = normalizeTemplateCategories(categories);
       state.templateLibrary.categories = normalized;
       state.templateLibrary.selectedCategoryId = ALL_CATEGORY_ID;
+      // 浏览器环境无法调用升级命令，按文件原有的版本写回
+      const version = (parsed as { version?: unknown }).version;
+      state.templateLibraryVersion = typeof version === 'number' ? version : 1;
     }
     resetTemplateSearchTerm();
     renderTemplateCategoryOptions();
     renderTemplateList();
-    if (state.templateLibrary.categories.length) {
+    if (warnings.length) {
+      console.warn('导入模板时的警告', warnings);
+      setTemplateStatusText(warnings[0], { variant: 'warning' });
+    } else if (repairedCount) {
+      setTemplateStatusText(`${repairedCount} 个分类的父分类引用无效，已移到顶层`, { variant: 'warning' });
+    } else if (upgradedFrom !== null) {
+      setTemplateStatusText(`模板库已从版本 ${upgradedFrom} 升级为版本 ${state.templateLibraryVersion}`);
+    } else if (legacyVersion !== null) {
+      setTemplateStatusText(`模板库保持旧版格式（版本 ${legacyVersion}）`);
+    } else if (state.templateLibrary.categories.length) {
       setTemplateStatusText(`已加载 ${state.templateLibrary.categories.length} 个模板分类`);
     } else {
       setTemplateStatusText('模板文件为空', { variant: 'warning' });
@@ -2117,9 +2361,10 @@ const handleTemplateFileHandle = async (handle: FileSystemFileHandle) => {
 
 const importTemplateViaTauri = async () => {
   try {
-    const filePath = await tauriApi.openFileDialog();
-    if (!filePath) return false;
-    const content = await tauriApi.readJsonFile(filePath);
+    const picked = await tauriApi.openFileDialog();
+    if (picked.status !== 'selected') return false;
+    const filePath = picked.path;
+    const { content, mismatch } = await recoverFromChecksumMismatch(filePath, await tauriApi.readJsonFile(filePath));
     const success = await importTemplateText(content, { silent: true });
     if (!success) {
       alert('无法读取该模板文件');
@@ -2129,7 +2374,9 @@ const importTemplateViaTauri = async () => {
     state.templateFilePath = filePath;
     state.templateFileName = extractFileName(filePath) || 'template-library.json';
     await persistTemplateLibrary({ skipBoundWrite: true });
-    await saveTemplatesToBoundFile();
+    if (!mismatch) {
+      await saveTemplatesToBoundFile();
+    }
     startTemplateAutosave();
     setTemplateStatusText(`已绑定 ${state.templateFileName} · 每分钟同步`);
     return true;
@@ -2179,18 +2426,10 @@ const exportTemplateLibrary = async () => {
   }
   if (isTauriEnv) {
     try {
-      const path = await tauriApi.saveFileDialog();
-      if (!path) return;
-      await tauriApi.writeJsonFile(
-        path,
-        JSON.stringify(
-          {
-            categories: state.templateLibrary.categories,
-          },
-          null,
-          2,
-        ),
-      );
+      const picked = await tauriApi.saveFileDialog();
+      if (picked.status !== 'selected') return;
+      const { path } = picked;
+      await tauriApi.writeJsonFile(path, serializeTemplateLibrary());
       showToast('已导出模板库', 'success');
     } catch (error) {
       console.error('导出模板库失败', error);
@@ -2198,16 +2437,7 @@ const exportTemplateLibrary = async () => {
     }
     return;
   }
-  downloadFile(
-    'template-library.json',
-    JSON.stringify(
-      {
-        categories: state.templateLibrary.categories,
-      },
-      null,
-      2,
-    ),
-  );
+  downloadFile('template-library.json', serializeTemplateLibrary());
 };
 
 // Event bindings -----------------------------------------------------------
@@ -2311,6 +2541,7 @@ const menuActionHandlers: Record<string, () => void> = {
   copyJson: copyJsonToClipboard,
   exportLatex,
   exportMarkdown,
+  exportPdf,
   toggleTemplatePopover,
   bindTemplateFile: () => {
     ensureTemplateLibraryLoaded();
@@ -2439,6 +2670,7 @@ importJsonInput.addEventListener('change', async (event) => {
 exportLatexButton.addEventListener('click', exportLatex);
 exportJsonButton.addEventListener('click', exportJson);
 exportMarkdownButton.addEventListener('click', exportMarkdown);
+exportPdfButton.addEventListener('click', exportPdf);
 copyJsonButton.addEventListener('click', copyJsonToClipboard);
 exportTemplateJsonButton.addEventListener('click', () => {
   ensureTemplateLibraryLoaded();
@@ -2614,6 +2846,37 @@ if (typeof window !== 'undefined') {
   }
 })();
 
+// 后端错误信息使用与界面相同的语言（取自 <html lang>），设置完成后再打开启动文件
+const backendLocaleReady: Promise<unknown> = isTauriEnv
+  ? tauriApi.setBackendLocale(document.documentElement.lang || 'zh-CN').catch((error) => {
+      console.info('无法设置后端语言', error);
+    })
+  : Promise.resolve();
+
+// 由 open_editor_window 打开的窗口通过 ?file= 传入初始文件；主窗口打开启动参数中的文件
+const initialFilePath = new URLSearchParams(window.location.search).get('file');
+if (isTauriEnv && initialFilePath) {
+  void backendLocaleReady.then(() => openJsonPathViaTauri(initialFilePath));
+} else if (isTauriEnv) {
+  backendLocaleReady
+    .then(() => tauriApi.takeStartupFile())
+    .then((path) => {
+      if (path) {
+        void openJsonPathViaTauri(path);
+      }
+    })
+    .catch((error) => {
+      console.info('无法读取启动参数中的文件', error);
+    });
+}
+
+// 通知后端前端已就绪，后端随后发送启动时缓存的深度链接
+if (isTauriEnv) {
+  tauriApi.frontendReady().catch((error) => {
+    console.info('无法通知后端前端已就绪', error);
+  });
+}
+
 // Notify parent (Flutter) that we are ready to receive messages
 if (window.parent && window.parent !== window) {
   window.parent.postMessage(JSON.stringify({ type: 'bridge-ready' }), '*');
@@ -3,22 +3,517 @@
  * 提供类型安全的Rust后端调用接口
  */
 
+interface FormulaEntry {
+  id: string;
+  index: number;
+  latex: string;
+  note?: string;
+  tags?: string[];
+  section?: string;
+  // 其他工具写入的字段会原样保留
+  [key: string]: unknown;
+}
+
+interface SearchOptions {
+  caseSensitive?: boolean;
+  fields?: 'latex' | 'note' | 'both';
+  regex?: boolean;
+}
+
+interface SvgOptions {
+  fontSize?: number;
+  display?: boolean;
+  color?: string;
+}
+
+interface PngOptions extends SvgOptions {
+  dpi?: number;
+  padding?: number;
+  transparent?: boolean;
+}
+
+interface ImageBatchOptions extends PngOptions {
+  format?: 'png' | 'svg';
+  pattern?: string;
+  overwrite?: boolean;
+}
+
+/** 无法确定的项为 null */
+interface SystemInfo {
+  os: string;
+  osName: string | null;
+  osVersion: string | null;
+  arch: string;
+  appVersion: string;
+  tauriVersion: string;
+  webviewVersion: string | null;
+  /** 字节 */
+  totalMemory: number | null;
+  availableMemory: number | null;
+  locale: string | null;
+  configDir: string | null;
+  dataDir: string | null;
+}
+
+interface ImportResult {
+  entries: FormulaEntry[];
+  warnings: Array<{ line: number; message: string }>;
+}
+
+interface DocxImport {
+  entries: FormulaEntry[];
+  failed: Array<{ index: number; omml: string; message: string; note: string | null }>;
+}
+
+type PasteFormat = 'json' | 'latex' | 'mathml';
+
+type ClipboardImport =
+  | { status: 'detected'; detectedFormat: PasteFormat; entries: FormulaEntry[]; warnings: string[] }
+  | {
+      status: 'needsUserChoice';
+      options: Array<{ format: PasteFormat; entries: FormulaEntry[]; warnings: string[] }>;
+    };
+
+type CsvColumn = string | number;
+
+interface CsvMapping {
+  latex?: CsvColumn;
+  note?: CsvColumn;
+  tags?: CsvColumn;
+  id?: CsvColumn;
+  hasHeader?: boolean;
+  maxRows?: number;
+}
+
+interface CsvImport {
+  entries: FormulaEntry[];
+  rewrittenIds: Array<{ position: number; original: string; id: string }>;
+  warnings: Array<{ line: number; message: string }>;
+}
+
+/**
+ * 对话框只选择路径时为 `selected`，导出命令写入文件后为 `saved`；
+ * 耗时导出选择路径后为 `started`，结果通过 `job:done` / `job:failed` 事件给出
+ */
+type FileOutcome =
+  | { status: 'selected'; path: string }
+  | { status: 'saved'; path: string; warnings?: string[] }
+  | { status: 'started'; jobId: string }
+  | { status: 'cancelled' };
+
+// 后台任务的事件，见 jobs.rs
+interface JobProgress {
+  jobId: string;
+  done: number;
+  total: number;
+  stage: string;
+}
+
+interface JobDone {
+  jobId: string;
+  result: unknown;
+}
+
+// 取消的任务以 code 为 cancelled 的错误结束
+interface JobFailed {
+  jobId: string;
+  error: { code: string; message: string; details?: unknown };
+}
+
+/**
+ * `overwrite: false` 时目标已存在且内容不同会返回 `code: 'conflict'` 的错误，
+ * details 中带有 path、existingSize、existingMtime；确认后以 `{ overwrite: true, path }` 重试
+ */
+interface WriteOptions {
+  overwrite?: boolean;
+  path?: string;
+  /** 文本导出的编码，默认 `utf8`；目标编码无法表示的字符会列在结果的 warnings 中 */
+  encoding?: 'utf8' | 'utf8-bom' | 'gb18030';
+  /** 文本导出的换行符，默认 `platform` */
+  lineEndings?: 'lf' | 'crlf' | 'platform';
+}
+
+interface DialogFilter {
+  name: string;
+  extensions: string[];
+}
+
+type MathEnvironment = 'equation' | 'equation*' | 'align' | 'align*' | 'gather' | 'gather*';
+
+interface LatexOptions {
+  documentClass?: string;
+  environment?: MathEnvironment;
+  classOptions?: string;
+  extraPackages?: string[];
+  preambleExtra?: string;
+  forceCtex?: boolean;
+  tableOfContents?: boolean;
+  numberWithinSection?: boolean;
+  defaultSection?: string;
+  /** 与 exportLatexFile 的 encoding 一致；`gb18030` 时以 GBK 选项引入 ctex */
+  encoding?: WriteOptions['encoding'];
+}
+
+type MarkdownFlavor = 'github' | 'obsidian' | 'zhihu' | 'wechat' | 'pandoc';
+
+interface MarkdownOptions {
+  layout?: 'blocks' | 'table';
+  headingLevel?: number | null;
+  headingText?: string;
+  mathDelimiter?: 'dollars' | 'brackets' | 'fenced' | 'parentheses' | 'zhihu-image';
+  includeNumbering?: boolean;
+  defaultSection?: string;
+}
+
+interface LatexDiagnostic {
+  // UTF-16 偏移，与字符串下标一致
+  offset: number;
+  severity: 'error' | 'warning';
+  message: string;
+}
+
+interface FormulaDiagnostics {
+  position: number;
+  id: string;
+  diagnostics: LatexDiagnostic[];
+}
+
+type LintRule =
+  | 'empty-latex'
+  | 'duplicate-formula'
+  | 'duplicate-note'
+  | 'repeated-index'
+  | 'non-sequential-index'
+  | 'needs-package';
+
+interface LintFinding {
+  rule: LintRule;
+  positions: number[];
+  message: string;
+}
+
+interface ParentRepair {
+  categoryId: string;
+  categoryName: string;
+  parentId: string;
+  reason: 'cycle' | 'missing_parent';
+}
+
+interface DedupeReport {
+  removed: Array<{ categoryId: string; categoryName: string; removed: number }>;
+  crossCategory: number;
+}
+
+interface NormalizedTemplates {
+  version: number;
+  categories: Array<any>;
+  selectedCategoryId: string;
+  sourceVersion: number;
+  repairs: ParentRepair[];
+  warnings: string[];
+  dedupe: DedupeReport;
+}
+
+interface TemplateMigration {
+  library: { version: number; categories: Array<any>; selectedCategoryId: string };
+  fromVersion: number;
+  toVersion: number;
+  changes: string[];
+}
+
+interface TemplateHit {
+  categoryId: string;
+  categoryPath: string;
+  template: { id: string; name: string; latex: string; note?: string; [key: string]: unknown };
+  field: 'name' | 'note' | 'latex';
+  score: number;
+}
+
+interface SearchHit {
+  position: number;
+  entry: FormulaEntry;
+  matches: Array<{ field: 'latex' | 'note'; start: number; end: number }>;
+}
+
+interface MergeResult {
+  entries: FormulaEntry[];
+  summary: { merged: number; duplicates: number; skipped: number; conflicting: number; renamedIds: number };
+}
+
+interface FormulaDiff {
+  added: FormulaEntry[];
+  removed: FormulaEntry[];
+  modified: Array<{ before: FormulaEntry; after: FormulaEntry }>;
+}
+
+interface RecentFile {
+  path: string;
+  name: string;
+  lastOpened: number;
+}
+
+interface BackupInfo {
+  index: number;
+  path: string;
+  size: number;
+  modified?: number;
+}
+
+interface FileChecksum {
+  sha256: string;
+  size: number;
+  mtime?: number | null;
+}
+
+// 文件内容与上次保存时记录的校验和不一致，由 file:checksum-mismatch 事件发送
+interface ChecksumMismatch {
+  code: 'checksum_mismatch';
+  path: string;
+  expected: string;
+  actual: string;
+  backups: number;
+}
+
+interface SnapshotInfo {
+  id: string;
+  sourcePath?: string | null;
+  savedAt: number;
+  size: number;
+}
+
+interface NormalizeResult {
+  version: number;
+  title?: string | null;
+  entries: FormulaEntry[];
+  rewrittenIds: Array<{ position: number; original: string; id: string }>;
+  warnings: Array<{ position: number; reason: 'not_an_object' | 'missing_latex' | 'empty_latex' }>;
+}
+
+interface EffectiveTheme {
+  preference: string;
+  theme: string;
+  dark: boolean;
+}
+
 interface TauriAPI {
-  readJsonFile: (path: string) => Promise<string>;
-  writeJsonFile: (path: string, content: string) => Promise<void>;
-  openFileDialog: () => Promise<string | null>;
-  saveFileDialog: () => Promise<string | null>;
+  readJsonFile: (path: string, maxBytes?: number) => Promise<string>;
+  readJsonFileHead: (path: string, bytes: number) => Promise<string>;
+  computeFileChecksum: (path: string) => Promise<FileChecksum>;
+  writeJsonFile: (path: string, content: string, overwrite?: boolean) => Promise<{ warning?: string | null }>;
+  listBackups: (path: string) => Promise<BackupInfo[]>;
+  restoreBackup: (path: string, backupIndex: number) => Promise<string>;
+  enableAutosave: (path: string, intervalSecs: number) => Promise<void>;
+  disableAutosave: () => Promise<void>;
+  queueAutosaveContent: (content: string) => Promise<void>;
+  watchFile: (path: string) => Promise<void>;
+  unwatchFile: () => Promise<void>;
+  writeRecoverySnapshot: (content: string, sourcePath?: string) => Promise<string>;
+  listRecoverySnapshots: () => Promise<SnapshotInfo[]>;
+  recoverSnapshot: (id: string) => Promise<string>;
+  discardSnapshot: (id: string) => Promise<void>;
+  openFileDialog: (filters?: DialogFilter[]) => Promise<FileOutcome>;
+  pickFiles: (filters?: DialogFilter[]) => Promise<string[] | null>;
+  pickDirectory: (options?: { defaultPath?: string; title?: string }) => Promise<string | null>;
+  saveFileDialog: () => Promise<FileOutcome>;
+  saveCopyDialog: (content: string, sourcePath?: string) => Promise<string | null>;
+  addRecentFile: (path: string) => Promise<RecentFile[]>;
+  getRecentFiles: () => Promise<RecentFile[]>;
+  clearRecentFiles: () => Promise<void>;
+  refreshRecentMenu: () => Promise<void>;
+  setTrayEnabled: (enabled: boolean) => Promise<void>;
+  setMinimizeToTray: (enabled: boolean) => Promise<void>;
+  registerGlobalShortcut: (accelerator: string) => Promise<void>;
+  unregisterGlobalShortcut: () => Promise<void>;
+  getGlobalShortcut: () => Promise<string | null>;
+  setZoomPreference: (factor: number) => Promise<number>;
+  getZoomPreference: () => Promise<number>;
+  getEffectiveTheme: () => Promise<EffectiveTheme>;
   getAppConfigDir: () => Promise<string>;
   fileExists: (path: string) => Promise<boolean>;
   setWindowTitle: (title: string) => Promise<void>;
+  openEditorWindow: (filePath?: string) => Promise<string>;
+  setWindowFile: (path: string | null) => Promise<void>;
+  setSizeConstraints: (constraints: {
+    minWidth?: number;
+    minHeight?: number;
+    maxWidth?: number;
+    maxHeight?: number;
+  }) => Promise<void>;
+  resetWindowState: () => Promise<void>;
+  frontendReady: () => Promise<void>;
+  onChecksumMismatch: (handler: (mismatch: ChecksumMismatch) => void) => Promise<() => void>;
+  takeStartupFile: () => Promise<string | null>;
+  registerFileAssociation: () => Promise<void>;
+  unregisterFileAssociation: () => Promise<void>;
+  registerUrlScheme: () => Promise<void>;
+  setAlwaysOnTop: (enabled: boolean) => Promise<void>;
+  getAlwaysOnTop: () => Promise<boolean>;
+  forceClose: () => Promise<void>;
+  cancelClose: () => Promise<void>;
   setThemePreference: (theme: string) => Promise<void>;
-  exportLatexFile: (content: string) => Promise<string>;
-  exportMarkdownFile: (content: string) => Promise<string>;
-  formatLatex: (formulas: Array<{ latex: string; note?: string }>) => Promise<string>;
-  formatMarkdown: (formulas: Array<{ latex: string; note?: string }>) => Promise<string>;
-  normalizeFormulas: (content: string) => Promise<Array<{ id: string; index: number; latex: string; note?: string }>>;
-  normalizeTemplates: (content: string) => Promise<{ categories: Array<any>; selectedCategoryId: string }>;
-  getSystemInfo: () => Promise<string>;
+  getThemePreference: () => Promise<string>;
+  resetSettings: () => Promise<string[]>;
+  getSetting: <T = unknown>(key: string) => Promise<T | null>;
+  setSetting: (key: string, value: unknown) => Promise<void>;
+  getAllSettings: () => Promise<Record<string, unknown>>;
+  exportLatexFile: (content: string, options?: WriteOptions) => Promise<FileOutcome>;
+  revealInFileManager: (path: string) => Promise<void>;
+  openPathWithDefaultApp: (path: string, options?: { allowExecutables?: boolean }) => Promise<void>;
+  exportMarkdownFile: (content: string, options?: WriteOptions) => Promise<FileOutcome>;
+  exportLatexDirectory: (
+    formulas: Array<{ latex: string; note?: string }>,
+    options?: { pattern?: string; includeNote?: boolean; overwrite?: boolean }
+  ) => Promise<FileOutcome & { written: string[]; skipped: string[] }>;
+  exportHtmlFile: (content: string, options?: WriteOptions) => Promise<FileOutcome>;
+  exportTypstFile: (content: string, options?: WriteOptions) => Promise<FileOutcome>;
+  exportMathmlFile: (content: string, options?: WriteOptions) => Promise<FileOutcome>;
+  exportOrgFile: (content: string, options?: WriteOptions) => Promise<FileOutcome>;
+  exportRstFile: (content: string, options?: WriteOptions) => Promise<FileOutcome>;
+  exportCsvFile: (content: string, options?: WriteOptions) => Promise<FileOutcome>;
+  exportAnkiFile: (
+    formulas: FormulaEntry[],
+    options?: { math?: 'mathjax' | 'legacy'; frontField?: string; skipEmpty?: boolean },
+    writeOptions?: WriteOptions
+  ) => Promise<FileOutcome & { cards: number }>;
+  copyTextToClipboard: (content: string) => Promise<void>;
+  copyExportToClipboard: (
+    formulas: Array<{ latex: string; note?: string; tags?: string[] }>,
+    format: 'latex' | 'markdown' | 'typst' | 'org' | 'rst' | 'mathml'
+  ) => Promise<void>;
+  exportBundleZip: (
+    formulas: FormulaEntry[],
+    options?: { title?: string; includeTags?: boolean }
+  ) => Promise<{ path: string; failures: Array<{ entry: string; message: string }> }>;
+  exportDocxFile: (
+    formulas: Array<{ latex: string; note?: string }>
+  ) => Promise<FileOutcome & { failures: Array<{ position: number; message: string }> }>;
+  /** 启动后台任务时为 `started`，任务结果为写入的路径；没有公式或用户取消时为 `cancelled` */
+  exportPdfFile: (
+    formulas: Array<{ latex: string; note?: string; tags?: string[] }>,
+    options?: { includeTags?: boolean; overwrite?: boolean; path?: string }
+  ) => Promise<FileOutcome>;
+  /** 启动后台任务时为 `started`；没有公式或用户取消时为 `cancelled` */
+  exportFormulaImages: (
+    formulas: Array<{ latex: string; note?: string }>,
+    options?: ImageBatchOptions
+  ) => Promise<FileOutcome>;
+  cancelJob: (jobId: string) => Promise<boolean>;
+  onJobProgress: (handler: (progress: JobProgress) => void) => Promise<() => void>;
+  onJobDone: (handler: (done: JobDone) => void) => Promise<() => void>;
+  onJobFailed: (handler: (failed: JobFailed) => void) => Promise<() => void>;
+  formatLatex: (
+    formulas: Array<{ latex: string; note?: string; tags?: string[]; environment?: MathEnvironment; id?: string; label?: string; section?: string }>,
+    includeTags?: boolean,
+    options?: LatexOptions
+  ) => Promise<{ content: string; warnings: string[] }>;
+  formatMarkdown: (
+    formulas: Array<{ latex: string; note?: string; tags?: string[]; section?: string }>,
+    notePosition?: 'above' | 'below',
+    includeTags?: boolean,
+    options?: MarkdownOptions,
+    flavor?: MarkdownFlavor
+  ) => Promise<string>;
+  formatHtml: (
+    formulas: Array<{ latex: string; note?: string; section?: string }>,
+    options?: { embedAssets?: boolean; title?: string }
+  ) => Promise<string>;
+  printFormulas: (
+    formulas: Array<{ latex: string; note?: string; section?: string }>,
+    options?: { title?: string; pageBreaks?: boolean }
+  ) => Promise<void>;
+  formatHtmlFragment: (
+    formulas: Array<{ latex: string; note?: string; id?: string }>,
+    options?: { delimiter?: 'parentheses' | 'dollars'; includeId?: boolean }
+  ) => Promise<string>;
+  latexToMathml: (latex: string, display: boolean) => Promise<string>;
+  renderFormulaSvg: (latex: string, options?: SvgOptions) => Promise<string>;
+  exportFormulaSvgFile: (latex: string, options?: SvgOptions) => Promise<FileOutcome>;
+  renderFormulaPng: (
+    latex: string,
+    options?: PngOptions
+  ) => Promise<{ data: string; width: number; height: number; warnings: string[] }>;
+  exportFormulaPngFile: (latex: string, options?: PngOptions) => Promise<FileOutcome>;
+  copyFormulaImageToClipboard: (latex: string, options?: PngOptions) => Promise<string[]>;
+  formatMathml: (
+    formulas: Array<{ latex: string; note?: string }>
+  ) => Promise<{ content: string; warnings: Array<{ position: number; message: string }> }>;
+  formatTypst: (
+    formulas: Array<{ latex: string; note?: string }>,
+    options?: { mode?: 'translate' | 'mitex' }
+  ) => Promise<{ content: string; warnings: Array<{ position: number; message: string }> }>;
+  formatOrg: (
+    formulas: Array<{ latex: string; note?: string }>,
+    options?: { mathStyle?: 'equation' | 'brackets' }
+  ) => Promise<string>;
+  formatRst: (formulas: Array<{ latex: string; note?: string }>) => Promise<string>;
+  formatCsv: (formulas: FormulaEntry[], options?: { bom?: boolean }) => Promise<string>;
+  normalizeFormulas: (content: string) => Promise<NormalizeResult>;
+  serializeFormulas: (entries: FormulaEntry[], title?: string) => Promise<string>;
+  formulasToYaml: (content: string) => Promise<string>;
+  yamlToFormulas: (content: string) => Promise<string>;
+  importTexFile: (path: string, options?: { splitRows?: boolean }) => Promise<ImportResult>;
+  importMarkdownFile: (path: string, options?: { inline?: boolean }) => Promise<ImportResult>;
+  importDocxFile: (path: string) => Promise<DocxImport>;
+  importFromClipboard: () => Promise<ClipboardImport>;
+  importCsvFile: (path: string, mapping?: CsvMapping) => Promise<CsvImport>;
+  mathmlToLatex: (mathml: string) => Promise<{ latex: string; warnings: string[] }>;
+  importMathmlFile: (path: string) => Promise<ImportResult>;
+  reimportExportedLatex: (content: string) => Promise<FormulaEntry[]>;
+  findFormulasUsingCommand: (formulas: FormulaEntry[], command: string) => Promise<Array<{ id: string; index: number }>>;
+  filterByTags: (formulas: FormulaEntry[], tags: string[], matchAll: boolean) => Promise<FormulaEntry[]>;
+  filterFormulas: (content: string, tags: string[], matchAll: boolean) => Promise<FormulaEntry[]>;
+  searchFormulas: (path: string, query: string, options?: SearchOptions) => Promise<SearchHit[]>;
+  sortFormulas: (
+    content: string,
+    key: 'index' | 'note' | 'latex_length' | 'created_at',
+    ascending: boolean
+  ) => Promise<FormulaEntry[]>;
+  mergeFormulaFiles: (
+    pathA: string,
+    pathB: string,
+    strategy: 'keep_both' | 'skip_duplicates' | 'prefer_b_note'
+  ) => Promise<MergeResult>;
+  diffFormulaFiles: (pathA: string, pathB: string, compareIndex?: boolean) => Promise<FormulaDiff>;
+  listTags: (formulas: FormulaEntry[]) => Promise<Array<{ tag: string; count: number }>>;
+  validateLatex: (latex: string) => Promise<LatexDiagnostic[]>;
+  validateFormulaSet: (formulas: FormulaEntry[]) => Promise<FormulaDiagnostics[]>;
+  lintFormulaSet: (formulas: FormulaEntry[]) => Promise<LintFinding[]>;
+  autofixFormulaSet: (formulas: FormulaEntry[]) => Promise<FormulaEntry[]>;
+  normalizeTemplates: (
+    content: string,
+    sortByName?: boolean,
+    maxDepth?: number,
+    dedupe?: boolean
+  ) => Promise<NormalizedTemplates>;
+  dedupeTemplateLibrary: (content: string, scope?: 'category' | 'library') => Promise<NormalizedTemplates>;
+  migrateTemplateLibrary: (content: string) => Promise<TemplateMigration>;
+  mergeTemplateLibraries: (
+    pathA: string,
+    pathB: string,
+    strategy?: 'merge' | 'rename'
+  ) => Promise<{
+    library: { categories: Array<any>; selectedCategoryId: string };
+    summary: {
+      mergedCategories: number;
+      renamedCategories: number;
+      dedupedTemplates: number;
+      regeneratedIds: number;
+    };
+  }>;
+  exportTemplateSubtree: (libraryContent: string, categoryId: string) => Promise<string>;
+  saveTemplateSubtree: (libraryContent: string, categoryId: string) => Promise<FileOutcome>;
+  searchTemplates: (libraryContent: string, query: string, limit?: number) => Promise<TemplateHit[]>;
+  formulasToTemplateLibrary: (
+    formulas: FormulaEntry[],
+    categoryName: string
+  ) => Promise<{ categories: Array<any>; selectedCategoryId: string }>;
+  templateLibraryToFormulas: (libraryContent: string, categoryId?: string) => Promise<FormulaEntry[]>;
+  getSystemInfo: () => Promise<SystemInfo>;
+  getSystemInfoText: () => Promise<string>;
+  /** 设置错误信息使用的语言，返回实际采用的语言（`zh-CN` 或 `en`） */
+  setBackendLocale: (locale: string) => Promise<string>;
+  logMessage: (level: 'error' | 'warn' | 'info' | 'debug', message: string) => Promise<void>;
+  getLogFilePath: () => Promise<string>;
 }
 
 // 获取可用的 Tauri invoke 函数（兼容不同版本）
@@ -29,18 +524,56 @@ const resolveTauriInvoke = (): ((cmd: string, args?: Record<string, unknown>) =>
   return typeof invoke === 'function' ? invoke : null;
 };
 
+// 订阅后端事件，返回取消订阅的函数；没有事件接口时不订阅
+const listen = async <T>(event: string, handler: (payload: T) => void): Promise<() => void> => {
+  const tauriGlobal: any = typeof window === 'undefined' ? undefined : (window as any).__TAURI__;
+  const subscribe = tauriGlobal?.event?.listen;
+  if (typeof subscribe !== 'function') return () => {};
+  return subscribe(event, (message: { payload: T }) => handler(message.payload));
+};
+
 // 检测Tauri环境
 export const isTauri = (): boolean => {
   return resolveTauriInvoke() !== null;
 };
 
-// 获取Tauri invoke函数
+// 后端结构化错误：code 为稳定的错误代码，message 可直接展示
+export class TauriCommandError extends Error {
+  code: string;
+  details: unknown;
+
+  constructor(code: string, message: string, details?: unknown) {
+    super(message);
+    this.name = 'TauriCommandError';
+    this.code = code;
+    this.details = details ?? null;
+  }
+}
+
+const toCommandError = (error: unknown): Error => {
+  if (error && typeof error === 'object' && 'code' in error && 'message' in error) {
+    const { code, message, details } = error as { code: string; message: string; details?: unknown };
+    return new TauriCommandError(code, message, details);
+  }
+  if (typeof error === 'string') {
+    return new Error(error);
+  }
+  return error instanceof Error ? error : new Error(String(error));
+};
+
+// 获取Tauri invoke函数，并把后端返回的错误统一转换为 Error
 const getTauriInvoke = () => {
   const invoke = resolveTauriInvoke();
   if (!invoke) {
     throw new Error('Tauri invoke not available');
   }
-  return invoke;
+  return async (cmd: string, args?: Record<string, unknown>) => {
+    try {
+      return await invoke(cmd, args);
+    } catch (error) {
+      throw toCommandError(error);
+    }
+  };
 };
 
 // 创建Tauri API代理
@@ -49,43 +582,337 @@ export const createTauriAPI = (): TauriAPI => {
     // 返回空实现用于非Tauri环境
     return {
       readJsonFile: async () => { throw new Error('Tauri not available'); },
+      readJsonFileHead: async () => { throw new Error('Tauri not available'); },
+      computeFileChecksum: async () => { throw new Error('Tauri not available'); },
       writeJsonFile: async () => { throw new Error('Tauri not available'); },
-      openFileDialog: async () => null,
-      saveFileDialog: async () => null,
+      listBackups: async () => [],
+      restoreBackup: async () => { throw new Error('Tauri not available'); },
+      enableAutosave: async () => {},
+      disableAutosave: async () => {},
+      queueAutosaveContent: async () => {},
+      watchFile: async () => {},
+      unwatchFile: async () => {},
+      writeRecoverySnapshot: async () => { throw new Error('Tauri not available'); },
+      listRecoverySnapshots: async () => [],
+      recoverSnapshot: async () => { throw new Error('Tauri not available'); },
+      discardSnapshot: async () => {},
+      openFileDialog: async () => ({ status: 'cancelled' }),
+      pickFiles: async () => null,
+      pickDirectory: async () => null,
+      saveFileDialog: async () => ({ status: 'cancelled' }),
+      saveCopyDialog: async () => null,
+      addRecentFile: async () => [],
+      getRecentFiles: async () => [],
+      clearRecentFiles: async () => {},
+      refreshRecentMenu: async () => {},
+      setTrayEnabled: async () => {},
+      setMinimizeToTray: async () => {},
+      registerGlobalShortcut: async () => { throw new Error('Tauri not available'); },
+      unregisterGlobalShortcut: async () => {},
+      getGlobalShortcut: async () => null,
+      setZoomPreference: async (factor: number) => factor,
+      getZoomPreference: async () => 1,
+      getEffectiveTheme: async () => ({ preference: 'system', theme: 'light', dark: false }),
       getAppConfigDir: async () => '',
       fileExists: async () => false,
       setWindowTitle: async () => {},
+      openEditorWindow: async () => { throw new Error('Tauri not available'); },
+      setWindowFile: async () => {},
+      setSizeConstraints: async () => {},
+      resetWindowState: async () => {},
+      frontendReady: async () => {},
+      onChecksumMismatch: async () => () => {},
+      takeStartupFile: async () => null,
+      registerFileAssociation: async () => {
+        throw new Error('Tauri not available');
+      },
+      unregisterFileAssociation: async () => {
+        throw new Error('Tauri not available');
+      },
+      registerUrlScheme: async () => {
+        throw new Error('Tauri not available');
+      },
+      setAlwaysOnTop: async () => {},
+      getAlwaysOnTop: async () => false,
+      forceClose: async () => {},
+      cancelClose: async () => {},
       setThemePreference: async () => {},
+      getThemePreference: async () => 'system',
+      resetSettings: async () => [],
+      getSetting: async () => null,
+      setSetting: async () => {},
+      getAllSettings: async () => ({}),
       exportLatexFile: async () => { throw new Error('Tauri not available'); },
+      revealInFileManager: async () => { throw new Error('Tauri not available'); },
+      openPathWithDefaultApp: async () => { throw new Error('Tauri not available'); },
       exportMarkdownFile: async () => { throw new Error('Tauri not available'); },
+      exportLatexDirectory: async () => { throw new Error('Tauri not available'); },
       formatLatex: async () => { throw new Error('Tauri not available'); },
       formatMarkdown: async () => { throw new Error('Tauri not available'); },
+      formatHtml: async () => { throw new Error('Tauri not available'); },
+      printFormulas: async () => { throw new Error('Tauri not available'); },
+      formatHtmlFragment: async () => { throw new Error('Tauri not available'); },
+      exportHtmlFile: async () => { throw new Error('Tauri not available'); },
+      exportTypstFile: async () => { throw new Error('Tauri not available'); },
+      exportMathmlFile: async () => { throw new Error('Tauri not available'); },
+      latexToMathml: async () => { throw new Error('Tauri not available'); },
+      renderFormulaSvg: async () => { throw new Error('Tauri not available'); },
+      exportFormulaSvgFile: async () => { throw new Error('Tauri not available'); },
+      renderFormulaPng: async () => { throw new Error('Tauri not available'); },
+      exportFormulaPngFile: async () => { throw new Error('Tauri not available'); },
+      copyFormulaImageToClipboard: async () => { throw new Error('Tauri not available'); },
+      formatMathml: async () => { throw new Error('Tauri not available'); },
+      exportOrgFile: async () => { throw new Error('Tauri not available'); },
+      exportRstFile: async () => { throw new Error('Tauri not available'); },
+      exportCsvFile: async () => { throw new Error('Tauri not available'); },
+      exportAnkiFile: async () => { throw new Error('Tauri not available'); },
+      exportBundleZip: async () => { throw new Error('Tauri not available'); },
+      copyTextToClipboard: async () => { throw new Error('Tauri not available'); },
+      copyExportToClipboard: async () => { throw new Error('Tauri not available'); },
+      formatCsv: async () => { throw new Error('Tauri not available'); },
+      formatRst: async () => { throw new Error('Tauri not available'); },
+      formatOrg: async () => { throw new Error('Tauri not available'); },
+      formatTypst: async () => { throw new Error('Tauri not available'); },
+      exportDocxFile: async () => { throw new Error('Tauri not available'); },
+      exportPdfFile: async () => { throw new Error('Tauri not available'); },
+      exportFormulaImages: async () => { throw new Error('Tauri not available'); },
+      cancelJob: async () => false,
+      onJobProgress: async () => () => {},
+      onJobDone: async () => () => {},
+      onJobFailed: async () => () => {},
       normalizeFormulas: async () => { throw new Error('Tauri not available'); },
+      serializeFormulas: async () => { throw new Error('Tauri not available'); },
+      formulasToYaml: async () => { throw new Error('Tauri not available'); },
+      yamlToFormulas: async () => { throw new Error('Tauri not available'); },
+      reimportExportedLatex: async () => { throw new Error('Tauri not available'); },
+      importTexFile: async () => { throw new Error('Tauri not available'); },
+      importMarkdownFile: async () => { throw new Error('Tauri not available'); },
+      importDocxFile: async () => { throw new Error('Tauri not available'); },
+      importFromClipboard: async () => { throw new Error('Tauri not available'); },
+      importCsvFile: async () => { throw new Error('Tauri not available'); },
+      mathmlToLatex: async () => { throw new Error('Tauri not available'); },
+      importMathmlFile: async () => { throw new Error('Tauri not available'); },
+      findFormulasUsingCommand: async () => { throw new Error('Tauri not available'); },
+      filterByTags: async () => { throw new Error('Tauri not available'); },
+      filterFormulas: async () => { throw new Error('Tauri not available'); },
+      searchFormulas: async () => { throw new Error('Tauri not available'); },
+      sortFormulas: async () => { throw new Error('Tauri not available'); },
+      mergeFormulaFiles: async () => { throw new Error('Tauri not available'); },
+      diffFormulaFiles: async () => { throw new Error('Tauri not available'); },
+      listTags: async () => { throw new Error('Tauri not available'); },
+      validateLatex: async () => { throw new Error('Tauri not available'); },
+      validateFormulaSet: async () => { throw new Error('Tauri not available'); },
+      lintFormulaSet: async () => { throw new Error('Tauri not available'); },
+      autofixFormulaSet: async () => { throw new Error('Tauri not available'); },
       normalizeTemplates: async () => { throw new Error('Tauri not available'); },
-      getSystemInfo: async () => 'Non-Tauri environment',
+      dedupeTemplateLibrary: async () => { throw new Error('Tauri not available'); },
+      migrateTemplateLibrary: async () => { throw new Error('Tauri not available'); },
+      mergeTemplateLibraries: async () => { throw new Error('Tauri not available'); },
+      exportTemplateSubtree: async () => { throw new Error('Tauri not available'); },
+      saveTemplateSubtree: async () => { throw new Error('Tauri not available'); },
+      searchTemplates: async () => { throw new Error('Tauri not available'); },
+      formulasToTemplateLibrary: async () => { throw new Error('Tauri not available'); },
+      templateLibraryToFormulas: async () => { throw new Error('Tauri not available'); },
+      getSystemInfo: async () => { throw new Error('Tauri not available'); },
+      getSystemInfoText: async () => 'Non-Tauri environment',
+      setBackendLocale: async () => { throw new Error('Tauri not available'); },
+      logMessage: async () => {},
+      getLogFilePath: async () => { throw new Error('Tauri not available'); },
     };
   }
 
   const invoke = getTauriInvoke();
 
   return {
-    readJsonFile: (path: string) => invoke('read_json_file', { path }),
-    writeJsonFile: (path: string, content: string) => invoke('write_json_file', { path, content }),
-    openFileDialog: () => invoke('open_file_dialog'),
+    readJsonFile: (path: string, maxBytes?: number) => invoke('read_json_file', { path, maxBytes }),
+    readJsonFileHead: (path: string, bytes: number) => invoke('read_json_file_head', { path, bytes }),
+    computeFileChecksum: (path: string) => invoke('compute_file_checksum', { path }),
+    writeJsonFile: (path: string, content: string, overwrite?: boolean) =>
+      invoke('write_json_file', { path, content, overwrite }),
+    listBackups: (path: string) => invoke('list_backups', { path }),
+    restoreBackup: (path: string, backupIndex: number) => invoke('restore_backup', { path, backupIndex }),
+    enableAutosave: (path: string, intervalSecs: number) => invoke('enable_autosave', { path, intervalSecs }),
+    disableAutosave: () => invoke('disable_autosave'),
+    queueAutosaveContent: (content: string) => invoke('queue_autosave_content', { content }),
+    watchFile: (path: string) => invoke('watch_file', { path }),
+    unwatchFile: () => invoke('unwatch_file'),
+    writeRecoverySnapshot: (content: string, sourcePath?: string) =>
+      invoke('write_recovery_snapshot', { content, sourcePath }),
+    listRecoverySnapshots: () => invoke('list_recovery_snapshots'),
+    recoverSnapshot: (id: string) => invoke('recover_snapshot', { id }),
+    discardSnapshot: (id: string) => invoke('discard_snapshot', { id }),
+    openFileDialog: (filters?: DialogFilter[]) => invoke('open_file_dialog', { filters }),
+    pickFiles: (filters?: DialogFilter[]) => invoke('pick_files', { filters }),
+    pickDirectory: (options?: { defaultPath?: string; title?: string }) => invoke('pick_directory', { options }),
     saveFileDialog: () => invoke('save_file_dialog'),
+    saveCopyDialog: (content: string, sourcePath?: string) =>
+      invoke('save_copy_dialog', { content, sourcePath }),
+    addRecentFile: (path: string) => invoke('add_recent_file', { path }),
+    getRecentFiles: () => invoke('get_recent_files'),
+    clearRecentFiles: () => invoke('clear_recent_files'),
+    refreshRecentMenu: () => invoke('refresh_recent_menu'),
+    setTrayEnabled: (enabled: boolean) => invoke('set_tray_enabled', { enabled }),
+    setMinimizeToTray: (enabled: boolean) => invoke('set_minimize_to_tray', { enabled }),
+    registerGlobalShortcut: (accelerator: string) => invoke('register_global_shortcut', { accelerator }),
+    unregisterGlobalShortcut: () => invoke('unregister_global_shortcut'),
+    getGlobalShortcut: () => invoke('get_global_shortcut'),
+    setZoomPreference: (factor: number) => invoke('set_zoom_preference', { factor }),
+    getZoomPreference: () => invoke('get_zoom_preference'),
+    getEffectiveTheme: () => invoke('get_effective_theme'),
     getAppConfigDir: () => invoke('get_app_config_dir'),
     fileExists: (path: string) => invoke('file_exists', { path }),
     setWindowTitle: (title: string) => invoke('set_window_title', { title }),
+    openEditorWindow: (filePath?: string) => invoke('open_editor_window', { filePath }),
+    setWindowFile: (path: string | null) => invoke('set_window_file', { path }),
+    setSizeConstraints: (constraints) => invoke('set_size_constraints', constraints),
+    resetWindowState: () => invoke('reset_window_state'),
+    frontendReady: () => invoke('frontend_ready'),
+    onChecksumMismatch: (handler) => listen<ChecksumMismatch>('file:checksum-mismatch', handler),
+    takeStartupFile: () => invoke('take_startup_file'),
+    registerFileAssociation: () => invoke('register_file_association'),
+    unregisterFileAssociation: () => invoke('unregister_file_association'),
+    registerUrlScheme: () => invoke('register_url_scheme'),
+    setAlwaysOnTop: (enabled: boolean) => invoke('set_always_on_top', { enabled }),
+    getAlwaysOnTop: () => invoke('get_always_on_top'),
+    forceClose: () => invoke('force_close'),
+    cancelClose: () => invoke('cancel_close'),
     setThemePreference: (theme: string) => invoke('set_theme_preference', { theme }),
-    exportLatexFile: (content: string) => invoke('export_latex_file', { content }),
-    exportMarkdownFile: (content: string) => invoke('export_markdown_file', { content }),
-    formatLatex: (formulas: Array<{ latex: string; note?: string }>) =>
-      invoke('format_latex', { formulas }),
-    formatMarkdown: (formulas: Array<{ latex: string; note?: string }>) =>
-      invoke('format_markdown', { formulas }),
+    getThemePreference: () => invoke('get_theme_preference'),
+    resetSettings: () => invoke('reset_settings'),
+    getSetting: (key: string) => invoke('get_setting', { key }),
+    setSetting: (key: string, value: unknown) => invoke('set_setting', { key, value }),
+    getAllSettings: () => invoke('get_all_settings'),
+    exportLatexFile: (content: string, options?: WriteOptions) => invoke('export_latex_file', { content, options }),
+    revealInFileManager: (path: string) => invoke('reveal_in_file_manager', { path }),
+    openPathWithDefaultApp: (path: string, options?: { allowExecutables?: boolean }) =>
+      invoke('open_path_with_default_app', { path, options }),
+    exportMarkdownFile: (content: string, options?: WriteOptions) => invoke('export_markdown_file', { content, options }),
+    exportLatexDirectory: (
+      formulas: Array<{ latex: string; note?: string }>,
+      options?: { pattern?: string; includeNote?: boolean; overwrite?: boolean }
+    ) => invoke('export_latex_directory', { formulas, options }),
+    exportHtmlFile: (content: string, options?: WriteOptions) => invoke('export_html_file', { content, options }),
+    exportTypstFile: (content: string, options?: WriteOptions) => invoke('export_typst_file', { content, options }),
+    exportMathmlFile: (content: string, options?: WriteOptions) => invoke('export_mathml_file', { content, options }),
+    exportOrgFile: (content: string, options?: WriteOptions) => invoke('export_org_file', { content, options }),
+    exportRstFile: (content: string, options?: WriteOptions) => invoke('export_rst_file', { content, options }),
+    exportCsvFile: (content: string, options?: WriteOptions) => invoke('export_csv_file', { content, options }),
+    exportAnkiFile: (
+      formulas: FormulaEntry[],
+      options?: { math?: 'mathjax' | 'legacy'; frontField?: string; skipEmpty?: boolean },
+      writeOptions?: WriteOptions
+    ) => invoke('export_anki_file', { formulas, options, writeOptions }),
+    copyTextToClipboard: (content: string) => invoke('copy_text_to_clipboard', { content }),
+    copyExportToClipboard: (
+      formulas: Array<{ latex: string; note?: string; tags?: string[] }>,
+      format: 'latex' | 'markdown' | 'typst' | 'org' | 'rst' | 'mathml'
+    ) => invoke('copy_export_to_clipboard', { formulas, format }),
+    exportBundleZip: (formulas: FormulaEntry[], options?: { title?: string; includeTags?: boolean }) =>
+      invoke('export_bundle_zip', { formulas, options }),
+    exportDocxFile: (formulas: Array<{ latex: string; note?: string }>) =>
+      invoke('export_docx_file', { formulas }),
+    exportPdfFile: (
+      formulas: Array<{ latex: string; note?: string; tags?: string[] }>,
+      options?: { includeTags?: boolean; overwrite?: boolean; path?: string }
+    ) =>
+      invoke('export_pdf_file', { formulas, options }),
+    exportFormulaImages: (formulas: Array<{ latex: string; note?: string }>, options?: ImageBatchOptions) =>
+      invoke('export_formula_images', { formulas, options }),
+    cancelJob: (jobId: string) => invoke('cancel_job', { jobId }),
+    onJobProgress: (handler) => listen<JobProgress>('job:progress', handler),
+    onJobDone: (handler) => listen<JobDone>('job:done', handler),
+    onJobFailed: (handler) => listen<JobFailed>('job:failed', handler),
+    formatLatex: (
+      formulas: Array<{ latex: string; note?: string; tags?: string[]; environment?: MathEnvironment; id?: string; label?: string; section?: string }>,
+      includeTags?: boolean,
+      options?: LatexOptions
+    ) => invoke('format_latex', { formulas, includeTags, options }),
+    formatMarkdown: (
+      formulas: Array<{ latex: string; note?: string; tags?: string[]; section?: string }>,
+      notePosition?: 'above' | 'below',
+      includeTags?: boolean,
+      options?: MarkdownOptions,
+      flavor?: MarkdownFlavor
+    ) => invoke('format_markdown', { formulas, notePosition, includeTags, options, flavor }),
+    formatHtml: (formulas: Array<{ latex: string; note?: string; section?: string }>, options?: { embedAssets?: boolean; title?: string }) =>
+      invoke('format_html', { formulas, options }),
+    printFormulas: (formulas: Array<{ latex: string; note?: string; section?: string }>, options?: { title?: string; pageBreaks?: boolean }) =>
+      invoke('print_formulas', { formulas, options }),
+    formatHtmlFragment: (
+      formulas: Array<{ latex: string; note?: string; id?: string }>,
+      options?: { delimiter?: 'parentheses' | 'dollars'; includeId?: boolean }
+    ) => invoke('format_html_fragment', { formulas, options }),
+    latexToMathml: (latex: string, display: boolean) => invoke('latex_to_mathml', { latex, display }),
+    renderFormulaSvg: (latex: string, options?: SvgOptions) => invoke('render_formula_svg', { latex, options }),
+    exportFormulaSvgFile: (latex: string, options?: SvgOptions) =>
+      invoke('export_formula_svg_file', { latex, options }),
+    renderFormulaPng: (latex: string, options?: PngOptions) => invoke('render_formula_png', { latex, options }),
+    exportFormulaPngFile: (latex: string, options?: PngOptions) =>
+      invoke('export_formula_png_file', { latex, options }),
+    copyFormulaImageToClipboard: (latex: string, options?: PngOptions) =>
+      invoke('copy_formula_image_to_clipboard', { latex, options }),
+    formatMathml: (formulas: Array<{ latex: string; note?: string }>) => invoke('format_mathml', { formulas }),
+    formatTypst: (formulas: Array<{ latex: string; note?: string }>, options?: { mode?: 'translate' | 'mitex' }) =>
+      invoke('format_typst', { formulas, options }),
+    formatOrg: (formulas: Array<{ latex: string; note?: string }>, options?: { mathStyle?: 'equation' | 'brackets' }) =>
+      invoke('format_org', { formulas, options }),
+    formatRst: (formulas: Array<{ latex: string; note?: string }>) => invoke('format_rst', { formulas }),
+    formatCsv: (formulas: FormulaEntry[], options?: { bom?: boolean }) => invoke('format_csv', { formulas, options }),
     normalizeFormulas: (content: string) => invoke('normalize_formulas', { content }),
-    normalizeTemplates: (content: string) => invoke('normalize_templates', { content }),
+    serializeFormulas: (entries: FormulaEntry[], title?: string) =>
+      invoke('serialize_formulas', { entries, title }),
+    formulasToYaml: (content: string) => invoke('formulas_to_yaml', { content }),
+    yamlToFormulas: (content: string) => invoke('yaml_to_formulas', { content }),
+    importTexFile: (path: string, options?: { splitRows?: boolean }) => invoke('import_tex_file', { path, options }),
+    importMarkdownFile: (path: string, options?: { inline?: boolean }) => invoke('import_markdown_file', { path, options }),
+    importDocxFile: (path: string) => invoke('import_docx_file', { path }),
+    importFromClipboard: () => invoke('import_from_clipboard'),
+    importCsvFile: (path: string, mapping?: CsvMapping) => invoke('import_csv_file', { path, mapping }),
+    mathmlToLatex: (mathml: string) => invoke('mathml_to_latex', { mathml }),
+    importMathmlFile: (path: string) => invoke('import_mathml_file', { path }),
+    reimportExportedLatex: (content: string) => invoke('reimport_exported_latex', { content }),
+    findFormulasUsingCommand: (formulas: FormulaEntry[], command: string) =>
+      invoke('find_formulas_using_command', { formulas, command }),
+    filterByTags: (formulas: FormulaEntry[], tags: string[], matchAll: boolean) =>
+      invoke('filter_by_tags', { formulas, tags, matchAll }),
+    filterFormulas: (content: string, tags: string[], matchAll: boolean) =>
+      invoke('filter_formulas', { content, tags, matchAll }),
+    searchFormulas: (path: string, query: string, options?: SearchOptions) =>
+      invoke('search_formulas', { path, query, options }),
+    sortFormulas: (content: string, key: 'index' | 'note' | 'latex_length' | 'created_at', ascending: boolean) =>
+      invoke('sort_formulas', { content, key, ascending }),
+    mergeFormulaFiles: (pathA: string, pathB: string, strategy: 'keep_both' | 'skip_duplicates' | 'prefer_b_note') =>
+      invoke('merge_formula_files', { pathA, pathB, strategy }),
+    diffFormulaFiles: (pathA: string, pathB: string, compareIndex?: boolean) =>
+      invoke('diff_formula_files', { pathA, pathB, compareIndex }),
+    listTags: (formulas: FormulaEntry[]) => invoke('list_tags', { formulas }),
+    validateLatex: (latex: string) => invoke('validate_latex', { latex }),
+    validateFormulaSet: (formulas: FormulaEntry[]) => invoke('validate_formula_set', { formulas }),
+    lintFormulaSet: (formulas: FormulaEntry[]) => invoke('lint_formula_set', { formulas }),
+    autofixFormulaSet: (formulas: FormulaEntry[]) => invoke('autofix_formula_set', { formulas }),
+    normalizeTemplates: (content: string, sortByName?: boolean, maxDepth?: number, dedupe?: boolean) =>
+      invoke('normalize_templates', { content, sortByName, maxDepth, dedupe }),
+    dedupeTemplateLibrary: (content: string, scope?: 'category' | 'library') =>
+      invoke('dedupe_template_library', { content, scope }),
+    migrateTemplateLibrary: (content: string) => invoke('migrate_template_library', { content }),
+    mergeTemplateLibraries: (pathA: string, pathB: string, strategy?: 'merge' | 'rename') =>
+      invoke('merge_template_libraries', { pathA, pathB, strategy }),
+    exportTemplateSubtree: (libraryContent: string, categoryId: string) =>
+      invoke('export_template_subtree', { libraryContent, categoryId }),
+    saveTemplateSubtree: (libraryContent: string, categoryId: string) =>
+      invoke('save_template_subtree', { libraryContent, categoryId }),
+    searchTemplates: (libraryContent: string, query: string, limit?: number) =>
+      invoke('search_templates', { libraryContent, query, limit }),
+    formulasToTemplateLibrary: (formulas: FormulaEntry[], categoryName: string) =>
+      invoke('formulas_to_template_library', { formulas, categoryName }),
+    templateLibraryToFormulas: (libraryContent: string, categoryId?: string) =>
+      invoke('template_library_to_formulas', { libraryContent, categoryId }),
     getSystemInfo: () => invoke('get_system_info'),
+    getSystemInfoText: () => invoke('get_system_info_text'),
+    setBackendLocale: (locale: string) => invoke('set_backend_locale', { locale }),
+    logMessage: (level: 'error' | 'warn' | 'info' | 'debug', message: string) =>
+      invoke('log_message', { level, message }),
+    getLogFilePath: () => invoke('get_log_file_path'),
   };
 };
 